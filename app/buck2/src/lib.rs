@@ -21,6 +21,7 @@ use buck2_client::commands::ctargets::ConfiguredTargetsCommand;
 use buck2_client::commands::debug::DebugCommand;
 use buck2_client::commands::expand_external_cell::ExpandExternalCellCommand;
 use buck2_client::commands::explain::ExplainCommand;
+use buck2_client::commands::external_cells::ExternalCellsCommand;
 use buck2_client::commands::help_env::HelpEnvCommand;
 use buck2_client::commands::init::InitCommand;
 use buck2_client::commands::install::InstallCommand;
@@ -251,6 +252,7 @@ pub(crate) enum CommandKind {
     #[clap(hide = true)] // TODO iguridi: remove
     Explain(ExplainCommand),
     ExpandExternalCell(ExpandExternalCellCommand),
+    ExternalCells(ExternalCellsCommand),
     Install(InstallCommand),
     Kill(KillCommand),
     Killall(KillallCommand),
@@ -424,6 +426,7 @@ impl CommandKind {
             CommandKind::Lsp(cmd) => cmd.exec(matches, command_ctx),
             CommandKind::Subscribe(cmd) => cmd.exec(matches, command_ctx),
             CommandKind::ExpandExternalCell(cmd) => cmd.exec(matches, command_ctx),
+            CommandKind::ExternalCells(cmd) => cmd.exec(matches, command_ctx),
         }
     }
 }
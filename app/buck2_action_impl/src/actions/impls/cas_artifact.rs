@@ -150,7 +150,16 @@ impl CasArtifactAction {
         &self,
         ctx: &mut dyn ActionExecutionCtx,
     ) -> anyhow::Result<(ActionOutputs, ActionExecutionMetadata)> {
-        let outputs = offline::declare_copy_from_offline_cache(ctx, &self.output).await?;
+        let outputs = if ctx.run_action_knobs().offline {
+            offline::declare_copy_from_offline_cache_or_refuse(
+                ctx,
+                &self.output,
+                &format!("CAS digest {}", self.inner.digest),
+            )
+            .await?
+        } else {
+            offline::declare_copy_from_offline_cache(ctx, &self.output).await?
+        };
 
         Ok((
             outputs,
@@ -158,6 +167,7 @@ impl CasArtifactAction {
                 execution_kind: ActionExecutionKind::Deferred,
                 timing: ActionExecutionTimingData::default(),
                 input_files_bytes: None,
+                download_source: None,
             },
         ))
     }
@@ -202,7 +212,9 @@ impl IncrementalActionExecutable for CasArtifactAction {
     ) -> Result<(ActionOutputs, ActionExecutionMetadata), ExecuteError> {
         // If running in offline environment, try to restore from cached outputs
         // first. Fallthrough to normal operation if unsuccessful.
-        if ctx.run_action_knobs().use_network_action_output_cache {
+        // With `--offline`, that is the only option.
+        if ctx.run_action_knobs().use_network_action_output_cache || ctx.run_action_knobs().offline
+        {
             return self.execute_for_offline(ctx).await.map_err(Into::into);
         }
 
@@ -321,6 +333,7 @@ impl IncrementalActionExecutable for CasArtifactAction {
                 execution_kind: ActionExecutionKind::Deferred,
                 timing: ActionExecutionTimingData::default(),
                 input_files_bytes: None,
+                download_source: None,
             },
         ))
     }
@@ -207,6 +207,7 @@ impl IncrementalActionExecutable for CopyAction {
                 execution_kind: ActionExecutionKind::Simple,
                 timing: ActionExecutionTimingData::default(),
                 input_files_bytes: None,
+                download_source: None,
             },
         ))
     }
@@ -26,20 +26,28 @@ use buck2_build_api::actions::UnregisteredAction;
 use buck2_build_api::artifact_groups::ArtifactGroup;
 use buck2_common::cas_digest::RawDigest;
 use buck2_common::file_ops::FileDigest;
+use buck2_common::file_ops::FileDigestConfig;
 use buck2_common::file_ops::FileMetadata;
 use buck2_common::file_ops::TrackedFileDigest;
 use buck2_common::io::trace::TracingIoProvider;
 use buck2_core::category::CategoryRef;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_directory::directory::entry::DirectoryEntry;
 use buck2_error::ErrorTag;
 use buck2_execute::artifact_value::ArtifactValue;
 use buck2_execute::digest_config::DigestConfig;
+use buck2_execute::directory::ActionDirectoryMember;
+use buck2_execute::entry::build_entry_from_disk;
 use buck2_execute::execute::command_executor::ActionExecutionTimingData;
+use buck2_execute::materialize::http::copy_from_mirror;
 use buck2_execute::materialize::http::http_download;
 use buck2_execute::materialize::http::http_head;
 use buck2_execute::materialize::http::Checksum;
 use buck2_execute::materialize::materializer::HttpDownloadInfo;
 use buck2_http::HttpClient;
 use dupe::Dupe;
+use http::Response;
 use indexmap::IndexSet;
 use starlark::values::OwnedFrozenValue;
 
@@ -137,17 +145,9 @@ impl DownloadFileAction {
         }
     }
 
-    /// Try to produce a FileMetadata without downloading the file.
-    async fn declared_metadata(
-        &self,
-        client: &HttpClient,
-        digest_config: DigestConfig,
-    ) -> anyhow::Result<Option<FileMetadata>> {
-        if !self.inner.is_deferrable {
-            return Ok(None);
-        }
-
-        let digest = if digest_config.cas_digest_config().allows_sha1() {
+    /// The digest of the file, if the checksum uses the algorithm we use for the CAS.
+    fn checksum_digest(&self, digest_config: DigestConfig) -> Option<RawDigest> {
+        if digest_config.cas_digest_config().allows_sha1() {
             self.inner
                 .checksum
                 .sha1()
@@ -159,15 +159,42 @@ impl DownloadFileAction {
                 .and_then(|sha256| RawDigest::parse_sha256(sha256.as_bytes()).ok())
         } else {
             None
-        };
+        }
+    }
 
-        let digest = match digest {
+    /// Send a HEAD request to each URL `http.url_rewrites` turns ours into, returning the first
+    /// that answers. We apply the rewrites rather than letting the client do it so that we know
+    /// which URL was used.
+    async fn head(&self, client: &HttpClient) -> anyhow::Result<(String, Response<()>)> {
+        let direct = client.without_url_rewrites();
+        let mut error = None;
+        for url in client.url_candidates(self.url(client)) {
+            match http_head(&direct, &url).await {
+                Ok(head) => return Ok((url.into_owned(), head)),
+                Err(e) => error = Some(e),
+            }
+        }
+        Err(error.expect("There is always at least one candidate URL"))
+    }
+
+    /// Try to produce a FileMetadata without downloading the file. Also returns the URL that
+    /// answered.
+    async fn declared_metadata(
+        &self,
+        client: &HttpClient,
+        digest_config: DigestConfig,
+    ) -> anyhow::Result<Option<(FileMetadata, String)>> {
+        if !self.inner.is_deferrable {
+            return Ok(None);
+        }
+
+        let digest = match self.checksum_digest(digest_config) {
             Some(digest) => digest,
             None => return Ok(None),
         };
 
-        let url = self.url(client);
-        let head = http_head(client, url)
+        let (url, head) = self
+            .head(client)
             .await
             .map_err(|e| buck2_error::Error::from(e).tag([ErrorTag::DownloadFileHeadRequest]))?;
 
@@ -198,15 +225,116 @@ impl DownloadFileAction {
                     FileDigest::new(digest, length),
                     digest_config.cas_digest_config(),
                 );
-                Ok(Some(FileMetadata {
-                    digest,
-                    is_executable: self.inner.is_executable,
-                }))
+                Ok(Some((
+                    FileMetadata {
+                        digest,
+                        is_executable: self.inner.is_executable,
+                    },
+                    url,
+                )))
             }
             None => Ok(None),
         }
     }
 
+    /// Download the file now, from the first URL `http.url_rewrites` turns ours into that works.
+    /// Returns the URL that was used.
+    async fn download(
+        &self,
+        ctx: &mut dyn ActionExecutionCtx,
+        client: &HttpClient,
+    ) -> anyhow::Result<(FileMetadata, String)> {
+        ctx.cleanup_outputs().await?;
+
+        let artifact_fs = ctx.fs();
+        let project_fs = artifact_fs.fs();
+        let rel_path = artifact_fs.resolve_build(self.output().get_path());
+
+        let direct = client.without_url_rewrites();
+        let mut error = None;
+        for url in client.url_candidates(self.url(client)) {
+            match http_download(
+                &direct,
+                project_fs,
+                ctx.digest_config(),
+                &rel_path,
+                &url,
+                &self.inner.checksum,
+                self.inner.is_executable,
+            )
+            .await
+            {
+                Ok(digest) => {
+                    let metadata = FileMetadata {
+                        digest,
+                        is_executable: self.inner.is_executable,
+                    };
+                    ctx.materializer()
+                        .declare_existing(vec![(rel_path, ArtifactValue::file(metadata.dupe()))])
+                        .await?;
+                    return Ok((metadata, url.into_owned()));
+                }
+                Err(e) => error = Some(e),
+            }
+        }
+        Err(error.expect("There is always at least one candidate URL"))
+    }
+
+    /// Copy the file from `download.mirror_dir`, if it's there. Returns the path it was copied
+    /// from.
+    async fn copy_from_mirror(
+        &self,
+        ctx: &mut dyn ActionExecutionCtx,
+    ) -> anyhow::Result<Option<(FileMetadata, String)>> {
+        let (Some(mirror_dir), Some(sha256)) = (
+            ctx.run_action_knobs().download_mirror_dir,
+            self.inner.checksum.sha256(),
+        ) else {
+            return Ok(None);
+        };
+        let mirror_path = mirror_dir.join(ForwardRelativePath::new(sha256)?);
+        if !fs_util::try_exists(&mirror_path)? {
+            return Ok(None);
+        }
+
+        ctx.cleanup_outputs().await?;
+
+        let artifact_fs = ctx.fs();
+        let rel_path = artifact_fs.resolve_build(self.output().get_path());
+        let digest = match copy_from_mirror(
+            artifact_fs.fs(),
+            ctx.blocking_executor(),
+            ctx.digest_config(),
+            &rel_path,
+            &mirror_path,
+            &self.inner.checksum,
+            self.inner.is_executable,
+        )
+        .await
+        {
+            Ok(Some(digest)) => digest,
+            Ok(None) => return Ok(None),
+            Err(e) => {
+                // The mirror is only a cache, so a bad file in it shouldn't fail the build.
+                tracing::warn!(
+                    "Not using `{}` from the download mirror: {:#}",
+                    mirror_path,
+                    e
+                );
+                return Ok(None);
+            }
+        };
+
+        let metadata = FileMetadata {
+            digest,
+            is_executable: self.inner.is_executable,
+        };
+        ctx.materializer()
+            .declare_existing(vec![(rel_path, ArtifactValue::file(metadata.dupe()))])
+            .await?;
+        Ok(Some((metadata, mirror_path.to_string())))
+    }
+
     /// Execute this action for offline builds (e.g. no network).
     async fn execute_for_offline(
         &self,
@@ -220,9 +348,99 @@ impl DownloadFileAction {
                 execution_kind: ActionExecutionKind::Simple,
                 timing: ActionExecutionTimingData::default(),
                 input_files_bytes: None,
+                download_source: None,
             },
         ))
     }
+
+    /// Execute this action for `--offline` builds, which must not use the network. The file has to
+    /// be materialized already, from a previous build, in `download.mirror_dir`, or in the offline
+    /// cache.
+    async fn execute_without_network(
+        &self,
+        ctx: &mut dyn ActionExecutionCtx,
+    ) -> anyhow::Result<(ActionOutputs, ActionExecutionMetadata)> {
+        if let Some(value) = self.existing_output(ctx).await? {
+            return Ok((
+                ActionOutputs::from_single(self.output().get_path().dupe(), value),
+                ActionExecutionMetadata {
+                    execution_kind: ActionExecutionKind::Simple,
+                    timing: ActionExecutionTimingData::default(),
+                    input_files_bytes: None,
+                    download_source: None,
+                },
+            ));
+        }
+
+        if let Some((metadata, mirror_path)) = self.copy_from_mirror(ctx).await? {
+            return Ok((
+                ActionOutputs::from_single(
+                    self.output().get_path().dupe(),
+                    ArtifactValue::file(metadata),
+                ),
+                ActionExecutionMetadata {
+                    execution_kind: ActionExecutionKind::Simple,
+                    timing: ActionExecutionTimingData::default(),
+                    input_files_bytes: None,
+                    download_source: Some(buck2_data::DownloadSource {
+                        original_url: self.url(&ctx.http_client()).to_string(),
+                        source: Some(buck2_data::download_source::Source::MirrorPath(mirror_path)),
+                    }),
+                },
+            ));
+        }
+
+        let url = self.url(&ctx.http_client()).dupe();
+        let outputs =
+            offline::declare_copy_from_offline_cache_or_refuse(ctx, self.output(), &url).await?;
+
+        Ok((
+            outputs,
+            ActionExecutionMetadata {
+                execution_kind: ActionExecutionKind::Simple,
+                timing: ActionExecutionTimingData::default(),
+                input_files_bytes: None,
+                download_source: None,
+            },
+        ))
+    }
+
+    /// The output, if the materializer knows about it and what's on disk matches the checksum.
+    async fn existing_output(
+        &self,
+        ctx: &mut dyn ActionExecutionCtx,
+    ) -> anyhow::Result<Option<ArtifactValue>> {
+        let Some(expected) = self.checksum_digest(ctx.digest_config()) else {
+            return Ok(None);
+        };
+
+        let rel_path = ctx.fs().resolve_build(self.output().get_path());
+        if !ctx.materializer().has_artifact_at(rel_path.clone()).await? {
+            return Ok(None);
+        }
+
+        let (entry, _hashing_info) = build_entry_from_disk(
+            ctx.fs().fs().resolve(&rel_path),
+            FileDigestConfig::build(ctx.digest_config().cas_digest_config()),
+            ctx.blocking_executor(),
+            ctx.fs().fs().root(),
+        )
+        .await?;
+        let metadata = match entry {
+            Some(DirectoryEntry::Leaf(ActionDirectoryMember::File(metadata)))
+                if *metadata.digest.raw_digest() == expected =>
+            {
+                metadata
+            }
+            _ => return Ok(None),
+        };
+
+        let value = ArtifactValue::file(metadata);
+        ctx.materializer()
+            .declare_existing(vec![(rel_path, value.dupe())])
+            .await?;
+        Ok(Some(value))
+    }
 }
 
 #[async_trait]
@@ -274,59 +492,57 @@ impl IncrementalActionExecutable for DownloadFileAction {
             return self.execute_for_offline(ctx).await.map_err(Into::into);
         }
 
-        let client = ctx.http_client();
-        let url = self.url(&client);
-
-        let (value, execution_kind) = {
-            match self.declared_metadata(&client, ctx.digest_config()).await? {
-                Some(metadata) => {
-                    let artifact_fs = ctx.fs();
-                    let rel_path = artifact_fs.resolve_build(self.output().get_path());
+        if ctx.run_action_knobs().offline {
+            return self.execute_without_network(ctx).await.map_err(Into::into);
+        }
 
-                    // Fast path: download later via the materializer.
-                    ctx.materializer()
-                        .declare_http(
-                            rel_path,
-                            HttpDownloadInfo {
-                                url: url.dupe(),
-                                checksum: self.inner.checksum.dupe(),
-                                metadata: metadata.dupe(),
-                                owner: ctx.target().owner().dupe(),
-                            },
-                            ctx.cancellation_context(),
+        let client = ctx.http_client();
+        let original_url = self.url(&client).to_string();
+
+        let (value, execution_kind, source) = {
+            if let Some((metadata, mirror_path)) = self.copy_from_mirror(ctx).await? {
+                (
+                    ArtifactValue::file(metadata),
+                    ActionExecutionKind::Simple,
+                    buck2_data::download_source::Source::MirrorPath(mirror_path),
+                )
+            } else {
+                match self.declared_metadata(&client, ctx.digest_config()).await? {
+                    Some((metadata, url)) => {
+                        let artifact_fs = ctx.fs();
+                        let rel_path = artifact_fs.resolve_build(self.output().get_path());
+
+                        // Fast path: download later via the materializer, from the URL that
+                        // answered our HEAD request.
+                        ctx.materializer()
+                            .declare_http(
+                                rel_path,
+                                HttpDownloadInfo {
+                                    url: Arc::from(url.as_str()),
+                                    checksum: self.inner.checksum.dupe(),
+                                    metadata: metadata.dupe(),
+                                    owner: ctx.target().owner().dupe(),
+                                },
+                                ctx.cancellation_context(),
+                            )
+                            .await?;
+
+                        (
+                            ArtifactValue::file(metadata),
+                            ActionExecutionKind::Deferred,
+                            buck2_data::download_source::Source::Url(url),
                         )
-                        .await?;
-
-                    (ArtifactValue::file(metadata), ActionExecutionKind::Deferred)
-                }
-                None => {
-                    ctx.cleanup_outputs().await?;
-
-                    let artifact_fs = ctx.fs();
-                    let project_fs = artifact_fs.fs();
-                    let rel_path = artifact_fs.resolve_build(self.output().get_path());
-
-                    // Slow path: download now.
-                    let digest = http_download(
-                        &client,
-                        project_fs,
-                        ctx.digest_config(),
-                        &rel_path,
-                        url,
-                        &self.inner.checksum,
-                        self.inner.is_executable,
-                    )
-                    .await?;
-
-                    let metadata = FileMetadata {
-                        digest,
-                        is_executable: self.inner.is_executable,
-                    };
-                    ctx.materializer()
-                        .declare_existing(vec![(rel_path, ArtifactValue::file(metadata.dupe()))])
-                        .await?;
-
-                    (ArtifactValue::file(metadata), ActionExecutionKind::Simple)
+                    }
+                    None => {
+                        // Slow path: download now.
+                        let (metadata, url) = self.download(ctx, &client).await?;
+
+                        (
+                            ArtifactValue::file(metadata),
+                            ActionExecutionKind::Simple,
+                            buck2_data::download_source::Source::Url(url),
+                        )
+                    }
                 }
             }
         };
@@ -347,6 +563,10 @@ impl IncrementalActionExecutable for DownloadFileAction {
                 execution_kind,
                 timing: ActionExecutionTimingData::default(),
                 input_files_bytes: None,
+                download_source: Some(buck2_data::DownloadSource {
+                    original_url,
+                    source: Some(source),
+                }),
             },
         ))
     }
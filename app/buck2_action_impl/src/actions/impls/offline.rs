@@ -15,9 +15,23 @@ use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
 use buck2_execute::artifact_value::ArtifactValue;
 use buck2_execute::directory::INTERNER;
 use buck2_execute::entry::build_entry_from_disk;
+use buck2_execute::execute::target::CommandExecutionTarget;
 use buck2_execute::materialize::materializer::CopiedArtifact;
 use dupe::Dupe;
 
+#[derive(Debug, buck2_error::Error)]
+#[buck2(input)]
+pub(crate) enum OfflineError {
+    #[error(
+        "Action `{action}` needs `{resource}`, but network access is disabled by `--offline` and it is not in the offline cache at `{offline_cache_path}`"
+    )]
+    NetworkAccessRefused {
+        action: String,
+        resource: String,
+        offline_cache_path: ProjectRelativePathBuf,
+    },
+}
+
 /// Declares a copy materialization to copy the output BuildArtifact to the
 /// offline cache for use in an offline build. Returns the project-relative path
 /// to the offline cached file.
@@ -69,6 +83,27 @@ pub(crate) async fn declare_copy_from_offline_cache(
     Ok(ActionOutputs::from_single(output.get_path().dupe(), value))
 }
 
+/// Like `declare_copy_from_offline_cache`, for `--offline` builds, where a missing cache entry
+/// means the action would have to use the network to fetch `resource`.
+pub(crate) async fn declare_copy_from_offline_cache_or_refuse(
+    ctx: &mut dyn ActionExecutionCtx,
+    output: &BuildArtifact,
+    resource: &str,
+) -> anyhow::Result<ActionOutputs> {
+    let offline_cache_path = ctx
+        .fs()
+        .resolve_offline_output_cache_path(output.get_path());
+    if !ctx.fs().fs().resolve(&offline_cache_path).exists() {
+        return Err(OfflineError::NetworkAccessRefused {
+            action: ctx.target().re_action_key(),
+            resource: resource.to_owned(),
+            offline_cache_path,
+        }
+        .into());
+    }
+    declare_copy_from_offline_cache(ctx, output).await
+}
+
 /// Declares a generic copy materialization from src to dest.
 async fn declare_copy_materialization(
     ctx: &dyn ActionExecutionCtx,
@@ -55,13 +55,16 @@ use buck2_execute::execute::request::CommandExecutionOutput;
 use buck2_execute::execute::request::CommandExecutionPaths;
 use buck2_execute::execute::request::CommandExecutionRequest;
 use buck2_execute::execute::request::ExecutorPreference;
+use buck2_execute::execute::request::ResourceLimits;
 use buck2_execute::execute::request::WorkerId;
+use buck2_execute::execute::request::WorkerProtocol;
 use buck2_execute::execute::request::WorkerSpec;
 use buck2_execute::execute::result::CommandExecutionResult;
 use derive_more::Display;
 use dupe::Dupe;
 use gazebo::prelude::*;
 use host_sharing::HostSharingRequirements;
+use host_sharing::ResourceEstimate;
 use host_sharing::WeightClass;
 use indexmap::indexmap;
 use indexmap::IndexSet;
@@ -173,6 +176,8 @@ pub(crate) struct UnregisteredRunAction {
     pub(crate) force_full_hybrid_if_capable: bool,
     pub(crate) unique_input_inodes: bool,
     pub(crate) remote_execution_dependencies: Vec<RemoteExecutorDependency>,
+    pub(crate) resource_limits: ResourceLimits,
+    pub(crate) resource_estimate: ResourceEstimate,
 }
 
 impl UnregisteredAction for UnregisteredRunAction {
@@ -255,6 +260,7 @@ struct UnpackedWorkerValues<'v> {
     exe: &'v dyn CommandLineArgLike,
     id: WorkerId,
     concurrency: Option<usize>,
+    protocol: WorkerProtocol,
 }
 
 struct UnpackedRunActionValues<'v> {
@@ -310,6 +316,7 @@ impl RunAction {
             exe: worker.exe_command_line(),
             id: WorkerId(worker.id),
             concurrency: worker.concurrency(),
+            protocol: worker.protocol(),
         });
 
         Ok(UnpackedRunActionValues {
@@ -345,6 +352,7 @@ impl RunAction {
                 exe: worker_rendered,
                 id: worker.id,
                 concurrency: worker.concurrency,
+                protocol: worker.protocol,
             })
         } else {
             None
@@ -561,7 +569,9 @@ impl RunAction {
             .with_local_environment_inheritance(EnvironmentInheritance::local_command_exclusions())
             .with_force_full_hybrid_if_capable(self.inner.force_full_hybrid_if_capable)
             .with_unique_input_inodes(self.inner.unique_input_inodes)
-            .with_remote_execution_dependencies(self.inner.remote_execution_dependencies.clone());
+            .with_remote_execution_dependencies(self.inner.remote_execution_dependencies.clone())
+            .with_resource_limits(self.inner.resource_limits)
+            .with_resource_estimate(self.inner.resource_estimate);
 
         let (dep_file_bundle, req) = if let Some(visitor) = dep_file_visitor {
             let bundle = make_dep_file_bundle(ctx, visitor, cmdline_digest, req.paths())?;
@@ -476,6 +476,7 @@ impl DepFileBundle {
                     execution_kind: ActionExecutionKind::LocalDepFile,
                     timing: Default::default(),
                     input_files_bytes: None,
+                    download_source: None,
                 },
             )
         });
@@ -512,6 +513,7 @@ impl DepFileBundle {
                     execution_kind: ActionExecutionKind::LocalDepFile,
                     timing: Default::default(),
                     input_files_bytes: None,
+                    download_source: None,
                 },
             )
         });
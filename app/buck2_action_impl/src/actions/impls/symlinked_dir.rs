@@ -264,6 +264,7 @@ impl IncrementalActionExecutable for SymlinkedDirAction {
                 execution_kind: ActionExecutionKind::Simple,
                 timing: ActionExecutionTimingData::default(),
                 input_files_bytes: None,
+                download_source: None,
             },
         ))
     }
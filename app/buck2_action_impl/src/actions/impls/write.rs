@@ -223,6 +223,7 @@ impl IncrementalActionExecutable for WriteAction {
                 execution_kind: ActionExecutionKind::Simple,
                 timing: ActionExecutionTimingData { wall_time },
                 input_files_bytes: None,
+                download_source: None,
             },
         ))
     }
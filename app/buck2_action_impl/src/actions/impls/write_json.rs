@@ -232,6 +232,7 @@ impl IncrementalActionExecutable for WriteJsonAction {
                 execution_kind: ActionExecutionKind::Simple,
                 timing: ActionExecutionTimingData { wall_time },
                 input_files_bytes: None,
+                download_source: None,
             },
         ))
     }
@@ -212,6 +212,7 @@ impl IncrementalActionExecutable for WriteMacrosToFileAction {
                 execution_kind: ActionExecutionKind::Simple,
                 timing: ActionExecutionTimingData { wall_time },
                 input_files_bytes: None,
+                download_source: None,
             },
         ))
     }
@@ -27,14 +27,17 @@ use buck2_build_api::interpreter::rule_defs::provider::builtin::worker_run_info:
 use buck2_core::category::CategoryRef;
 use buck2_core::execution_types::executor_config::RemoteExecutorDependency;
 use buck2_core::fs::paths::forward_rel_path::ForwardRelativePathBuf;
+use buck2_execute::execute::request::ResourceLimits;
 use dupe::Dupe;
 use either::Either;
+use host_sharing::ResourceEstimate;
 use host_sharing::WeightClass;
 use host_sharing::WeightPercentage;
 use starlark::environment::MethodsBuilder;
 use starlark::eval::Evaluator;
 use starlark::starlark_module;
 use starlark::values::dict::UnpackDictEntries;
+use starlark::values::float::UnpackFloat;
 use starlark::values::list::UnpackList;
 use starlark::values::none::NoneOr;
 use starlark::values::none::NoneType;
@@ -60,6 +63,14 @@ pub(crate) enum RunActionError {
     InvalidWeight(i32),
     #[error("`weight` and `weight_percentage` cannot both be passed")]
     DuplicateWeightsSpecified,
+    #[error("`memory_limit_mb` must be a positive integer, got `{0}`")]
+    InvalidMemoryLimit(u64),
+    #[error("`cpu_limit` must be a positive number, got `{0}`")]
+    InvalidCpuLimit(f64),
+    #[error("`memory_estimate_mb` must be a positive integer, got `{0}`")]
+    InvalidMemoryEstimate(u64),
+    #[error("`cpu_estimate` must be a positive number, got `{0}`")]
+    InvalidCpuEstimate(f64),
     #[error("`dep_files` value with key `{}` has an invalid count of associated outputs. Expected 1, got {}.", .key, .count)]
     InvalidDepFileOutputs { key: String, count: usize },
     #[error("`dep_files` with keys `{}` and {} are using the same tag", .first, .second)]
@@ -88,6 +99,15 @@ pub(crate) fn analysis_actions_methods_run(methods: &mut MethodsBuilder) {
     ///   event stream, and must be unique for a given target
     /// * `weight`: used to note how heavy the command is and will typically be set to a higher
     ///   value to indicate that less such commands should be run in parallel (if running locally)
+    /// * `memory_limit_mb` and `cpu_limit`: the memory (in MiB) and number of CPUs the command may
+    ///   use when it runs locally. They are only enforced when `buck2_resource_control` is
+    ///   enabled, in which case each local command runs in its own cgroup. A command that exceeds
+    ///   its memory limit is killed and fails.
+    /// * `memory_estimate_mb` and `cpu_estimate`: the memory (in MiB) and number of CPUs the
+    ///   command is expected to use when it runs locally. Local commands are only started once
+    ///   their estimate fits in what is left of the machine's memory and CPUs, so that e.g. many
+    ///   concurrent links don't run out of memory. They default to `memory_limit_mb` and
+    ///   `cpu_limit`.
     /// * `no_outputs_cleanup`: if this flag is set then Buck2 won't clean the outputs of a previous
     ///   build that might be present on a disk; in which case, command from arguments should be
     ///   responsible for the cleanup (that is useful, for example, when an action is supporting
@@ -145,6 +165,10 @@ pub(crate) fn analysis_actions_methods_run(methods: &mut MethodsBuilder) {
         #[starlark(require = named, default = false)] always_print_stderr: bool,
         #[starlark(require = named)] weight: Option<i32>,
         #[starlark(require = named)] weight_percentage: Option<i32>,
+        #[starlark(require = named)] memory_limit_mb: Option<u64>,
+        #[starlark(require = named)] cpu_limit: Option<UnpackFloat>,
+        #[starlark(require = named)] memory_estimate_mb: Option<u64>,
+        #[starlark(require = named)] cpu_estimate: Option<UnpackFloat>,
         #[starlark(require = named)] dep_files: Option<SmallMap<&'v str, &'v ArtifactTag>>,
         #[starlark(require = named)] metadata_env_var: Option<String>,
         #[starlark(require = named)] metadata_path: Option<String>,
@@ -251,6 +275,34 @@ pub(crate) fn analysis_actions_methods_run(methods: &mut MethodsBuilder) {
             }
         };
 
+        let resource_limits = ResourceLimits {
+            memory_max_bytes: match memory_limit_mb {
+                Some(0) => return Err(RunActionError::InvalidMemoryLimit(0).into()),
+                v => v.map(|mb| mb.saturating_mul(1024 * 1024)),
+            },
+            cpu_max_millicores: match cpu_limit {
+                Some(UnpackFloat(v)) if v.is_nan() || v <= 0.0 => {
+                    return Err(RunActionError::InvalidCpuLimit(v).into());
+                }
+                v => v.map(|UnpackFloat(cpus)| (cpus * 1000.0).ceil() as u64),
+            },
+        };
+
+        let resource_estimate = ResourceEstimate {
+            memory_bytes: match memory_estimate_mb {
+                Some(0) => return Err(RunActionError::InvalidMemoryEstimate(0).into()),
+                Some(mb) => Some(mb.saturating_mul(1024 * 1024)),
+                None => resource_limits.memory_max_bytes,
+            },
+            cpu_millicores: match cpu_estimate {
+                Some(UnpackFloat(v)) if v.is_nan() || v <= 0.0 => {
+                    return Err(RunActionError::InvalidCpuEstimate(v).into());
+                }
+                Some(UnpackFloat(cpus)) => Some((cpus * 1000.0).ceil() as u64),
+                None => resource_limits.cpu_max_millicores,
+            },
+        };
+
         let starlark_env = match &env {
             None => None,
             Some(env) => {
@@ -343,6 +395,8 @@ pub(crate) fn analysis_actions_methods_run(methods: &mut MethodsBuilder) {
             force_full_hybrid_if_capable,
             unique_input_inodes,
             remote_execution_dependencies: re_dependencies,
+            resource_limits,
+            resource_estimate,
         };
         this.state()?.register_action(
             artifacts.inputs,
@@ -31,6 +31,7 @@ use buck2_build_api::interpreter::rule_defs::context::AnalysisActions;
 use buck2_build_api::interpreter::rule_defs::context::AnalysisContext;
 use buck2_build_api::interpreter::rule_defs::provider::collection::FrozenProviderCollectionValue;
 use buck2_build_api::interpreter::rule_defs::provider::collection::ProviderCollection;
+use buck2_common::dice::invalidation_tracking::get_invalidation_info;
 use buck2_core::base_deferred_key::BaseDeferredKey;
 use buck2_core::fs::artifact_path_resolver::ArtifactFs;
 use buck2_error::buck2_error_anyhow;
@@ -173,6 +174,7 @@ async fn execute_lambda(
         };
 
         let artifact_fs = dice.get_artifact_fs().await?;
+        let invalidation_info = get_invalidation_info(dice);
 
         span_async(start_event, async {
             let mut declared_actions = None;
@@ -274,6 +276,7 @@ async fn execute_lambda(
                     profile: None,
                     declared_actions,
                     declared_artifacts,
+                    invalidation_info,
                 },
             )
         })
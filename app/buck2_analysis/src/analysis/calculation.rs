@@ -21,6 +21,7 @@ use buck2_build_api::analysis::calculation::EVAL_ANALYSIS_QUERY;
 use buck2_build_api::analysis::calculation::RULE_ANALYSIS_CALCULATION;
 use buck2_build_api::analysis::AnalysisResult;
 use buck2_build_api::keep_going::KeepGoing;
+use buck2_common::dice::invalidation_tracking::get_invalidation_info;
 use buck2_core::configuration::compatibility::MaybeCompatible;
 use buck2_core::provider::label::ConfiguredProvidersLabel;
 use buck2_core::target::configured_target_label::ConfiguredTargetLabel;
@@ -294,6 +295,8 @@ async fn get_analysis_result_inner(
                         MaybeCompatible::Compatible(result)
                     };
 
+                    let invalidation_info = get_invalidation_info(ctx);
+
                     (
                         result,
                         buck2_data::AnalysisEnd {
@@ -302,6 +305,7 @@ async fn get_analysis_result_inner(
                             profile,
                             declared_actions,
                             declared_artifacts,
+                            invalidation_info,
                         },
                     )
                 })
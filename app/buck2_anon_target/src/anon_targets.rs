@@ -434,6 +434,7 @@ impl AnonTargetKey {
                     profile: None, // Not implemented for anon targets
                     declared_actions: res.as_ref().ok().map(|v| v.num_declared_actions),
                     declared_artifacts: res.as_ref().ok().map(|v| v.num_declared_artifacts),
+                    invalidation_info: None, // Not implemented for anon targets
                 };
                 (res, end)
             }),
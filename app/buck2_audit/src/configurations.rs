@@ -26,6 +26,10 @@ pub struct AuditConfigurationsCommand {
     )]
     pub configs: Vec<String>,
 
+    /// Show only the differences between the two given configurations.
+    #[clap(long, requires = "configurations")]
+    pub diff: bool,
+
     /// Command doesn't need these flags, but they are used in mode files, so we need to keep them.
     #[clap(flatten)]
     _target_cfg: TargetCfgUnusedOptions,
@@ -27,6 +27,7 @@ use classpath::AuditClasspathCommand;
 use crate::analysis_queries::AuditAnalysisQueriesCommand;
 use crate::cell::AuditCellCommand;
 use crate::config::AuditConfigCommand;
+use crate::configuration_origins::AuditConfigurationOriginsCommand;
 use crate::configurations::AuditConfigurationsCommand;
 use crate::deferred_materializer::DeferredMaterializerCommand;
 use crate::dep_files::AuditDepFilesCommand;
@@ -45,6 +46,7 @@ pub mod analysis_queries;
 pub mod cell;
 pub mod classpath;
 pub mod config;
+pub mod configuration_origins;
 pub mod configurations;
 pub mod deferred_materializer;
 pub mod dep_files;
@@ -65,6 +67,7 @@ pub enum AuditCommand {
     Classpath(AuditClasspathCommand),
     Config(AuditConfigCommand),
     Configurations(AuditConfigurationsCommand),
+    ConfigurationOrigins(AuditConfigurationOriginsCommand),
     Includes(AuditIncludesCommand),
     Prelude(AuditPreludeCommand),
     Providers(AuditProvidersCommand),
@@ -101,6 +104,7 @@ impl AuditCommand {
             AuditCommand::Classpath(cmd) => cmd,
             AuditCommand::Config(cmd) => cmd,
             AuditCommand::Configurations(cmd) => cmd,
+            AuditCommand::ConfigurationOrigins(cmd) => cmd,
             AuditCommand::Includes(cmd) => cmd,
             AuditCommand::Prelude(cmd) => cmd,
             AuditCommand::Providers(cmd) => cmd,
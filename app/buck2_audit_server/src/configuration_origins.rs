@@ -0,0 +1,242 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::io::Write;
+
+use async_trait::async_trait;
+use buck2_audit::configuration_origins::AuditConfigurationOriginsCommand;
+use buck2_build_api::audit_configuration_origins::ConfigurationOrigin;
+use buck2_build_api::audit_configuration_origins::ConfigurationOriginEdge;
+use buck2_build_api::audit_configuration_origins::AUDIT_CONFIGURATION_ORIGINS;
+use buck2_cli_proto::ClientContext;
+use buck2_core::configuration::cfg_diff::cfg_diff;
+use buck2_core::configuration::compatibility::MaybeCompatible;
+use buck2_core::pattern::pattern_type::TargetPatternExtra;
+use buck2_core::target::configured_target_label::ConfiguredTargetLabel;
+use buck2_core::target::label::label::TargetLabel;
+use buck2_node::cfg_constructor::CFG_CONSTRUCTOR_CALCULATION_IMPL;
+use buck2_node::nodes::configured::ConfiguredTargetNode;
+use buck2_node::nodes::configured_frontend::ConfiguredTargetNodeCalculation;
+use buck2_node::nodes::frontend::TargetGraphCalculation;
+use buck2_node::nodes::unconfigured::RuleKind;
+use buck2_server_ctx::ctx::ServerCommandContextTrait;
+use buck2_server_ctx::ctx::ServerCommandDiceContext;
+use buck2_server_ctx::partial_result_dispatcher::PartialResultDispatcher;
+use buck2_server_ctx::pattern_parse_and_resolve::parse_and_resolve_patterns_to_targets_from_cli_args;
+use dice::DiceComputations;
+use dupe::Dupe;
+use futures::FutureExt;
+use indent_write::io::IndentWriter;
+
+use crate::common::configured_target_labels::audit_command_configured_target_labels;
+use crate::ServerAuditSubcommand;
+
+#[derive(Debug, buck2_error::Error)]
+#[buck2(input)]
+enum AuditConfigurationOriginsError {
+    #[error("Expected `{0}` to be a single target, got {1} targets")]
+    NotASingleTarget(String, usize),
+}
+
+#[async_trait]
+impl ServerAuditSubcommand for AuditConfigurationOriginsCommand {
+    async fn server_execute(
+        &self,
+        server_ctx: &dyn ServerCommandContextTrait,
+        mut stdout: PartialResultDispatcher<buck2_cli_proto::StdoutBytes>,
+        _client_ctx: ClientContext,
+    ) -> anyhow::Result<()> {
+        server_ctx
+            .with_dice_ctx(|server_ctx, mut ctx| async move {
+                let targets = parse_and_resolve_patterns_to_targets_from_cli_args::<
+                    TargetPatternExtra,
+                >(
+                    &mut ctx, &[self.target.clone()], server_ctx.working_dir()
+                )
+                .await?;
+                let target = match targets.as_slice() {
+                    [target] => target.target_label.dupe(),
+                    targets => {
+                        return Err(AuditConfigurationOriginsError::NotASingleTarget(
+                            self.target.clone(),
+                            targets.len(),
+                        )
+                        .into());
+                    }
+                };
+                let roots = audit_command_configured_target_labels(
+                    &mut ctx,
+                    &self.roots,
+                    &self.target_cfg,
+                    server_ctx,
+                )
+                .await?;
+
+                let origins = audit_configuration_origins(&mut ctx, &target, &roots).await?;
+
+                let mut stdout = stdout.as_writer();
+                if origins.is_empty() {
+                    writeln!(stdout, "{} is not reachable from the given roots", target)?;
+                    return Ok(());
+                }
+                writeln!(
+                    stdout,
+                    "{} exists in {} configuration(s)",
+                    target,
+                    origins.len()
+                )?;
+                for origin in &origins {
+                    writeln!(stdout)?;
+                    writeln!(stdout, "{}:", origin.target)?;
+                    writeln!(stdout, "  Shortest path:")?;
+                    for (label, edge) in &origin.path {
+                        match edge {
+                            None => writeln!(stdout, "    {}", label)?,
+                            Some(edge) => writeln!(stdout, "    -> {} via {}", label, edge)?,
+                        }
+                    }
+                    if let Some(modifiers) = &origin.root_modifiers {
+                        writeln!(
+                            stdout,
+                            "  Modifiers of {}:",
+                            origin.path[0].0.unconfigured()
+                        )?;
+                        if let Some(package) = &modifiers.package {
+                            writeln!(stdout, "    PACKAGE: {}", package.as_json())?;
+                        }
+                        if let Some(target_modifiers) = &modifiers.target {
+                            writeln!(stdout, "    target: {}", target_modifiers.as_json())?;
+                        }
+                        if !self.target_cfg.target_cfg.cli_modifier.is_empty() {
+                            writeln!(
+                                stdout,
+                                "    command line: {}",
+                                self.target_cfg.target_cfg.cli_modifier.join(", ")
+                            )?;
+                        }
+                    }
+                    if let Some(diff) = &origin.diff {
+                        writeln!(stdout, "  Difference from {}:", origins[0].target.cfg())?;
+                        write!(IndentWriter::new("    ", &mut stdout), "{}", diff)?;
+                    }
+                }
+
+                Ok(())
+            })
+            .await
+    }
+}
+
+/// How `dep` was configured as a dependency of `node`.
+fn configuration_origin_edge(
+    node: &ConfiguredTargetNode,
+    dep: &ConfiguredTargetNode,
+) -> ConfigurationOriginEdge {
+    if node.forward_target().is_some() {
+        if let Some(tr) = &dep.target_node().rule.cfg {
+            return ConfigurationOriginEdge::IncomingTransition(tr.dupe());
+        }
+    }
+    let execution_platform = || match node.execution_platform_resolution().platform() {
+        Ok(platform) => platform.id(),
+        Err(_) => "<unresolved>".to_owned(),
+    };
+    if node.exec_deps().any(|d| d.ptr_eq(dep)) {
+        return ConfigurationOriginEdge::ExecDep(execution_platform());
+    }
+    if dep.rule_kind() == RuleKind::Toolchain {
+        return ConfigurationOriginEdge::ToolchainDep(execution_platform());
+    }
+    if dep.label().cfg() != node.label().cfg() {
+        if let Some((_, tr)) = node
+            .target_node()
+            .transition_deps()
+            .find(|(label, _)| *label == dep.label().unconfigured())
+        {
+            return ConfigurationOriginEdge::Transition(tr.dupe());
+        }
+    }
+    ConfigurationOriginEdge::Dep
+}
+
+async fn audit_configuration_origins(
+    ctx: &mut DiceComputations<'_>,
+    target: &TargetLabel,
+    roots: &[ConfiguredTargetLabel],
+) -> anyhow::Result<Vec<ConfigurationOrigin>> {
+    // Breadth first, so that the first time a target is reached is through a shortest path.
+    // Each reached target maps to the target it was reached from, and how.
+    let mut reached: HashMap<
+        ConfiguredTargetLabel,
+        Option<(ConfiguredTargetLabel, ConfigurationOriginEdge)>,
+    > = HashMap::new();
+    let mut queue = VecDeque::new();
+    for root in roots {
+        if let MaybeCompatible::Compatible(node) = ctx.get_configured_target_node(root).await? {
+            if let Entry::Vacant(e) = reached.entry(node.label().dupe()) {
+                e.insert(None);
+                queue.push_back(node);
+            }
+        }
+    }
+
+    let mut found = Vec::new();
+    while let Some(node) = queue.pop_front() {
+        // Forward nodes only exist to apply incoming transitions, the configuration they forward
+        // to is the one that is built.
+        if node.label().unconfigured() == target && node.forward_target().is_none() {
+            found.push(node.label().dupe());
+        }
+        for dep in node.deps() {
+            if let Entry::Vacant(e) = reached.entry(dep.label().dupe()) {
+                e.insert(Some((
+                    node.label().dupe(),
+                    configuration_origin_edge(&node, dep),
+                )));
+                queue.push_back(dep.dupe());
+            }
+        }
+    }
+
+    let mut origins = Vec::with_capacity(found.len());
+    for label in &found {
+        let mut path = Vec::new();
+        let mut current = label.dupe();
+        while let Some(Some((parent, edge))) = reached.get(&current) {
+            let parent = parent.dupe();
+            path.push((current, Some(edge.clone())));
+            current = parent;
+        }
+        let (root_node, super_package) = ctx
+            .get_target_node_with_super_package(current.unconfigured())
+            .await?;
+        let root_modifiers = CFG_CONSTRUCTOR_CALCULATION_IMPL
+            .get()?
+            .cfg_modifiers(ctx, root_node.as_ref(), &super_package)
+            .await?;
+        path.push((current, None));
+        path.reverse();
+
+        origins.push(ConfigurationOrigin {
+            target: label.dupe(),
+            path,
+            root_modifiers,
+            diff: cfg_diff(found[0].cfg(), label.cfg()).err(),
+        });
+    }
+    Ok(origins)
+}
+
+pub(crate) fn init_audit_configuration_origins() {
+    AUDIT_CONFIGURATION_ORIGINS
+        .init(|ctx, target, roots| audit_configuration_origins(ctx, target, roots).boxed());
+}
@@ -13,6 +13,7 @@ use async_trait::async_trait;
 use buck2_audit::configurations::AuditConfigurationsCommand;
 use buck2_cli_proto::ClientContext;
 use buck2_core::configuration::bound_id::BoundConfigurationId;
+use buck2_core::configuration::cfg_diff::cfg_diff;
 use buck2_core::configuration::data::ConfigurationData;
 use buck2_server_ctx::ctx::ServerCommandContextTrait;
 use buck2_server_ctx::partial_result_dispatcher::PartialResultDispatcher;
@@ -20,6 +21,13 @@ use itertools::Itertools;
 
 use crate::ServerAuditSubcommand;
 
+#[derive(Debug, buck2_error::Error)]
+#[buck2(input)]
+enum AuditConfigurationsError {
+    #[error("`--diff` takes exactly two configurations, got {0}")]
+    DiffRequiresTwo(usize),
+}
+
 #[async_trait]
 impl ServerAuditSubcommand for AuditConfigurationsCommand {
     async fn server_execute(
@@ -30,7 +38,19 @@ impl ServerAuditSubcommand for AuditConfigurationsCommand {
     ) -> anyhow::Result<()> {
         let mut stdout = stdout.as_writer();
 
-        if self.configs.is_empty() {
+        if self.diff {
+            let [a, b] = self.configs.as_slice() else {
+                return Err(AuditConfigurationsError::DiffRequiresTwo(self.configs.len()).into());
+            };
+            let a = ConfigurationData::lookup_bound(BoundConfigurationId::parse(a)?)?;
+            let b = ConfigurationData::lookup_bound(BoundConfigurationId::parse(b)?)?;
+            writeln!(stdout, "--- {}", cfg_name(&a))?;
+            writeln!(stdout, "+++ {}", cfg_name(&b))?;
+            match cfg_diff(&a, &b) {
+                Ok(()) => writeln!(stdout, "Configurations are identical")?,
+                Err(diff) => write!(stdout, "{}", diff)?,
+            }
+        } else if self.configs.is_empty() {
             for cfg in ConfigurationData::iter_existing()
                 .filter(|c| c.is_bound())
                 .sorted_by_cached_key(|c| c.full_name().to_owned())
@@ -49,8 +69,16 @@ impl ServerAuditSubcommand for AuditConfigurationsCommand {
     }
 }
 
+/// The full name of a configuration, along with its display name if it has one.
+fn cfg_name(cfg: &ConfigurationData) -> String {
+    match cfg.display_name() {
+        Some(display_name) => format!("{} ({})", cfg.full_name(), display_name),
+        None => cfg.full_name().to_owned(),
+    }
+}
+
 fn print_cfg(stdout: &mut impl Write, cfg: &ConfigurationData) -> anyhow::Result<()> {
-    writeln!(stdout, "{}:", cfg.full_name())?;
+    writeln!(stdout, "{}:", cfg_name(cfg))?;
     let data = cfg.data()?;
     for (constraint_key, constraint_value) in data
         .constraints
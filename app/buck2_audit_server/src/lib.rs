@@ -24,6 +24,7 @@ mod cell;
 mod classpath;
 mod common;
 mod config;
+mod configuration_origins;
 mod configurations;
 pub mod deferred_materializer;
 mod dep_files;
@@ -85,6 +86,7 @@ impl AuditCommandExt for AuditCommand {
             AuditCommand::Classpath(cmd) => cmd,
             AuditCommand::Config(cmd) => cmd,
             AuditCommand::Configurations(cmd) => cmd,
+            AuditCommand::ConfigurationOrigins(cmd) => cmd,
             AuditCommand::Includes(cmd) => cmd,
             AuditCommand::Prelude(cmd) => cmd,
             AuditCommand::Providers(cmd) => cmd,
@@ -107,6 +109,7 @@ pub fn init_late_bindings() {
     ONCE.call_once(|| {
         output::command::init_audit_output();
         cell::init_audit_cell();
+        configuration_origins::init_audit_configuration_origins();
         server::init_audit_server_command();
     })
 }
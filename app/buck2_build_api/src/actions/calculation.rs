@@ -18,8 +18,10 @@ use async_trait::async_trait;
 use buck2_artifact::actions::key::ActionKey;
 use buck2_artifact::artifact::build_artifact::BuildArtifact;
 use buck2_build_signals::env::NodeDuration;
+use buck2_common::dice::invalidation_tracking::invalidation_info_to_proto;
 use buck2_common::events::HasEvents;
 use buck2_core::base_deferred_key::BaseDeferredKey;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
 use buck2_core::target::configured_target_label::ConfiguredTargetLabel;
 use buck2_data::ActionErrorDiagnostics;
 use buck2_data::ActionSubErrors;
@@ -39,7 +41,6 @@ use buck2_interpreter::soft_error::Buck2StarlarkSoftErrorHandler;
 use buck2_node::nodes::configured_frontend::ConfiguredTargetNodeCalculation;
 use derive_more::Display;
 use dice::DiceComputations;
-use dice::DiceTrackedInvalidationPath;
 use dice::Key;
 use dupe::Dupe;
 use futures::future::BoxFuture;
@@ -195,7 +196,7 @@ async fn build_action_inner(
     action: &Arc<RegisteredAction>,
     target_rule_type_name: Option<String>,
 ) -> (ActionExecutionData, Box<buck2_data::ActionExecutionEnd>) {
-    let (execute_result, command_reports) = executor
+    let (execute_result, command_reports, input_fingerprints) = executor
         .execute(materialized_inputs, action, cancellation)
         .await;
 
@@ -219,6 +220,16 @@ async fn build_action_inner(
         identifier: action.identifier().unwrap_or("").to_owned(),
     };
 
+    if let Some(report) = command_reports.last() {
+        if !report.undeclared_inputs.is_empty() {
+            get_dispatcher().console_warning(undeclared_inputs_warning(
+                action,
+                target_rule_type_name.as_deref(),
+                &report.undeclared_inputs,
+            ));
+        }
+    }
+
     let action_result;
     let execution_kind;
     let wall_time;
@@ -238,6 +249,7 @@ async fn build_action_inner(
     let mut buck2_build_time = None;
     let mut hostname = None;
     let mut input_files_bytes = None;
+    let mut download_source = None;
     let error_diagnostics = match execute_result {
         Ok((outputs, meta)) => {
             output_size = outputs.calc_output_count_and_bytes().bytes;
@@ -246,6 +258,7 @@ async fn build_action_inner(
             wall_time = Some(meta.timing.wall_time);
             error = None;
             input_files_bytes = meta.input_files_bytes;
+            download_source = meta.download_source;
 
             if let Some(command) = meta.execution_kind.command() {
                 prefers_local = Some(command.prefers_local);
@@ -321,22 +334,7 @@ async fn build_action_inner(
         .unwrap_or_default();
 
     let invalidation_info = if executor.invalidation_tracking_enabled() {
-        fn to_proto(
-            invalidation_path: &DiceTrackedInvalidationPath,
-        ) -> Option<buck2_data::command_invalidation_info::InvalidationSource> {
-            match invalidation_path {
-                dice::DiceTrackedInvalidationPath::Clean
-                | dice::DiceTrackedInvalidationPath::Unknown => None,
-                dice::DiceTrackedInvalidationPath::Invalidated(_) => {
-                    Some(buck2_data::command_invalidation_info::InvalidationSource {})
-                }
-            }
-        }
-        let invalidation_paths = ctx.get_invalidation_paths();
-        Some(buck2_data::CommandInvalidationInfo {
-            changed_any: to_proto(&invalidation_paths.normal_priority_path),
-            changed_file: to_proto(&invalidation_paths.high_priority_path),
-        })
+        Some(invalidation_info_to_proto(&ctx.get_invalidation_paths()))
     } else {
         None
     };
@@ -377,6 +375,8 @@ async fn build_action_inner(
             error_diagnostics,
             input_files_bytes,
             invalidation_info,
+            download_source,
+            input_fingerprints,
         }),
     )
 }
@@ -617,7 +617,48 @@ pub async fn command_details(
         command_kind,
         signed_exit_code,
         metadata: Some(command.timing.to_proto()),
+        undeclared_inputs: command
+            .undeclared_inputs
+            .iter()
+            .map(|p| p.to_string())
+            .collect(),
+    }
+}
+
+/// Don't flood the console for actions that read a lot of undeclared files.
+const MAX_REPORTED_UNDECLARED_INPUTS: usize = 10;
+
+fn undeclared_inputs_warning(
+    action: &RegisteredAction,
+    target_rule_type_name: Option<&str>,
+    undeclared_inputs: &[ProjectRelativePathBuf],
+) -> String {
+    let mut message = format!(
+        "Action `{}` ({}",
+        action.owner(),
+        action.category().as_str()
+    );
+    if let Some(identifier) = action.identifier() {
+        message.push_str(&format!(" {}", identifier));
+    }
+    message.push(')');
+    if let Some(rule) = target_rule_type_name {
+        message.push_str(&format!(" of rule `{}`", rule));
+    }
+    message.push_str(" read files that are not declared inputs:");
+    for path in undeclared_inputs
+        .iter()
+        .take(MAX_REPORTED_UNDECLARED_INPUTS)
+    {
+        message.push_str(&format!("\n  {}", path));
+    }
+    if undeclared_inputs.len() > MAX_REPORTED_UNDECLARED_INPUTS {
+        message.push_str(&format!(
+            "\n  ... and {} more",
+            undeclared_inputs.len() - MAX_REPORTED_UNDECLARED_INPUTS
+        ));
     }
+    message
 }
 
 pub async fn get_target_rule_type_name(
@@ -199,6 +199,7 @@ mod tests {
                         }),
                         configuration: Some(buck2_data::Configuration {
                             full_name: "conf".into(),
+                            display_name: None,
                         }),
                         execution_configuration: None,
                     },
@@ -15,6 +15,7 @@ use allocative::Allocative;
 use anyhow::Context;
 use async_trait::async_trait;
 use buck2_artifact::artifact::build_artifact::BuildArtifact;
+use buck2_common::dice::data::GetInvalidationTrackingConfig;
 use buck2_common::dice::data::HasIoProvider;
 use buck2_common::events::HasEvents;
 use buck2_common::http::HasHttpClient;
@@ -23,11 +24,17 @@ use buck2_common::liveliness_observer::NoopLivelinessObserver;
 use buck2_core::execution_types::executor_config::CommandExecutorConfig;
 use buck2_core::fs::artifact_path_resolver::ArtifactFs;
 use buck2_core::fs::buck_out_path::BuckOutPath;
+use buck2_directory::directory::directory::Directory;
+use buck2_directory::directory::directory_iterator::DirectoryIterator;
+use buck2_directory::directory::directory_iterator::DirectoryIteratorPathStack;
+use buck2_directory::directory::entry::DirectoryEntry;
+use buck2_directory::directory::walk::unordered_entry_walk;
 use buck2_events::dispatch::EventDispatcher;
 use buck2_execute::artifact::fs::ExecutorFs;
 use buck2_execute::artifact_value::ArtifactValue;
 use buck2_execute::digest_config::DigestConfig;
 use buck2_execute::digest_config::HasDigestConfig;
+use buck2_execute::directory::ActionDirectoryMember;
 use buck2_execute::execute::action_digest_and_blobs::ActionDigestAndBlobs;
 use buck2_execute::execute::blocking::BlockingExecutor;
 use buck2_execute::execute::blocking::HasBlockingExecutor;
@@ -43,6 +50,7 @@ use buck2_execute::execute::kind::CommandExecutionKind;
 use buck2_execute::execute::manager::CommandExecutionManager;
 use buck2_execute::execute::prepared::PreparedAction;
 use buck2_execute::execute::prepared::PreparedCommand;
+use buck2_execute::execute::request::CommandExecutionInput;
 use buck2_execute::execute::request::CommandExecutionRequest;
 use buck2_execute::execute::request::ExecutorPreference;
 use buck2_execute::execute::request::OutputType;
@@ -71,7 +79,6 @@ use crate::actions::artifact::get_artifact_fs::GetArtifactFs;
 use crate::actions::execute::action_execution_target::ActionExecutionTarget;
 use crate::actions::execute::dice_data::CommandExecutorResponse;
 use crate::actions::execute::dice_data::DiceHasCommandExecutor;
-use crate::actions::execute::dice_data::GetInvalidationTrackingConfig;
 use crate::actions::execute::dice_data::GetReClient;
 use crate::actions::execute::error::ExecuteError;
 use crate::actions::impls::run_action_knobs::HasRunActionKnobs;
@@ -114,6 +121,8 @@ pub struct ActionExecutionMetadata {
     pub execution_kind: ActionExecutionKind,
     pub timing: ActionExecutionTimingData,
     pub input_files_bytes: Option<u64>,
+    /// Where a `download_file` action got its file from.
+    pub download_source: Option<buck2_data::DownloadSource>,
 }
 
 /// The *way* that a particular action was executed.
@@ -320,6 +329,8 @@ struct BuckActionExecutionContext<'a> {
     inputs: IndexMap<ArtifactGroup, ArtifactGroupValues>,
     outputs: &'a [BuildArtifact],
     command_reports: &'a mut Vec<CommandExecutionReport>,
+    /// Set when the command is prepared, if `record_input_fingerprints` is enabled.
+    input_fingerprints: &'a mut Option<buck2_data::ActionInputFingerprints>,
     cancellations: &'a CancellationContext<'a>,
 }
 
@@ -375,7 +386,7 @@ impl ActionExecutionCtx for BuckActionExecutionContext<'_> {
     }
 
     fn run_action_knobs(&self) -> RunActionKnobs {
-        self.executor.run_action_knobs
+        self.executor.run_action_knobs.dupe()
     }
 
     fn cancellation_context(&self) -> &CancellationContext {
@@ -390,6 +401,9 @@ impl ActionExecutionCtx for BuckActionExecutionContext<'_> {
         &mut self,
         request: &CommandExecutionRequest,
     ) -> anyhow::Result<PreparedAction> {
+        if self.run_action_knobs().record_input_fingerprints {
+            *self.input_fingerprints = Some(input_fingerprints(request, self.fs())?);
+        }
         self.executor
             .command_executor
             .prepare_action(request, self.digest_config())
@@ -462,6 +476,7 @@ impl ActionExecutionCtx for BuckActionExecutionContext<'_> {
                         },
                         timing: report.timing.into(),
                         input_files_bytes,
+                        download_source: None,
                     },
                 );
                 Ok(result)
@@ -574,8 +589,10 @@ impl BuckActionExecutor {
     ) -> (
         Result<(ActionOutputs, ActionExecutionMetadata), ExecuteError>,
         Vec<CommandExecutionReport>,
+        Option<buck2_data::ActionInputFingerprints>,
     ) {
         let mut command_reports = Vec::new();
+        let mut input_fingerprints = None;
 
         let res = async {
             let outputs = action.outputs();
@@ -586,6 +603,7 @@ impl BuckActionExecutor {
                 inputs,
                 outputs: outputs.as_ref(),
                 command_reports: &mut command_reports,
+                input_fingerprints: &mut input_fingerprints,
                 cancellations,
             };
 
@@ -655,7 +673,7 @@ impl BuckActionExecutor {
         }
         .await;
 
-        (res, command_reports)
+        (res, command_reports, input_fingerprints)
     }
 
     pub fn invalidation_tracking_enabled(&self) -> bool {
@@ -663,6 +681,69 @@ impl BuckActionExecutor {
     }
 }
 
+/// What a command depends on: its command line, its environment and the digest of every input
+/// file, sorted by path. Directories are expanded into their files, so that a change to a directory
+/// shows which file changed.
+fn input_fingerprints(
+    request: &CommandExecutionRequest,
+    fs: &ArtifactFs,
+) -> anyhow::Result<buck2_data::ActionInputFingerprints> {
+    let mut inputs = Vec::new();
+    for input in request.inputs() {
+        match input {
+            CommandExecutionInput::Artifact(group) => {
+                for (artifact, value) in group.iter() {
+                    let path = artifact.resolve_path(fs)?;
+                    let mut walk =
+                        unordered_entry_walk(value.entry().as_ref().map_dir(Directory::as_ref));
+                    while let Some((entry_path, entry)) = walk.next() {
+                        let digest = match entry {
+                            DirectoryEntry::Dir(_) => continue,
+                            DirectoryEntry::Leaf(ActionDirectoryMember::File(file)) => {
+                                file.digest.to_string()
+                            }
+                            DirectoryEntry::Leaf(ActionDirectoryMember::Symlink(symlink)) => {
+                                format!("symlink:{}", symlink.target())
+                            }
+                            DirectoryEntry::Leaf(ActionDirectoryMember::ExternalSymlink(
+                                symlink,
+                            )) => format!("symlink:{}", symlink.target().display()),
+                        };
+                        inputs.push(buck2_data::ActionInputFingerprint {
+                            path: path.join(entry_path.get()).to_string(),
+                            digest,
+                        });
+                    }
+                }
+            }
+            CommandExecutionInput::ActionMetadata(metadata) => {
+                inputs.push(buck2_data::ActionInputFingerprint {
+                    path: fs.resolve_build(&metadata.path).to_string(),
+                    digest: metadata.digest.to_string(),
+                });
+            }
+            // Scratch paths always start out empty.
+            CommandExecutionInput::ScratchPath(..) => {}
+        }
+    }
+    inputs.sort_by(|a, b| a.path.cmp(&b.path));
+    // The same file can be in more than one input.
+    inputs.dedup_by(|a, b| a.path == b.path);
+
+    Ok(buck2_data::ActionInputFingerprints {
+        argv: request.all_args_vec(),
+        env: request
+            .env()
+            .iter()
+            .map(|(key, value)| buck2_data::EnvironmentEntry {
+                key: key.clone(),
+                value: value.clone(),
+            })
+            .collect(),
+        inputs,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use std::borrow::Cow;
@@ -898,6 +979,7 @@ mod tests {
                         execution_kind: ActionExecutionKind::Simple,
                         timing: ActionExecutionTimingData::default(),
                         input_files_bytes: None,
+                        download_source: None,
                     },
                 ))
             }
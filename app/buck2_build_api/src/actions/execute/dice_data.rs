@@ -123,31 +123,3 @@ impl GetReClient for UserComputationData {
             .dupe()
     }
 }
-
-#[derive(Debug, Clone, Copy, Dupe)]
-pub struct InvalidationTrackingConfig {
-    pub enabled: bool,
-}
-
-pub trait SetInvalidationTrackingConfig {
-    fn set_invalidation_tracking_config(&mut self, enabled: bool);
-}
-
-pub trait GetInvalidationTrackingConfig {
-    fn get_invalidation_tracking_config(&self) -> InvalidationTrackingConfig;
-}
-
-impl SetInvalidationTrackingConfig for DiceDataBuilder {
-    fn set_invalidation_tracking_config(&mut self, enabled: bool) {
-        self.set(InvalidationTrackingConfig { enabled });
-    }
-}
-
-impl GetInvalidationTrackingConfig for DiceComputations<'_> {
-    fn get_invalidation_tracking_config(&self) -> InvalidationTrackingConfig {
-        *self
-            .global_data()
-            .get::<InvalidationTrackingConfig>()
-            .expect("InvalidationTrackingConfig should be set")
-    }
-}
@@ -7,11 +7,14 @@
  * of this source tree.
  */
 
+use std::sync::Arc;
+
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
 use dice::UserComputationData;
 use dupe::Dupe;
 
 /// Knobs controlling how RunAction works.
-#[derive(Copy, Clone, Dupe, Default)]
+#[derive(Clone, Dupe, Default)]
 pub struct RunActionKnobs {
     /// Process dep files as they are generated.
     pub eager_dep_files: bool,
@@ -25,6 +28,17 @@ pub struct RunActionKnobs {
     /// builds.
     pub use_network_action_output_cache: bool,
 
+    /// Whether this is an `--offline` build, where actions must not use the network.
+    pub offline: bool,
+
+    /// A directory of files named by their sha256, which `download_file` copies from instead of
+    /// downloading.
+    pub download_mirror_dir: Option<Arc<AbsNormPathBuf>>,
+
+    /// Record the command line, environment and input digests of every command in its
+    /// `ActionExecutionEnd`, so that `buck2 log why-rebuilt` can compare two executions.
+    pub record_input_fingerprints: bool,
+
     /// TODO(cjhopman): Modifies action digest, remove after migration
     pub new_style_scratch_path: bool,
 }
@@ -41,9 +55,9 @@ impl HasRunActionKnobs for UserComputationData {
     }
 
     fn get_run_action_knobs(&self) -> RunActionKnobs {
-        *self
-            .data
+        self.data
             .get::<RunActionKnobs>()
             .expect("RunActionKnobs should be set")
+            .dupe()
     }
 }
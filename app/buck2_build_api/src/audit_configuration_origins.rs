@@ -0,0 +1,83 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::fmt;
+use std::sync::Arc;
+
+use buck2_core::configuration::transition::id::TransitionId;
+use buck2_core::target::configured_target_label::ConfiguredTargetLabel;
+use buck2_core::target::label::label::TargetLabel;
+use buck2_node::cfg_constructor::CfgModifiers;
+use buck2_util::late_binding::LateBinding;
+use dice::DiceComputations;
+use futures::future::BoxFuture;
+
+/// How a target on the path from a root was reached from the target before it.
+#[derive(Debug, Clone)]
+pub enum ConfigurationOriginEdge {
+    /// A plain dependency, which keeps the configuration of the dependent.
+    Dep,
+    /// A dependency through an attribute with an outgoing (possibly split) transition.
+    Transition(Arc<TransitionId>),
+    /// The incoming transition set by the rule of the dependency.
+    IncomingTransition(Arc<TransitionId>),
+    /// An exec dep, configured for the execution platform resolved for the dependent.
+    ExecDep(String),
+    /// A toolchain dep, which uses the execution platform resolved for the dependent.
+    ToolchainDep(String),
+}
+
+impl fmt::Display for ConfigurationOriginEdge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigurationOriginEdge::Dep => write!(f, "dep"),
+            ConfigurationOriginEdge::Transition(tr) => write!(f, "transition {}", tr),
+            ConfigurationOriginEdge::IncomingTransition(tr) => {
+                write!(f, "incoming transition {}", tr)
+            }
+            ConfigurationOriginEdge::ExecDep(platform) => {
+                write!(f, "exec_dep on execution platform {}", platform)
+            }
+            ConfigurationOriginEdge::ToolchainDep(platform) => {
+                write!(f, "toolchain_dep on execution platform {}", platform)
+            }
+        }
+    }
+}
+
+/// Why a target exists in one particular configuration.
+#[derive(Debug, Clone)]
+pub struct ConfigurationOrigin {
+    pub target: ConfiguredTargetLabel,
+    /// The shortest path from one of the roots to `target`, starting with the root, along with
+    /// the edge each target was reached by (`None` for the root).
+    pub path: Vec<(ConfiguredTargetLabel, Option<ConfigurationOriginEdge>)>,
+    /// The modifiers the cfg constructor was invoked with for the root, if there is a cfg
+    /// constructor.
+    pub root_modifiers: Option<CfgModifiers>,
+    /// How the configuration differs from the one of the first origin, `None` for the first one.
+    pub diff: Option<String>,
+}
+
+pub static AUDIT_CONFIGURATION_ORIGINS: LateBinding<
+    for<'v> fn(
+        ctx: &'v mut DiceComputations<'_>,
+        target: &'v TargetLabel,
+        roots: &'v [ConfiguredTargetLabel],
+    ) -> BoxFuture<'v, anyhow::Result<Vec<ConfigurationOrigin>>>,
+> = LateBinding::new("AUDIT_CONFIGURATION_ORIGINS");
+
+/// Find each configuration of `target` reachable from `roots`, and how it was reached.
+pub async fn audit_configuration_origins<'v>(
+    ctx: &'v mut DiceComputations<'_>,
+    target: &'v TargetLabel,
+    roots: &'v [ConfiguredTargetLabel],
+) -> anyhow::Result<Vec<ConfigurationOrigin>> {
+    (AUDIT_CONFIGURATION_ORIGINS.get()?)(ctx, target, roots).await
+}
@@ -10,6 +10,7 @@
 use std::sync::Arc;
 
 use buck2_common::dice::cells::SetCellResolver;
+use buck2_common::dice::data::SetInvalidationTrackingConfig;
 use buck2_common::dice::data::SetIoProvider;
 use buck2_common::io::IoProvider;
 use buck2_common::legacy_configs::configs::LegacyBuckConfig;
@@ -22,8 +23,6 @@ use dice::DetectCycles;
 use dice::Dice;
 use dice::WhichDice;
 
-use crate::actions::execute::dice_data::SetInvalidationTrackingConfig;
-
 /// This is just a simple version number to allow us to more easily rollout modern dice.
 const CURRENT_MODERN_DICE_VERSION: u32 = 3;
 
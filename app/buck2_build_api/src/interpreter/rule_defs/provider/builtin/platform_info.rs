@@ -15,10 +15,12 @@ use buck2_core::configuration::data::ConfigurationData;
 use starlark::any::ProvidesStaticType;
 use starlark::coerce::Coerce;
 use starlark::environment::GlobalsBuilder;
+use starlark::values::none::NoneOr;
 use starlark::values::Freeze;
 use starlark::values::Heap;
 use starlark::values::StringValue;
 use starlark::values::Trace;
+use starlark::values::Value;
 use starlark::values::ValueLifetimeless;
 use starlark::values::ValueLike;
 use starlark::values::ValueOf;
@@ -34,11 +36,13 @@ use crate::interpreter::rule_defs::provider::builtin::configuration_info::Frozen
 pub struct PlatformInfoGen<V: ValueLifetimeless> {
     label: ValueOfUncheckedGeneric<V, String>,
     configuration: ValueOfUncheckedGeneric<V, FrozenConfigurationInfo>,
+    /// Short name to show for the configuration in place of its label and hash.
+    display_name: ValueOfUncheckedGeneric<V, NoneOr<String>>,
 }
 
 impl<'v, V: ValueLike<'v>> PlatformInfoGen<V> {
     pub fn to_configuration(&self) -> anyhow::Result<ConfigurationData> {
-        ConfigurationData::from_platform(
+        ConfigurationData::from_platform_with_display_name(
             self.label
                 .to_value()
                 .get()
@@ -48,6 +52,7 @@ impl<'v, V: ValueLike<'v>> PlatformInfoGen<V> {
             ConfigurationInfo::from_value(self.configuration.get().to_value())
                 .expect("type checked during construction")
                 .to_configuration_data()?,
+            self.display_name.get().to_value().unpack_str(),
         )
     }
 }
@@ -62,9 +67,14 @@ impl<'v> PlatformInfo<'v> {
             cfg.data()?,
             heap,
         ));
+        let display_name = match cfg.display_name() {
+            Some(display_name) => heap.alloc_str(display_name).to_value(),
+            None => Value::new_none(),
+        };
         Ok(PlatformInfoGen {
             label: label.to_value_of_unchecked().cast(),
             configuration: ValueOfUnchecked::<FrozenConfigurationInfo>::new(configuration),
+            display_name: ValueOfUnchecked::new(display_name),
         })
     }
 }
@@ -75,10 +85,16 @@ fn platform_info_creator(globals: &mut GlobalsBuilder) {
     fn PlatformInfo<'v>(
         #[starlark(require = named)] label: StringValue<'v>,
         #[starlark(require = named)] configuration: ValueOf<'v, &'v ConfigurationInfo<'v>>,
+        #[starlark(require = named, default = NoneOr::None)] display_name: NoneOr<StringValue<'v>>,
     ) -> anyhow::Result<PlatformInfo<'v>> {
+        let display_name = match display_name {
+            NoneOr::None => Value::new_none(),
+            NoneOr::Other(display_name) => display_name.to_value(),
+        };
         Ok(PlatformInfo {
             label: label.to_value_of_unchecked().cast(),
             configuration: ValueOfUnchecked::<FrozenConfigurationInfo>::new(configuration.value),
+            display_name: ValueOfUnchecked::new(display_name),
         })
     }
 }
@@ -14,6 +14,8 @@ use std::sync::atomic::AtomicU64;
 use allocative::Allocative;
 use anyhow::Context;
 use buck2_build_api_derive::internal_provider;
+use buck2_execute::execute::request::BazelWorkerEncoding;
+use buck2_execute::execute::request::WorkerProtocol;
 use starlark::any::ProvidesStaticType;
 use starlark::coerce::Coerce;
 use starlark::environment::GlobalsBuilder;
@@ -45,10 +47,48 @@ pub struct WorkerInfoGen<V: ValueLifetimeless> {
     pub exe: ValueOfUncheckedGeneric<V, FrozenStarlarkCmdArgs>,
     // Maximum number of concurrent commands to execute on a worker instance without queuing
     pub concurrency: ValueOfUncheckedGeneric<V, NoneOr<usize>>,
+    // Protocol spoken by the worker: `buck2`, `bazel_json` or `bazel_proto`
+    pub protocol: ValueOfUncheckedGeneric<V, String>,
+    // Whether a Bazel worker accepts several requests at once
+    pub multiplex: ValueOfUncheckedGeneric<V, bool>,
+    // Whether a Bazel worker handles `cancel` requests, which are sent when a request times out
+    pub supports_cancellation: ValueOfUncheckedGeneric<V, bool>,
 
     pub id: u64,
 }
 
+#[derive(Debug, buck2_error::Error)]
+enum WorkerInfoError {
+    #[error("Unknown worker protocol `{0}`, expected one of `buck2`, `bazel_json`, `bazel_proto`")]
+    UnknownProtocol(String),
+    #[error("`multiplex` is only supported for Bazel workers")]
+    MultiplexRequiresBazel,
+    #[error("`supports_cancellation` is only supported for Bazel workers")]
+    CancellationRequiresBazel,
+}
+
+fn parse_protocol(
+    protocol: &str,
+    multiplex: bool,
+    supports_cancellation: bool,
+) -> anyhow::Result<WorkerProtocol> {
+    let encoding = match protocol {
+        "buck2" if multiplex => return Err(WorkerInfoError::MultiplexRequiresBazel.into()),
+        "buck2" if supports_cancellation => {
+            return Err(WorkerInfoError::CancellationRequiresBazel.into());
+        }
+        "buck2" => return Ok(WorkerProtocol::Buck2),
+        "bazel_json" => BazelWorkerEncoding::Json,
+        "bazel_proto" => BazelWorkerEncoding::Proto,
+        _ => return Err(WorkerInfoError::UnknownProtocol(protocol.to_owned()).into()),
+    };
+    Ok(WorkerProtocol::Bazel {
+        encoding,
+        multiplex,
+        supports_cancellation,
+    })
+}
+
 fn next_id() -> u64 {
     static LAST_ID: AtomicU64 = AtomicU64::new(0);
     LAST_ID.fetch_add(1, atomic::Ordering::Relaxed) + 1
@@ -62,16 +102,23 @@ fn worker_info_creator(globals: &mut GlobalsBuilder) {
         #[starlark(require = named, default = NoneOr::None)] concurrency: NoneOr<
             ValueOf<'v, usize>,
         >,
+        #[starlark(require = named, default = "buck2")] protocol: &str,
+        #[starlark(require = named, default = false)] multiplex: bool,
+        #[starlark(require = named, default = false)] supports_cancellation: bool,
         eval: &mut Evaluator<'v, '_, '_>,
     ) -> anyhow::Result<WorkerInfo<'v>> {
         let heap = eval.heap();
         let valid_exe = StarlarkCmdArgs::try_from_value(exe)?;
         let exe = ValueOfUnchecked::new(heap.alloc(valid_exe));
+        parse_protocol(protocol, multiplex, supports_cancellation)?;
         let id = next_id();
         Ok(WorkerInfo {
             exe,
             id,
             concurrency: heap.alloc_typed_unchecked(concurrency).cast(),
+            protocol: heap.alloc_typed_unchecked(protocol).cast(),
+            multiplex: heap.alloc_typed_unchecked(multiplex).cast(),
+            supports_cancellation: heap.alloc_typed_unchecked(supports_cancellation).cast(),
         })
     }
 }
@@ -90,6 +137,26 @@ impl<'v, V: ValueLike<'v>> WorkerInfoGen<V> {
             .expect("validated at construction")
             .into_option()
     }
+
+    pub fn protocol(&self) -> WorkerProtocol {
+        let protocol = self
+            .protocol
+            .to_value()
+            .unpack_str()
+            .expect("validated at construction");
+        let multiplex = self
+            .multiplex
+            .to_value()
+            .unpack_bool()
+            .expect("validated at construction");
+        let supports_cancellation = self
+            .supports_cancellation
+            .to_value()
+            .unpack_bool()
+            .expect("validated at construction");
+        parse_protocol(protocol, multiplex, supports_cancellation)
+            .expect("validated at construction")
+    }
 }
 
 fn validate_worker_info<'v, V>(info: &WorkerInfoGen<V>) -> anyhow::Result<()>
@@ -109,5 +176,29 @@ where
         ));
     }
 
+    let protocol = info.protocol.to_value().unpack_str().with_context(|| {
+        format!(
+            "Value for `protocol` field is not a string: `{}`",
+            info.protocol
+        )
+    })?;
+    let multiplex = info.multiplex.to_value().unpack_bool().with_context(|| {
+        format!(
+            "Value for `multiplex` field is not a bool: `{}`",
+            info.multiplex
+        )
+    })?;
+    let supports_cancellation = info
+        .supports_cancellation
+        .to_value()
+        .unpack_bool()
+        .with_context(|| {
+            format!(
+                "Value for `supports_cancellation` field is not a bool: `{}`",
+                info.supports_cancellation
+            )
+        })?;
+    parse_protocol(protocol, multiplex, supports_cancellation)?;
+
     Ok(())
 }
@@ -25,6 +25,7 @@ pub mod analysis;
 pub mod artifact_groups;
 pub mod attrs;
 pub mod audit_cell;
+pub mod audit_configuration_origins;
 pub mod audit_dep_files;
 pub mod audit_output;
 pub mod build;
@@ -27,7 +27,6 @@ use buck2_build_api::actions::execute::dice_data::set_fallback_executor_config;
 use buck2_build_api::actions::execute::dice_data::CommandExecutorResponse;
 use buck2_build_api::actions::execute::dice_data::HasCommandExecutor;
 use buck2_build_api::actions::execute::dice_data::SetCommandExecutor;
-use buck2_build_api::actions::execute::dice_data::SetInvalidationTrackingConfig;
 use buck2_build_api::actions::execute::dice_data::SetReClient;
 use buck2_build_api::actions::impls::run_action_knobs::RunActionKnobs;
 use buck2_build_api::actions::registry::RecordedActions;
@@ -42,6 +41,7 @@ use buck2_build_api::keep_going::HasKeepGoing;
 use buck2_build_api::spawner::BuckSpawner;
 use buck2_common::dice::cells::SetCellResolver;
 use buck2_common::dice::data::testing::SetTestingIoProvider;
+use buck2_common::dice::data::SetInvalidationTrackingConfig;
 use buck2_common::external_symlink::ExternalSymlink;
 use buck2_common::file_ops::testing::TestFileOps;
 use buck2_common::file_ops::FileMetadata;
@@ -538,6 +538,7 @@ async fn test_command_details_omission() {
             stderr: "stderr".to_owned().into_bytes(),
         },
         exit_code: Some(1),
+        undeclared_inputs: Vec::new(),
     };
 
     let proto = command_details(&report, false).await;
@@ -10,12 +10,16 @@
 use allocative::Allocative;
 use anyhow::Context as _;
 use buck2_build_api::audit_cell::audit_cell;
+use buck2_build_api::audit_configuration_origins::audit_configuration_origins;
 use buck2_build_api::audit_output::audit_output;
 use buck2_build_api::audit_output::AuditOutputResult;
 use buck2_common::global_cfg_options::GlobalCfgOptions;
 use buck2_core::cells::CellResolver;
 use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+use buck2_interpreter::types::target_label::StarlarkConfiguredTargetLabel;
 use buck2_interpreter::types::target_label::StarlarkTargetLabel;
+use buck2_node::nodes::configured::ConfiguredTargetNode;
+use buck2_node::nodes::unconfigured::TargetNode;
 use derivative::Derivative;
 use derive_more::Display;
 use dupe::Dupe;
@@ -26,6 +30,7 @@ use starlark::environment::MethodsBuilder;
 use starlark::environment::MethodsStatic;
 use starlark::starlark_module;
 use starlark::values::dict::AllocDict;
+use starlark::values::list::AllocList;
 use starlark::values::list_or_tuple::UnpackListOrTuple;
 use starlark::values::none::NoneOr;
 use starlark::values::starlark_value;
@@ -39,6 +44,11 @@ use starlark::values::ValueTyped;
 
 use crate::bxl::starlark_defs::context::BxlContext;
 use crate::bxl::starlark_defs::nodes::action::StarlarkAction;
+use crate::bxl::starlark_defs::target_list_expr::filter_incompatible;
+use crate::bxl::starlark_defs::target_list_expr::ConfiguredTargetListExprArg;
+use crate::bxl::starlark_defs::target_list_expr::TargetListExpr;
+use crate::bxl::starlark_defs::target_list_expr::TargetListExprArg;
+use crate::bxl::starlark_defs::target_list_expr::TargetNodeOrTargetLabelOrStr;
 use crate::bxl::value_as_starlark_target_label::ValueAsStarlarkTargetLabel;
 
 #[derive(
@@ -200,4 +210,115 @@ fn audit_methods(builder: &mut MethodsBuilder) {
             .boxed_local()
         })
     }
+
+    /// Explains why a target exists in multiple configurations, same as
+    /// `buck2 audit configuration-origins`.
+    ///
+    /// Takes the following parameters:
+    /// * `target` - the unconfigured target to explain the configurations of.
+    /// * `roots` - the configured targets to search for configurations of `target` from.
+    /// * optional `target_platform` - the target platform to configure `roots` with, if they are
+    /// unconfigured. Otherwise the default target platform is used.
+    ///
+    /// Returns a list with a dict for each configuration of `target` reachable from `roots`, with:
+    /// * `target` - the configured target label.
+    /// * `path` - the shortest path from one of the roots, as a list of (configured target label,
+    /// edge) tuples. The edge describes the transition or execution platform the target was
+    /// configured with, and is None for the root.
+    /// * `modifiers` - a dict with the `package` and `target` modifiers of the root, or None if
+    /// there is no cfg constructor.
+    /// * `diff` - how the configuration differs from the first one, or None for the first one.
+    ///
+    /// Sample usage:
+    /// ```text
+    /// def _impl_audit_configuration_origins(ctx):
+    ///     for origin in ctx.audit().configuration_origins("root//:lib", "root//:bin"):
+    ///         ctx.output.print(origin["target"], origin["path"])
+    /// ```
+    fn configuration_origins<'v>(
+        this: &StarlarkAuditCtx<'v>,
+        #[starlark(require = pos)] target: TargetNodeOrTargetLabelOrStr<'v>,
+        #[starlark(require = pos)] roots: ConfiguredTargetListExprArg<'v>,
+        #[starlark(require = named, default = ValueAsStarlarkTargetLabel::NONE)]
+        target_platform: ValueAsStarlarkTargetLabel<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Vec<Value<'v>>> {
+        let target_platform = target_platform.parse_target_platforms(
+            this.ctx.target_alias_resolver(),
+            this.ctx.cell_resolver(),
+            this.ctx.cell_alias_resolver(),
+            this.ctx.cell_name(),
+            &this.ctx.global_cfg_options().target_platform,
+        )?;
+        let global_cfg_options = GlobalCfgOptions {
+            target_platform,
+            cli_modifiers: vec![].into(),
+        };
+
+        this.ctx.async_ctx.borrow_mut().via(|ctx| {
+            async move {
+                let target = TargetListExpr::<'v, TargetNode>::unpack(
+                    TargetListExprArg::Target(target),
+                    &this.ctx.data,
+                    ctx,
+                )
+                .await?
+                .get_one(ctx)
+                .await?
+                .context("Expected a single target")?;
+                let roots = filter_incompatible(
+                    TargetListExpr::<'v, ConfiguredTargetNode>::unpack(
+                        roots,
+                        &global_cfg_options,
+                        &this.ctx.data,
+                        ctx,
+                    )
+                    .await?
+                    .get(ctx)
+                    .await?,
+                    &this.ctx.data,
+                )?;
+                let roots: Vec<_> = roots.iter().map(|root| root.label().dupe()).collect();
+
+                let origins = audit_configuration_origins(ctx, target.label(), &roots).await?;
+                Ok(origins
+                    .into_iter()
+                    .map(|origin| {
+                        let path = origin.path.into_iter().map(|(label, edge)| {
+                            (
+                                StarlarkConfiguredTargetLabel::new(label),
+                                NoneOr::from_option(edge.map(|edge| edge.to_string())),
+                            )
+                        });
+                        let modifiers = origin.root_modifiers.map(|modifiers| {
+                            AllocDict([
+                                (
+                                    "package",
+                                    NoneOr::from_option(
+                                        modifiers.package.map(|m| m.as_json().to_string()),
+                                    ),
+                                ),
+                                (
+                                    "target",
+                                    NoneOr::from_option(
+                                        modifiers.target.map(|m| m.as_json().to_string()),
+                                    ),
+                                ),
+                            ])
+                        });
+                        heap.alloc(AllocDict([
+                            (
+                                "target",
+                                heap.alloc(StarlarkConfiguredTargetLabel::new(origin.target)),
+                            ),
+                            ("path", heap.alloc(AllocList(path))),
+                            ("modifiers", heap.alloc(NoneOr::from_option(modifiers))),
+                            ("diff", heap.alloc(NoneOr::from_option(origin.diff))),
+                        ]))
+                    })
+                    .collect())
+            }
+            .boxed_local()
+        })
+    }
 }
@@ -25,6 +25,7 @@ use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
 use buck2_core::package::package_relative_path::PackageRelativePath;
 use buck2_core::package::source_path::SourcePath;
 use buck2_core::package::PackageLabel;
+use buck2_interpreter_for_build::interpreter::globspec::GlobSpec;
 use buck2_node::nodes::unconfigured::TargetNode;
 use derivative::Derivative;
 use derive_more::Display;
@@ -36,6 +37,9 @@ use starlark::environment::MethodsBuilder;
 use starlark::environment::MethodsStatic;
 use starlark::eval::Evaluator;
 use starlark::starlark_module;
+use starlark::values::list::AllocList;
+use starlark::values::list::UnpackList;
+use starlark::values::list_or_tuple::UnpackListOrTuple;
 use starlark::values::none::NoneOr;
 use starlark::values::starlark_value;
 use starlark::values::AllocValue;
@@ -46,6 +50,7 @@ use starlark::values::StringValue;
 use starlark::values::Trace;
 use starlark::values::Value;
 use starlark::values::ValueOf;
+use starlark::values::ValueOfUnchecked;
 use starlark::values::ValueTyped;
 
 use super::BxlContext;
@@ -108,6 +113,9 @@ pub(crate) enum BxlFilesystemError {
     PackageMismatch(PackageLabel, CellPath),
     #[error("Expected a single target hint, not an iterable: `{0}`")]
     MultipleTargetHintsNotSupported(String),
+    #[error("Error parsing `{0}` as JSON")]
+    #[buck2(input)]
+    InvalidJson(CellPath),
 }
 
 impl<'v> BxlFilesystem<'v> {
@@ -209,6 +217,96 @@ fn fs_operations(builder: &mut MethodsBuilder) {
         })
     }
 
+    /// Returns the contents of the given file as a string, reading it through Buck's cached
+    /// filesystem, so that the bxl function is rerun when the file changes. Errors if the file does
+    /// not exist.
+    /// The input is a either a literal, a source artifact (via `artifact`), or a `file_node`.
+    ///
+    /// Sample usage:
+    /// ```text
+    /// def _impl_read_text(ctx):
+    ///     ctx.output.print(ctx.fs.read_text("foo/config.txt"))
+    /// ```
+    fn read_text<'v>(
+        this: &'v BxlFilesystem<'v>,
+        expr: FileExpr<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<StringValue<'v>> {
+        let contents = this.ctx.async_ctx.borrow_mut().via(|dice| {
+            async {
+                let path = expr.get(dice, this.cell()?).await?;
+                DiceFileComputations::read_file(dice, path.as_ref()).await
+            }
+            .boxed_local()
+        })?;
+        Ok(heap.alloc_str(&contents))
+    }
+
+    /// Like `read_text`, but parses the contents of the file as JSON and returns the corresponding
+    /// Starlark value.
+    ///
+    /// Sample usage:
+    /// ```text
+    /// def _impl_read_json(ctx):
+    ///     config = ctx.fs.read_json("foo/config.json")
+    ///     ctx.output.print(config["name"])
+    /// ```
+    fn read_json<'v>(
+        this: &'v BxlFilesystem<'v>,
+        expr: FileExpr<'v>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<Value<'v>> {
+        let json: serde_json::Value = this.ctx.async_ctx.borrow_mut().via(|dice| {
+            async {
+                let path = expr.get(dice, this.cell()?).await?;
+                let contents = DiceFileComputations::read_file(dice, path.as_ref()).await?;
+                serde_json::from_str(&contents).map_err(|e| {
+                    anyhow::Error::new(e).context(BxlFilesystemError::InvalidJson(path))
+                })
+            }
+            .boxed_local()
+        })?;
+        Ok(heap.alloc(json))
+    }
+
+    /// Returns the files of the package in the directory `package` that match any of `patterns`
+    /// and none of `exclude`, sorted. Like `glob` in `BUCK` files, the patterns and the returned
+    /// paths are relative to the package, and files in subpackages or ignored by Buck are never
+    /// matched. Errors if the directory is not a package. The package is listed through Buck's
+    /// cached filesystem, so the bxl function is rerun when files are added or removed.
+    /// The package is a either a literal, a source artifact (via `artifact`), or a `file_node`.
+    ///
+    /// Sample usage:
+    /// ```text
+    /// def _impl_glob(ctx):
+    ///     for path in ctx.fs.glob("foo", ["**/*.json"], exclude = ["test/**"]):
+    ///         ctx.output.print(path)
+    /// ```
+    fn glob<'v>(
+        this: &'v BxlFilesystem<'v>,
+        package: FileExpr<'v>,
+        patterns: UnpackListOrTuple<String>,
+        #[starlark(require = named, default = UnpackListOrTuple::default())]
+        exclude: UnpackListOrTuple<String>,
+        heap: &'v Heap,
+    ) -> anyhow::Result<ValueOfUnchecked<'v, UnpackList<String>>> {
+        let spec = GlobSpec::new(&patterns.items, &exclude.items)?;
+        let listing = this.ctx.async_ctx.borrow_mut().via(|dice| {
+            async {
+                let path = package.get(dice, this.cell()?).await?;
+                let package = PackageLabel::from_cell_path(path.as_ref());
+                Ok(DicePackageListingResolver(dice).resolve(package).await?)
+            }
+            .boxed_local()
+        })?;
+        // The listing is sorted, so the matches are too.
+        Ok(heap
+            .alloc_typed_unchecked(AllocList(
+                spec.resolve_glob(listing.files()).map(|p| p.as_str()),
+            ))
+            .cast())
+    }
+
     /// Returns whether the provided path is a dir. Returns false is the dir does not exist.
     /// The input is a either a literal, a source artifact (via `artifact`), or a `file_node`.
     ///
@@ -16,6 +16,7 @@ use anyhow::Context;
 use async_trait::async_trait;
 use buck2_build_api::actions::artifact::get_artifact_fs::GetArtifactFs;
 use buck2_build_api::artifact_groups::ArtifactGroup;
+use buck2_build_api::artifact_groups::ArtifactGroupValues;
 use buck2_build_api::build::build_report::generate_build_report;
 use buck2_build_api::build::build_report::BuildReportOpts;
 use buck2_build_api::build::ConfiguredBuildTargetResult;
@@ -126,12 +127,12 @@ impl ServerCommandTemplate for BxlServerCommand {
     }
 }
 
-async fn bxl(
+/// Resolve the `BxlKey` to evaluate for the request, or `None` if the user passed `--help`.
+pub(crate) async fn bxl_key(
     server_ctx: &dyn ServerCommandContextTrait,
-    stdout: impl Write,
-    mut ctx: DiceTransaction,
+    ctx: &mut DiceTransaction,
     request: &BxlRequest,
-) -> anyhow::Result<buck2_cli_proto::BxlResponse> {
+) -> anyhow::Result<Option<BxlKey>> {
     let cwd = server_ctx.working_dir();
     let cell_resolver = ctx.get_cell_resolver().await?;
     let cell_alias_resolver = ctx.get_cell_alias_resolver_for_dir(cwd).await?;
@@ -141,7 +142,6 @@ async fn bxl(
         &cell_resolver,
         &cell_alias_resolver,
     )?;
-    let project_root = server_ctx.project_root().to_string();
 
     let global_cfg_options = global_cfg_options_from_client_context(
         request
@@ -149,36 +149,51 @@ async fn bxl(
             .as_ref()
             .internal_error_anyhow("target_cfg must be set")?,
         server_ctx,
-        &mut ctx,
+        ctx,
     )
     .await?;
 
     let bxl_args =
-        match get_bxl_cli_args(cwd, &mut ctx, &bxl_label, &request.bxl_args, &cell_resolver).await?
-        {
+        match get_bxl_cli_args(cwd, ctx, &bxl_label, &request.bxl_args, &cell_resolver).await? {
             BxlResolvedCliArgs::Resolved(bxl_args) => Arc::new(bxl_args),
-            // Return early if user passed in `--help`
-            BxlResolvedCliArgs::Help => {
-                return Ok(BxlResponse {
-                    project_root,
-                    errors: Vec::new(),
-                    serialized_build_report: None,
-                });
-            }
+            BxlResolvedCliArgs::Help => return Ok(None),
         };
 
+    Ok(Some(BxlKey::new(
+        bxl_label,
+        bxl_args,
+        request.print_stacktrace,
+        global_cfg_options,
+    )))
+}
+
+async fn bxl(
+    server_ctx: &dyn ServerCommandContextTrait,
+    stdout: impl Write,
+    mut ctx: DiceTransaction,
+    request: &BxlRequest,
+) -> anyhow::Result<buck2_cli_proto::BxlResponse> {
+    let cwd = server_ctx.working_dir();
+    let project_root = server_ctx.project_root().to_string();
+
+    let bxl_key = match bxl_key(server_ctx, &mut ctx, request).await? {
+        Some(bxl_key) => bxl_key,
+        // Return early if user passed in `--help`
+        None => {
+            return Ok(BxlResponse {
+                project_root,
+                errors: Vec::new(),
+                serialized_build_report: None,
+            });
+        }
+    };
+    let cell_resolver = ctx.get_cell_resolver().await?;
+
     let final_artifact_materializations =
         Materializations::from_i32(request.final_artifact_materializations)
             .with_context(|| "Invalid final_artifact_materializations")
             .unwrap();
 
-    let bxl_key = BxlKey::new(
-        bxl_label.clone(),
-        bxl_args,
-        request.print_stacktrace,
-        global_cfg_options,
-    );
-
     let bxl_result = match eval_bxl(&mut ctx, bxl_key.clone()).await {
         Ok(result) => result.0,
         Err(e) => {
@@ -289,7 +304,7 @@ pub(crate) async fn get_bxl_cli_args(
     resolve_cli_args(bxl_label, &cli_ctx, bxl_args, &frozen_callable).await
 }
 
-async fn copy_output<W: Write>(
+pub(crate) async fn copy_output<W: Write>(
     mut output: W,
     dice: &mut DiceComputations<'_>,
     output_loc: &BuckOutPath,
@@ -315,12 +330,12 @@ async fn copy_output<W: Write>(
     Ok(())
 }
 
-async fn ensure_artifacts(
+pub(crate) async fn ensure_artifacts(
     ctx: &mut DiceComputations<'_>,
     materialization_ctx: &MaterializationContext,
     target_results: impl IntoIterator<Item = &ConfiguredBuildTargetResult>,
     artifacts: Option<&Vec<ArtifactGroup>>,
-) -> Result<(), Vec<buck2_error::Error>> {
+) -> Result<Vec<ArtifactGroupValues>, Vec<buck2_error::Error>> {
     if let Some(artifacts) = artifacts {
         return {
             get_dispatcher()
@@ -334,7 +349,7 @@ async fn ensure_artifacts(
                 .await
         };
     }
-    Ok(())
+    Ok(Vec::new())
 }
 
 async fn ensure_artifacts_inner(
@@ -342,7 +357,7 @@ async fn ensure_artifacts_inner(
     materialization_ctx: &MaterializationContext,
     target_results: impl IntoIterator<Item = &ConfiguredBuildTargetResult>,
     artifacts: &[ArtifactGroup],
-) -> Result<(), Vec<buck2_error::Error>> {
+) -> Result<Vec<ArtifactGroupValues>, Vec<buck2_error::Error>> {
     let mut artifacts_to_materialize: Vec<_> = artifacts.iter().duped().collect();
     let mut errors = Vec::new();
 
@@ -359,18 +374,23 @@ async fn ensure_artifacts_inner(
         }
     }
 
-    let materialize_errors = ctx
+    let materialize_results = ctx
         .compute_join(artifacts_to_materialize, |ctx, artifact| {
             async move {
-                materialize_artifact_group(ctx, &artifact, materialization_ctx).await?;
-                Ok(())
+                Ok(materialize_artifact_group(ctx, &artifact, materialization_ctx).await?)
             }
             .boxed()
         })
         .await;
-    errors.extend(materialize_errors.into_iter().filter_map(|v| v.err()));
+    let mut values = Vec::new();
+    for result in materialize_results {
+        match result {
+            Ok(v) => values.push(v),
+            Err(e) => errors.push(e),
+        }
+    }
     if errors.is_empty() {
-        Ok(())
+        Ok(values)
     } else {
         Err(errors)
     }
@@ -428,7 +448,7 @@ pub(crate) fn parse_bxl_label_from_cli(
     })
 }
 
-fn filter_bxl_build_results(
+pub(crate) fn filter_bxl_build_results(
     build_results: Option<&Vec<BxlBuildResult>>,
 ) -> BTreeMap<ConfiguredProvidersLabel, ConfiguredBuildTargetResult> {
     let mut btree = BTreeMap::new();
@@ -16,6 +16,7 @@ use buck2_server_ctx::partial_result_dispatcher::PartialResultDispatcher;
 
 use crate::command::bxl_command;
 use crate::profile_command::bxl_profile_command;
+use crate::watch_command::bxl_watch_command;
 
 struct BxlServerCommandsInstance;
 
@@ -27,7 +28,11 @@ impl BxlServerCommands for BxlServerCommandsInstance {
         partial_result_dispatcher: PartialResultDispatcher<buck2_cli_proto::StdoutBytes>,
         req: buck2_cli_proto::BxlRequest,
     ) -> anyhow::Result<buck2_cli_proto::BxlResponse> {
-        bxl_command(ctx, partial_result_dispatcher, req).await
+        if req.watch {
+            bxl_watch_command(ctx, partial_result_dispatcher, req).await
+        } else {
+            bxl_command(ctx, partial_result_dispatcher, req).await
+        }
     }
 
     async fn bxl_profile(
@@ -17,6 +17,7 @@ pub(crate) mod bxl;
 pub(crate) mod command;
 mod commands;
 pub(crate) mod profile_command;
+mod watch_command;
 
 pub fn init_late_bindings() {
     static ONCE: Once = Once::new();
@@ -0,0 +1,269 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! `buck2 bxl --watch`: keep the command running, and re-run the script whenever DICE shows that
+//! something it depends on changed.
+//!
+//! Between rounds, the command waits for the file watcher to see changes. Every round opens a new
+//! DICE transaction, which syncs the file watcher. If nothing the script depends on was
+//! invalidated, DICE hands back the result of the previous round and nothing is reported.
+//!
+//! The artifacts the script ensured are still re-ensured every round, since they can change
+//! without the script needing to be re-evaluated (e.g. when a source file is edited).
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::ops::ControlFlow;
+use std::sync::Arc;
+
+use anyhow::Context;
+use buck2_build_api::actions::artifact::get_artifact_fs::GetArtifactFs;
+use buck2_build_api::bxl::result::BxlResult;
+use buck2_cli_proto::build_request::Materializations;
+use buck2_cli_proto::BxlRequest;
+use buck2_cli_proto::BxlResponse;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+use buck2_events::dispatch::span_async;
+use buck2_events::errors::create_error_report;
+use buck2_execute::artifact_value::ArtifactValue;
+use buck2_server_ctx::commands::command_end;
+use buck2_server_ctx::ctx::ServerCommandContextTrait;
+use buck2_server_ctx::ctx::ServerCommandDiceContext;
+use buck2_server_ctx::partial_result_dispatcher::PartialResultDispatcher;
+use dice::DiceTransaction;
+use dupe::Dupe;
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::bxl::calculation::eval_bxl;
+use crate::command::bxl_key;
+use crate::command::copy_output;
+use crate::command::ensure_artifacts;
+use crate::command::filter_bxl_build_results;
+
+pub(crate) async fn bxl_watch_command(
+    ctx: &dyn ServerCommandContextTrait,
+    partial_result_dispatcher: PartialResultDispatcher<buck2_cli_proto::StdoutBytes>,
+    req: BxlRequest,
+) -> anyhow::Result<BxlResponse> {
+    let start_event = buck2_data::CommandStart {
+        metadata: ctx.request_metadata().await?,
+        data: Some(
+            buck2_data::BxlCommandStart {
+                bxl_label: req.bxl_label.clone(),
+            }
+            .into(),
+        ),
+    };
+    span_async(start_event, async move {
+        let result = bxl_watch(ctx, partial_result_dispatcher, &req)
+            .await
+            .map_err(Into::into);
+        let end_event = command_end(
+            &result,
+            buck2_data::BxlCommandEnd {
+                bxl_label: req.bxl_label.clone(),
+            },
+        );
+        (result.map_err(Into::into), end_event)
+    })
+    .await
+}
+
+#[derive(Debug, buck2_error::Error)]
+enum BxlWatchError {
+    #[error(
+        "`--watch` needs a file watcher that reports changes as they happen, set `buck2.file_watcher` to `notify` or `watchman`"
+    )]
+    #[buck2(input)]
+    NoFileChangeNotifications,
+    #[error("The file watcher stopped reporting changes")]
+    FileWatcherStopped,
+}
+
+#[derive(Default)]
+struct BxlWatchState {
+    round: u64,
+    /// The result of the last round, to tell whether DICE re-evaluated the script.
+    result: Option<Arc<BxlResult>>,
+    /// The values of the artifacts the script ensured in the last round.
+    outputs: HashMap<ProjectRelativePathBuf, ArtifactValue>,
+    /// The errors of the last round, so that they are only reported once.
+    errors: Vec<String>,
+}
+
+/// What is printed for every round in `--watch-events` mode, as a single line of JSON.
+#[derive(Serialize)]
+struct BxlWatchEvent<'a> {
+    round: u64,
+    /// Project relative paths of the ensured artifacts that are new or changed since the previous
+    /// round.
+    changed_outputs: &'a [String],
+    errors: &'a [String],
+}
+
+async fn bxl_watch(
+    server_ctx: &dyn ServerCommandContextTrait,
+    mut partial_result_dispatcher: PartialResultDispatcher<buck2_cli_proto::StdoutBytes>,
+    request: &BxlRequest,
+) -> anyhow::Result<BxlResponse> {
+    let mut changes = server_ctx
+        .file_changes()
+        .ok_or(BxlWatchError::NoFileChangeNotifications)?;
+    let mut state = BxlWatchState::default();
+    loop {
+        // Changes seen from here on are picked up by this round, or wake up the next one.
+        changes.borrow_and_update();
+        let flow = server_ctx
+            .with_dice_ctx(|server_ctx, ctx| {
+                bxl_watch_round(
+                    server_ctx,
+                    &mut partial_result_dispatcher,
+                    ctx,
+                    request,
+                    &mut state,
+                )
+            })
+            .await?;
+        if flow.is_break() {
+            return Ok(BxlResponse {
+                project_root: server_ctx.project_root().to_string(),
+                errors: Vec::new(),
+                serialized_build_report: None,
+            });
+        }
+        changes
+            .changed()
+            .await
+            .map_err(|_| BxlWatchError::FileWatcherStopped)?;
+    }
+}
+
+/// Run the script in a new transaction, and report the round if anything changed. Breaks if the
+/// user passed `--help`, as there is nothing to watch then.
+async fn bxl_watch_round(
+    server_ctx: &dyn ServerCommandContextTrait,
+    stdout: &mut PartialResultDispatcher<buck2_cli_proto::StdoutBytes>,
+    mut ctx: DiceTransaction,
+    request: &BxlRequest,
+    state: &mut BxlWatchState,
+) -> anyhow::Result<ControlFlow<()>> {
+    let bxl_result = match bxl_key(server_ctx, &mut ctx, request).await {
+        Ok(None) => return Ok(ControlFlow::Break(())),
+        Ok(Some(bxl_key)) => eval_bxl(&mut ctx, bxl_key).await.map(|result| result.0),
+        Err(e) => Err(e),
+    };
+    let bxl_result = match bxl_result {
+        Ok(bxl_result) => bxl_result,
+        Err(e) => {
+            let errors = vec![create_error_report(&buck2_error::Error::from(e)).message];
+            if errors != state.errors {
+                state.round += 1;
+                state.result = None;
+                state.errors = errors;
+                report_round(server_ctx, stdout, request, state, &[])?;
+            }
+            return Ok(ControlFlow::Continue(()));
+        }
+    };
+    let reevaluated = !state
+        .result
+        .as_ref()
+        .is_some_and(|previous| Arc::ptr_eq(previous, &bxl_result));
+
+    let final_artifact_materializations =
+        Materializations::from_i32(request.final_artifact_materializations)
+            .context("Invalid final_artifact_materializations")?;
+    let build_results = filter_bxl_build_results(bxl_result.get_build_result_opt());
+    let (values, errors) = match ensure_artifacts(
+        &mut ctx,
+        &final_artifact_materializations.into(),
+        build_results.values(),
+        bxl_result.get_artifacts_opt(),
+    )
+    .await
+    {
+        Ok(values) => (values, Vec::new()),
+        Err(errors) => (
+            Vec::new(),
+            errors
+                .iter()
+                .map(|e| create_error_report(e).message)
+                .unique()
+                .collect(),
+        ),
+    };
+
+    let artifact_fs = ctx.get_artifact_fs().await?;
+    let mut outputs = HashMap::new();
+    for values in &values {
+        for (artifact, value) in values.iter() {
+            outputs.insert(artifact.resolve_path(&artifact_fs)?, value.dupe());
+        }
+    }
+    let changed_outputs: Vec<String> = outputs
+        .iter()
+        .filter(|(path, value)| state.outputs.get(*path) != Some(*value))
+        .map(|(path, _)| path.to_string())
+        .sorted()
+        .collect();
+
+    if !reevaluated && changed_outputs.is_empty() && errors == state.errors {
+        return Ok(ControlFlow::Continue(()));
+    }
+
+    state.round += 1;
+    state.result = Some(bxl_result.dupe());
+    state.outputs = outputs;
+    state.errors = errors;
+    if !request.watch_events {
+        copy_output(stdout.as_writer(), &mut ctx, bxl_result.get_output_loc()).await?;
+    }
+    copy_output(server_ctx.stderr()?, &mut ctx, bxl_result.get_error_loc()).await?;
+    report_round(server_ctx, stdout, request, state, &changed_outputs)?;
+    Ok(ControlFlow::Continue(()))
+}
+
+fn report_round(
+    server_ctx: &dyn ServerCommandContextTrait,
+    stdout: &mut PartialResultDispatcher<buck2_cli_proto::StdoutBytes>,
+    request: &BxlRequest,
+    state: &BxlWatchState,
+    changed_outputs: &[String],
+) -> anyhow::Result<()> {
+    if request.watch_events {
+        let mut stdout = stdout.as_writer();
+        serde_json::to_writer(
+            &mut stdout,
+            &BxlWatchEvent {
+                round: state.round,
+                changed_outputs,
+                errors: &state.errors,
+            },
+        )?;
+        writeln!(stdout)?;
+        return Ok(());
+    }
+
+    let mut stderr = server_ctx.stderr()?;
+    for error in &state.errors {
+        writeln!(stderr, "{}", error)?;
+    }
+    writeln!(
+        stderr,
+        "BXL {} (round {}), waiting for changes",
+        if state.errors.is_empty() {
+            "SUCCEEDED"
+        } else {
+            "FAILED"
+        },
+        state.round
+    )?;
+    Ok(())
+}
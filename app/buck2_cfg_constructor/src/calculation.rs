@@ -20,6 +20,7 @@ use buck2_core::target::label::label::TargetLabel;
 use buck2_interpreter_for_build::interpreter::package_file_calculation::EvalPackageFile;
 use buck2_node::cfg_constructor::CfgConstructorCalculationImpl;
 use buck2_node::cfg_constructor::CfgConstructorImpl;
+use buck2_node::cfg_constructor::CfgModifiers;
 use buck2_node::metadata::value::MetadataValue;
 use buck2_node::nodes::unconfigured::TargetNodeRef;
 use buck2_node::rule_type::RuleType;
@@ -140,27 +141,10 @@ impl CfgConstructorCalculationImpl for CfgConstructorCalculationInstance {
             // no cfg constructors are available.
             return Ok(cfg);
         };
-        let modifier_key = cfg_constructor.key();
-        let package_cfg_modifiers = super_package
-            .package_values()
-            .get_package_value_json(modifier_key)?
-            .map(MetadataValue::new);
-
-        let metadata_modifiers = target.metadata()?.and_then(|m| m.get(modifier_key));
-        let target_modifiers = target.target_modifiers()?;
-        let target_cfg_modifiers = match (metadata_modifiers, target_modifiers) {
-            (None, Some(t)) if !t.is_empty() => Some(MetadataValue(t.as_json())),
-            (Some(_), Some(t)) if !t.is_empty() => {
-                return Err(
-                    CalculationCfgConstructorError::TargetModifiersAttrAndMetadataNotAllowed(
-                        target.label().dupe(),
-                    )
-                    .into(),
-                );
-            }
-            (Some(m), _) => Some(m.dupe()),
-            _ => None,
-        };
+        let CfgModifiers {
+            package: package_cfg_modifiers,
+            target: target_cfg_modifiers,
+        } = get_cfg_modifiers(&*cfg_constructor, target, super_package)?;
 
         // If there are no PACKAGE/target/cli modifiers, return the original configuration without computing DICE call
         // TODO(scottcao): This is just for rollout purpose. Remove once modifier is rolled out
@@ -180,4 +164,49 @@ impl CfgConstructorCalculationImpl for CfgConstructorCalculationInstance {
         };
         Ok(ctx.compute(&key).await??)
     }
+
+    async fn cfg_modifiers(
+        &self,
+        ctx: &mut DiceComputations<'_>,
+        target: TargetNodeRef<'_>,
+        super_package: &SuperPackage,
+    ) -> anyhow::Result<Option<CfgModifiers>> {
+        let Some(cfg_constructor) = get_cfg_constructor(ctx).await? else {
+            return Ok(None);
+        };
+        Ok(Some(get_cfg_modifiers(
+            &*cfg_constructor,
+            target,
+            super_package,
+        )?))
+    }
+}
+
+fn get_cfg_modifiers(
+    cfg_constructor: &dyn CfgConstructorImpl,
+    target: TargetNodeRef<'_>,
+    super_package: &SuperPackage,
+) -> anyhow::Result<CfgModifiers> {
+    let modifier_key = cfg_constructor.key();
+    let package = super_package
+        .package_values()
+        .get_package_value_json(modifier_key)?
+        .map(MetadataValue::new);
+
+    let metadata_modifiers = target.metadata()?.and_then(|m| m.get(modifier_key));
+    let target_modifiers = target.target_modifiers()?;
+    let target = match (metadata_modifiers, target_modifiers) {
+        (None, Some(t)) if !t.is_empty() => Some(MetadataValue(t.as_json())),
+        (Some(_), Some(t)) if !t.is_empty() => {
+            return Err(
+                CalculationCfgConstructorError::TargetModifiersAttrAndMetadataNotAllowed(
+                    target.label().dupe(),
+                )
+                .into(),
+            );
+        }
+        (Some(m), _) => Some(m.dupe()),
+        _ => None,
+    };
+    Ok(CfgModifiers { package, target })
 }
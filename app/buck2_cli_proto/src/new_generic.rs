@@ -19,8 +19,10 @@ pub enum NewGenericRequest {
     DebugEval(DebugEvalRequest),
     Explain(ExplainRequest),
     ExpandExternalCell(ExpandExternalCellRequest),
+    ExternalCells(ExternalCellsRequest),
     Complete(CompleteRequest),
     Docs(DocsRequest),
+    MaterializerFsck(MaterializerFsckRequest),
 }
 
 #[derive(Serialize, Deserialize)]
@@ -29,8 +31,10 @@ pub enum NewGenericResponse {
     DebugEval(DebugEvalResponse),
     Explain(ExplainResponse),
     ExpandExternalCell(ExpandExternalCellResponse),
+    ExternalCells(ExternalCellsResponse),
     Complete(CompleteResponse),
     Docs(DocsResponse),
+    MaterializerFsck(MaterializerFsckResponse),
 }
 
 #[derive(Serialize, Deserialize)]
@@ -42,6 +46,32 @@ pub struct MaterializeRequest {
 #[derive(Serialize, Deserialize)]
 pub struct MaterializeResponse {}
 
+#[derive(Serialize, Deserialize)]
+pub struct MaterializerFsckRequest {
+    /// Invalidate missing and modified artifacts.
+    pub repair: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MaterializerFsckIssue {
+    pub path: String,
+    /// One of `missing`, `modified` or `untracked`.
+    pub kind: String,
+    pub detail: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MaterializerFsckResponse {
+    /// Number of materialized artifacts that were checked.
+    pub checked: u64,
+    pub issues: Vec<MaterializerFsckIssue>,
+    /// Number of artifacts that were invalidated.
+    pub repaired: u64,
+    /// Number of invalidated artifacts that the running daemon held in its graph. The graph is
+    /// dropped on the next command so that they are rebuilt.
+    pub repaired_active: u64,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct DebugEvalRequest {
     pub paths: Vec<String>,
@@ -74,6 +104,18 @@ pub struct ExpandExternalCellResponse {
     pub path: String,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct ExternalCellsRequest {
+    /// Rewrite the lockfile if it's out of date, rather than only checking it.
+    pub update: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExternalCellsResponse {
+    /// How the resolved external cells differ from the lockfile, one line per cell.
+    pub changes: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct CompleteRequest {
     pub target_cfg: TargetCfg,
@@ -36,6 +36,16 @@ pub struct BxlCommand {
     #[clap(flatten)]
     bxl_opts: BxlCommandOptions,
 
+    /// Keep running, and re-run the script whenever the files it depends on change. The output of
+    /// the script is printed again after every round that changed something.
+    #[clap(long)]
+    watch: bool,
+
+    /// With `--watch`, instead of the output of the script, print one line of JSON per round with
+    /// the ensured outputs that changed and the errors of the round.
+    #[clap(long, requires = "watch")]
+    watch_events: bool,
+
     #[clap(flatten)]
     target_cfg: TargetCfgOptions,
 
@@ -102,6 +112,8 @@ impl StreamingCommand for BxlCommand {
                     final_artifact_materializations: self.bxl_opts.materializations.to_proto()
                         as i32,
                     print_stacktrace: ctx.verbosity.print_success_stderr(),
+                    watch: self.watch,
+                    watch_events: self.watch_events,
                 },
                 ctx.stdin()
                     .console_interaction_stream(&self.common_ops.console_opts),
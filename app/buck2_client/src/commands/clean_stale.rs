@@ -79,6 +79,13 @@ fn format_result_stats(stats: buck2_data::CleanStaleStats) -> String {
         stats.untracked_artifact_count,
         bytesize::to_string(stats.untracked_bytes, true),
     );
+    if stats.evicted_artifact_count > 0 {
+        output += &format!(
+            "Evicted {} artifacts ({}) to fit buck2.buck_out_max_size_gb\n",
+            stats.evicted_artifact_count,
+            bytesize::to_string(stats.evicted_bytes, true),
+        );
+    }
     if stats.cleaned_artifact_count > 0 || stats.cleaned_bytes > 0 {
         output += &format!("Cleaned {} paths\n", stats.cleaned_artifact_count,);
         output += &format!(
@@ -21,6 +21,7 @@ use flush_dep_files::FlushDepFilesCommand;
 use heap_dump::HeapDumpCommand;
 use internal_version::InternalVersionCommand;
 use materialize::MaterializeCommand;
+use materializer_fsck::MaterializerFsckCommand;
 
 use crate::commands::debug::allocative::AllocativeCommand;
 use crate::commands::debug::daemon_dir::DaemonDirCommand;
@@ -29,10 +30,12 @@ use crate::commands::debug::exe::ExeCommand;
 use crate::commands::debug::log_perf::LogPerfCommand;
 use crate::commands::debug::paranoid::ParanoidCommand;
 use crate::commands::debug::persist_event_logs::PersistEventLogsCommand;
+use crate::commands::debug::serve_ui::ServeUiCommand;
 use crate::commands::debug::set_log_filter::SetLogFilterCommand;
 use crate::commands::debug::thread_dump::ThreadDumpCommand;
 use crate::commands::debug::trace_io::TraceIoCommand;
 use crate::commands::debug::upload_re_logs::UploadReLogsCommand;
+use crate::commands::debug::why_recomputed::WhyRecomputedCommand;
 use crate::commands::log::debug_replay::DebugReplayCommand;
 use crate::commands::log::debug_what_ran::DebugWhatRanCommand;
 
@@ -50,12 +53,15 @@ mod heap_dump;
 mod internal_version;
 mod log_perf;
 mod materialize;
+mod materializer_fsck;
 mod paranoid;
 mod persist_event_logs;
+mod serve_ui;
 mod set_log_filter;
 mod thread_dump;
 mod trace_io;
 pub(crate) mod upload_re_logs;
+mod why_recomputed;
 
 #[derive(Debug, clap::Parser)]
 #[clap(about = "Hidden debug commands useful for testing buck2")]
@@ -77,6 +83,8 @@ pub enum DebugCommand {
     FlushDepFiles(FlushDepFilesCommand),
     /// Forces materialization of a path, even on the deferred materializer
     Materialize(MaterializeCommand),
+    /// Verifies buck-out against the materializer state, and optionally repairs it.
+    MaterializerFsck(MaterializerFsckCommand),
     // Upload RE logs given an RE session ID
     UploadReLogs(UploadReLogsCommand),
     /// Validates that Buck2 and disk agree on the state of files.
@@ -100,6 +108,9 @@ pub enum DebugCommand {
     Paranoid(ParanoidCommand),
     Eval(EvalCommand),
     ThreadDump(ThreadDumpCommand),
+    WhyRecomputed(WhyRecomputedCommand),
+    /// Serves a web UI for a build on localhost.
+    ServeUi(ServeUiCommand),
 }
 
 impl DebugCommand {
@@ -116,6 +127,7 @@ impl DebugCommand {
             DebugCommand::FlushDepFiles(cmd) => cmd.exec(matches, ctx),
             DebugCommand::WhatRan(cmd) => cmd.exec(matches, ctx),
             DebugCommand::Materialize(cmd) => cmd.exec(matches, ctx),
+            DebugCommand::MaterializerFsck(cmd) => cmd.exec(matches, ctx),
             DebugCommand::UploadReLogs(cmd) => cmd.exec(matches, ctx),
             DebugCommand::DaemonDir(cmd) => cmd.exec(matches, ctx),
             DebugCommand::Exe(cmd) => cmd.exec(matches, ctx),
@@ -128,6 +140,8 @@ impl DebugCommand {
             DebugCommand::Paranoid(cmd) => cmd.exec(matches, ctx),
             DebugCommand::Eval(cmd) => cmd.exec(matches, ctx),
             DebugCommand::ThreadDump(cmd) => cmd.exec(matches, ctx),
+            DebugCommand::WhyRecomputed(cmd) => cmd.exec(matches, ctx),
+            DebugCommand::ServeUi(cmd) => cmd.exec(matches, ctx),
         }
     }
 
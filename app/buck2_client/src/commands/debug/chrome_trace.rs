@@ -29,6 +29,7 @@ use buck2_event_log::stream_value::StreamValue;
 use buck2_event_log::utils::Invocation;
 use buck2_event_observer::display;
 use buck2_event_observer::display::TargetDisplayOptions;
+use buck2_event_observer::span_tracker::SpanTrackable;
 use buck2_events::BuckEvent;
 use derive_more::Display;
 use dupe::Dupe;
@@ -37,6 +38,8 @@ use futures::TryStreamExt;
 use serde::Serialize;
 use serde_json::json;
 
+mod perfetto;
+
 #[derive(Debug, clap::Parser)]
 pub struct ChromeTraceCommand {
     #[clap(
@@ -61,6 +64,33 @@ pub struct ChromeTraceCommand {
         value_name = "NUMBER"
     )]
     pub recent: Option<usize>,
+
+    /// Also show local actions per executor slot, remote execution stages, DICE computations
+    /// grouped by key type and materializations, alongside the critical path. By default, only
+    /// the critical path and the longest spans are shown.
+    #[clap(long)]
+    pub combined: bool,
+
+    /// The format of the trace file.
+    #[clap(long, value_enum, default_value = "json")]
+    pub format: TraceFormat,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum TraceFormat {
+    /// Chrome's JSON trace event format.
+    Json,
+    /// Perfetto's protobuf trace format, which stays loadable for traces of large builds.
+    Perfetto,
+}
+
+impl TraceFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "trace",
+            Self::Perfetto => "pftrace",
+        }
+    }
 }
 
 struct ChromeTraceFirstPass {
@@ -82,6 +112,7 @@ struct ChromeTraceFirstPass {
     pub long_analyses: HashSet<buck2_events::span::SpanId>,
     pub long_loads: HashSet<buck2_events::span::SpanId>,
     pub local_actions: HashSet<buck2_events::span::SpanId>,
+    pub remote_actions: HashSet<buck2_events::span::SpanId>,
     pub critical_path_action_keys: HashSet<buck2_data::ActionKey>,
     pub critical_path_span_ids: HashSet<u64>,
 }
@@ -94,6 +125,7 @@ impl ChromeTraceFirstPass {
             long_analyses: HashSet::new(),
             long_loads: HashSet::new(),
             local_actions: HashSet::new(),
+            remote_actions: HashSet::new(),
             critical_path_action_keys: HashSet::new(),
             critical_path_span_ids: HashSet::new(),
         }
@@ -107,11 +139,16 @@ impl ChromeTraceFirstPass {
                         // A local stage means that we want to show the entire action execution.
                         use buck2_data::executor_stage_start::Stage;
 
+                        if let Some(Stage::Re(..)) = &exec.stage {
+                            self.remote_actions.insert(event.parent_id().unwrap());
+                        }
+
                         if let Some(Stage::Local(local)) = &exec.stage {
                             use buck2_data::local_stage::Stage;
 
                             let local_execution = match local.stage.as_ref() {
                                 Some(Stage::Queued(..)) => false,
+                                Some(Stage::ResourceQueued(..)) => false,
                                 Some(Stage::Execute(..)) => true,
                                 Some(Stage::MaterializeInputs(..)) => false,
                                 Some(Stage::PrepareOutputs(..)) => false,
@@ -525,6 +562,7 @@ struct ChromeTraceWriter {
     snapshot_counters: SimpleCounters<u64>,
     process_memory_counters: SimpleCounters<f64>,
     rate_of_change_counters: AverageRateOfChangeCounters,
+    combined: bool,
 }
 
 #[derive(Copy, Clone, Dupe, Debug, Display, Hash, PartialEq, Eq)]
@@ -533,12 +571,20 @@ enum SpanCategorization {
     Uncategorized,
     #[display("critical-path")]
     CriticalPath,
+    #[display("local")]
+    LocalExecution,
+    #[display("remote")]
+    RemoteExecution,
+    #[display("dice-{}", _0)]
+    Dice(&'static str),
+    #[display("materialization")]
+    Materialization,
 }
 
 impl ChromeTraceWriter {
     const BYTES_PER_GIGABYTE: f64 = 1000000000.0;
 
-    pub fn new(invocation: Invocation, first_pass: ChromeTraceFirstPass) -> Self {
+    pub fn new(invocation: Invocation, first_pass: ChromeTraceFirstPass, combined: bool) -> Self {
         Self {
             trace_events: vec![],
             open_spans: HashMap::new(),
@@ -549,6 +595,7 @@ impl ChromeTraceWriter {
             snapshot_counters: SimpleCounters::<u64>::new("snapshot_counters", 0),
             process_memory_counters: SimpleCounters::<f64>::new("process_memory", 0.0),
             rate_of_change_counters: AverageRateOfChangeCounters::new("rate_of_change_counters"),
+            combined,
         }
     }
 
@@ -570,6 +617,11 @@ impl ChromeTraceWriter {
                     SpanCategorization::CriticalPath => None,
                     // No point showing hundreds of tracks for the rest. Show what you can.
                     SpanCategorization::Uncategorized => Some(20),
+                    // Asked for explicitly, and bounded by the concurrency of the build.
+                    SpanCategorization::LocalExecution
+                    | SpanCategorization::RemoteExecution
+                    | SpanCategorization::Dice(..)
+                    | SpanCategorization::Materialization => None,
                 };
 
                 let track = self
@@ -587,10 +639,7 @@ impl ChromeTraceWriter {
         }
     }
 
-    pub fn to_writer<W>(mut self, file: W) -> anyhow::Result<()>
-    where
-        W: Write,
-    {
+    fn into_trace_events(mut self) -> anyhow::Result<Vec<serde_json::Value>> {
         self.span_counters
             .counter
             .flush_all_to(&mut self.trace_events)?;
@@ -601,13 +650,23 @@ impl ChromeTraceWriter {
         self.rate_of_change_counters
             .counters
             .flush_all_to(&mut self.trace_events)?;
+        Ok(self.trace_events)
+    }
 
-        serde_json::to_writer(
-            file,
-            &json!({
-                "traceEvents": self.trace_events
-            }),
-        )?;
+    pub fn to_writer<W>(self, file: W, format: TraceFormat) -> anyhow::Result<()>
+    where
+        W: Write,
+    {
+        let trace_events = self.into_trace_events()?;
+        match format {
+            TraceFormat::Json => serde_json::to_writer(
+                file,
+                &json!({
+                    "traceEvents": trace_events
+                }),
+            )?,
+            TraceFormat::Perfetto => perfetto::write_trace(&trace_events, file)?,
+        }
         Ok(())
     }
 
@@ -625,6 +684,10 @@ impl ChromeTraceWriter {
         // Allocate this span to its parent's track or to a new track.
         let track = self.assign_track_for_span(track_key, event)?;
         if let Some(track) = track {
+            let mut categories = vec!["buck2"];
+            if track_key == SpanCategorization::CriticalPath {
+                categories.push("critical_path");
+            }
             self.open_span(
                 event,
                 ChromeTraceOpenSpan {
@@ -632,7 +695,7 @@ impl ChromeTraceWriter {
                     start: event.timestamp(),
                     process_id: 0,
                     track,
-                    categories: vec!["buck2"],
+                    categories,
                     args: json!({
                         "span_id": event.span_id(),
                     }),
@@ -677,6 +740,8 @@ impl ChromeTraceWriter {
 
                         let category = if on_critical_path {
                             Some(SpanCategorization::CriticalPath)
+                        } else if self.combined {
+                            Some(SpanCategorization::Dice(Self::dice_key_type(event)))
                         } else if self
                             .first_pass
                             .long_analyses
@@ -714,6 +779,8 @@ impl ChromeTraceWriter {
 
                         let category = if on_critical_path {
                             Some(SpanCategorization::CriticalPath)
+                        } else if self.combined {
+                            Some(SpanCategorization::Dice(Self::dice_key_type(event)))
                         } else if self
                             .first_pass
                             .long_loads
@@ -747,7 +814,18 @@ impl ChromeTraceWriter {
                             .local_actions
                             .contains(&event.span_id().unwrap())
                         {
-                            Some(SpanCategorization::Uncategorized)
+                            if self.combined {
+                                Some(SpanCategorization::LocalExecution)
+                            } else {
+                                Some(SpanCategorization::Uncategorized)
+                            }
+                        } else if self.combined
+                            && self
+                                .first_pass
+                                .remote_actions
+                                .contains(&event.span_id().unwrap())
+                        {
+                            Some(SpanCategorization::RemoteExecution)
                         } else {
                             None
                         };
@@ -787,16 +865,42 @@ impl ChromeTraceWriter {
                         self.span_counters.bump_counter_while_span(event, name, 1)?;
                         Categorization::ShowIfParent { name: name.into() }
                     }
+                    buck2_data::span_start_event::Data::LoadPackage(load) if self.combined => {
+                        Categorization::Show {
+                            category: if on_critical_path {
+                                SpanCategorization::CriticalPath
+                            } else {
+                                SpanCategorization::Dice(Self::dice_key_type(event))
+                            },
+                            name: format!("listing {}", load.path).into(),
+                        }
+                    }
                     buck2_data::span_start_event::Data::FinalMaterialization(..) => {
                         if on_critical_path {
                             Categorization::Show {
                                 category: SpanCategorization::CriticalPath,
                                 name: "materialization".into(),
                             }
+                        } else if self.combined {
+                            Categorization::Show {
+                                category: SpanCategorization::Materialization,
+                                name: "final materialization".into(),
+                            }
                         } else {
                             Categorization::Omit
                         }
                     }
+                    buck2_data::span_start_event::Data::Materialization(materialization)
+                        if self.combined =>
+                    {
+                        Categorization::Show {
+                            category: SpanCategorization::Materialization,
+                            name: match &materialization.action_digest {
+                                Some(digest) => format!("materialization {}", digest).into(),
+                                None => "materialization".into(),
+                            },
+                        }
+                    }
                     buck2_data::span_start_event::Data::FileWatcher(_file_watcher) => {
                         Categorization::Show {
                             category: SpanCategorization::CriticalPath,
@@ -911,6 +1015,19 @@ impl ChromeTraceWriter {
         Ok(())
     }
 
+    /// The DICE key type the span is computing, so that computations of the same kind end up
+    /// next to each other. Loads are not counted as DICE keys by the span tracker, but they are
+    /// worth their own lanes here.
+    fn dice_key_type(event: &BuckEvent) -> &'static str {
+        use buck2_data::span_start_event::Data;
+
+        match event.span_start_event().and_then(|span| span.data.as_ref()) {
+            Some(Data::Load(..)) => "InterpreterResultsKey",
+            Some(Data::LoadPackage(..)) => "PackageListingKey",
+            _ => SpanTrackable::dice_key_type(event).unwrap_or("other"),
+        }
+    }
+
     fn handle_event_end(
         &mut self,
         end: &buck2_data::SpanEndEvent,
@@ -951,7 +1068,11 @@ impl ChromeTraceCommand {
         Ok((invocation, Box::pin(stream)))
     }
 
-    fn trace_path_from_dir(dir: AbsPathBuf, log: &std::path::Path) -> anyhow::Result<AbsPathBuf> {
+    fn trace_path_from_dir(
+        dir: AbsPathBuf,
+        log: &std::path::Path,
+        format: TraceFormat,
+    ) -> anyhow::Result<AbsPathBuf> {
         match log.file_name() {
             None => Err(anyhow::anyhow!(
                 "Could not determine filename from event log path: `{:#}`",
@@ -960,7 +1081,7 @@ impl ChromeTraceCommand {
             Some(file_name) => {
                 let mut trace_path = dir;
                 trace_path.push(file_name);
-                trace_path.set_extension("trace");
+                trace_path.set_extension(format.extension());
                 Ok(trace_path)
             }
         }
@@ -979,26 +1100,30 @@ impl ChromeTraceCommand {
 
         let trace_path = self.trace_path.resolve(&ctx.working_dir);
         let dest_path = if trace_path.is_dir() {
-            Self::trace_path_from_dir(trace_path, &log).context("Could not determine trace path")?
+            Self::trace_path_from_dir(trace_path, &log, self.format)
+                .context("Could not determine trace path")?
         } else {
             trace_path
         };
 
         let log = EventLogPathBuf::infer(log)?;
 
-        let writer = ctx.with_runtime(|_| Self::trace_writer(log))?;
+        let writer = ctx.with_runtime(|_| Self::trace_writer(log, self.combined))?;
 
         let tracefile = std::fs::OpenOptions::new()
             .create(true)
             .write(true)
             .truncate(true)
             .open(dest_path)?;
-        writer.to_writer(BufWriter::new(tracefile))?;
+        writer.to_writer(BufWriter::new(tracefile), self.format)?;
 
         ExitResult::success()
     }
 
-    async fn trace_writer(log: EventLogPathBuf) -> anyhow::Result<ChromeTraceWriter> {
+    async fn trace_writer(
+        log: EventLogPathBuf,
+        combined: bool,
+    ) -> anyhow::Result<ChromeTraceWriter> {
         let (invocation, mut stream) = Self::load_events(log.clone()).await?;
         let mut first_pass = ChromeTraceFirstPass::new();
         while let Some(event) = tokio_stream::StreamExt::try_next(&mut stream).await? {
@@ -1007,7 +1132,7 @@ impl ChromeTraceCommand {
                 .with_context(|| display::InvalidBuckEvent(Arc::new(event.clone())))?;
         }
 
-        let mut writer = ChromeTraceWriter::new(invocation, first_pass);
+        let mut writer = ChromeTraceWriter::new(invocation, first_pass, combined);
 
         // We just read events again from log file, in order to avoid holding all logs in memory
         let (_invocation, mut stream) = Self::load_events(log).await?;
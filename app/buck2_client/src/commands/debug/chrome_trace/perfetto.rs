@@ -0,0 +1,335 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Converts Chrome trace events into Perfetto's protobuf trace format.
+//!
+//! Only the handful of messages from `perfetto/trace/trace_packet.proto` that we need are
+//! declared here, by hand, rather than vendoring the Perfetto protos.
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use anyhow::Context as _;
+use prost::Message;
+
+/// `TrackEvent.Type`.
+const TYPE_SLICE_BEGIN: i32 = 1;
+const TYPE_SLICE_END: i32 = 2;
+const TYPE_COUNTER: i32 = 4;
+
+/// All our packets come from one writer, so they share a sequence.
+const SEQUENCE_ID: u32 = 1;
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct TracePacket {
+    #[prost(uint64, optional, tag = "8")]
+    timestamp: Option<u64>,
+    #[prost(uint32, optional, tag = "10")]
+    trusted_packet_sequence_id: Option<u32>,
+    #[prost(message, optional, tag = "11")]
+    track_event: Option<TrackEvent>,
+    #[prost(message, optional, tag = "60")]
+    track_descriptor: Option<TrackDescriptor>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct TrackDescriptor {
+    #[prost(uint64, optional, tag = "1")]
+    uuid: Option<u64>,
+    #[prost(string, optional, tag = "2")]
+    name: Option<String>,
+    #[prost(uint64, optional, tag = "5")]
+    parent_uuid: Option<u64>,
+    #[prost(message, optional, tag = "8")]
+    counter: Option<CounterDescriptor>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct CounterDescriptor {}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct TrackEvent {
+    #[prost(int32, optional, tag = "9")]
+    r#type: Option<i32>,
+    #[prost(uint64, optional, tag = "11")]
+    track_uuid: Option<u64>,
+    #[prost(string, repeated, tag = "22")]
+    categories: Vec<String>,
+    #[prost(string, optional, tag = "23")]
+    name: Option<String>,
+    #[prost(double, optional, tag = "44")]
+    double_counter_value: Option<f64>,
+}
+
+/// Perfetto wants slices on a track to be properly nested, so order the events such that, at
+/// equal timestamps, slices end before others begin, and outer slices begin before (and end
+/// after) the slices they contain.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct SortKey {
+    timestamp_us: u64,
+    order: u8,
+    tie_break: u64,
+}
+
+#[derive(Default)]
+struct Tracks {
+    uuids: HashMap<String, u64>,
+}
+
+impl Tracks {
+    /// The uuid of the track, and its descriptor if this is the first time it is used.
+    fn get(&mut self, name: &str, counter: bool) -> (u64, Option<TrackDescriptor>) {
+        if let Some(uuid) = self.uuids.get(name) {
+            return (*uuid, None);
+        }
+
+        let uuid = self.uuids.len() as u64 + 1;
+        self.uuids.insert(name.to_owned(), uuid);
+        let descriptor = TrackDescriptor {
+            uuid: Some(uuid),
+            name: Some(name.to_owned()),
+            parent_uuid: None,
+            counter: counter.then_some(CounterDescriptor {}),
+        };
+        (uuid, Some(descriptor))
+    }
+
+    /// The uuid of the track, passing its descriptor to `f` first if this is its first use.
+    fn uuid(
+        &mut self,
+        name: &str,
+        counter: bool,
+        f: &mut dyn FnMut(TracePacket) -> anyhow::Result<()>,
+    ) -> anyhow::Result<Option<u64>> {
+        let (uuid, descriptor) = self.get(name, counter);
+        if let Some(descriptor) = descriptor {
+            f(TracePacket {
+                trusted_packet_sequence_id: Some(SEQUENCE_ID),
+                track_descriptor: Some(descriptor),
+                ..Default::default()
+            })?;
+        }
+        Ok(Some(uuid))
+    }
+}
+
+/// A packet to produce, borrowing from the Chrome trace event it comes from.
+enum Item<'a> {
+    SliceBegin {
+        track: &'a str,
+        name: &'a str,
+        categories: &'a str,
+    },
+    SliceEnd {
+        track: &'a str,
+    },
+    Counter {
+        track: String,
+        value: f64,
+    },
+}
+
+fn string_field<'a>(event: &'a serde_json::Value, field: &str) -> anyhow::Result<&'a str> {
+    event[field]
+        .as_str()
+        .with_context(|| format!("Trace event is missing `{}`: {}", field, event))
+}
+
+fn u64_field(event: &serde_json::Value, field: &str) -> anyhow::Result<u64> {
+    event[field]
+        .as_u64()
+        .with_context(|| format!("Trace event is missing `{}`: {}", field, event))
+}
+
+fn track_event_packet(timestamp_us: u64, event: TrackEvent) -> TracePacket {
+    TracePacket {
+        timestamp: Some(timestamp_us * 1000),
+        trusted_packet_sequence_id: Some(SEQUENCE_ID),
+        track_event: Some(event),
+        track_descriptor: None,
+    }
+}
+
+/// The packets to produce for `events`, in the order Perfetto wants them.
+fn items(events: &[serde_json::Value]) -> anyhow::Result<Vec<(SortKey, Item<'_>)>> {
+    let mut keyed = Vec::new();
+
+    for event in events {
+        match string_field(event, "ph")? {
+            "X" => {
+                let name = string_field(event, "name")?;
+                let ts = u64_field(event, "ts")?;
+                let dur = u64_field(event, "dur")?;
+                let track = string_field(event, "tid")?;
+
+                keyed.push((
+                    SortKey {
+                        timestamp_us: ts,
+                        order: 1,
+                        tie_break: u64::MAX - dur,
+                    },
+                    Item::SliceBegin {
+                        track,
+                        name,
+                        categories: event["cat"].as_str().unwrap_or_default(),
+                    },
+                ));
+                keyed.push((
+                    SortKey {
+                        timestamp_us: ts + dur,
+                        order: 0,
+                        tie_break: dur,
+                    },
+                    Item::SliceEnd { track },
+                ));
+            }
+            "C" => {
+                let name = string_field(event, "name")?;
+                let ts = u64_field(event, "ts")?;
+                let args = event["args"]
+                    .as_object()
+                    .with_context(|| format!("Counter event is missing `args`: {}", event))?;
+
+                for (key, value) in args {
+                    let Some(value) = value.as_f64() else {
+                        continue;
+                    };
+                    keyed.push((
+                        SortKey {
+                            timestamp_us: ts,
+                            order: 2,
+                            tie_break: 0,
+                        },
+                        Item::Counter {
+                            track: format!("{}.{}", name, key),
+                            value,
+                        },
+                    ));
+                }
+            }
+            // We don't emit anything else.
+            _ => {}
+        }
+    }
+
+    // The sort is stable, so events that compare equal stay in the order they were emitted.
+    keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(keyed)
+}
+
+/// Produces the packets for `events` one at a time. Tracks are described right before their
+/// first use.
+fn for_each_packet(
+    events: &[serde_json::Value],
+    mut f: impl FnMut(TracePacket) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut tracks = Tracks::default();
+
+    for (key, item) in items(events)? {
+        let event = match item {
+            Item::SliceBegin {
+                track: name,
+                name: slice_name,
+                categories,
+            } => TrackEvent {
+                r#type: Some(TYPE_SLICE_BEGIN),
+                track_uuid: tracks.uuid(name, false, &mut f)?,
+                categories: categories
+                    .split(',')
+                    .filter(|c| !c.is_empty())
+                    .map(|c| c.to_owned())
+                    .collect(),
+                name: Some(slice_name.to_owned()),
+                double_counter_value: None,
+            },
+            Item::SliceEnd { track: name } => TrackEvent {
+                r#type: Some(TYPE_SLICE_END),
+                track_uuid: tracks.uuid(name, false, &mut f)?,
+                ..Default::default()
+            },
+            Item::Counter { track: name, value } => TrackEvent {
+                r#type: Some(TYPE_COUNTER),
+                track_uuid: tracks.uuid(&name, true, &mut f)?,
+                double_counter_value: Some(value),
+                ..Default::default()
+            },
+        };
+        f(track_event_packet(key.timestamp_us, event))?;
+    }
+    Ok(())
+}
+
+/// Write the events as a `perfetto.protos.Trace`, i.e. a stream of `repeated TracePacket packet =
+/// 1`, which lets us write packets one at a time as they are produced.
+pub(crate) fn write_trace(events: &[serde_json::Value], mut out: impl Write) -> anyhow::Result<()> {
+    let mut buf = Vec::new();
+    for_each_packet(events, |packet| {
+        buf.clear();
+        prost::encoding::encode_key(1, prost::encoding::WireType::LengthDelimited, &mut buf);
+        packet.encode_length_delimited(&mut buf)?;
+        out.write_all(&buf)?;
+        Ok(())
+    })?;
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_packets() -> anyhow::Result<()> {
+        let events = vec![
+            json!({"name": "inner", "ts": 10, "dur": 5, "ph": "X", "pid": 0, "tid": "local-00", "cat": "buck2", "args": {}}),
+            json!({"name": "outer", "ts": 10, "dur": 20, "ph": "X", "pid": 0, "tid": "local-00", "cat": "buck2,critical_path", "args": {}}),
+            json!({"name": "spans", "ts": 12, "ph": "C", "pid": 0, "tid": "counters", "args": {"analysis": 3}}),
+        ];
+
+        let mut packets = Vec::new();
+        for_each_packet(&events, |packet| {
+            packets.push(packet);
+            Ok(())
+        })?;
+
+        let descriptors = packets
+            .iter()
+            .filter_map(|p| p.track_descriptor.as_ref())
+            .map(|d| (d.name.as_deref().unwrap(), d.counter.is_some()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            descriptors,
+            vec![("local-00", false), ("spans.analysis", true)]
+        );
+
+        let events = packets
+            .iter()
+            .filter_map(|p| Some((p.timestamp?, p.track_event.as_ref()?)))
+            .map(|(ts, e)| (ts, e.r#type.unwrap(), e.name.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                (10000, TYPE_SLICE_BEGIN, Some("outer")),
+                (10000, TYPE_SLICE_BEGIN, Some("inner")),
+                (12000, TYPE_COUNTER, None),
+                (15000, TYPE_SLICE_END, None),
+                (30000, TYPE_SLICE_END, None),
+            ]
+        );
+
+        let mut out = Vec::new();
+        write_trace(&[], &mut out)?;
+        assert!(out.is_empty());
+
+        Ok(())
+    }
+}
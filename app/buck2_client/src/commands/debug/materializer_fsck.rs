@@ -0,0 +1,126 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::fmt::Write;
+
+use async_trait::async_trait;
+use buck2_cli_proto::new_generic::MaterializerFsckRequest;
+use buck2_cli_proto::new_generic::NewGenericRequest;
+use buck2_cli_proto::new_generic::NewGenericResponse;
+use buck2_client_ctx::client_ctx::ClientCommandContext;
+use buck2_client_ctx::common::ui::CommonConsoleOptions;
+use buck2_client_ctx::common::CommonBuildConfigurationOptions;
+use buck2_client_ctx::common::CommonCommandOptions;
+use buck2_client_ctx::common::CommonEventLogOptions;
+use buck2_client_ctx::common::CommonStarlarkOptions;
+use buck2_client_ctx::daemon::client::BuckdClientConnector;
+use buck2_client_ctx::exit_result::ExitCode;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_client_ctx::streaming::StreamingCommand;
+
+/// Verifies `buck-out` against the deferred materializer state.
+///
+/// Every materialized artifact is re-hashed and compared to the digest recorded in the
+/// materializer state. Artifacts that are missing or modified are reported, as are paths under
+/// `buck-out` that the materializer does not track.
+///
+/// Exits with a non-zero status if problems were found and not repaired. Untracked paths are not
+/// considered problems with `--repair`.
+#[derive(Debug, clap::Parser)]
+pub struct MaterializerFsckCommand {
+    /// Invalidate missing and modified artifacts so they get rematerialized or rebuilt when next
+    /// needed. Untracked paths are not deleted, use `buck2 clean --stale` for that.
+    #[clap(long)]
+    repair: bool,
+
+    #[clap(flatten)]
+    common_opts: CommonCommandOptions,
+}
+
+#[async_trait]
+impl StreamingCommand for MaterializerFsckCommand {
+    const COMMAND_NAME: &'static str = "materializer-fsck";
+
+    fn existing_only() -> bool {
+        true
+    }
+
+    async fn exec_impl(
+        self,
+        buckd: &mut BuckdClientConnector,
+        matches: &clap::ArgMatches,
+        ctx: &mut ClientCommandContext<'_>,
+    ) -> ExitResult {
+        let context = ctx.client_context(matches, &self)?;
+        let resp = buckd
+            .with_flushing()
+            .new_generic(
+                context,
+                NewGenericRequest::MaterializerFsck(MaterializerFsckRequest {
+                    repair: self.repair,
+                }),
+                ctx.stdin()
+                    .console_interaction_stream(&self.common_opts.console_opts),
+            )
+            .await??;
+        let NewGenericResponse::MaterializerFsck(resp) = resp else {
+            return ExitResult::bail("Unexpected response type from generic command");
+        };
+
+        let mut stdout = String::new();
+        for issue in &resp.issues {
+            writeln!(stdout, "{}\t{}\t{}", issue.kind, issue.path, issue.detail)?;
+        }
+
+        buck2_client_ctx::eprintln!(
+            "Checked {} artifacts, found {} problems",
+            resp.checked,
+            resp.issues.len()
+        )?;
+        if self.repair {
+            buck2_client_ctx::eprintln!("Invalidated {} artifacts", resp.repaired)?;
+            if resp.repaired_active > 0 {
+                buck2_client_ctx::eprintln!(
+                    "{} of them were used by the running daemon, which will rebuild them on the \
+                    next command",
+                    resp.repaired_active
+                )?;
+            }
+        }
+
+        let problems = if self.repair {
+            resp.issues.iter().filter(|i| i.kind != "untracked").count() as u64
+        } else {
+            resp.issues.len() as u64
+        };
+        let unrepaired = problems.saturating_sub(resp.repaired);
+        let result = if unrepaired == 0 {
+            ExitResult::success()
+        } else {
+            ExitResult::status(ExitCode::UserError)
+        };
+        result.with_stdout(stdout.into_bytes())
+    }
+
+    fn console_opts(&self) -> &CommonConsoleOptions {
+        &self.common_opts.console_opts
+    }
+
+    fn event_log_opts(&self) -> &CommonEventLogOptions {
+        &self.common_opts.event_log_opts
+    }
+
+    fn build_config_opts(&self) -> &CommonBuildConfigurationOptions {
+        &self.common_opts.config_opts
+    }
+
+    fn starlark_opts(&self) -> &CommonStarlarkOptions {
+        &self.common_opts.starlark_opts
+    }
+}
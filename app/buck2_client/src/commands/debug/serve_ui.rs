@@ -0,0 +1,716 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use anyhow::Context as _;
+use buck2_client_ctx::client_ctx::ClientCommandContext;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_data::buck_event::Data;
+use buck2_data::command_execution::Status;
+use buck2_data::command_execution_kind::Command;
+use buck2_event_log::read::EventLogPathBuf;
+use buck2_event_log::stream_value::StreamValue;
+use buck2_event_observer::display;
+use buck2_event_observer::display::TargetDisplayOptions;
+use buck2_events::span::SpanId;
+use buck2_events::BuckEvent;
+use futures::Stream;
+use serde::Serialize;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+use tokio::net::TcpStream;
+use tokio_stream::StreamExt;
+
+use crate::commands::log::options::EventLogOptions;
+
+const INDEX_HTML: &str = include_str!("serve_ui/index.html");
+
+/// Requests are a single `GET` line and a few headers, anything bigger is not for us.
+const MAX_REQUEST_SIZE: usize = 16 * 1024;
+
+/// Serve a web UI for a build on localhost.
+///
+/// The UI shows a timeline of the actions, the critical path, cache hits, test results and
+/// errors, and the command and output of each action. It reads the event log of the build, and
+/// keeps following it while the build is running.
+#[derive(Debug, clap::Parser)]
+pub struct ServeUiCommand {
+    #[clap(flatten)]
+    event_log: EventLogOptions,
+
+    /// The port to listen on. By default, any free port is used.
+    #[clap(long, default_value = "0")]
+    port: u16,
+}
+
+impl ServeUiCommand {
+    pub fn exec(self, _matches: &clap::ArgMatches, ctx: ClientCommandContext<'_>) -> ExitResult {
+        let Self { event_log, port } = self;
+
+        ctx.with_runtime(|ctx| async move {
+            let log_path = event_log.get(&ctx).await?;
+            let (invocation, events) = log_path.unpack_stream_following().await?;
+            let command = invocation.display_command_line();
+            let state = Arc::new(Mutex::new(BuildState::new(command.clone())));
+
+            let listener = TcpListener::bind(("127.0.0.1", port))
+                .await
+                .with_context(|| format!("Error listening on port {}", port))?;
+            buck2_client_ctx::eprintln!(
+                "Serving the UI for `{}` at http://{}/",
+                command,
+                listener.local_addr()?
+            )?;
+
+            tokio::spawn(follow_build(log_path, events, state.clone()));
+            serve(listener, state).await
+        })?;
+
+        ExitResult::success()
+    }
+}
+
+/// Applies the events of the log as they are written, until the build is done.
+async fn follow_build(
+    log_path: EventLogPathBuf,
+    events: impl Stream<Item = anyhow::Result<StreamValue>>,
+    state: Arc<Mutex<BuildState>>,
+) {
+    let mut events = std::pin::pin!(events);
+    loop {
+        let event = match events.try_next().await {
+            Ok(Some(StreamValue::Event(event))) => event,
+            Ok(Some(_)) => continue,
+            Ok(None) => return,
+            Err(e) => {
+                tracing::warn!("Error reading `{}`: {:#}", log_path.path(), e);
+                return;
+            }
+        };
+        let mut build = state.lock().unwrap();
+        let res = BuckEvent::try_from(event).and_then(|event| build.handle_event(&event));
+        if let Err(e) = res {
+            tracing::warn!("Invalid event in `{}`: {:#}", log_path.path(), e);
+            return;
+        }
+        if build.finished {
+            return;
+        }
+    }
+}
+
+async fn serve(listener: TcpListener, state: Arc<Mutex<BuildState>>) -> anyhow::Result<()> {
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, &state).await {
+                tracing::debug!("Error serving request: {:#}", e);
+            }
+        });
+    }
+}
+
+async fn handle_connection(mut stream: TcpStream, state: &Mutex<BuildState>) -> anyhow::Result<()> {
+    let mut request = Vec::new();
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        if request.len() > MAX_REQUEST_SIZE {
+            return write_response(&mut stream, "413 Payload Too Large", "text/plain", b"").await;
+        }
+        let mut buf = [0; 4096];
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            return Ok(());
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let response = respond(&request, &state.lock().unwrap())?;
+    write_response(
+        &mut stream,
+        response.status,
+        response.content_type,
+        &response.body,
+    )
+    .await
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn ok(content_type: &'static str, body: Vec<u8>) -> Self {
+        Self {
+            status: "200 OK",
+            content_type,
+            body,
+        }
+    }
+
+    fn error(status: &'static str) -> Self {
+        Self {
+            status,
+            content_type: "text/plain",
+            body: Vec::new(),
+        }
+    }
+}
+
+fn respond(request: &str, build: &BuildState) -> anyhow::Result<Response> {
+    if !is_local_host(request) {
+        return Ok(Response::error("403 Forbidden"));
+    }
+    let Some(path) = request_path(request) else {
+        return Ok(Response::error("405 Method Not Allowed"));
+    };
+    Ok(match Route::parse(path) {
+        Some(Route::Index) => Response::ok("text/html", INDEX_HTML.as_bytes().to_vec()),
+        Some(Route::Build) => Response::ok("application/json", serde_json::to_vec(build)?),
+        Some(Route::Action(id)) => match build.actions.get(id) {
+            Some(action) => Response::ok(
+                "application/json",
+                serde_json::to_vec(&ActionDetails {
+                    action,
+                    commands: &action.commands,
+                })?,
+            ),
+            None => Response::error("404 Not Found"),
+        },
+        None => Response::error("404 Not Found"),
+    })
+}
+
+async fn write_response(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> anyhow::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}; charset=utf-8\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Whether the `Host` header names this machine. Other names are rejected, so that a web page
+/// can't read the build through a domain it made resolve to 127.0.0.1 (DNS rebinding).
+fn is_local_host(request: &str) -> bool {
+    let host = request
+        .lines()
+        .skip(1)
+        .take_while(|line| !line.is_empty())
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.trim()
+                .eq_ignore_ascii_case("host")
+                .then_some(value.trim())
+        });
+    let Some(host) = host else {
+        return false;
+    };
+    let host = match host.strip_prefix('[') {
+        Some(ipv6) => ipv6.split_once(']').map_or(ipv6, |(host, _)| host),
+        None => host.split_once(':').map_or(host, |(host, _)| host),
+    };
+    host.eq_ignore_ascii_case("localhost") || host == "127.0.0.1" || host == "::1"
+}
+
+/// Returns the path of a `GET` request, without the query string.
+fn request_path(request: &str) -> Option<&str> {
+    let mut parts = request.lines().next()?.split(' ');
+    if parts.next()? != "GET" {
+        return None;
+    }
+    let target = parts.next()?;
+    Some(target.split_once('?').map_or(target, |(path, _)| path))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Route {
+    Index,
+    Build,
+    Action(usize),
+}
+
+impl Route {
+    fn parse(path: &str) -> Option<Self> {
+        match path {
+            "/" | "/index.html" => Some(Self::Index),
+            "/api/build" => Some(Self::Build),
+            _ => Some(Self::Action(
+                path.strip_prefix("/api/actions/")?.parse().ok()?,
+            )),
+        }
+    }
+}
+
+/// What the UI knows about a build, in the shape it consumes it. Times are in microseconds since
+/// the start of the command.
+#[derive(Serialize)]
+struct BuildState {
+    command: String,
+    finished: bool,
+    success: Option<bool>,
+    /// Time of the last event, which is "now" for a running build.
+    last_event_us: u64,
+    actions: Vec<ActionState>,
+    critical_path: Vec<CriticalPathNode>,
+    tests: Vec<TestState>,
+    errors: Vec<String>,
+    #[serde(skip)]
+    start: Option<SystemTime>,
+    #[serde(skip)]
+    running: HashMap<SpanId, usize>,
+}
+
+#[derive(Serialize)]
+struct ActionState {
+    id: usize,
+    name: String,
+    category: String,
+    start_us: u64,
+    end_us: Option<u64>,
+    execution_kind: Option<&'static str>,
+    cache_hit: bool,
+    failed: bool,
+    #[serde(skip)]
+    commands: Vec<CommandState>,
+}
+
+#[derive(Serialize)]
+struct ActionDetails<'a> {
+    #[serde(flatten)]
+    action: &'a ActionState,
+    commands: &'a [CommandState],
+}
+
+#[derive(Serialize)]
+struct CommandState {
+    executor: &'static str,
+    argv: Vec<String>,
+    action_digest: Option<String>,
+    status: &'static str,
+    exit_code: Option<i32>,
+    stdout: String,
+    stderr: String,
+}
+
+#[derive(Serialize)]
+struct CriticalPathNode {
+    kind: &'static str,
+    name: String,
+    duration_us: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct TestState {
+    name: String,
+    target: Option<String>,
+    status: &'static str,
+    duration_us: Option<u64>,
+    details: String,
+}
+
+fn duration_us(duration: Option<&prost_types::Duration>) -> Option<u64> {
+    let duration: Duration = duration?.clone().try_into().ok()?;
+    Some(duration.as_micros() as u64)
+}
+
+/// Same format as `display::display_action_identity`, for the critical path entries which don't
+/// carry an `ActionKey`.
+fn action_identity(owner: String, name: Option<&buck2_data::ActionName>) -> String {
+    match name {
+        Some(name) if !name.identifier.is_empty() => {
+            format!("{} ({} {})", owner, name.category, name.identifier)
+        }
+        Some(name) => format!("{} ({})", owner, name.category),
+        None => owner,
+    }
+}
+
+impl CommandState {
+    fn from_proto(command: &buck2_data::CommandExecution) -> Self {
+        let details = command.details.as_ref();
+        let (executor, argv, action_digest) = match details
+            .and_then(|d| d.command_kind.as_ref())
+            .and_then(|k| k.command.as_ref())
+        {
+            Some(Command::LocalCommand(c)) => ("local", c.argv.clone(), Some(&c.action_digest)),
+            Some(Command::RemoteCommand(c)) => ("remote", Vec::new(), Some(&c.action_digest)),
+            Some(Command::OmittedLocalCommand(c)) => ("local", Vec::new(), Some(&c.action_digest)),
+            Some(Command::WorkerInitCommand(c)) => ("worker_init", c.argv.clone(), None),
+            Some(Command::WorkerCommand(c)) => ("worker", c.argv.clone(), Some(&c.action_digest)),
+            None => ("unknown", Vec::new(), None),
+        };
+        let status = match &command.status {
+            Some(Status::Success(_)) => "success",
+            Some(Status::Failure(_)) => "failure",
+            Some(Status::Timeout(_)) => "timeout",
+            Some(Status::Error(_)) => "error",
+            Some(Status::Cancelled(_)) => "cancelled",
+            None => "unknown",
+        };
+        Self {
+            executor,
+            argv,
+            action_digest: action_digest.cloned(),
+            status,
+            exit_code: details.and_then(|d| d.signed_exit_code),
+            stdout: details.map(|d| d.stdout.clone()).unwrap_or_default(),
+            stderr: details.map(|d| d.stderr.clone()).unwrap_or_default(),
+        }
+    }
+}
+
+impl BuildState {
+    fn new(command: String) -> Self {
+        Self {
+            command,
+            finished: false,
+            success: None,
+            last_event_us: 0,
+            actions: Vec::new(),
+            critical_path: Vec::new(),
+            tests: Vec::new(),
+            errors: Vec::new(),
+            start: None,
+            running: HashMap::new(),
+        }
+    }
+
+    fn handle_event(&mut self, event: &BuckEvent) -> anyhow::Result<()> {
+        let start = *self.start.get_or_insert(event.timestamp());
+        let now_us = event
+            .timestamp()
+            .duration_since(start)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        self.last_event_us = self.last_event_us.max(now_us);
+        let opts = TargetDisplayOptions::for_log();
+
+        match event.data() {
+            Data::SpanStart(span) => {
+                if let (
+                    Some(buck2_data::span_start_event::Data::ActionExecution(action)),
+                    Some(span_id),
+                ) = (&span.data, event.span_id())
+                {
+                    let id = self.actions.len();
+                    self.running.insert(span_id, id);
+                    self.actions.push(ActionState {
+                        id,
+                        name: display::display_action_identity(
+                            action.key.as_ref(),
+                            action.name.as_ref(),
+                            opts,
+                        )?,
+                        category: action
+                            .name
+                            .as_ref()
+                            .map(|n| n.category.clone())
+                            .unwrap_or_default(),
+                        start_us: now_us,
+                        end_us: None,
+                        execution_kind: None,
+                        cache_hit: false,
+                        failed: false,
+                        commands: Vec::new(),
+                    });
+                }
+            }
+            Data::SpanEnd(span) => match &span.data {
+                Some(buck2_data::span_end_event::Data::ActionExecution(end)) => {
+                    if let Some(id) = event.span_id().and_then(|s| self.running.remove(&s)) {
+                        use buck2_data::ActionExecutionKind;
+
+                        let kind = ActionExecutionKind::from_i32(end.execution_kind)
+                            .unwrap_or(ActionExecutionKind::NotSet);
+                        let action = &mut self.actions[id];
+                        action.end_us = Some(now_us);
+                        action.execution_kind = Some(kind.as_str_name());
+                        action.cache_hit = matches!(
+                            kind,
+                            ActionExecutionKind::ActionCache
+                                | ActionExecutionKind::RemoteDepFileCache
+                                | ActionExecutionKind::LocalDepFile
+                        );
+                        action.failed = end.failed;
+                        action.commands =
+                            end.commands.iter().map(CommandState::from_proto).collect();
+                    }
+                }
+                Some(buck2_data::span_end_event::Data::Command(end)) => {
+                    self.finished = true;
+                    self.success = Some(end.is_success);
+                    self.errors
+                        .extend(end.errors.iter().map(|e| e.message.clone()));
+                }
+                _ => {}
+            },
+            Data::Instant(instant) => match &instant.data {
+                Some(buck2_data::instant_event::Data::BuildGraphInfo(info)) => {
+                    self.critical_path = Self::critical_path(info, opts)?;
+                }
+                Some(buck2_data::instant_event::Data::TestResult(result)) => {
+                    self.tests.push(TestState {
+                        name: result.name.clone(),
+                        target: result
+                            .target_label
+                            .as_ref()
+                            .map(|t| display::display_configured_target_label(t, opts))
+                            .transpose()?,
+                        status: buck2_data::TestStatus::from_i32(result.status)
+                            .unwrap_or(buck2_data::TestStatus::NotSetTestStatus)
+                            .as_str_name(),
+                        duration_us: duration_us(result.duration.as_ref()),
+                        details: result.details.clone(),
+                    });
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn critical_path(
+        info: &buck2_data::BuildGraphExecutionInfo,
+        opts: TargetDisplayOptions,
+    ) -> anyhow::Result<Vec<CriticalPathNode>> {
+        use buck2_data::critical_path_entry2::Entry;
+
+        let mut nodes = Vec::new();
+        for entry in &info.critical_path2 {
+            let (kind, name) = match &entry.entry {
+                Some(Entry::Analysis(analysis)) => {
+                    use buck2_data::critical_path_entry2::analysis::Target;
+
+                    match &analysis.target {
+                        Some(Target::StandardTarget(t)) => (
+                            "analysis",
+                            display::display_configured_target_label(t, opts)?,
+                        ),
+                        None => continue,
+                    }
+                }
+                Some(Entry::ActionExecution(action)) => {
+                    use buck2_data::critical_path_entry2::action_execution::Owner;
+
+                    let owner = match &action.owner {
+                        Some(Owner::TargetLabel(t)) => {
+                            display::display_configured_target_label(t, opts)?
+                        }
+                        Some(Owner::BxlKey(t)) => display::display_bxl_key(t)?,
+                        Some(Owner::AnonTarget(t)) => display::display_anon_target(t)?,
+                        None => continue,
+                    };
+                    ("action", action_identity(owner, action.name.as_ref()))
+                }
+                Some(Entry::Materialization(materialization)) => {
+                    ("materialization", materialization.path.clone())
+                }
+                Some(Entry::ComputeCriticalPath(..)) => ("compute-critical-path", String::new()),
+                Some(Entry::Load(load)) => ("load", load.package.clone()),
+                Some(Entry::Listing(listing)) => ("listing", listing.package.clone()),
+                None => continue,
+            };
+            nodes.push(CriticalPathNode {
+                kind,
+                name,
+                duration_us: duration_us(entry.total_duration.as_ref()),
+            });
+        }
+        Ok(nodes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_wrapper_common::invocation_id::TraceId;
+
+    use super::*;
+
+    #[test]
+    fn test_request_path() {
+        assert_eq!(
+            request_path("GET /api/build?t=1 HTTP/1.1\r\nHost: localhost\r\n\r\n"),
+            Some("/api/build")
+        );
+        assert_eq!(request_path("POST / HTTP/1.1\r\n\r\n"), None);
+        assert_eq!(request_path(""), None);
+    }
+
+    #[test]
+    fn test_is_local_host() {
+        assert!(is_local_host(
+            "GET / HTTP/1.1\r\nHost: localhost:8080\r\n\r\n"
+        ));
+        assert!(is_local_host("GET / HTTP/1.1\r\nhost: 127.0.0.1\r\n\r\n"));
+        assert!(is_local_host("GET / HTTP/1.1\r\nHost: [::1]:80\r\n\r\n"));
+        assert!(!is_local_host(
+            "GET / HTTP/1.1\r\nHost: attacker.example:8080\r\n\r\n"
+        ));
+        assert!(!is_local_host(
+            "GET / HTTP/1.1\r\nHost: localhost.attacker.example\r\n\r\n"
+        ));
+        assert!(!is_local_host("GET / HTTP/1.1\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_route() {
+        assert_eq!(Route::parse("/"), Some(Route::Index));
+        assert_eq!(Route::parse("/api/build"), Some(Route::Build));
+        assert_eq!(Route::parse("/api/actions/12"), Some(Route::Action(12)));
+        assert_eq!(Route::parse("/api/actions/x"), None);
+        assert_eq!(Route::parse("/favicon.ico"), None);
+    }
+
+    fn event(data: buck2_data::buck_event::Data, span_id: SpanId, offset_ms: u64) -> BuckEvent {
+        BuckEvent::new(
+            SystemTime::UNIX_EPOCH + Duration::from_millis(offset_ms),
+            TraceId::new(),
+            Some(span_id),
+            None,
+            data,
+        )
+    }
+
+    fn build_state() -> anyhow::Result<BuildState> {
+        let mut build = BuildState::new("buck2 build //:x".to_owned());
+        let span_id = SpanId::next();
+        build.handle_event(&event(
+            buck2_data::SpanStartEvent {
+                data: Some(
+                    buck2_data::ActionExecutionStart {
+                        key: Some(buck2_data::ActionKey {
+                            owner: Some(buck2_data::action_key::Owner::TargetLabel(
+                                buck2_data::ConfiguredTargetLabel {
+                                    label: Some(buck2_data::TargetLabel {
+                                        package: "root//".to_owned(),
+                                        name: "x".to_owned(),
+                                    }),
+                                    configuration: Some(buck2_data::Configuration {
+                                        full_name: "cfg".to_owned(),
+                                        ..Default::default()
+                                    }),
+                                    execution_configuration: None,
+                                },
+                            )),
+                            ..Default::default()
+                        }),
+                        name: Some(buck2_data::ActionName {
+                            category: "cc".to_owned(),
+                            identifier: "x.o".to_owned(),
+                        }),
+                        ..Default::default()
+                    }
+                    .into(),
+                ),
+            }
+            .into(),
+            span_id,
+            0,
+        ))?;
+        build.handle_event(&event(
+            buck2_data::SpanEndEvent {
+                data: Some(
+                    buck2_data::ActionExecutionEnd {
+                        failed: true,
+                        execution_kind: buck2_data::ActionExecutionKind::Local as i32,
+                        commands: vec![buck2_data::CommandExecution {
+                            details: Some(buck2_data::CommandExecutionDetails {
+                                stderr: "error: x".to_owned(),
+                                signed_exit_code: Some(1),
+                                ..Default::default()
+                            }),
+                            status: Some(Status::Failure(Default::default())),
+                        }],
+                        ..Default::default()
+                    }
+                    .into(),
+                ),
+                ..Default::default()
+            }
+            .into(),
+            span_id,
+            1500,
+        ))?;
+        Ok(build)
+    }
+
+    #[test]
+    fn test_handle_events() -> anyhow::Result<()> {
+        let build = build_state()?;
+        assert!(!build.finished);
+        assert_eq!(1_500_000, build.last_event_us);
+        let action = &build.actions[0];
+        assert_eq!("root//:x (cfg) (cc x.o)", action.name);
+        assert_eq!(Some(1_500_000), action.end_us);
+        assert_eq!(Some("ACTION_EXECUTION_KIND_LOCAL"), action.execution_kind);
+        assert!(action.failed);
+        assert_eq!("failure", action.commands[0].status);
+        assert_eq!(Some(1), action.commands[0].exit_code);
+        Ok(())
+    }
+
+    #[test]
+    fn test_respond() -> anyhow::Result<()> {
+        let build = build_state()?;
+        let get = |path: &str| {
+            respond(
+                &format!("GET {} HTTP/1.1\r\nHost: localhost:1234\r\n\r\n", path),
+                &build,
+            )
+        };
+
+        let response = get("/")?;
+        assert_eq!("200 OK", response.status);
+        assert_eq!("text/html", response.content_type);
+
+        let response: serde_json::Value = serde_json::from_slice(&get("/api/build")?.body)?;
+        assert_eq!("buck2 build //:x", response["command"]);
+        assert_eq!(1, response["actions"].as_array().unwrap().len());
+        // Commands are only sent for the action that is looked at.
+        assert!(response["actions"][0].get("commands").is_none());
+
+        let response: serde_json::Value = serde_json::from_slice(&get("/api/actions/0")?.body)?;
+        assert_eq!("error: x", response["commands"][0]["stderr"]);
+
+        assert_eq!("404 Not Found", get("/api/actions/1")?.status);
+        assert_eq!(
+            "403 Forbidden",
+            respond(
+                "GET /api/build HTTP/1.1\r\nHost: attacker.example\r\n\r\n",
+                &build
+            )?
+            .status
+        );
+        assert_eq!(
+            "405 Method Not Allowed",
+            respond("POST / HTTP/1.1\r\nHost: localhost\r\n\r\n", &build)?.status
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use buck2_client_ctx::client_ctx::ClientCommandContext;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_data::command_invalidation_info::InvalidationPathEntry;
+use buck2_data::command_invalidation_info::InvalidationSource;
+use buck2_event_log::stream_value::StreamValue;
+use buck2_event_observer::display;
+use buck2_event_observer::display::TargetDisplayOptions;
+use tokio_stream::StreamExt;
+
+use crate::commands::log::options::EventLogOptions;
+
+/// Explains why keys were recomputed in the selected invocation.
+///
+/// For every build file load, analysis and action that was recomputed, prints the DICE invalidation path from the
+/// invalidation source (e.g. a changed file or a buckconfig change) to the recomputed key, and
+/// then summarizes which invalidation sources caused the most recomputation.
+///
+/// This requires the invocation to have run with `buck2.invalidation_tracking_enabled`.
+#[derive(Debug, clap::Parser)]
+pub struct WhyRecomputedCommand {
+    #[clap(flatten)]
+    event_log: EventLogOptions,
+
+    /// Only consider invalidations caused by file changes (high priority invalidation sources).
+    #[clap(long)]
+    file_changes_only: bool,
+
+    /// Print the full invalidation path for each recomputed key, not just its source.
+    #[clap(long)]
+    show_path: bool,
+
+    /// Number of invalidation sources to show in the summary.
+    #[clap(long, default_value = "10")]
+    top: usize,
+
+    /// Print the output as JSON lines. Each line has a `type`: `recomputed` for a recomputed key,
+    /// `summary` for an invalidation source in the summary.
+    #[clap(long)]
+    json: bool,
+}
+
+#[derive(serde::Serialize)]
+struct RecomputedRecord {
+    kind: &'static str,
+    key: String,
+    path: Vec<String>,
+}
+
+#[derive(serde::Serialize)]
+struct SourceSummary<'a> {
+    source: &'a str,
+    recomputed: u64,
+}
+
+#[derive(serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum JsonLine<'a> {
+    Recomputed(&'a RecomputedRecord),
+    Summary(SourceSummary<'a>),
+}
+
+fn display_path_entry(entry: &InvalidationPathEntry) -> String {
+    format!("{} ({}) @ v{}", entry.key, entry.key_type, entry.version)
+}
+
+impl WhyRecomputedCommand {
+    fn pick<'a>(
+        &self,
+        info: Option<&'a buck2_data::CommandInvalidationInfo>,
+    ) -> Option<&'a InvalidationSource> {
+        let info = info?;
+        if self.file_changes_only {
+            info.changed_file.as_ref()
+        } else {
+            info.changed_any.as_ref()
+        }
+    }
+
+    pub fn exec(self, _matches: &clap::ArgMatches, ctx: ClientCommandContext<'_>) -> ExitResult {
+        buck2_client_ctx::stdio::print_with_writer::<anyhow::Error, _>(|w| {
+            ctx.with_runtime(|ctx| async move {
+                let log_path = self.event_log.get(&ctx).await?;
+
+                let (invocation, mut events) = log_path.unpack_stream().await?;
+                buck2_client_ctx::eprintln!(
+                    "Showing recomputations from: {}",
+                    invocation.display_command_line()
+                )?;
+
+                let mut analysis_starts = HashMap::new();
+                let mut by_source: HashMap<String, u64> = HashMap::new();
+                let mut any_tracked = false;
+
+                while let Some(event) = events.try_next().await? {
+                    let event = match event {
+                        StreamValue::Event(event) => event,
+                        StreamValue::Result(..) | StreamValue::PartialResult(..) => continue,
+                    };
+
+                    let record = match event.data {
+                        Some(buck2_data::buck_event::Data::SpanStart(start)) => {
+                            if let Some(buck2_data::span_start_event::Data::Analysis(analysis)) =
+                                start.data
+                            {
+                                analysis_starts.insert(event.span_id, analysis);
+                            }
+                            continue;
+                        }
+                        Some(buck2_data::buck_event::Data::SpanEnd(end)) => match end.data {
+                            Some(buck2_data::span_end_event::Data::Load(load)) => {
+                                any_tracked |= load.invalidation_info.is_some();
+                                let Some(source) = self.pick(load.invalidation_info.as_ref())
+                                else {
+                                    continue;
+                                };
+                                ("load", load.module_id, source.path.clone())
+                            }
+                            Some(buck2_data::span_end_event::Data::ActionExecution(action)) => {
+                                any_tracked |= action.invalidation_info.is_some();
+                                let Some(source) = self.pick(action.invalidation_info.as_ref())
+                                else {
+                                    continue;
+                                };
+                                let key = display::display_action_identity(
+                                    action.key.as_ref(),
+                                    action.name.as_ref(),
+                                    TargetDisplayOptions::for_log(),
+                                )?;
+                                ("action", key, source.path.clone())
+                            }
+                            Some(buck2_data::span_end_event::Data::Analysis(analysis)) => {
+                                let start = analysis_starts.remove(&event.span_id);
+                                any_tracked |= analysis.invalidation_info.is_some();
+                                let Some(source) = self.pick(analysis.invalidation_info.as_ref())
+                                else {
+                                    continue;
+                                };
+                                let key = match start.as_ref().and_then(|s| s.target.as_ref()) {
+                                    Some(target) => display::display_analysis_target(
+                                        target,
+                                        TargetDisplayOptions::for_log(),
+                                    )?,
+                                    None => "<unknown>".to_owned(),
+                                };
+                                ("analysis", key, source.path.clone())
+                            }
+                            _ => continue,
+                        },
+                        _ => continue,
+                    };
+
+                    let (kind, key, path) = record;
+                    let Some(root) = path.first() else {
+                        continue;
+                    };
+                    *by_source.entry(display_path_entry(root)).or_default() += 1;
+
+                    let record = RecomputedRecord {
+                        kind,
+                        key,
+                        path: path.iter().map(display_path_entry).collect(),
+                    };
+                    if self.json {
+                        serde_json::to_writer(w.by_ref(), &JsonLine::Recomputed(&record))?;
+                        w.write_all(b"\n")?;
+                    } else if self.show_path {
+                        writeln!(w, "{}\t{}", record.kind, record.key)?;
+                        for entry in &record.path {
+                            writeln!(w, "  <- {}", entry)?;
+                        }
+                    } else {
+                        writeln!(w, "{}\t{}\t{}", record.kind, record.key, record.path[0])?;
+                    }
+                }
+
+                if !any_tracked {
+                    buck2_client_ctx::eprintln!(
+                        "No invalidation information found in this log; \
+                        set `buck2.invalidation_tracking_enabled = true` to record it"
+                    )?;
+                    return anyhow::Ok(());
+                }
+
+                let mut by_source: Vec<_> = by_source.into_iter().collect();
+                by_source.sort_by(|(a_source, a), (b_source, b)| {
+                    b.cmp(a).then_with(|| a_source.cmp(b_source))
+                });
+
+                if self.json {
+                    for (source, recomputed) in by_source.iter().take(self.top) {
+                        serde_json::to_writer(
+                            w.by_ref(),
+                            &JsonLine::Summary(SourceSummary {
+                                source,
+                                recomputed: *recomputed,
+                            }),
+                        )?;
+                        w.write_all(b"\n")?;
+                    }
+                } else {
+                    buck2_client_ctx::eprintln!("Top invalidation sources:")?;
+                    for (source, recomputed) in by_source.iter().take(self.top) {
+                        buck2_client_ctx::eprintln!("  {}\t{}", recomputed, source)?;
+                    }
+                }
+
+                anyhow::Ok(())
+            })?;
+            anyhow::Ok(())
+        })?;
+        ExitResult::success()
+    }
+}
@@ -0,0 +1,108 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::fmt::Write;
+
+use buck2_cli_proto::new_generic::ExternalCellsRequest;
+use buck2_cli_proto::new_generic::NewGenericRequest;
+use buck2_cli_proto::new_generic::NewGenericResponse;
+use buck2_client_ctx::client_ctx::ClientCommandContext;
+use buck2_client_ctx::common::ui::CommonConsoleOptions;
+use buck2_client_ctx::common::CommonBuildConfigurationOptions;
+use buck2_client_ctx::common::CommonEventLogOptions;
+use buck2_client_ctx::common::CommonStarlarkOptions;
+use buck2_client_ctx::daemon::client::BuckdClientConnector;
+use buck2_client_ctx::exit_result::ExitCode;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_client_ctx::streaming::StreamingCommand;
+use clap::ArgMatches;
+
+/// Manage the lockfile pinning the versions of external cells.
+///
+/// Cells declare the external cells they need, with version constraints, in an
+/// `external_cells.toml` at their root. These commands resolve them to one version per external
+/// cell, and compare the result with `external_cells.lock` in the project root.
+#[derive(Debug, clap::Parser)]
+#[clap(name = "external-cells")]
+pub struct ExternalCellsCommand {
+    #[clap(subcommand)]
+    action: ExternalCellsAction,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum ExternalCellsAction {
+    /// Resolve the manifests and rewrite the lockfile if it's out of date.
+    Update,
+    /// Resolve the manifests and fail if the lockfile is out of date.
+    Verify,
+}
+
+#[async_trait::async_trait]
+impl StreamingCommand for ExternalCellsCommand {
+    const COMMAND_NAME: &'static str = "external-cells";
+
+    async fn exec_impl(
+        self,
+        buckd: &mut BuckdClientConnector,
+        matches: &ArgMatches,
+        ctx: &mut ClientCommandContext<'_>,
+    ) -> ExitResult {
+        let update = match self.action {
+            ExternalCellsAction::Update => true,
+            ExternalCellsAction::Verify => false,
+        };
+        let context = ctx.client_context(matches, &self)?;
+        let resp = buckd
+            .with_flushing()
+            .new_generic(
+                context,
+                NewGenericRequest::ExternalCells(ExternalCellsRequest { update }),
+                None,
+            )
+            .await??;
+        let NewGenericResponse::ExternalCells(resp) = resp else {
+            return ExitResult::bail("Unexpected response type from generic command");
+        };
+
+        let mut stdout = String::new();
+        for change in &resp.changes {
+            writeln!(stdout, "{}", change)?;
+        }
+
+        let result = if resp.changes.is_empty() {
+            buck2_client_ctx::eprintln!("`external_cells.lock` is up to date")?;
+            ExitResult::success()
+        } else if update {
+            buck2_client_ctx::eprintln!("Updated `external_cells.lock`")?;
+            ExitResult::success()
+        } else {
+            buck2_client_ctx::eprintln!(
+                "`external_cells.lock` is out of date, run `buck2 external-cells update`"
+            )?;
+            ExitResult::status(ExitCode::UserError)
+        };
+        result.with_stdout(stdout.into_bytes())
+    }
+
+    fn console_opts(&self) -> &CommonConsoleOptions {
+        CommonConsoleOptions::default_ref()
+    }
+
+    fn event_log_opts(&self) -> &CommonEventLogOptions {
+        CommonEventLogOptions::default_ref()
+    }
+
+    fn build_config_opts(&self) -> &CommonBuildConfigurationOptions {
+        CommonBuildConfigurationOptions::default_ref()
+    }
+
+    fn starlark_opts(&self) -> &CommonStarlarkOptions {
+        CommonStarlarkOptions::default_ref()
+    }
+}
@@ -23,6 +23,7 @@ mod what_materialized;
 pub(crate) mod what_ran;
 mod what_up;
 mod what_uploaded;
+mod why_rebuilt;
 
 use std::fmt::Debug;
 
@@ -94,6 +95,7 @@ pub enum LogCommand {
     Summary(summary::SummaryCommand),
     #[clap(subcommand)]
     Diff(diff::DiffCommand),
+    WhyRebuilt(why_rebuilt::WhyRebuiltCommand),
 }
 
 impl LogCommand {
@@ -112,6 +114,7 @@ impl LogCommand {
             Self::ShowUser(cmd) => cmd.exec(matches, ctx),
             Self::Summary(cmd) => cmd.exec(matches, ctx),
             Self::Diff(cmd) => cmd.exec(matches, ctx),
+            Self::WhyRebuilt(cmd) => cmd.exec(matches, ctx),
         }
     }
 
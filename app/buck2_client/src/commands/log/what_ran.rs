@@ -341,7 +341,15 @@ impl WhatRanOutputWriter for OutputFormatWithWriter<'_> {
 
         match &mut self.format {
             LogCommandOutputFormatWithWriter::Tabulated(w) => {
-                w.write_all(format!("{}\n", command.as_tabulated_reproducer()).as_bytes())?;
+                write!(w, "{}", command.as_tabulated_reproducer())?;
+                if let Some(memory_peak_bytes) = command.memory_peak_bytes {
+                    write!(
+                        w,
+                        "\tpeak memory: {}",
+                        bytesize::to_string(memory_peak_bytes, true)
+                    )?;
+                }
+                w.write_all(b"\n")?;
                 if let Some(std_err) = std_err_formatted {
                     write!(
                         w,
@@ -431,6 +439,7 @@ impl WhatRanOutputWriter for OutputFormatWithWriter<'_> {
                         .map(|duration| fmt_duration::fmt_duration(duration, 1.0)),
                     extra: command.extra.map(Into::into),
                     std_err,
+                    memory_peak_bytes: command.memory_peak_bytes,
                 };
                 serde_json::to_writer(w.by_ref(), &command)?;
                 w.write_all("\n".as_bytes())?;
@@ -479,6 +488,8 @@ struct JsonCommand<'a> {
     extra: Option<JsonExtra<'a>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     std_err: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    memory_peak_bytes: Option<u64>,
 }
 
 mod json_reproducer {
@@ -555,6 +566,7 @@ mod tests {
             duration: Some("1".to_owned()),
             extra: None,
             std_err: None,
+            memory_peak_bytes: None,
         }
     }
 
@@ -572,6 +584,7 @@ mod tests {
             duration: Some("1".to_owned()),
             extra: None,
             std_err: None,
+            memory_peak_bytes: None,
         }
     }
 
@@ -632,6 +645,65 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn serialize_what_ran_command_with_memory_peak() -> anyhow::Result<()> {
+        let mut command = make_base_command();
+        command.memory_peak_bytes = Some(1048576);
+
+        let expected = r#"{
+  "reason": "test.run",
+  "identity": "some/target",
+  "reproducer": {
+    "executor": "Local",
+    "details": {
+      "command": [
+        "some",
+        "command"
+      ],
+      "env": {
+        "KEY": "val"
+      }
+    }
+  },
+  "duration": "1",
+  "memory_peak_bytes": 1048576
+}"#;
+        assert_eq!(expected, serde_json::to_string_pretty(&command)?);
+        Ok(())
+    }
+
+    #[test]
+    fn tabulated_what_ran_command_with_memory_peak() -> anyhow::Result<()> {
+        let local_execute = buck2_data::LocalExecute {
+            command: Some(buck2_data::LocalCommand {
+                argv: vec!["some".to_owned(), "command".to_owned()],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut out = Vec::new();
+        let mut output = OutputFormatWithWriter {
+            format: LogCommandOutputFormatWithWriter::Tabulated(&mut out),
+            include_std_err: false,
+            omit_empty_std_err: false,
+        };
+        output.emit_command(WhatRanOutputCommand {
+            reason: "build",
+            identity: "some/target",
+            repro: CommandReproducer::LocalExecute(&local_execute),
+            extra: None,
+            std_err: None,
+            duration: None,
+            memory_peak_bytes: Some(1048576),
+        })?;
+
+        assert_eq!(
+            "build\tsome/target\tlocal\tsome command\tpeak memory: 1.0 MiB\n",
+            String::from_utf8(out)?
+        );
+        Ok(())
+    }
+
     #[test]
     fn serialize_what_ran_command_in_re() -> anyhow::Result<()> {
         let command = make_base_command_in_re();
@@ -0,0 +1,476 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use buck2_client_ctx::client_ctx::ClientCommandContext;
+use buck2_client_ctx::exit_result::ExitResult;
+use buck2_client_ctx::path_arg::PathArg;
+use buck2_data::ActionInputFingerprints;
+use buck2_event_log::file_names::get_local_logs;
+use buck2_event_log::read::EventLogPathBuf;
+use buck2_event_log::stream_value::StreamValue;
+use buck2_event_observer::display::display_action_identity;
+use buck2_event_observer::display::TargetDisplayOptions;
+use buck2_util::indent::indent;
+use tokio_stream::StreamExt;
+
+use crate::commands::log::options::EventLogOptions;
+
+#[derive(Debug, buck2_error::Error)]
+enum WhyRebuiltError {
+    #[error("No action matching `{0}` ran in the selected invocation")]
+    NotFound(String),
+    #[error(
+        "`{0}` matches several actions, please be more specific:\n{}",
+        indent("  ", &_1.join("\n"))
+    )]
+    Ambiguous(String, Vec<String>),
+    #[error("`{0}` did not run in any earlier invocation")]
+    NoPreviousExecution(String),
+    #[error(
+        "No input fingerprints were recorded for `{0}` in {1}; \
+        set `buck2.record_action_input_fingerprints = true` to record them"
+    )]
+    NoFingerprints(String, &'static str),
+}
+
+/// Explains why an action ran again, by comparing it with its previous execution.
+///
+/// Finds the action in the selected invocation, then looks through the older invocations for the
+/// last time the same action ran, and prints which arguments, environment variables and input
+/// files changed in between.
+///
+/// This requires both invocations to have run with `buck2.record_action_input_fingerprints`.
+#[derive(Debug, clap::Parser)]
+pub struct WhyRebuiltCommand {
+    /// The action to explain, matched against its identity as printed by `buck2 log what-ran`
+    /// (e.g. `root//foo:bar (cxx_compile bar.cpp)`). Any unique substring is enough.
+    #[clap(value_name = "ACTION")]
+    action: String,
+
+    #[clap(flatten)]
+    event_log: EventLogOptions,
+
+    /// A path to the event-log file of the invocation to compare against. By default, the most
+    /// recent earlier invocation that ran the action is used.
+    #[clap(long, value_name = "PATH")]
+    previous: Option<PathArg>,
+
+    /// Print the changes as JSON lines.
+    #[clap(long)]
+    json: bool,
+}
+
+#[derive(Debug, PartialEq, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum Change {
+    /// An argument of the command line changed. The index is in the previous command line if the
+    /// argument was removed, and in the current one otherwise.
+    Argument {
+        index: usize,
+        previous: Option<String>,
+        current: Option<String>,
+    },
+    Env {
+        key: String,
+        previous: Option<String>,
+        current: Option<String>,
+    },
+    Input {
+        path: String,
+        previous: Option<String>,
+        current: Option<String>,
+    },
+}
+
+impl Change {
+    fn display(&self) -> String {
+        fn value(v: &Option<String>) -> String {
+            match v {
+                Some(v) => format!("`{}`", v),
+                None => "<none>".to_owned(),
+            }
+        }
+
+        match self {
+            Change::Argument {
+                index,
+                previous,
+                current,
+            } => format!("argv[{}]: {} -> {}", index, value(previous), value(current)),
+            Change::Env {
+                key,
+                previous,
+                current,
+            } => format!("env {}: {} -> {}", key, value(previous), value(current)),
+            Change::Input {
+                path,
+                previous,
+                current,
+            } => format!("input {}: {} -> {}", path, value(previous), value(current)),
+        }
+    }
+}
+
+/// Compare two executions of the same action.
+fn diff_fingerprints(
+    previous: &ActionInputFingerprints,
+    current: &ActionInputFingerprints,
+) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    // Only compare what is between the common prefix and suffix of both command lines, so that a
+    // single inserted or removed argument doesn't show up as every following argument changing.
+    let (prev, cur) = (&previous.argv, &current.argv);
+    let prefix = prev.iter().zip(cur).take_while(|(a, b)| a == b).count();
+    let suffix = prev[prefix..]
+        .iter()
+        .rev()
+        .zip(cur[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (prev_mid, cur_mid) = (
+        &prev[prefix..prev.len() - suffix],
+        &cur[prefix..cur.len() - suffix],
+    );
+    if prev_mid.len() == cur_mid.len() {
+        for (i, (a, b)) in prev_mid.iter().zip(cur_mid).enumerate() {
+            changes.push(Change::Argument {
+                index: prefix + i,
+                previous: Some(a.clone()),
+                current: Some(b.clone()),
+            });
+        }
+    } else {
+        for (i, a) in prev_mid.iter().enumerate() {
+            changes.push(Change::Argument {
+                index: prefix + i,
+                previous: Some(a.clone()),
+                current: None,
+            });
+        }
+        for (i, b) in cur_mid.iter().enumerate() {
+            changes.push(Change::Argument {
+                index: prefix + i,
+                previous: None,
+                current: Some(b.clone()),
+            });
+        }
+    }
+
+    let prev_env: BTreeMap<_, _> = previous.env.iter().map(|e| (&e.key, &e.value)).collect();
+    let cur_env: BTreeMap<_, _> = current.env.iter().map(|e| (&e.key, &e.value)).collect();
+    for (key, previous, current) in diff_maps(&prev_env, &cur_env) {
+        changes.push(Change::Env {
+            key,
+            previous,
+            current,
+        });
+    }
+
+    let prev_inputs: BTreeMap<_, _> = previous
+        .inputs
+        .iter()
+        .map(|i| (&i.path, &i.digest))
+        .collect();
+    let cur_inputs: BTreeMap<_, _> = current
+        .inputs
+        .iter()
+        .map(|i| (&i.path, &i.digest))
+        .collect();
+    for (path, previous, current) in diff_maps(&prev_inputs, &cur_inputs) {
+        changes.push(Change::Input {
+            path,
+            previous,
+            current,
+        });
+    }
+
+    changes
+}
+
+/// The keys whose values differ between the two maps, in order.
+fn diff_maps(
+    previous: &BTreeMap<&String, &String>,
+    current: &BTreeMap<&String, &String>,
+) -> Vec<(String, Option<String>, Option<String>)> {
+    let mut keys: Vec<_> = previous.keys().chain(current.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter_map(|key| {
+            let (a, b) = (previous.get(key), current.get(key));
+            if a == b {
+                return None;
+            }
+            Some((
+                (*key).clone(),
+                a.map(|v| (*v).clone()),
+                b.map(|v| (*v).clone()),
+            ))
+        })
+        .collect()
+}
+
+/// The last execution of every action in the log matching `matches`, keyed by action identity.
+async fn read_executions(
+    log: &EventLogPathBuf,
+    matches: impl Fn(&str) -> bool,
+) -> anyhow::Result<BTreeMap<String, Option<ActionInputFingerprints>>> {
+    let (_, mut events) = log.unpack_stream().await?;
+    let mut executions = BTreeMap::new();
+    while let Some(event) = events.try_next().await? {
+        let StreamValue::Event(event) = event else {
+            continue;
+        };
+        let Some(buck2_data::buck_event::Data::SpanEnd(end)) = event.data else {
+            continue;
+        };
+        let Some(buck2_data::span_end_event::Data::ActionExecution(action)) = end.data else {
+            continue;
+        };
+        let identity = display_action_identity(
+            action.key.as_ref(),
+            action.name.as_ref(),
+            TargetDisplayOptions::for_log(),
+        )?;
+        if matches(&identity) {
+            executions.insert(identity, action.input_fingerprints);
+        }
+    }
+    Ok(executions)
+}
+
+/// When the invocation of the log started, from its first event.
+async fn start_time(log: &EventLogPathBuf) -> anyhow::Result<Option<(i64, i32)>> {
+    let (_, mut events) = log.unpack_stream().await?;
+    while let Some(event) = events.try_next().await? {
+        if let StreamValue::Event(event) = event {
+            return Ok(event.timestamp.map(|t| (t.seconds, t.nanos)));
+        }
+    }
+    Ok(None)
+}
+
+/// The logs of the invocations that started before the one of `log`, newest first. Logs that can't
+/// be read are skipped.
+async fn logs_started_before(
+    log: &EventLogPathBuf,
+    logs: Vec<EventLogPathBuf>,
+) -> anyhow::Result<Vec<EventLogPathBuf>> {
+    let Some(start) = start_time(log).await? else {
+        return Ok(Vec::new());
+    };
+    let mut older = Vec::new();
+    for other in logs {
+        if let Ok(Some(other_start)) = start_time(&other).await {
+            if other_start < start {
+                older.push((other_start, other));
+            }
+        }
+    }
+    older.sort_by(|a, b| b.0.cmp(&a.0));
+    Ok(older.into_iter().map(|(_, log)| log).collect())
+}
+
+impl WhyRebuiltCommand {
+    pub fn exec(self, _matches: &clap::ArgMatches, ctx: ClientCommandContext<'_>) -> ExitResult {
+        buck2_client_ctx::stdio::print_with_writer::<anyhow::Error, _>(|w| {
+            ctx.with_runtime(|ctx| async move {
+                let log_path = self.event_log.get(&ctx).await?;
+
+                let mut current =
+                    read_executions(&log_path, |identity| identity.contains(&self.action)).await?;
+                let (identity, current) = match current.len() {
+                    0 => return Err(WhyRebuiltError::NotFound(self.action).into()),
+                    1 => current.pop_first().unwrap(),
+                    _ => {
+                        return Err(WhyRebuiltError::Ambiguous(
+                            self.action,
+                            current.into_keys().collect(),
+                        )
+                        .into());
+                    }
+                };
+                let current = current.ok_or_else(|| {
+                    WhyRebuiltError::NoFingerprints(identity.clone(), "the selected invocation")
+                })?;
+
+                let previous_logs = match &self.previous {
+                    Some(path) => vec![EventLogPathBuf::infer(path.resolve(&ctx.working_dir))?],
+                    None => {
+                        let logs = get_local_logs(&ctx.paths()?.log_dir())?;
+                        match logs.iter().position(|log| log.path() == log_path.path()) {
+                            // Newest first, only the ones older than the selected log.
+                            Some(i) => logs.into_iter().take(i).rev().collect(),
+                            // The selected log is not in the log directory (e.g. it was passed
+                            // with `--path`), so go by when the invocations started.
+                            None => logs_started_before(&log_path, logs).await?,
+                        }
+                    }
+                };
+
+                let mut previous = None;
+                for log in previous_logs {
+                    if let Some(execution) = read_executions(&log, |i| i == identity)
+                        .await?
+                        .remove(&identity)
+                    {
+                        previous = Some((log, execution));
+                        break;
+                    }
+                }
+                let Some((previous_log, previous)) = previous else {
+                    return Err(WhyRebuiltError::NoPreviousExecution(identity).into());
+                };
+                let previous = previous.ok_or_else(|| {
+                    WhyRebuiltError::NoFingerprints(identity.clone(), "the previous invocation")
+                })?;
+
+                let (previous_invocation, _) = previous_log.unpack_stream().await?;
+                buck2_client_ctx::eprintln!(
+                    "Comparing `{}` with its previous execution in: {}",
+                    identity,
+                    previous_invocation.display_command_line()
+                )?;
+
+                let changes = diff_fingerprints(&previous, &current);
+                for change in &changes {
+                    if self.json {
+                        serde_json::to_writer(w.by_ref(), change)?;
+                        w.write_all(b"\n")?;
+                    } else {
+                        writeln!(w, "{}", change.display())?;
+                    }
+                }
+                if changes.is_empty() {
+                    buck2_client_ctx::eprintln!(
+                        "The command line, environment and inputs are unchanged; the action most \
+                        likely ran again because the daemon restarted or its outputs were lost"
+                    )?;
+                }
+                anyhow::Ok(())
+            })
+        })?;
+        ExitResult::success()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fingerprints(
+        argv: &[&str],
+        env: &[(&str, &str)],
+        inputs: &[(&str, &str)],
+    ) -> ActionInputFingerprints {
+        ActionInputFingerprints {
+            argv: argv.iter().map(|a| (*a).to_owned()).collect(),
+            env: env
+                .iter()
+                .map(|(key, value)| buck2_data::EnvironmentEntry {
+                    key: (*key).to_owned(),
+                    value: (*value).to_owned(),
+                })
+                .collect(),
+            inputs: inputs
+                .iter()
+                .map(|(path, digest)| buck2_data::ActionInputFingerprint {
+                    path: (*path).to_owned(),
+                    digest: (*digest).to_owned(),
+                })
+                .collect(),
+        }
+    }
+
+    fn arg(index: usize, previous: Option<&str>, current: Option<&str>) -> Change {
+        Change::Argument {
+            index,
+            previous: previous.map(str::to_owned),
+            current: current.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn test_unchanged() {
+        let f = fingerprints(&["cc", "-c", "a.c"], &[("A", "1")], &[("a.c", "sha1:1")]);
+        assert_eq!(diff_fingerprints(&f, &f), Vec::new());
+    }
+
+    #[test]
+    fn test_changed_argument() {
+        let previous = fingerprints(&["cc", "-O2", "-c", "a.c"], &[], &[]);
+        let current = fingerprints(&["cc", "-O3", "-c", "a.c"], &[], &[]);
+        assert_eq!(
+            diff_fingerprints(&previous, &current),
+            vec![arg(1, Some("-O2"), Some("-O3"))]
+        );
+    }
+
+    #[test]
+    fn test_inserted_argument() {
+        let previous = fingerprints(&["cc", "-c", "a.c"], &[], &[]);
+        let current = fingerprints(&["cc", "-g", "-c", "a.c"], &[], &[]);
+        assert_eq!(
+            diff_fingerprints(&previous, &current),
+            vec![arg(1, None, Some("-g"))]
+        );
+        assert_eq!(
+            diff_fingerprints(&current, &previous),
+            vec![arg(1, Some("-g"), None)]
+        );
+    }
+
+    #[test]
+    fn test_env_and_inputs() {
+        let previous = fingerprints(
+            &["cc"],
+            &[("A", "1"), ("B", "2")],
+            &[("a.c", "sha1:1"), ("a.h", "sha1:2")],
+        );
+        let current = fingerprints(
+            &["cc"],
+            &[("A", "1"), ("B", "3"), ("C", "4")],
+            &[("a.c", "sha1:5"), ("b.h", "sha1:6")],
+        );
+        assert_eq!(
+            diff_fingerprints(&previous, &current),
+            vec![
+                Change::Env {
+                    key: "B".to_owned(),
+                    previous: Some("2".to_owned()),
+                    current: Some("3".to_owned()),
+                },
+                Change::Env {
+                    key: "C".to_owned(),
+                    previous: None,
+                    current: Some("4".to_owned()),
+                },
+                Change::Input {
+                    path: "a.c".to_owned(),
+                    previous: Some("sha1:1".to_owned()),
+                    current: Some("sha1:5".to_owned()),
+                },
+                Change::Input {
+                    path: "a.h".to_owned(),
+                    previous: Some("sha1:2".to_owned()),
+                    current: None,
+                },
+                Change::Input {
+                    path: "b.h".to_owned(),
+                    previous: None,
+                    current: Some("sha1:6".to_owned()),
+                },
+            ]
+        );
+    }
+}
@@ -113,6 +113,15 @@ pub struct CommonBuildOptions {
     #[clap(long, env = buck2_env_name!("BUCK_OFFLINE_BUILD"), value_parser = FalseyValueParser::new())]
     no_remote_cache: bool,
 
+    /// Guarantee that the build does not use the network. Implies `--local-only` and
+    /// `--no-remote-cache`. Actions that would download something that is neither materialized
+    /// already nor in the offline cache fail, as do fetches of external cells.
+    #[clap(
+        long,
+        conflicts_with_all = &["remote_only", "prefer_remote", "write_to_cache_anyway", "upload_all_actions"]
+    )]
+    offline: bool,
+
     /// Could be used to enable the action cache writes on the RE worker when no_remote_cache is specified
     #[clap(long, requires = "no_remote_cache")]
     write_to_cache_anyway: bool,
@@ -202,7 +211,7 @@ impl CommonBuildOptions {
 
         buck2_cli_proto::CommonBuildOptions {
             concurrency,
-            execution_strategy: if self.local_only {
+            execution_strategy: if self.local_only || self.offline {
                 ExecutionStrategy::LocalOnly as i32
             } else if self.remote_only {
                 ExecutionStrategy::RemoteOnly as i32
@@ -219,14 +228,15 @@ impl CommonBuildOptions {
             unstable_build_report_filename,
             eager_dep_files: self.eager_dep_files,
             upload_all_actions: self.upload_all_actions,
-            skip_cache_read: self.no_remote_cache,
-            skip_cache_write: self.no_remote_cache && !self.write_to_cache_anyway,
+            skip_cache_read: self.no_remote_cache || self.offline,
+            skip_cache_write: (self.no_remote_cache && !self.write_to_cache_anyway) || self.offline,
             fail_fast: self.fail_fast,
             keep_going: self.keep_going,
             skip_missing_targets: self.skip_missing_targets,
             skip_incompatible_targets: self.skip_incompatible_targets,
             materialize_failed_inputs: self.materialize_failed_inputs,
             enable_optional_validations,
+            offline: self.offline,
             unstable_include_failures_build_report,
             unstable_include_package_project_relative_paths,
         }
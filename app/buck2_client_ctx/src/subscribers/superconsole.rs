@@ -1065,6 +1065,7 @@ mod tests {
                     starlark_peak_allocated_bytes: Some(0),
                     cpu_instruction_count: None,
                     target_count: Some(10),
+                    invalidation_info: None,
                 })),
                 stats: None,
                 duration: None,
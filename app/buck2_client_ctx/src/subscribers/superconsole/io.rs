@@ -146,6 +146,21 @@ fn do_render(
         )?]));
     }
 
+    // Only interesting once actions declare (or we learned) how much they need.
+    if snapshot.local_memory_admitted_bytes > 0
+        || snapshot.local_cpu_admitted_millicores > 0
+        || snapshot.local_resource_waiting > 0
+    {
+        lines.push(Line::unstyled(&format!(
+            "Local resources: memory = {} / {}  CPUs = {:.1} / {:.1}  waiting = {}",
+            HumanizedBytes::new(snapshot.local_memory_admitted_bytes),
+            HumanizedBytes::new(snapshot.local_memory_budget_bytes),
+            snapshot.local_cpu_admitted_millicores as f64 / 1000.0,
+            snapshot.local_cpu_budget_millicores as f64 / 1000.0,
+            snapshot.local_resource_waiting,
+        ))?);
+    }
+
     let mut counters = Vec::new();
     for (key, value) in io_in_flight_non_zero_counters(snapshot) {
         counters.push(format!("{:?} = {}", key, value));
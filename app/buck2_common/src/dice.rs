@@ -13,3 +13,4 @@ pub mod cells;
 pub mod cycles;
 pub mod data;
 pub mod file_ops;
+pub mod invalidation_tracking;
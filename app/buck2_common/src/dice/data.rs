@@ -11,6 +11,7 @@
 
 use std::sync::Arc;
 
+use dice::DiceComputations;
 use dice::DiceData;
 use dice::DiceDataBuilder;
 use dupe::Dupe;
@@ -41,6 +42,34 @@ impl SetIoProvider for DiceDataBuilder {
     }
 }
 
+#[derive(Debug, Clone, Copy, Dupe)]
+pub struct InvalidationTrackingConfig {
+    pub enabled: bool,
+}
+
+pub trait SetInvalidationTrackingConfig {
+    fn set_invalidation_tracking_config(&mut self, enabled: bool);
+}
+
+pub trait GetInvalidationTrackingConfig {
+    fn get_invalidation_tracking_config(&self) -> InvalidationTrackingConfig;
+}
+
+impl SetInvalidationTrackingConfig for DiceDataBuilder {
+    fn set_invalidation_tracking_config(&mut self, enabled: bool) {
+        self.set(InvalidationTrackingConfig { enabled });
+    }
+}
+
+impl GetInvalidationTrackingConfig for DiceComputations<'_> {
+    fn get_invalidation_tracking_config(&self) -> InvalidationTrackingConfig {
+        *self
+            .global_data()
+            .get::<InvalidationTrackingConfig>()
+            .expect("InvalidationTrackingConfig should be set")
+    }
+}
+
 pub mod testing {
     use buck2_core::fs::project::ProjectRootTemp;
 
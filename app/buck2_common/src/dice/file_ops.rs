@@ -16,8 +16,11 @@ use allocative::Allocative;
 use async_trait::async_trait;
 use buck2_core::cells::cell_path::CellPath;
 use buck2_core::cells::cell_path::CellPathRef;
+use buck2_core::cells::external::ExternalCellOrigin;
 use buck2_core::cells::name::CellName;
+use buck2_core::cells::CellResolver;
 use buck2_core::fs::paths::file_name::FileNameBuf;
+use buck2_core::fs::project_rel_path::ProjectRelativePath;
 use buck2_futures::cancellation::CancellationContext;
 use cmp_any::PartialEqAny;
 use derive_more::Display;
@@ -168,7 +171,13 @@ impl FileChangeTracker {
         }
     }
 
-    pub fn write_to_dice(self, ctx: &mut DiceTransactionUpdater) -> anyhow::Result<()> {
+    pub fn write_to_dice(
+        mut self,
+        ctx: &mut DiceTransactionUpdater,
+        cells: &CellResolver,
+    ) -> anyhow::Result<()> {
+        self.add_local_external_cell_changes(cells)?;
+
         ctx.changed(self.files_to_dirty)?;
         ctx.changed(self.dirs_to_dirty)?;
         ctx.changed(self.paths_to_dirty)?;
@@ -176,6 +185,59 @@ impl FileChangeTracker {
         Ok(())
     }
 
+    /// Local external cells are read in place, so a change to a path in one of their directories
+    /// is also a change to that path in the cell.
+    fn add_local_external_cell_changes(&mut self, cells: &CellResolver) -> anyhow::Result<()> {
+        let local_dirs: Vec<(CellName, &ProjectRelativePath)> = cells
+            .cells()
+            .filter_map(|(cell, instance)| match instance.external() {
+                Some(ExternalCellOrigin::Local(setup)) => {
+                    Some((cell, setup.project_relative_path()?))
+                }
+                _ => None,
+            })
+            .collect();
+        if local_dirs.is_empty() {
+            return Ok(());
+        }
+
+        let in_local_cells = |path: &CellPath| -> anyhow::Result<Vec<CellPath>> {
+            let path = cells.resolve_path(path.as_ref())?;
+            Ok(local_dirs
+                .iter()
+                .filter_map(|(cell, dir)| {
+                    let path = path.strip_prefix_opt(dir)?;
+                    Some(CellPath::new(*cell, path.to_owned().into()))
+                })
+                .collect())
+        };
+
+        let mut files = Vec::new();
+        for key in &self.files_to_dirty {
+            files.extend(in_local_cells(&key.0)?);
+        }
+        let mut dirs = Vec::new();
+        for key in &self.dirs_to_dirty {
+            for path in in_local_cells(&key.path)? {
+                dirs.push(ReadDirKey {
+                    path,
+                    check_ignores: key.check_ignores,
+                });
+            }
+        }
+        let mut paths = Vec::new();
+        for key in &self.paths_to_dirty {
+            paths.extend(in_local_cells(&key.0)?);
+        }
+
+        self.files_to_dirty
+            .extend(files.into_iter().map(|path| ReadFileKey(Arc::new(path))));
+        self.dirs_to_dirty.extend(dirs);
+        self.paths_to_dirty
+            .extend(paths.into_iter().map(PathMetadataKey));
+        Ok(())
+    }
+
     fn file_contents_modify(&mut self, path: CellPath) {
         self.files_to_dirty
             .insert(ReadFileKey(Arc::new(path.clone())));
@@ -0,0 +1,60 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Conversion of DICE invalidation paths into the event log representation
+//! consumed by `buck2 debug why-recomputed`.
+
+use dice::DiceComputations;
+use dice::DiceKeyTrackedInvalidationPaths;
+use dice::DiceTrackedInvalidationPath;
+
+use crate::dice::data::GetInvalidationTrackingConfig;
+
+fn invalidation_source_to_proto(
+    invalidation_path: &DiceTrackedInvalidationPath,
+) -> Option<buck2_data::command_invalidation_info::InvalidationSource> {
+    match invalidation_path {
+        DiceTrackedInvalidationPath::Clean | DiceTrackedInvalidationPath::Unknown => None,
+        DiceTrackedInvalidationPath::Invalidated(path) => {
+            let path = path
+                .get_invalidation_path()
+                .into_iter()
+                .map(
+                    |entry| buck2_data::command_invalidation_info::InvalidationPathEntry {
+                        key_type: entry.key.key_type_name().to_owned(),
+                        key: entry.key.to_string(),
+                        version: entry.version.value() as u64,
+                    },
+                )
+                .collect();
+            Some(buck2_data::command_invalidation_info::InvalidationSource { path })
+        }
+    }
+}
+
+pub fn invalidation_info_to_proto(
+    invalidation_paths: &DiceKeyTrackedInvalidationPaths,
+) -> buck2_data::CommandInvalidationInfo {
+    buck2_data::CommandInvalidationInfo {
+        changed_any: invalidation_source_to_proto(&invalidation_paths.normal_priority_path),
+        changed_file: invalidation_source_to_proto(&invalidation_paths.high_priority_path),
+    }
+}
+
+/// Returns the invalidation info of the key currently being computed, or `None` if
+/// invalidation tracking is disabled.
+pub fn get_invalidation_info(
+    ctx: &mut DiceComputations<'_>,
+) -> Option<buck2_data::CommandInvalidationInfo> {
+    if ctx.get_invalidation_tracking_config().enabled {
+        Some(invalidation_info_to_proto(&ctx.get_invalidation_paths()))
+    } else {
+        None
+    }
+}
@@ -18,6 +18,8 @@ use dice::DiceComputations;
 
 use crate::dice::file_ops::delegate::FileOpsDelegate;
 
+pub mod lockfile;
+
 #[async_trait]
 pub trait ExternalCellsImpl: Send + Sync + 'static {
     async fn get_file_ops_delegate(
@@ -36,6 +38,15 @@ pub trait ExternalCellsImpl: Send + Sync + 'static {
         origin: ExternalCellOrigin,
         path: &CellRootPath,
     ) -> anyhow::Result<()>;
+
+    /// Resolve the manifests of all the cells in the project and compare the result against the
+    /// lockfile. With `update`, the lockfile is rewritten if it's out of date. Returns how the
+    /// resolved versions differ from the lockfile, if at all.
+    async fn resolve_lockfile(
+        &self,
+        ctx: &mut DiceComputations<'_>,
+        update: bool,
+    ) -> anyhow::Result<Vec<String>>;
 }
 
 pub static EXTERNAL_CELLS_IMPL: LateBinding<&'static dyn ExternalCellsImpl> =
@@ -0,0 +1,151 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! The lockfile that pins the versions of external cells that cells' manifests asked for.
+//!
+//! Cells declare the external cells they need, with version constraints, in an
+//! `external_cells.toml` manifest at their root. `buck2 external-cells update` resolves those to
+//! one version per external cell and writes the result to `external_cells.lock` in the project
+//! root, which external cells with the `locked` origin are then loaded from.
+
+use std::collections::BTreeMap;
+
+use anyhow::Context;
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Name of the manifest at the root of a cell.
+pub const EXTERNAL_CELLS_MANIFEST: &str = "external_cells.toml";
+
+/// Name of the lockfile in the project root.
+pub const EXTERNAL_CELLS_LOCKFILE: &str = "external_cells.lock";
+
+#[derive(Debug, buck2_error::Error)]
+enum LockfileError {
+    #[error("Unsupported `external_cells.lock` version {0}, expected {1}")]
+    #[buck2(input)]
+    UnsupportedVersion(u32, u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedCell {
+    /// The git repo the cell is fetched from.
+    pub git: String,
+    /// The version that was selected, as parsed from `tag`.
+    pub version: String,
+    pub tag: String,
+    /// The sha1 of the commit the tag pointed to at resolution time.
+    pub commit: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExternalCellsLockfile {
+    pub version: u32,
+    /// Keyed by the name of the cell.
+    pub cells: BTreeMap<String, LockedCell>,
+}
+
+impl Default for ExternalCellsLockfile {
+    fn default() -> Self {
+        Self {
+            version: Self::VERSION,
+            cells: BTreeMap::new(),
+        }
+    }
+}
+
+impl ExternalCellsLockfile {
+    pub const VERSION: u32 = 1;
+
+    pub fn parse(contents: &str) -> anyhow::Result<Self> {
+        let lockfile: Self = serde_json::from_str(contents)
+            .with_context(|| format!("Error parsing `{}`", EXTERNAL_CELLS_LOCKFILE))?;
+        if lockfile.version != Self::VERSION {
+            return Err(LockfileError::UnsupportedVersion(lockfile.version, Self::VERSION).into());
+        }
+        Ok(lockfile)
+    }
+
+    /// The contents to write to disk. Keys are sorted, so that the file diffs well.
+    pub fn serialize(&self) -> anyhow::Result<String> {
+        let mut contents = serde_json::to_string_pretty(self)?;
+        contents.push('\n');
+        Ok(contents)
+    }
+
+    /// Describe how `new` differs from `self`, one line per cell. Empty if they are the same.
+    pub fn diff(&self, new: &ExternalCellsLockfile) -> Vec<String> {
+        let mut diff = Vec::new();
+        for (name, old) in &self.cells {
+            match new.cells.get(name) {
+                None => diff.push(format!("removed {} {} ({})", name, old.version, old.commit)),
+                Some(new) if new != old => diff.push(format!(
+                    "changed {} {} ({}) -> {} ({})",
+                    name, old.version, old.commit, new.version, new.commit
+                )),
+                Some(_) => {}
+            }
+        }
+        for (name, new) in &new.cells {
+            if !self.cells.contains_key(name) {
+                diff.push(format!("added {} {} ({})", name, new.version, new.commit));
+            }
+        }
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn locked(version: &str, commit: &str) -> LockedCell {
+        LockedCell {
+            git: "https://example.com/foo.git".to_owned(),
+            version: version.to_owned(),
+            tag: format!("v{}", version),
+            commit: commit.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() -> anyhow::Result<()> {
+        let mut lockfile = ExternalCellsLockfile::default();
+        lockfile
+            .cells
+            .insert("foo".to_owned(), locked("1.0.0", "aaaa"));
+        assert_eq!(
+            lockfile,
+            ExternalCellsLockfile::parse(&lockfile.serialize()?)?
+        );
+
+        assert!(ExternalCellsLockfile::parse(r#"{"version": 2, "cells": {}}"#).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut old = ExternalCellsLockfile::default();
+        old.cells.insert("foo".to_owned(), locked("1.0.0", "aaaa"));
+        old.cells.insert("bar".to_owned(), locked("1.0.0", "bbbb"));
+        let mut new = ExternalCellsLockfile::default();
+        new.cells.insert("foo".to_owned(), locked("1.1.0", "cccc"));
+        new.cells.insert("baz".to_owned(), locked("2.0.0", "dddd"));
+
+        assert_eq!(Vec::<String>::new(), old.diff(&old));
+        assert_eq!(
+            vec![
+                "removed bar 1.0.0 (bbbb)",
+                "changed foo 1.0.0 (aaaa) -> 1.1.0 (cccc)",
+                "added baz 2.0.0 (dddd)",
+            ],
+            old.diff(&new)
+        );
+    }
+}
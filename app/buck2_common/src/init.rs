@@ -56,6 +56,10 @@ pub struct HttpConfig {
     write_timeout_ms: Option<u64>,
     pub http2: bool,
     pub max_redirects: Option<usize>,
+    /// Whitespace separated `<regex>=><replacement>` rules applied to download URLs, in order.
+    pub url_rewrites: Option<String>,
+    /// Whether to try the original URL once all the rewritten ones failed. Defaults to true.
+    url_rewrite_fallback: Option<bool>,
 }
 
 impl HttpConfig {
@@ -82,6 +86,16 @@ impl HttpConfig {
                 property: "http2",
             })?
             .unwrap_or(true);
+        let url_rewrites = config
+            .get(BuckconfigKeyRef {
+                section: "http",
+                property: "url_rewrites",
+            })
+            .map(str::to_owned);
+        let url_rewrite_fallback = config.parse(BuckconfigKeyRef {
+            section: "http",
+            property: "url_rewrite_fallback",
+        })?;
 
         Ok(Self {
             connect_timeout_ms,
@@ -89,9 +103,15 @@ impl HttpConfig {
             write_timeout_ms,
             max_redirects,
             http2,
+            url_rewrites,
+            url_rewrite_fallback,
         })
     }
 
+    pub fn url_rewrite_fallback(&self) -> bool {
+        self.url_rewrite_fallback.unwrap_or(true)
+    }
+
     pub fn connect_timeout(&self) -> Timeout {
         match self.connect_timeout_ms.map(Duration::from_millis) {
             Some(Duration::ZERO) => Timeout::NoTimeout,
@@ -162,8 +162,32 @@ impl InvocationPaths {
         FileName::unchecked_new("materializer_state")
     }
 
+    /// Subdirectory of `cache_dir` storing what the hybrid executor learned about actions
+    pub fn hybrid_routing_state_path(&self) -> AbsNormPathBuf {
+        self.cache_dir_path()
+            .join(self.hybrid_routing_state_dir_name())
+    }
+
+    pub fn hybrid_routing_state_dir_name(&self) -> &FileName {
+        FileName::unchecked_new("hybrid_routing")
+    }
+
+    /// Subdirectory of `cache_dir` storing the memory local commands were learned to use
+    pub fn local_resources_state_path(&self) -> AbsNormPathBuf {
+        self.cache_dir_path()
+            .join(self.local_resources_state_dir_name())
+    }
+
+    pub fn local_resources_state_dir_name(&self) -> &FileName {
+        FileName::unchecked_new("local_resources")
+    }
+
     pub fn valid_cache_dirs(&self) -> Vec<&FileName> {
-        vec![self.materializer_state_dir_name()]
+        vec![
+            self.materializer_state_dir_name(),
+            self.hybrid_routing_state_dir_name(),
+            self.local_resources_state_dir_name(),
+        ]
     }
 }
 
@@ -18,6 +18,8 @@ use buck2_core::cells::cell_root_path::CellRootPath;
 use buck2_core::cells::cell_root_path::CellRootPathBuf;
 use buck2_core::cells::external::ExternalCellOrigin;
 use buck2_core::cells::external::GitCellSetup;
+use buck2_core::cells::external::HttpArchiveCellSetup;
+use buck2_core::cells::external::LocalCellSetup;
 use buck2_core::cells::name::CellName;
 use buck2_core::cells::CellAliasResolver;
 use buck2_core::cells::CellResolver;
@@ -33,6 +35,8 @@ use dupe::Dupe;
 use crate::cas_digest::RawDigest;
 use crate::dice::cells::HasCellResolver;
 use crate::dice::data::HasIoProvider;
+use crate::external_cells::lockfile::ExternalCellsLockfile;
+use crate::external_cells::lockfile::EXTERNAL_CELLS_LOCKFILE;
 use crate::external_cells::EXTERNAL_CELLS_IMPL;
 use crate::legacy_configs::aggregator::CellsAggregator;
 use crate::legacy_configs::args::resolve_config_args;
@@ -282,10 +286,25 @@ impl BuckConfigBasedCells {
         let mut aggregator = CellsAggregator::new(cell_definitions, root_aliases)?;
 
         if let Some(external_cells) = root_config.get_section("external_cells") {
+            // Only read the lockfile when something uses it, so that projects that don't aren't
+            // affected by a stray or broken one.
+            let lockfile = if external_cells
+                .iter()
+                .any(|(_, origin)| origin.as_str() == "locked")
+            {
+                Self::read_external_cells_lockfile(&mut file_ops).await?
+            } else {
+                None
+            };
             for (alias, origin) in external_cells.iter() {
                 let alias = NonEmptyCellAlias::new(alias.to_owned())?;
                 let name = aggregator.resolve_root_alias(alias)?;
-                let origin = Self::parse_external_cell_origin(name, origin.as_str(), &root_config)?;
+                let origin = Self::parse_external_cell_origin(
+                    name,
+                    origin.as_str(),
+                    &root_config,
+                    lockfile.as_ref(),
+                )?;
                 if let ExternalCellOrigin::Bundled(name) = origin {
                     // This code is executed both in the client and in the daemon. When in the
                     // client and using a client-only build, this late binding might not be bound,
@@ -387,10 +406,24 @@ impl BuckConfigBasedCells {
         .await
     }
 
+    async fn read_external_cells_lockfile(
+        file_ops: &mut dyn ConfigParserFileOps,
+    ) -> anyhow::Result<Option<ExternalCellsLockfile>> {
+        let path = ConfigPath::Project(
+            ProjectRelativePath::unchecked_new(EXTERNAL_CELLS_LOCKFILE).to_owned(),
+        );
+        let Some(lines) = file_ops.read_file_lines_if_exists(&path).await? else {
+            return Ok(None);
+        };
+        let contents = lines.collect::<Result<Vec<_>, _>>()?.join("\n");
+        Ok(Some(ExternalCellsLockfile::parse(&contents)?))
+    }
+
     fn parse_external_cell_origin(
         cell: CellName,
         value: &str,
         config: &LegacyBuckConfig,
+        lockfile: Option<&ExternalCellsLockfile>,
     ) -> anyhow::Result<ExternalCellOrigin> {
         #[derive(buck2_error::Error, Debug)]
         enum ExternalCellOriginParseError {
@@ -398,6 +431,10 @@ impl BuckConfigBasedCells {
             Unknown(String),
             #[error("Missing buckconfig `{0}.{1}` for external cell configuration")]
             MissingConfiguration(String, String),
+            #[error(
+                "External cell `{0}` has origin `locked`, but `external_cells.lock` doesn't pin it, run `buck2 external-cells update`"
+            )]
+            NotLocked(CellName),
         }
 
         let get_config = |section: &str, property: &str| {
@@ -423,6 +460,53 @@ impl BuckConfigBasedCells {
                 git_origin: get_config(section, "git_origin")?.into(),
                 commit,
             }))
+        } else if value == "locked" {
+            let locked = lockfile
+                .and_then(|lockfile| lockfile.cells.get(cell.as_str()))
+                .ok_or(ExternalCellOriginParseError::NotLocked(cell))?;
+            let _ = RawDigest::parse_sha1(locked.commit.as_bytes())?;
+            Ok(ExternalCellOrigin::Git(GitCellSetup {
+                git_origin: locked.git.as_str().into(),
+                commit: locked.commit.as_str().into(),
+            }))
+        } else if value == "http_archive" {
+            let section = &format!("external_cell_{}", cell.as_str());
+            let urls: Vec<String> = config
+                .parse_list(crate::legacy_configs::key::BuckconfigKeyRef {
+                    section,
+                    property: "urls",
+                })?
+                .unwrap_or_default();
+            if urls.is_empty() {
+                return Err(ExternalCellOriginParseError::MissingConfiguration(
+                    section.to_owned(),
+                    "urls".to_owned(),
+                )
+                .into());
+            }
+            let sha256: Arc<str> = get_config(section, "sha256")?.into();
+            let _ = RawDigest::parse_sha256(sha256.as_bytes())?;
+            let strip_prefix = config
+                .get(crate::legacy_configs::key::BuckconfigKeyRef {
+                    section,
+                    property: "strip_prefix",
+                })
+                .map(|p| {
+                    ForwardRelativePath::new(p)?;
+                    anyhow::Ok(p.into())
+                })
+                .transpose()?;
+            Ok(ExternalCellOrigin::HttpArchive(HttpArchiveCellSetup {
+                urls: urls.into_iter().map(Arc::from).collect(),
+                sha256,
+                strip_prefix,
+            }))
+        } else if value == "local" {
+            let section = &format!("external_cell_{}", cell.as_str());
+            Ok(ExternalCellOrigin::Local(LocalCellSetup {
+                cell,
+                path: get_config(section, "local_path")?.into(),
+            }))
         } else {
             Err(ExternalCellOriginParseError::Unknown(value.to_owned()).into())
         }
@@ -432,11 +516,12 @@ impl BuckConfigBasedCells {
 async fn get_external_buckconfig_paths(
     file_ops: &mut dyn ConfigParserFileOps,
 ) -> anyhow::Result<Vec<ConfigPath>> {
-    let skip_default_external_config = buck2_env_anyhow!(
-        "BUCK2_TEST_SKIP_DEFAULT_EXTERNAL_CONFIG",
-        bool,
-        applicability = testing
-    )?;
+    let skip_default_external_config = buck2_env_anyhow!("BUCK2_IGNORE_EXTERNAL_CONFIG", bool)?
+        || buck2_env_anyhow!(
+            "BUCK2_TEST_SKIP_DEFAULT_EXTERNAL_CONFIG",
+            bool,
+            applicability = testing
+        )?;
 
     let mut buckconfig_paths: Vec<ConfigPath> = Vec::new();
 
@@ -540,6 +625,8 @@ mod tests {
     use buck2_core::cells::cell_root_path::CellRootPathBuf;
     use buck2_core::cells::external::ExternalCellOrigin;
     use buck2_core::cells::external::GitCellSetup;
+    use buck2_core::cells::external::HttpArchiveCellSetup;
+    use buck2_core::cells::external::LocalCellSetup;
     use buck2_core::cells::name::CellName;
     use buck2_core::fs::project_rel_path::ProjectRelativePath;
     use dice::DiceComputations;
@@ -1099,6 +1186,15 @@ mod tests {
                 // Not used in these tests
                 unreachable!()
             }
+
+            async fn resolve_lockfile(
+                &self,
+                _ctx: &mut DiceComputations<'_>,
+                _update: bool,
+            ) -> anyhow::Result<Vec<String>> {
+                // Not used in these tests
+                unreachable!()
+            }
         }
 
         static INIT: std::sync::Once = std::sync::Once::new();
@@ -1311,6 +1407,139 @@ mod tests {
         let e = format!("{:?}", e);
         assert!(e.contains("not a valid SHA1 digest"), "error: {}", e);
 
+        Ok(())
+    }
+    #[tokio::test]
+    async fn test_http_archive_and_local_external_cells() -> anyhow::Result<()> {
+        initialize_external_cells_impl();
+
+        let mut file_ops = TestConfigParserFileOps::new(&[(
+            ".buckconfig",
+            indoc!(
+                r#"
+                    [cells]
+                        root = .
+                        libfoo = foo/
+                        libbar = bar/
+                    [external_cells]
+                        libfoo = http_archive
+                        libbar = local
+                    [external_cell_libfoo]
+                        urls = https://example.com/libfoo.tar.gz, https://mirror.example.com/libfoo.tar.gz
+                        sha256 = 0000000000000000000000000000000000000000000000000000000000000000
+                        strip_prefix = libfoo-1.0
+                    [external_cell_libbar]
+                        local_path = ../libbar
+                "#
+            ),
+        )])?;
+
+        let project_fs = create_project_filesystem();
+        let resolver = BuckConfigBasedCells::testing_parse_with_file_ops(
+            &project_fs,
+            &mut file_ops,
+            &[],
+            ProjectRelativePath::empty(),
+        )
+        .await?
+        .cell_resolver;
+
+        assert_eq!(
+            resolver
+                .get(CellName::testing_new("libfoo"))
+                .unwrap()
+                .external(),
+            Some(&ExternalCellOrigin::HttpArchive(HttpArchiveCellSetup {
+                urls: vec![
+                    "https://example.com/libfoo.tar.gz".into(),
+                    "https://mirror.example.com/libfoo.tar.gz".into(),
+                ]
+                .into(),
+                sha256: "0000000000000000000000000000000000000000000000000000000000000000".into(),
+                strip_prefix: Some("libfoo-1.0".into()),
+            })),
+        );
+        assert_eq!(
+            resolver
+                .get(CellName::testing_new("libbar"))
+                .unwrap()
+                .external(),
+            Some(&ExternalCellOrigin::Local(LocalCellSetup {
+                cell: CellName::testing_new("libbar"),
+                path: "../libbar".into(),
+            })),
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_locked_external_cells() -> anyhow::Result<()> {
+        initialize_external_cells_impl();
+
+        let buckconfig = indoc!(
+            r#"
+                [cells]
+                    root = .
+                    libfoo = foo/
+                [external_cells]
+                    libfoo = locked
+            "#
+        );
+        let lockfile = indoc!(
+            r#"
+                {
+                  "version": 1,
+                  "cells": {
+                    "libfoo": {
+                      "git": "https://github.com/facebook/foo",
+                      "version": "1.2.0",
+                      "tag": "v1.2.0",
+                      "commit": "1234567890123456789012345678901234567890"
+                    }
+                  }
+                }
+            "#
+        );
+
+        let project_fs = create_project_filesystem();
+        let mut file_ops = TestConfigParserFileOps::new(&[
+            (".buckconfig", buckconfig),
+            ("external_cells.lock", lockfile),
+        ])?;
+        let resolver = BuckConfigBasedCells::testing_parse_with_file_ops(
+            &project_fs,
+            &mut file_ops,
+            &[],
+            ProjectRelativePath::empty(),
+        )
+        .await?
+        .cell_resolver;
+
+        assert_eq!(
+            resolver
+                .get(CellName::testing_new("libfoo"))
+                .unwrap()
+                .external(),
+            Some(&ExternalCellOrigin::Git(GitCellSetup {
+                git_origin: "https://github.com/facebook/foo".into(),
+                commit: "1234567890123456789012345678901234567890".into(),
+            })),
+        );
+
+        // Without the lockfile, the cell can't be resolved.
+        let mut file_ops = TestConfigParserFileOps::new(&[(".buckconfig", buckconfig)])?;
+        assert!(
+            BuckConfigBasedCells::testing_parse_with_file_ops(
+                &project_fs,
+                &mut file_ops,
+                &[],
+                ProjectRelativePath::empty(),
+            )
+            .await
+            .is_err()
+        );
+
         Ok(())
     }
 }
@@ -180,6 +180,12 @@ impl LivelinessObserver for NoopLivelinessObserver {
     }
 }
 
+impl LivelinessObserverSync for NoopLivelinessObserver {
+    fn is_alive_sync(&self) -> bool {
+        true
+    }
+}
+
 #[async_trait]
 impl LivelinessObserver for Arc<dyn LivelinessObserver> {
     async fn while_alive(&self) {
@@ -8,11 +8,18 @@
  */
 
 use std::collections::HashMap;
+use std::sync::mpsc;
 use std::sync::Arc;
 
 use anyhow::Context;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::file_name::FileName;
+use dupe::Dupe;
 use itertools::Itertools;
 use parking_lot::Mutex;
+use parking_lot::MutexGuard;
 use rusqlite::Connection;
 
 /// A generic sqlite table for storing string key-value pairs.
@@ -91,6 +98,122 @@ impl KeyValueSqliteTable {
     }
 }
 
+const VERSIONED_DB_FILENAME: &str = "db.sqlite";
+
+/// Sqlite limits the number of parameters of a statement, so writers get at most this many
+/// updates at a time.
+const MAX_WRITE_BATCH: usize = 1000;
+
+/// A sqlite db alone in its own directory, holding what the daemon learned and would rather keep
+/// across restarts, but can do without. It is dropped and started over whenever its schema
+/// version changes.
+pub struct VersionedSqliteDb {
+    path: AbsNormPathBuf,
+    connection: Arc<Mutex<Connection>>,
+    versions: KeyValueSqliteTable,
+}
+
+impl VersionedSqliteDb {
+    /// Open the db in `state_dir`, without checking its version.
+    pub fn open(state_dir: &AbsNormPath) -> anyhow::Result<Self> {
+        let path = state_dir.join(FileName::unchecked_new(VERSIONED_DB_FILENAME));
+        let connection = Connection::open(&path)?;
+        if cfg!(unix) {
+            connection.pragma_update(None, "journal_mode", "WAL")?;
+        }
+        // Like the materializer state, losing the latest writes on a power loss is harmless.
+        connection.pragma_update(None, "synchronous", "OFF")?;
+
+        let connection = Arc::new(Mutex::new(connection));
+        let versions = KeyValueSqliteTable::new("versions".to_owned(), connection.dupe());
+        Ok(Self {
+            path,
+            connection,
+            versions,
+        })
+    }
+
+    /// Open the db in `state_dir` and `load` what it holds. If that fails, e.g. because the db
+    /// doesn't exist or has another schema version, remove `state_dir` and start over with an
+    /// empty db set up by `create_tables`, and return `None`.
+    pub fn load_or_recreate<T>(
+        state_dir: &AbsNormPath,
+        schema_version: u64,
+        create_tables: impl FnOnce(&Connection) -> anyhow::Result<()>,
+        load: impl FnOnce(&Connection) -> anyhow::Result<T>,
+    ) -> anyhow::Result<(Self, Option<T>)> {
+        let versions = HashMap::from([("schema_version".to_owned(), schema_version.to_string())]);
+
+        let loaded = (|| -> anyhow::Result<_> {
+            let path = state_dir.join(FileName::unchecked_new(VERSIONED_DB_FILENAME));
+            if !path.exists() {
+                return Err(anyhow::anyhow!("`{}` does not exist", path));
+            }
+            let db = Self::open(state_dir)?;
+            let found = db.versions.read_all()?;
+            if found != versions {
+                return Err(anyhow::anyhow!(
+                    "Expected versions {:?}, found {:?}",
+                    versions,
+                    found
+                ));
+            }
+            let loaded = load(&db.connection())?;
+            Ok((db, loaded))
+        })();
+
+        match loaded {
+            Ok((db, loaded)) => Ok((db, Some(loaded))),
+            Err(e) => {
+                tracing::debug!("Starting `{}` over: {:#}", state_dir, e);
+                if state_dir.exists() {
+                    fs_util::remove_dir_all(state_dir)?;
+                }
+                fs_util::create_dir_all(state_dir)?;
+                let db = Self::open(state_dir)?;
+                db.versions.create_table()?;
+                create_tables(&db.connection())?;
+                db.versions.insert_all(versions)?;
+                Ok((db, None))
+            }
+        }
+    }
+
+    pub fn connection(&self) -> MutexGuard<'_, Connection> {
+        self.connection.lock()
+    }
+
+    /// Write what is sent to the returned sender on a thread of its own, so that the daemon never
+    /// waits for sqlite. `write` gets whatever queued up while it was writing, in order. Writing
+    /// stops at the first error, and what is sent after that is dropped.
+    pub fn spawn_writer<U: Send + 'static>(
+        self,
+        thread_name: &str,
+        mut write: impl FnMut(&Connection, Vec<U>) -> anyhow::Result<()> + Send + 'static,
+    ) -> anyhow::Result<mpsc::Sender<U>> {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::Builder::new()
+            .name(thread_name.to_owned())
+            .spawn(move || {
+                while let Ok(update) = receiver.recv() {
+                    let mut updates = vec![update];
+                    while updates.len() < MAX_WRITE_BATCH {
+                        match receiver.try_recv() {
+                            Ok(update) => updates.push(update),
+                            Err(..) => break,
+                        }
+                    }
+                    if let Err(e) = write(&self.connection(), updates) {
+                        tracing::warn!("Error writing to `{}`: {:#}", self.path, e);
+                        return;
+                    }
+                }
+            })
+            .with_context(|| format!("Error spawning writer for `{}`", self.path))?;
+        Ok(sender)
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -124,4 +247,53 @@ mod tests {
         assert_eq!(table.get("foo").unwrap().as_deref(), Some("foo"));
         assert_eq!(table.get("baz").unwrap(), None);
     }
+
+    #[test]
+    fn test_versioned_sqlite_db() -> anyhow::Result<()> {
+        let fs = ProjectRootTemp::new()?;
+        let state_dir = fs
+            .path()
+            .resolve(ProjectRelativePath::unchecked_new("state"));
+        let create_tables = |connection: &Connection| -> anyhow::Result<()> {
+            connection.execute("CREATE TABLE t (value INTEGER NOT NULL)", [])?;
+            Ok(())
+        };
+        let load = |connection: &Connection| -> anyhow::Result<i64> {
+            Ok(connection.query_row("SELECT COUNT(*) FROM t", [], |row| row.get(0))?)
+        };
+
+        let (db, loaded) = VersionedSqliteDb::load_or_recreate(&state_dir, 1, create_tables, load)?;
+        assert_eq!(loaded, None);
+        let sender = db.spawn_writer("test-writer", |connection, values: Vec<i64>| {
+            for value in values {
+                connection.execute("INSERT INTO t (value) VALUES (?)", [value])?;
+            }
+            Ok(())
+        })?;
+        sender.send(1)?;
+        sender.send(2)?;
+        drop(sender);
+
+        // Writes are asynchronous, so wait for them to land.
+        let mut count = 0;
+        for _ in 0..100 {
+            count = load(&VersionedSqliteDb::open(&state_dir)?.connection())?;
+            if count == 2 {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(count, 2);
+
+        let (db, loaded) = VersionedSqliteDb::load_or_recreate(&state_dir, 1, create_tables, load)?;
+        assert_eq!(loaded, Some(2));
+        drop(db);
+
+        // Another version starts over.
+        let (_db, loaded) =
+            VersionedSqliteDb::load_or_recreate(&state_dir, 2, create_tables, load)?;
+        assert_eq!(loaded, None);
+        assert_eq!(load(&VersionedSqliteDb::open(&state_dir)?.connection())?, 0);
+        Ok(())
+    }
 }
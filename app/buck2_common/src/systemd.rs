@@ -76,6 +76,8 @@ impl SystemdRunner {
                 // and we don't assume the upper-layer unit collects the garbage of this
                 // scope after being killed.
                 args.push("--collect".to_owned());
+                // The forkserver runs each action in a child cgroup of this scope.
+                args.push("--property=Delegate=yes".to_owned());
             }
             SystemdPropertySetType::Worker => { // TODO
             }
@@ -13,11 +13,14 @@ use std::sync::Arc;
 use dupe::Dupe;
 
 use crate::cells::name::CellName;
+use crate::fs::project_rel_path::ProjectRelativePath;
 
-#[derive(Debug, Clone, Dupe, allocative::Allocative, PartialEq, Eq)]
+#[derive(Debug, Clone, Dupe, allocative::Allocative, PartialEq, Eq, Hash)]
 pub enum ExternalCellOrigin {
     Bundled(CellName),
     Git(GitCellSetup),
+    HttpArchive(HttpArchiveCellSetup),
+    Local(LocalCellSetup),
 }
 
 #[derive(
@@ -37,11 +40,59 @@ pub struct GitCellSetup {
     pub commit: Arc<str>,
 }
 
+#[derive(Debug, Clone, Dupe, allocative::Allocative, PartialEq, Eq, Hash)]
+pub struct HttpArchiveCellSetup {
+    /// Tried in order until one works.
+    pub urls: Arc<[Arc<str>]>,
+    // Guaranteed to be a valid sha256 hash
+    pub sha256: Arc<str>,
+    /// Directory within the archive that becomes the root of the cell.
+    pub strip_prefix: Option<Arc<str>>,
+}
+
+impl fmt::Display for HttpArchiveCellSetup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "http_archive(")?;
+        for url in self.urls.iter() {
+            write!(f, "{}, ", url)?;
+        }
+        write!(f, "{})", self.sha256)
+    }
+}
+
+/// A directory on disk, e.g. a local checkout of a cell that is normally fetched from elsewhere.
+#[derive(
+    Debug,
+    derive_more::Display,
+    Clone,
+    Dupe,
+    allocative::Allocative,
+    PartialEq,
+    Eq,
+    Hash
+)]
+#[display("local({})", path)]
+pub struct LocalCellSetup {
+    pub cell: CellName,
+    /// Absolute, or relative to the project root.
+    pub path: Arc<str>,
+}
+
+impl LocalCellSetup {
+    /// The directory, if `path` is a normalized relative path, and so inside the project. Only
+    /// these directories are seen by the file watcher.
+    pub fn project_relative_path(&self) -> Option<&ProjectRelativePath> {
+        ProjectRelativePath::new(self.path.trim_end_matches('/')).ok()
+    }
+}
+
 impl fmt::Display for ExternalCellOrigin {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Bundled(cell) => write!(f, "bundled({})", cell),
             Self::Git(git) => write!(f, "{}", git),
+            Self::HttpArchive(http_archive) => write!(f, "{}", http_archive),
+            Self::Local(local) => write!(f, "{}", local),
         }
     }
 }
@@ -43,6 +43,16 @@ pub fn cfg_diff(a: &ConfigurationData, b: &ConfigurationData) -> Result<(), Stri
             }
         }
 
+        fn diff_display_name(&mut self, a: Option<&str>, b: Option<&str>) {
+            if a != b {
+                for (sign, display_name) in [('-', a), ('+', b)] {
+                    if let Some(display_name) = display_name {
+                        self.print_diff_line(sign, &format!("display name: {}", display_name));
+                    }
+                }
+            }
+        }
+
         fn print_cfg_data_result_line(
             &mut self,
             sign: char,
@@ -140,6 +150,7 @@ pub fn cfg_diff(a: &ConfigurationData, b: &ConfigurationData) -> Result<(), Stri
 
     let mut diff = DiffPrinter::default();
     diff.diff_label_result(a.label(), b.label());
+    diff.diff_display_name(a.display_name(), b.display_name());
     diff.diff_cfg_data_result(a.data(), b.data());
 
     Err(diff.s)
@@ -202,4 +213,23 @@ mod tests {
             diff
         );
     }
+
+    #[test]
+    fn test_diff_display_name() {
+        let data = || {
+            ConfigurationDataData::new(BTreeMap::from_iter([(
+                ConstraintKey(TargetLabel::testing_parse("foo//bar:c")),
+                ConstraintValue(TargetLabel::testing_parse("foo//bar:v")),
+            )]))
+        };
+        let unnamed = ConfigurationData::from_platform("xx".to_owned(), data()).unwrap();
+        let named = ConfigurationData::from_platform_with_display_name(
+            "xx".to_owned(),
+            data(),
+            Some("linux-opt"),
+        )
+        .unwrap();
+        let diff = cfg_diff(&unnamed, &named).unwrap_err();
+        assert_eq!("+ display name: linux-opt\n", diff);
+    }
 }
@@ -11,6 +11,7 @@ use std::collections::hash_map::DefaultHasher;
 use std::collections::BTreeMap;
 use std::hash::Hash;
 use std::hash::Hasher;
+use std::sync::Arc;
 
 use allocative::Allocative;
 use buck2_data::ToProtoMessage;
@@ -45,6 +46,10 @@ enum ConfigurationError {
         "Attempted to access the configuration data for the \"unspecified_exec\" platform. This platform is used when no execution platform was resolved for a target."
     )]
     UnspecifiedExec,
+    #[error(
+        "Invalid configuration display name `{0}`, expected letters, digits, `-`, `_`, `.` and `+`"
+    )]
+    InvalidDisplayName(String),
 }
 
 #[derive(Debug, buck2_error::Error)]
@@ -91,10 +96,35 @@ static INTERNER: Interner<HashedConfigurationPlatform, BuckHasher> = Interner::n
 impl ConfigurationData {
     /// Produces a "bound" configuration for a platform. The label should be a unique identifier for the data.
     pub fn from_platform(label: String, data: ConfigurationDataData) -> anyhow::Result<Self> {
+        Self::from_platform_with_display_name(label, data, None)
+    }
+
+    /// Like `from_platform`, with a short user-declared name to show in place of the label. The
+    /// name is part of the configuration, so it changes the hash.
+    pub fn from_platform_with_display_name(
+        label: String,
+        data: ConfigurationDataData,
+        display_name: Option<&str>,
+    ) -> anyhow::Result<Self> {
         let label = BoundConfigurationLabel::new(label)?;
-        Ok(Self::from_data(HashedConfigurationPlatform::new(
-            ConfigurationPlatform::Bound(label, data),
-        )))
+        let display_name = display_name
+            .map(|name| {
+                if name.is_empty()
+                    || !name
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '+'))
+                {
+                    return Err(ConfigurationError::InvalidDisplayName(name.to_owned()));
+                }
+                Ok(Arc::from(name))
+            })
+            .transpose()?;
+        Ok(Self::from_data(
+            HashedConfigurationPlatform::new_with_display_name(
+                ConfigurationPlatform::Bound(label, data),
+                display_name,
+            ),
+        ))
     }
 
     pub fn unspecified() -> Self {
@@ -259,6 +289,11 @@ impl ConfigurationData {
     pub fn full_name(&self) -> &str {
         &self.0.full_name
     }
+
+    /// The display name declared for this configuration, if any.
+    pub fn display_name(&self) -> Option<&str> {
+        self.0.display_name.as_deref()
+    }
 }
 
 impl Serialize for ConfigurationData {
@@ -276,6 +311,7 @@ impl ToProtoMessage for ConfigurationData {
     fn as_proto(&self) -> Self::Message {
         buck2_data::Configuration {
             full_name: self.full_name().to_owned(),
+            display_name: self.display_name().map(|n| n.to_owned()),
         }
     }
 }
@@ -354,7 +390,10 @@ impl ConfigurationDataData {
 #[display("{}", full_name)]
 pub(crate) struct HashedConfigurationPlatform {
     configuration_platform: ConfigurationPlatform,
-    // The remaining fields are computed from `platform_configuration_data`.
+    /// Short name users declared for the configuration. Part of the configuration, and of its hash
+    /// when set.
+    display_name: Option<Arc<str>>,
+    // The remaining fields are computed from `platform_configuration_data` and `display_name`.
     /// The "full name" includes both the platform and a hash of the configuration data.
     full_name: String,
     /// A hash of the configuration data that is used for determining output paths.
@@ -371,9 +410,20 @@ impl std::hash::Hash for HashedConfigurationPlatform {
 
 impl HashedConfigurationPlatform {
     fn new(configuration_platform: ConfigurationPlatform) -> Self {
+        Self::new_with_display_name(configuration_platform, None)
+    }
+
+    fn new_with_display_name(
+        configuration_platform: ConfigurationPlatform,
+        display_name: Option<Arc<str>>,
+    ) -> Self {
         // TODO(cjhopman): Should this be a crypto hasher?
         let mut hasher = DefaultHasher::new();
         configuration_platform.hash(&mut hasher);
+        // Only hashed when set, so that configurations without one keep their hashes.
+        if let Some(display_name) = &display_name {
+            display_name.hash(&mut hasher);
+        }
         let output_hash = hasher.finish();
         let output_hash = ConfigurationHash::new(output_hash);
 
@@ -385,6 +435,7 @@ impl HashedConfigurationPlatform {
         };
         Self {
             configuration_platform,
+            display_name,
             full_name,
             output_hash,
         }
@@ -463,4 +514,53 @@ mod tests {
         .unwrap();
         assert_eq!(configuration, looked_up);
     }
+
+    #[test]
+    fn test_display_name() -> anyhow::Result<()> {
+        let data = || ConfigurationDataData {
+            constraints: BTreeMap::from_iter([(
+                ConstraintKey(TargetLabel::testing_parse("foo//bar:c")),
+                ConstraintValue(TargetLabel::testing_parse("foo//bar:asan")),
+            )]),
+        };
+        let label = "cfg_for//:testing_display_name";
+        let unnamed = ConfigurationData::from_platform(label.to_owned(), data())?;
+        assert_eq!(None, unnamed.display_name());
+
+        let named = ConfigurationData::from_platform_with_display_name(
+            label.to_owned(),
+            data(),
+            Some("linux-opt-asan"),
+        )?;
+        assert_eq!(Some("linux-opt-asan"), named.display_name());
+        // The name is part of the configuration, so the same constraints with or without a name
+        // (or with another name) do not collide.
+        assert_ne!(unnamed, named);
+        assert_ne!(unnamed.output_hash(), named.output_hash());
+        let renamed = ConfigurationData::from_platform_with_display_name(
+            label.to_owned(),
+            data(),
+            Some("linux-asan"),
+        )?;
+        assert_ne!(named.output_hash(), renamed.output_hash());
+        assert!(
+            named
+                .to_string()
+                .starts_with("cfg_for//:testing_display_name#")
+        );
+
+        assert!(
+            ConfigurationData::from_platform_with_display_name(label.to_owned(), data(), Some(""))
+                .is_err()
+        );
+        assert!(
+            ConfigurationData::from_platform_with_display_name(
+                label.to_owned(),
+                data(),
+                Some("linux opt")
+            )
+            .is_err()
+        );
+        Ok(())
+    }
 }
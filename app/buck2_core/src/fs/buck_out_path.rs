@@ -228,6 +228,10 @@ impl BuckOutPathResolver {
             match origin {
                 ExternalCellOrigin::Bundled(_) => ForwardRelativePath::new("bundled").unwrap(),
                 ExternalCellOrigin::Git(_) => ForwardRelativePath::new("git").unwrap(),
+                ExternalCellOrigin::HttpArchive(_) => {
+                    ForwardRelativePath::new("http_archive").unwrap()
+                }
+                ExternalCellOrigin::Local(_) => ForwardRelativePath::new("local").unwrap(),
             },
             match &origin {
                 ExternalCellOrigin::Bundled(cell) => {
@@ -236,6 +240,12 @@ impl BuckOutPathResolver {
                 ExternalCellOrigin::Git(setup) => {
                     ForwardRelativePath::new(setup.commit.as_ref()).unwrap()
                 }
+                ExternalCellOrigin::HttpArchive(setup) => {
+                    ForwardRelativePath::new(setup.sha256.as_ref()).unwrap()
+                }
+                ExternalCellOrigin::Local(setup) => {
+                    ForwardRelativePath::new(setup.cell.as_str()).unwrap()
+                }
             },
             path.as_ref(),
         ]))
@@ -425,6 +425,23 @@ impl ProjectRoot {
         })
     }
 
+    /// Like `copy`, but from a path that may be outside the project.
+    pub fn copy_from_external(
+        &self,
+        src_abs: &AbsNormPathBuf,
+        dest: impl AsRef<ProjectRelativePath>,
+    ) -> anyhow::Result<()> {
+        let dest_abs = self.resolve(dest);
+
+        let result = self.copy_resolved(src_abs, &dest_abs);
+        result.with_context(|| {
+            format!(
+                "Error copying from src path `{}` to dest path `{}`",
+                src_abs, dest_abs
+            )
+        })
+    }
+
     fn copy_resolved(
         &self,
         src_abs: &AbsNormPathBuf,
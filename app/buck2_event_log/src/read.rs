@@ -7,6 +7,7 @@
  * of this source tree.
  */
 
+use std::future::Future;
 use std::io;
 use std::pin::Pin;
 use std::str::FromStr;
@@ -14,6 +15,7 @@ use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::task::Context;
 use std::task::Poll;
+use std::time::Duration;
 use std::time::SystemTime;
 
 use anyhow::Context as _;
@@ -115,6 +117,46 @@ where
     }
 }
 
+/// How long [`FollowingReader`] waits before reading again at the end of the file.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(200);
+
+mod following_reader {
+    use super::*;
+
+    /// Reads a file that is still being written: at the end of the file, waits for more data
+    /// instead of returning EOF.
+    #[pin_project]
+    pub struct FollowingReader<T> {
+        #[pin]
+        pub(super) inner: T,
+        pub(super) sleep: Option<Pin<Box<tokio::time::Sleep>>>,
+    }
+}
+
+use following_reader::FollowingReader;
+
+impl<T: AsyncRead> AsyncRead for FollowingReader<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut this = self.project();
+        loop {
+            if let Some(sleep) = this.sleep.as_mut() {
+                futures::ready!(sleep.as_mut().poll(cx));
+                *this.sleep = None;
+            }
+            let before = buf.filled().len();
+            futures::ready!(this.inner.as_mut().poll_read(cx, buf))?;
+            if buf.remaining() == 0 || buf.filled().len() > before {
+                return Poll::Ready(Ok(()));
+            }
+            *this.sleep = Some(Box::pin(tokio::time::sleep(FOLLOW_INTERVAL)));
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct EventLogPathBuf {
     pub(crate) path: AbsPathBuf,
@@ -190,10 +232,11 @@ impl EventLogPathBuf {
     async fn unpack_stream_json<'a>(
         &self,
         stats: Option<&'a ReaderStats>,
+        follow: bool,
     ) -> anyhow::Result<(Invocation, BoxStream<'a, anyhow::Result<StreamValue>>)> {
         assert_eq!(self.encoding.mode, LogMode::Json);
 
-        let log_file = self.open(stats).await?;
+        let log_file = self.open(stats, follow).await?;
         let log_file = BufReader::new(log_file);
         let mut log_lines = log_file.lines();
 
@@ -217,10 +260,11 @@ impl EventLogPathBuf {
     async fn unpack_stream_protobuf<'a>(
         &self,
         stats: Option<&'a ReaderStats>,
+        follow: bool,
     ) -> anyhow::Result<(Invocation, BoxStream<'a, anyhow::Result<StreamValue>>)> {
         assert_eq!(self.encoding.mode, LogMode::Protobuf);
 
-        let log_file = self.open(stats).await?;
+        let log_file = self.open(stats, follow).await?;
         let mut stream = FramedRead::new(log_file, ProtobufSplitter);
 
         let invocation = stream.try_next().await?.context("No invocation found")?;
@@ -257,13 +301,14 @@ impl EventLogPathBuf {
     async fn unpack_stream_inner<'a>(
         &self,
         stats: Option<&'a ReaderStats>,
+        follow: bool,
     ) -> anyhow::Result<(
         Invocation,
         impl Stream<Item = anyhow::Result<StreamValue>> + 'a,
     )> {
         match self.encoding.mode {
-            LogMode::Json => self.unpack_stream_json(stats).await,
-            LogMode::Protobuf => self.unpack_stream_protobuf(stats).await,
+            LogMode::Json => self.unpack_stream_json(stats, follow).await,
+            LogMode::Protobuf => self.unpack_stream_protobuf(stats, follow).await,
         }
     }
 
@@ -275,7 +320,7 @@ impl EventLogPathBuf {
         Invocation,
         impl Stream<Item = anyhow::Result<StreamValue>> + 'a,
     )> {
-        self.unpack_stream_inner(Some(stats)).await
+        self.unpack_stream_inner(Some(stats), false).await
     }
 
     pub async fn unpack_stream(
@@ -284,10 +329,26 @@ impl EventLogPathBuf {
         Invocation,
         impl Stream<Item = anyhow::Result<StreamValue>> + 'static,
     )> {
-        self.unpack_stream_inner(None).await
+        self.unpack_stream_inner(None, false).await
     }
 
-    async fn open<'a>(&self, stats: Option<&'a ReaderStats>) -> anyhow::Result<EventLogReader<'a>> {
+    /// Like `unpack_stream`, for the log of a command that is still running: the stream waits
+    /// for more events at the end of the log instead of ending. It is up to the caller to stop
+    /// reading once the command is done.
+    pub async fn unpack_stream_following(
+        &self,
+    ) -> anyhow::Result<(
+        Invocation,
+        impl Stream<Item = anyhow::Result<StreamValue>> + 'static,
+    )> {
+        self.unpack_stream_inner(None, true).await
+    }
+
+    async fn open<'a>(
+        &self,
+        stats: Option<&'a ReaderStats>,
+        follow: bool,
+    ) -> anyhow::Result<EventLogReader<'a>> {
         tracing::info!(
             "Open {} using encoding {:?}",
             self.path.display(),
@@ -303,6 +364,14 @@ impl EventLogPathBuf {
         };
 
         let file = async_fs_util::open(&self.path).await?;
+        let file: EventLogReader = if follow {
+            Box::new(FollowingReader {
+                inner: file,
+                sleep: None,
+            })
+        } else {
+            Box::new(file)
+        };
         let file = CountingReader::new(file, compressed_bytes);
         let file = match self.encoding.compression {
             Compression::None => {
@@ -400,6 +469,37 @@ mod tests {
         Ok(event)
     }
 
+    #[tokio::test]
+    async fn test_following_reader() -> anyhow::Result<()> {
+        use std::io::Write;
+
+        use tokio::io::AsyncReadExt;
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("log");
+        let mut writer = std::fs::File::create(&path)?;
+        writer.write_all(b"abc")?;
+
+        let mut reader = FollowingReader {
+            inner: tokio::fs::File::open(&path).await?,
+            sleep: None,
+        };
+        let mut buf = [0; 3];
+        reader.read_exact(&mut buf).await?;
+        assert_eq!(&buf, b"abc");
+
+        // At the end of the file, the read waits for the writer.
+        let read = tokio::spawn(async move {
+            let mut buf = [0; 3];
+            reader.read_exact(&mut buf).await.map(|_| buf)
+        });
+        tokio::time::sleep(FOLLOW_INTERVAL * 2).await;
+        assert!(!read.is_finished());
+        writer.write_all(b"def")?;
+        assert_eq!(&read.await??, b"def");
+        Ok(())
+    }
+
     fn logdir() -> AbsNormPathBuf {
         if cfg!(windows) {
             AbsNormPathBuf::new("C:\\foo".into()).unwrap()
@@ -84,7 +84,17 @@ pub fn display_configured_target_label(
     } = ctl
     {
         Ok(if opts.with_configuration {
-            format!("{}:{} ({})", package, name, configuration.full_name)
+            match &configuration.display_name {
+                // Display names need not be unique, so keep the hash to tell configurations apart.
+                Some(display_name) => match configuration.full_name.rsplit_once('#') {
+                    Some((_, hash)) => format!("{}:{} ({}#{})", package, name, display_name, hash),
+                    None => format!(
+                        "{}:{} ({} ({}))",
+                        package, name, display_name, configuration.full_name
+                    ),
+                },
+                None => format!("{}:{} ({})", package, name, configuration.full_name),
+            }
         } else {
             format!("{}:{}", package, name)
         })
@@ -385,6 +395,7 @@ fn display_file_watcher(provider: i32) -> &'static str {
         Some(buck2_data::FileWatcherProvider::Watchman) => "Watchman",
         Some(buck2_data::FileWatcherProvider::RustNotify) => "notify",
         Some(buck2_data::FileWatcherProvider::FsHashCrawler) => "fs_hash_crawler",
+        Some(buck2_data::FileWatcherProvider::Git) => "git",
         None => "unknown mechanism",
     }
 }
@@ -487,6 +498,7 @@ pub fn display_executor_stage(
 
             match local.stage.as_ref()? {
                 Stage::Queued(..) => "local_queued",
+                Stage::ResourceQueued(..) => "local_resource_queued",
                 Stage::Execute(..) => "local_execute",
                 Stage::MaterializeInputs(..) => "local_materialize_inputs",
                 Stage::PrepareOutputs(_) => "local_prepare_outputs",
@@ -916,4 +928,30 @@ mod tests {
         let res = strip_trailing_newline(stream_contents);
         assert_eq!(res, "test");
     }
+
+    #[test]
+    fn prefers_configuration_display_name() -> anyhow::Result<()> {
+        let mut label = buck2_data::ConfiguredTargetLabel {
+            label: Some(buck2_data::TargetLabel {
+                package: "root//pkg".to_owned(),
+                name: "lib".to_owned(),
+            }),
+            configuration: Some(buck2_data::Configuration {
+                full_name: "cfg:linux-x86_64#a1b2c3".to_owned(),
+                display_name: None,
+            }),
+            execution_configuration: None,
+        };
+        assert_eq!(
+            "root//pkg:lib (cfg:linux-x86_64#a1b2c3)",
+            display_configured_target_label(&label, TargetDisplayOptions::for_log())?
+        );
+
+        label.configuration.as_mut().unwrap().display_name = Some("linux-opt-asan".to_owned());
+        assert_eq!(
+            "root//pkg:lib (linux-opt-asan#a1b2c3)",
+            display_configured_target_label(&label, TargetDisplayOptions::for_log())?
+        );
+        Ok(())
+    }
 }
@@ -445,7 +445,11 @@ impl SpanTrackable for BuckEvent {
                         use buck2_data::local_stage::Stage;
 
                         match stage.stage.as_ref() {
-                            Some(Stage::Queued(..) | Stage::AcquireLocalResource(..)) => true,
+                            Some(
+                                Stage::Queued(..)
+                                | Stage::AcquireLocalResource(..)
+                                | Stage::ResourceQueued(..),
+                            ) => true,
                             _ => false,
                         }
                     }
@@ -100,6 +100,8 @@ pub struct WhatRanOutputCommand<'a> {
     pub extra: Option<WhatRanOutputCommandExtra<'a>>,
     pub std_err: Option<&'a str>,
     pub duration: Option<std::time::Duration>,
+    /// Peak memory usage of the command, if it ran locally in a cgroup.
+    pub memory_peak_bytes: Option<u64>,
 }
 
 impl<'a> WhatRanOutputCommand<'a> {
@@ -259,6 +261,22 @@ pub fn emit_what_ran_entry(
 
         _ => None,
     };
+    let memory_peak_bytes = match data {
+        Some(buck2_data::span_end_event::Data::ActionExecution(action_exec)) => action_exec
+            .commands
+            .iter()
+            .last()
+            .and_then(|cmd| {
+                cmd.details
+                    .as_ref()?
+                    .metadata
+                    .as_ref()?
+                    .execution_stats
+                    .as_ref()
+            })
+            .and_then(|stats| stats.memory_peak_bytes),
+        _ => None,
+    };
     output.emit_command(WhatRanOutputCommand {
         reason,
         identity: &identity,
@@ -266,6 +284,7 @@ pub fn emit_what_ran_entry(
         extra,
         std_err,
         duration,
+        memory_peak_bytes,
     })?;
 
     Ok(())
@@ -11,6 +11,7 @@
 //! sink during normal operation.
 pub(crate) mod channel;
 pub(crate) mod null;
+pub mod otlp;
 pub mod remote;
 pub(crate) mod smart_truncate_event;
 pub mod tee;
@@ -0,0 +1,751 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A Sink for exporting commands, actions, analysis and tests to an OpenTelemetry collector.
+//!
+//! Spans are exported as OTLP traces, and a summary of every command (action counts, cache hit
+//! ratio, durations) as OTLP metrics. Both go over OTLP/HTTP using the JSON encoding, so this
+//! works with any collector without needing the OpenTelemetry protos.
+
+use std::collections::HashMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use buck2_data::ActionExecutionKind;
+use buck2_http::HttpClient;
+use bytes::Bytes;
+use gazebo::variants::VariantName;
+use serde_json::json;
+use tokio::sync::mpsc;
+
+use crate::BuckEvent;
+use crate::Event;
+use crate::EventSink;
+use crate::EventSinkStats;
+use crate::EventSinkWithStats;
+
+/// `Span.SpanKind.SPAN_KIND_INTERNAL`.
+const SPAN_KIND_INTERNAL: u32 = 1;
+/// `Status.StatusCode.STATUS_CODE_ERROR`.
+const STATUS_CODE_ERROR: u32 = 2;
+/// `AggregationTemporality.AGGREGATION_TEMPORALITY_DELTA`.
+const AGGREGATION_TEMPORALITY_DELTA: u32 = 1;
+/// Commands we accumulate metrics for at once. Commands whose end we never see (e.g. because the
+/// client went away) are forgotten, oldest first, once we go over this.
+const MAX_TRACKED_COMMANDS: usize = 64;
+
+#[derive(Clone, Debug)]
+pub struct OtlpConfig {
+    /// Base URL of the collector, e.g. `http://localhost:4318`. `/v1/traces` and `/v1/metrics`
+    /// are appended to it.
+    pub endpoint: String,
+    /// Maximum number of spans sent in a single request.
+    pub batch_size: usize,
+    /// Maximum number of exports waiting to be sent. Exports beyond that are dropped.
+    pub queue_size: usize,
+    /// How long spans may wait for a batch to fill up before being sent anyway.
+    pub flush_interval: Duration,
+}
+
+enum Export {
+    Span(serde_json::Value),
+    Metrics(serde_json::Value),
+}
+
+#[derive(Default)]
+struct Counters {
+    successes: AtomicU64,
+    failures: AtomicU64,
+    dropped: AtomicU64,
+    bytes_written: AtomicU64,
+}
+
+/// What we know about a command so far, to be exported as metrics when it finishes.
+#[derive(Default)]
+struct CommandMetrics {
+    /// Order in which commands were first seen, for eviction.
+    first_seen: u64,
+    actions: HashMap<&'static str, u64>,
+    cache_hits: u64,
+    failed_actions: u64,
+    action_duration_seconds: f64,
+    analyses: u64,
+    tests: u64,
+}
+
+pub struct OtlpEventSink {
+    queue: mpsc::Sender<Export>,
+    queue_size: usize,
+    commands: Mutex<Commands>,
+    counters: Arc<Counters>,
+}
+
+/// Metrics of the commands in progress, by trace id.
+#[derive(Default)]
+struct Commands {
+    metrics: HashMap<String, CommandMetrics>,
+    seen: u64,
+}
+
+impl Commands {
+    fn get(&mut self, trace_id: &str) -> &mut CommandMetrics {
+        if !self.metrics.contains_key(trace_id) {
+            if self.metrics.len() >= MAX_TRACKED_COMMANDS {
+                let oldest = self
+                    .metrics
+                    .iter()
+                    .min_by_key(|(_, m)| m.first_seen)
+                    .map(|(trace_id, _)| trace_id.clone());
+                if let Some(oldest) = oldest {
+                    self.metrics.remove(&oldest);
+                }
+            }
+            self.seen += 1;
+            self.metrics.insert(
+                trace_id.to_owned(),
+                CommandMetrics {
+                    first_seen: self.seen,
+                    ..Default::default()
+                },
+            );
+        }
+        self.metrics.get_mut(trace_id).unwrap()
+    }
+
+    fn remove(&mut self, trace_id: &str) -> CommandMetrics {
+        self.metrics.remove(trace_id).unwrap_or_default()
+    }
+}
+
+impl OtlpEventSink {
+    /// Creates the sink and spawns the task exporting to the collector. Must be called from within
+    /// a Tokio runtime.
+    pub fn new(config: OtlpConfig, client: HttpClient) -> OtlpEventSink {
+        let (queue, recv) = mpsc::channel(config.queue_size);
+        let counters = Arc::new(Counters::default());
+        tokio::spawn(export_loop(recv, config.clone(), client, counters.clone()));
+        OtlpEventSink {
+            queue,
+            queue_size: config.queue_size,
+            commands: Mutex::new(Commands::default()),
+            counters,
+        }
+    }
+
+    fn offer(&self, export: Export) {
+        if self.queue.try_send(export).is_err() {
+            self.counters.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn handle_event(&self, event: &BuckEvent) {
+        use buck2_data::span_end_event::Data;
+
+        let buck2_data::buck_event::Data::SpanEnd(end) = event.data() else {
+            return;
+        };
+        let Some(data) = &end.data else {
+            return;
+        };
+        let trace_id = &event.event().trace_id;
+
+        let mut commands = self.commands.lock().unwrap();
+        let mut metrics_export = None;
+
+        let (name, attributes, failed) = match data {
+            Data::Command(command) => {
+                let command_name = command
+                    .data
+                    .as_ref()
+                    .map_or("unknown", |d| d.variant_name());
+                metrics_export = Some(command_metrics(
+                    event,
+                    end,
+                    command_name,
+                    command.is_success,
+                    &commands.remove(trace_id),
+                ));
+                (
+                    format!("buck2 {}", command_name),
+                    vec![
+                        attribute("buck2.command", command_name),
+                        attribute("buck2.trace_id", trace_id.as_str()),
+                    ],
+                    !command.is_success,
+                )
+            }
+            Data::ActionExecution(action) => {
+                let kind = ActionExecutionKind::from_i32(action.execution_kind)
+                    .unwrap_or(ActionExecutionKind::NotSet);
+                let kind_name = execution_kind_name(kind);
+                match kind {
+                    // Not actually executed, so not interesting for metrics.
+                    ActionExecutionKind::Simple | ActionExecutionKind::Deferred => {}
+                    _ => {
+                        let metrics = commands.get(trace_id);
+                        *metrics.actions.entry(kind_name).or_default() += 1;
+                        if is_cache_hit(kind) {
+                            metrics.cache_hits += 1;
+                        }
+                        if action.failed {
+                            metrics.failed_actions += 1;
+                        }
+                        metrics.action_duration_seconds += end
+                            .duration
+                            .as_ref()
+                            .map_or(0.0, |d| d.seconds as f64 + d.nanos as f64 / 1e9);
+                    }
+                }
+
+                let mut attributes = vec![
+                    attribute("buck2.action.execution_kind", kind_name),
+                    attribute("buck2.action.cache_hit", is_cache_hit(kind)),
+                ];
+                if let Some(name) = &action.name {
+                    attributes.push(attribute("buck2.action.category", name.category.as_str()));
+                    attributes.push(attribute(
+                        "buck2.action.identifier",
+                        name.identifier.as_str(),
+                    ));
+                }
+                (
+                    match &action.name {
+                        Some(name) if !name.identifier.is_empty() => {
+                            format!("{} {}", name.category, name.identifier)
+                        }
+                        Some(name) => name.category.clone(),
+                        None => "action".to_owned(),
+                    },
+                    attributes,
+                    action.failed,
+                )
+            }
+            Data::Analysis(analysis) => {
+                commands.get(trace_id).analyses += 1;
+                let mut attributes = vec![attribute("buck2.analysis.rule", analysis.rule.as_str())];
+                if let Some(buck2_data::analysis_end::Target::StandardTarget(target)) =
+                    &analysis.target
+                {
+                    attributes.push(attribute("buck2.target", target_label(target)));
+                }
+                ("analysis".to_owned(), attributes, false)
+            }
+            Data::TestEnd(test) => {
+                commands.get(trace_id).tests += 1;
+                let mut attributes = Vec::new();
+                if let Some(suite) = &test.suite {
+                    attributes.push(attribute("buck2.test.suite", suite.suite_name.as_str()));
+                    if let Some(target) = &suite.target_label {
+                        attributes.push(attribute("buck2.target", target_label(target)));
+                    }
+                }
+                let status = test
+                    .command_report
+                    .as_ref()
+                    .and_then(|report| report.status.as_ref());
+                if let Some(status) = status {
+                    attributes.push(attribute("buck2.test.status", status.variant_name()));
+                }
+                let failed = !matches!(
+                    status,
+                    Some(buck2_data::command_execution::Status::Success(..)) | None
+                );
+                ("test".to_owned(), attributes, failed)
+            }
+            _ => return,
+        };
+        drop(commands);
+
+        self.offer(Export::Span(span(event, end, name, attributes, failed)));
+        // After the command's span, so that the whole trace gets sent along with the metrics.
+        if let Some(metrics) = metrics_export {
+            self.offer(Export::Metrics(metrics));
+        }
+    }
+}
+
+impl EventSink for OtlpEventSink {
+    fn send(&self, event: Event) {
+        match event {
+            Event::Buck(event) => self.handle_event(&event),
+            Event::CommandResult(..) => {}
+            Event::PartialResult(..) => {}
+        }
+    }
+}
+
+impl EventSinkWithStats for OtlpEventSink {
+    fn to_event_sync(self: Arc<Self>) -> Arc<dyn EventSink> {
+        self as _
+    }
+
+    fn stats(&self) -> EventSinkStats {
+        EventSinkStats {
+            successes: self.counters.successes.load(Ordering::Relaxed),
+            failures_invalid_request: 0,
+            failures_unauthorized: 0,
+            failures_rate_limited: 0,
+            failures_pushed_back: 0,
+            failures_enqueue_failed: 0,
+            failures_internal_error: 0,
+            failures_timed_out: 0,
+            failures_unknown: self.counters.failures.load(Ordering::Relaxed),
+            buffered: (self.queue_size - self.queue.capacity()) as u64,
+            dropped: self.counters.dropped.load(Ordering::Relaxed),
+            bytes_written: self.counters.bytes_written.load(Ordering::Relaxed),
+        }
+    }
+}
+
+fn execution_kind_name(kind: ActionExecutionKind) -> &'static str {
+    match kind {
+        ActionExecutionKind::NotSet => "not_set",
+        ActionExecutionKind::Local => "local",
+        ActionExecutionKind::Remote => "remote",
+        ActionExecutionKind::ActionCache => "action_cache",
+        ActionExecutionKind::Simple => "simple",
+        ActionExecutionKind::Deferred => "deferred",
+        ActionExecutionKind::LocalDepFile => "local_dep_file",
+        ActionExecutionKind::LocalWorker => "local_worker",
+        ActionExecutionKind::RemoteDepFileCache => "remote_dep_file_cache",
+    }
+}
+
+fn is_cache_hit(kind: ActionExecutionKind) -> bool {
+    matches!(
+        kind,
+        ActionExecutionKind::ActionCache
+            | ActionExecutionKind::RemoteDepFileCache
+            | ActionExecutionKind::LocalDepFile
+    )
+}
+
+fn target_label(target: &buck2_data::ConfiguredTargetLabel) -> String {
+    let label = target
+        .label
+        .as_ref()
+        .map_or(String::new(), |l| format!("{}:{}", l.package, l.name));
+    match &target.configuration {
+        Some(configuration) => format!("{} ({})", label, configuration.full_name),
+        None => label,
+    }
+}
+
+fn attribute(key: &str, value: impl Into<AttributeValue>) -> serde_json::Value {
+    let value = match value.into() {
+        AttributeValue::String(s) => json!({ "stringValue": s }),
+        AttributeValue::Bool(b) => json!({ "boolValue": b }),
+    };
+    json!({ "key": key, "value": value })
+}
+
+enum AttributeValue {
+    String(String),
+    Bool(bool),
+}
+
+impl From<&str> for AttributeValue {
+    fn from(s: &str) -> Self {
+        AttributeValue::String(s.to_owned())
+    }
+}
+
+impl From<String> for AttributeValue {
+    fn from(s: String) -> Self {
+        AttributeValue::String(s)
+    }
+}
+
+impl From<bool> for AttributeValue {
+    fn from(b: bool) -> Self {
+        AttributeValue::Bool(b)
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos())
+        .to_string()
+}
+
+/// The span ends when the event is emitted; it started `duration` before that.
+fn span_times(event: &BuckEvent, end: &buck2_data::SpanEndEvent) -> (SystemTime, SystemTime) {
+    let end_time = event.timestamp();
+    let duration = end.duration.as_ref().map_or(Duration::ZERO, |d| {
+        Duration::new(d.seconds.max(0) as u64, d.nanos.max(0) as u32)
+    });
+    (end_time.checked_sub(duration).unwrap_or(end_time), end_time)
+}
+
+/// OTLP trace ids are 16 bytes and span ids 8 bytes, hex encoded, which is exactly what our
+/// UUID trace ids and `u64` span ids are.
+fn span(
+    event: &BuckEvent,
+    end: &buck2_data::SpanEndEvent,
+    name: String,
+    attributes: Vec<serde_json::Value>,
+    failed: bool,
+) -> serde_json::Value {
+    let (start_time, end_time) = span_times(event, end);
+    let mut span = json!({
+        "traceId": event.event().trace_id.replace('-', ""),
+        "spanId": format!("{:016x}", event.span_id().map_or(0, |s| s.0.get())),
+        "name": name,
+        "kind": SPAN_KIND_INTERNAL,
+        "startTimeUnixNano": unix_nanos(start_time),
+        "endTimeUnixNano": unix_nanos(end_time),
+        "attributes": attributes,
+    });
+    if let Some(parent_id) = event.parent_id() {
+        span["parentSpanId"] = json!(format!("{:016x}", parent_id.0.get()));
+    }
+    if failed {
+        span["status"] = json!({ "code": STATUS_CODE_ERROR });
+    }
+    span
+}
+
+fn command_metrics(
+    event: &BuckEvent,
+    end: &buck2_data::SpanEndEvent,
+    command_name: &str,
+    is_success: bool,
+    metrics: &CommandMetrics,
+) -> serde_json::Value {
+    let (start_time, end_time) = span_times(event, end);
+    let start_time = unix_nanos(start_time);
+    let end_time = unix_nanos(end_time);
+    let common = vec![
+        attribute("buck2.command", command_name),
+        attribute("buck2.success", is_success),
+    ];
+
+    let sum = |name: &str, unit: &str, points: Vec<(Vec<serde_json::Value>, u64)>| {
+        json!({
+            "name": name,
+            "unit": unit,
+            "sum": {
+                "aggregationTemporality": AGGREGATION_TEMPORALITY_DELTA,
+                "isMonotonic": true,
+                "dataPoints": points.into_iter().map(|(attributes, value)| json!({
+                    "attributes": common.iter().cloned().chain(attributes).collect::<Vec<_>>(),
+                    "startTimeUnixNano": start_time,
+                    "timeUnixNano": end_time,
+                    "asInt": value.to_string(),
+                })).collect::<Vec<_>>(),
+            },
+        })
+    };
+    let gauge = |name: &str, unit: &str, value: f64| {
+        json!({
+            "name": name,
+            "unit": unit,
+            "gauge": {
+                "dataPoints": [{
+                    "attributes": common,
+                    "timeUnixNano": end_time,
+                    "asDouble": value,
+                }],
+            },
+        })
+    };
+
+    let executed: u64 = metrics.actions.values().sum();
+    let mut actions = metrics
+        .actions
+        .iter()
+        .map(|(kind, count)| {
+            (
+                vec![attribute("buck2.action.execution_kind", *kind)],
+                *count,
+            )
+        })
+        .collect::<Vec<_>>();
+    actions.sort_by(|a, b| a.0.to_string().cmp(&b.0.to_string()));
+
+    let mut exported = vec![
+        gauge(
+            "buck2.command.duration",
+            "s",
+            end.duration
+                .as_ref()
+                .map_or(0.0, |d| d.seconds as f64 + d.nanos as f64 / 1e9),
+        ),
+        sum("buck2.actions", "{action}", actions),
+        sum(
+            "buck2.actions.cache_hits",
+            "{action}",
+            vec![(vec![], metrics.cache_hits)],
+        ),
+        sum(
+            "buck2.actions.failed",
+            "{action}",
+            vec![(vec![], metrics.failed_actions)],
+        ),
+        gauge(
+            "buck2.actions.duration",
+            "s",
+            metrics.action_duration_seconds,
+        ),
+        sum(
+            "buck2.analyses",
+            "{analysis}",
+            vec![(vec![], metrics.analyses)],
+        ),
+        sum("buck2.tests", "{test}", vec![(vec![], metrics.tests)]),
+    ];
+    if executed > 0 {
+        exported.push(gauge(
+            "buck2.actions.cache_hit_ratio",
+            "1",
+            metrics.cache_hits as f64 / executed as f64,
+        ));
+    }
+
+    json!({
+        "resourceMetrics": [{
+            "resource": resource(),
+            "scopeMetrics": [{
+                "scope": { "name": "buck2" },
+                "metrics": exported,
+            }],
+        }],
+    })
+}
+
+fn resource() -> serde_json::Value {
+    json!({
+        "attributes": [
+            attribute("service.name", "buck2"),
+            attribute("service.version", buck2_build_info::revision().unwrap_or("unknown")),
+            attribute("host.name", hostname::get().ok().and_then(|h| h.into_string().ok()).unwrap_or_default()),
+        ],
+    })
+}
+
+fn traces(spans: Vec<serde_json::Value>) -> serde_json::Value {
+    json!({
+        "resourceSpans": [{
+            "resource": resource(),
+            "scopeSpans": [{
+                "scope": { "name": "buck2" },
+                "spans": spans,
+            }],
+        }],
+    })
+}
+
+async fn post(
+    client: &HttpClient,
+    config: &OtlpConfig,
+    path: &str,
+    body: serde_json::Value,
+    counters: &Counters,
+) {
+    let body = Bytes::from(body.to_string());
+    let len = body.len() as u64;
+    let url = format!("{}{}", config.endpoint.trim_end_matches('/'), path);
+    let headers = vec![("Content-Type".to_owned(), "application/json".to_owned())];
+    match client.post(&url, body, headers).await {
+        Ok(_) => {
+            counters.successes.fetch_add(1, Ordering::Relaxed);
+            counters.bytes_written.fetch_add(len, Ordering::Relaxed);
+        }
+        Err(_) => {
+            counters.failures.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+async fn export_loop(
+    mut recv: mpsc::Receiver<Export>,
+    config: OtlpConfig,
+    client: HttpClient,
+    counters: Arc<Counters>,
+) {
+    let mut spans = Vec::new();
+    let mut interval = tokio::time::interval(config.flush_interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        let flush = tokio::select! {
+            export = recv.recv() => match export {
+                Some(Export::Span(span)) => {
+                    spans.push(span);
+                    spans.len() >= config.batch_size
+                }
+                Some(Export::Metrics(metrics)) => {
+                    post(&client, &config, "/v1/metrics", metrics, &counters).await;
+                    // The command finished, don't hold on to its spans.
+                    true
+                }
+                None => {
+                    if !spans.is_empty() {
+                        post(&client, &config, "/v1/traces", traces(spans), &counters).await;
+                    }
+                    return;
+                }
+            },
+            _ = interval.tick() => true,
+        };
+
+        if flush && !spans.is_empty() {
+            let batch = std::mem::take(&mut spans);
+            post(&client, &config, "/v1/traces", traces(batch), &counters).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_http::HttpClientBuilder;
+    use buck2_wrapper_common::invocation_id::TraceId;
+    use dupe::Dupe;
+    use httptest::matchers::*;
+    use httptest::responders;
+    use httptest::Expectation;
+
+    use super::*;
+    use crate::span::SpanId;
+
+    fn span_end(
+        trace_id: &TraceId,
+        span_id: u64,
+        parent_id: Option<u64>,
+        data: impl Into<buck2_data::span_end_event::Data>,
+    ) -> Event {
+        Event::Buck(BuckEvent::new(
+            SystemTime::now(),
+            trace_id.dupe(),
+            SpanId::from_u64_opt(span_id),
+            parent_id.and_then(SpanId::from_u64_opt),
+            buck2_data::SpanEndEvent {
+                stats: None,
+                duration: Some(prost_types::Duration {
+                    seconds: 1,
+                    nanos: 0,
+                }),
+                data: Some(data.into()),
+            }
+            .into(),
+        ))
+    }
+
+    fn action(kind: ActionExecutionKind) -> buck2_data::ActionExecutionEnd {
+        buck2_data::ActionExecutionEnd {
+            name: Some(buck2_data::ActionName {
+                category: "cxx_compile".to_owned(),
+                identifier: "foo.cpp".to_owned(),
+            }),
+            execution_kind: kind as i32,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_export_to_collector() -> anyhow::Result<()> {
+        let collector = httptest::Server::run();
+        collector.expect(
+            Expectation::matching(all_of![
+                request::method_path("POST", "/v1/traces"),
+                request::body(json_decoded(|body: &serde_json::Value| {
+                    let spans = &body["resourceSpans"][0]["scopeSpans"][0]["spans"];
+                    spans.as_array().map_or(0, |s| s.len()) == 3
+                        && spans[0]["name"] == "cxx_compile foo.cpp"
+                        && spans[0]["parentSpanId"] == "0000000000000001"
+                        && spans[2]["name"] == "buck2 Build"
+                })),
+            ])
+            .respond_with(responders::status_code(200)),
+        );
+        collector.expect(
+            Expectation::matching(all_of![
+                request::method_path("POST", "/v1/metrics"),
+                request::body(json_decoded(|body: &serde_json::Value| {
+                    let metrics = body["resourceMetrics"][0]["scopeMetrics"][0]["metrics"]
+                        .as_array()
+                        .cloned()
+                        .unwrap_or_default();
+                    metrics.iter().any(|m| {
+                        m["name"] == "buck2.actions.cache_hit_ratio"
+                            && m["gauge"]["dataPoints"][0]["asDouble"] == 0.5
+                    })
+                })),
+            ])
+            .respond_with(responders::status_code(200)),
+        );
+
+        let sink = Arc::new(OtlpEventSink::new(
+            OtlpConfig {
+                endpoint: collector.url_str(""),
+                batch_size: 100,
+                queue_size: 100,
+                flush_interval: Duration::from_secs(60),
+            },
+            HttpClientBuilder::https_with_system_roots().await?.build(),
+        ));
+
+        let trace_id = TraceId::new();
+        sink.send(span_end(
+            &trace_id,
+            2,
+            Some(1),
+            action(ActionExecutionKind::Local),
+        ));
+        sink.send(span_end(
+            &trace_id,
+            3,
+            Some(1),
+            action(ActionExecutionKind::ActionCache),
+        ));
+        sink.send(span_end(
+            &trace_id,
+            1,
+            None,
+            buck2_data::CommandEnd {
+                data: Some(buck2_data::BuildCommandEnd::default().into()),
+                is_success: true,
+                ..Default::default()
+            },
+        ));
+
+        // The collector verifies its expectations when dropped, so wait for both requests.
+        for _ in 0..100 {
+            if sink.stats().successes == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(2, sink.stats().successes);
+        assert_eq!(0, sink.stats().dropped);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_commands_bounded() {
+        let mut commands = Commands::default();
+        for i in 0..MAX_TRACKED_COMMANDS + 10 {
+            commands.get(&i.to_string()).tests += 1;
+        }
+        assert_eq!(MAX_TRACKED_COMMANDS, commands.metrics.len());
+        // The oldest commands are the ones forgotten.
+        assert_eq!(0, commands.remove("0").tests);
+        assert_eq!(
+            1,
+            commands
+                .remove(&(MAX_TRACKED_COMMANDS + 9).to_string())
+                .tests
+        );
+    }
+}
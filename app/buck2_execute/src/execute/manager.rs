@@ -135,6 +135,7 @@ impl CommandExecutionManagerLike for CommandExecutionManager {
                 timing,
                 std_streams,
                 exit_code,
+                undeclared_inputs: Vec::new(),
             },
             rejected_execution: None,
             did_cache_upload: false,
@@ -215,6 +216,7 @@ impl CommandExecutionManagerLike for CommandExecutionManagerWithClaim {
                 timing,
                 std_streams,
                 exit_code,
+                undeclared_inputs: Vec::new(),
             },
             rejected_execution: None,
             did_cache_upload: false,
@@ -29,6 +29,7 @@ use derive_more::Display;
 use dupe::Dupe;
 use gazebo::variants::UnpackVariants;
 use host_sharing::host_sharing::HostSharingRequirements;
+use host_sharing::ResourceEstimate;
 use indexmap::IndexSet;
 use itertools::Itertools;
 use prost::Message;
@@ -276,11 +277,45 @@ impl CommandExecutionPaths {
 #[derive(Copy, Clone, Dupe, Debug, Display, Allocative, Hash, PartialEq, Eq)]
 pub struct WorkerId(pub u64);
 
+/// How Buck2 talks to a worker.
+#[derive(Copy, Clone, Dupe, Debug, Default, PartialEq, Eq)]
+pub enum WorkerProtocol {
+    /// The `Worker` gRPC service from `buck2_worker_proto`, over a Unix domain socket.
+    #[default]
+    Buck2,
+    /// A Bazel persistent worker, which reads requests on stdin and writes responses on stdout.
+    /// Multiplex workers handle several requests at once. Workers that support cancellation are
+    /// sent a `cancel` request when a request times out.
+    Bazel {
+        encoding: BazelWorkerEncoding,
+        multiplex: bool,
+        supports_cancellation: bool,
+    },
+}
+
+#[derive(Copy, Clone, Dupe, Debug, PartialEq, Eq)]
+pub enum BazelWorkerEncoding {
+    /// Newline-delimited JSON.
+    Json,
+    /// Length-delimited protobuf.
+    Proto,
+}
+
 #[derive(Clone)]
 pub struct WorkerSpec {
     pub id: WorkerId,
     pub exe: Vec<String>,
     pub concurrency: Option<usize>,
+    pub protocol: WorkerProtocol,
+}
+
+/// Limits on the resources a command may use when it runs locally. They are enforced when the
+/// forkserver runs commands in cgroups, i.e. with `buck2_resource_control` enabled.
+#[derive(Debug, Default, Clone, Copy, Dupe, PartialEq, Eq, Hash, Allocative)]
+pub struct ResourceLimits {
+    pub memory_max_bytes: Option<u64>,
+    /// In thousandths of a CPU.
+    pub cpu_max_millicores: Option<u64>,
 }
 
 /// The data contains the information about the command to be executed.
@@ -322,6 +357,11 @@ pub struct CommandExecutionRequest {
     pub remote_dep_file_key: Option<DepFileDigest>,
     /// RE dependencies to pass in action metadata.
     remote_execution_dependencies: Vec<RemoteExecutorDependency>,
+    /// Limits enforced when running locally.
+    resource_limits: ResourceLimits,
+    /// What the command is expected to use when running locally, to admit it against the
+    /// machine's resources.
+    resource_estimate: ResourceEstimate,
 }
 
 impl CommandExecutionRequest {
@@ -351,6 +391,8 @@ impl CommandExecutionRequest {
             unique_input_inodes: false,
             remote_dep_file_key: None,
             remote_execution_dependencies: Vec::new(),
+            resource_limits: ResourceLimits::default(),
+            resource_estimate: ResourceEstimate::default(),
         }
     }
 
@@ -542,6 +584,24 @@ impl CommandExecutionRequest {
     pub fn remote_execution_dependencies(&self) -> &Vec<RemoteExecutorDependency> {
         &self.remote_execution_dependencies
     }
+
+    pub fn with_resource_limits(mut self, resource_limits: ResourceLimits) -> Self {
+        self.resource_limits = resource_limits;
+        self
+    }
+
+    pub fn resource_limits(&self) -> ResourceLimits {
+        self.resource_limits
+    }
+
+    pub fn with_resource_estimate(mut self, resource_estimate: ResourceEstimate) -> Self {
+        self.resource_estimate = resource_estimate;
+        self
+    }
+
+    pub fn resource_estimate(&self) -> ResourceEstimate {
+        self.resource_estimate
+    }
 }
 
 /// Is an output a file or a directory
@@ -17,6 +17,7 @@ use std::time::SystemTime;
 use allocative::Allocative;
 use buck2_action_metadata_proto::RemoteDepFile;
 use buck2_core::fs::artifact_path_resolver::ArtifactFs;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
 use derivative::Derivative;
 use dupe::Dupe;
 use indexmap::IndexMap;
@@ -271,6 +272,8 @@ pub struct CommandExecutionReport {
     /// No exit_code means the command did not finish executing. Signals get mapped into this as
     /// 128 + SIGNUM, which is the convention shells follow.
     pub exit_code: Option<i32>,
+    /// Files the command read without declaring them, if its inputs were traced.
+    pub undeclared_inputs: Vec<ProjectRelativePathBuf>,
 }
 
 impl CommandExecutionReport {
@@ -350,6 +353,11 @@ impl CommandExecutionReport {
             command_kind,
             signed_exit_code,
             metadata: Some(self.timing.to_proto()),
+            undeclared_inputs: self
+                .undeclared_inputs
+                .iter()
+                .map(|p| p.to_string())
+                .collect(),
         }
     }
 }
@@ -402,6 +410,7 @@ mod tests {
                     time_enabled: 50,
                     time_running: 100,
                 }),
+                ..Default::default()
             }),
             input_materialization_duration: Duration::from_secs(6),
             hashing_duration: Duration::from_secs(7),
@@ -419,6 +428,7 @@ mod tests {
             timing,
             std_streams,
             exit_code: Some(456),
+            undeclared_inputs: Vec::new(),
         }
     }
 
@@ -452,6 +462,7 @@ mod tests {
                 time_enabled: 50,
                 time_running: 100,
             }),
+            ..Default::default()
         };
         let command_execution_metadata = buck2_data::CommandExecutionMetadata {
             wall_time: Some(Duration {
@@ -487,6 +498,7 @@ mod tests {
             stderr: "DEF".to_owned(),
             command_kind: Some(command_execution_kind),
             metadata: Some(command_execution_metadata),
+            undeclared_inputs: Vec::new(),
         };
 
         buck2_data::CommandExecution {
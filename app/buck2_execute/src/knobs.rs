@@ -17,4 +17,15 @@ pub struct ExecutorGlobalKnobs {
     /// Whether to emit action keys to execution logs (thos are pretty verbose and omitted by
     /// default).
     pub log_action_keys: bool,
+
+    /// Run local actions in a sandbox that only exposes their declared inputs and outputs
+    /// (Linux only).
+    pub sandbox_local_actions: bool,
+
+    /// Whether sandboxed local actions can access the network.
+    pub sandbox_allow_network: bool,
+
+    /// Trace the files local actions read, and report the ones that are not declared inputs
+    /// (Linux only, requires `strace`).
+    pub paranoid_inputs: bool,
 }
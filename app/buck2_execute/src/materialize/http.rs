@@ -7,6 +7,7 @@
  * of this source tree.
  */
 
+use std::io::BufRead;
 use std::io::Write;
 use std::sync::Arc;
 use std::time::Duration;
@@ -15,11 +16,14 @@ use allocative::Allocative;
 use anyhow::Context as _;
 use buck2_common::cas_digest::CasDigestConfig;
 use buck2_common::cas_digest::DigestAlgorithmFamily;
+use buck2_common::cas_digest::Digester;
 use buck2_common::cas_digest::SHA1_SIZE;
 use buck2_common::cas_digest::SHA256_SIZE;
 use buck2_common::file_ops::FileDigest;
+use buck2_common::file_ops::FileDigestKind;
 use buck2_common::file_ops::TrackedFileDigest;
 use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
 use buck2_core::fs::project::ProjectRoot;
 use buck2_core::fs::project_rel_path::ProjectRelativePath;
 use buck2_http::retries::http_retry;
@@ -38,6 +42,7 @@ use sha2::Sha256;
 use smallvec::SmallVec;
 
 use crate::digest_config::DigestConfig;
+use crate::execute::blocking::BlockingExecutor;
 
 #[derive(Debug, Clone, Dupe, Allocative)]
 pub enum Checksum {
@@ -258,51 +263,181 @@ pub async fn http_download(
     .await?)
 }
 
-/// Copy a stream into a writer while producing its digest and checksumming it.
-async fn copy_and_hash(
-    url: &str,
-    abs_path: &(impl std::fmt::Display + ?Sized),
-    mut stream: impl Stream<Item = Result<Bytes, hyper::Error>> + Unpin,
-    mut writer: impl Write,
-    digest_config: CasDigestConfig,
+/// Copy a file from a directory of files named by their sha256, checking it like we would check a
+/// download. Returns `None` if the mirror doesn't have the file. The copy streams the file on the
+/// blocking executor.
+pub async fn copy_from_mirror(
+    fs: &ProjectRoot,
+    blocking_executor: &dyn BlockingExecutor,
+    digest_config: DigestConfig,
+    path: &ProjectRelativePath,
+    mirror_path: &AbsNormPath,
     checksum: &Checksum,
-    is_vpnless: bool,
-) -> Result<FileDigest, HttpDownloadError> {
-    let mut digester = FileDigest::digester(digest_config);
+    executable: bool,
+) -> anyhow::Result<Option<TrackedFileDigest>> {
+    let abs_path = fs.resolve(path);
+    let digest = blocking_executor
+        .execute_io_inline(|| {
+            let mut reader = match fs_util::open_file_if_exists(mirror_path)? {
+                Some(file) => std::io::BufReader::new(file),
+                None => return Ok(None),
+            };
+
+            if let Some(dir) = abs_path.parent() {
+                fs_util::create_dir_all(dir)?;
+            }
+            let mut writer = std::io::BufWriter::new(fs_util::create_file(&abs_path)?);
+
+            let mut hasher = ChecksumHasher::new(digest_config.cas_digest_config(), checksum);
+            loop {
+                let chunk = reader
+                    .fill_buf()
+                    .with_context(|| format!("read({})", mirror_path))?;
+                if chunk.is_empty() {
+                    break;
+                }
+                writer
+                    .write_all(chunk)
+                    .with_context(|| format!("write({})", abs_path))?;
+                hasher.update(chunk);
+                let len = chunk.len();
+                reader.consume(len);
+            }
+            writer
+                .flush()
+                .with_context(|| format!("flush({})", abs_path))?;
 
-    // For each checksum entry we have, we're going to add a validator. We might have to create
-    // a new hasher, or reuse the `FileDigest::digester` if it matches.
+            let url = mirror_path.as_path().to_string_lossy();
+            Ok(Some(hasher.finish(&url, &abs_path, false)?))
+        })
+        .await?;
+
+    let Some(digest) = digest else {
+        return Ok(None);
+    };
 
-    enum Validator {
-        PrimaryDigest,
-        ExtraDigest(Box<dyn DynDigest + Send>),
+    if executable {
+        fs.set_executable(path)?;
     }
 
-    let mut validators = SmallVec::<[_; 2]>::new();
+    Ok(Some(TrackedFileDigest::new(
+        digest,
+        digest_config.cas_digest_config(),
+    )))
+}
 
-    if let Some(sha1) = checksum.sha1() {
-        let validator = if digester.algorithm() == DigestAlgorithmFamily::Sha1 {
-            Validator::PrimaryDigest
-        } else {
-            Validator::ExtraDigest(Box::new(Sha1::new()) as _)
-        };
+/// Produces the digest of the data passed to it, and checks it against a `Checksum`.
+struct ChecksumHasher<'a> {
+    digester: Digester<FileDigestKind>,
+    validators: SmallVec<[(Validator, &'a str, &'static str); 2]>,
+}
 
-        validators.push((validator, sha1, "sha1"));
+enum Validator {
+    PrimaryDigest,
+    ExtraDigest(Box<dyn DynDigest + Send>),
+}
+
+impl<'a> ChecksumHasher<'a> {
+    fn new(digest_config: CasDigestConfig, checksum: &'a Checksum) -> Self {
+        let digester = FileDigest::digester(digest_config);
+
+        // For each checksum entry we have, we're going to add a validator. We might have to create
+        // a new hasher, or reuse the `FileDigest::digester` if it matches.
+
+        let mut validators = SmallVec::new();
+
+        if let Some(sha1) = checksum.sha1() {
+            let validator = if digester.algorithm() == DigestAlgorithmFamily::Sha1 {
+                Validator::PrimaryDigest
+            } else {
+                Validator::ExtraDigest(Box::new(Sha1::new()) as _)
+            };
+
+            validators.push((validator, sha1, "sha1"));
+        }
+
+        if let Some(sha256) = checksum.sha256() {
+            let validator = if digester.algorithm() == DigestAlgorithmFamily::Sha256 {
+                Validator::PrimaryDigest
+            } else {
+                Validator::ExtraDigest(Box::new(Sha256::new()) as _)
+            };
+
+            validators.push((validator, sha256, "sha256"));
+        }
+
+        Self {
+            digester,
+            validators,
+        }
     }
 
-    if let Some(sha256) = checksum.sha256() {
-        let validator = if digester.algorithm() == DigestAlgorithmFamily::Sha256 {
-            Validator::PrimaryDigest
-        } else {
-            Validator::ExtraDigest(Box::new(Sha256::new()) as _)
-        };
+    fn bytes_read(&self) -> u64 {
+        self.digester.bytes_read()
+    }
 
-        validators.push((validator, sha256, "sha256"));
+    fn update(&mut self, chunk: &[u8]) {
+        self.digester.update(chunk);
+        for (validator, _expected, _kind) in self.validators.iter_mut() {
+            if let Validator::ExtraDigest(hasher) = validator {
+                hasher.update(chunk);
+            }
+        }
     }
 
+    fn finish(
+        self,
+        url: &str,
+        abs_path: &(impl std::fmt::Display + ?Sized),
+        is_vpnless: bool,
+    ) -> Result<FileDigest, HttpDownloadError> {
+        let digest = self.digester.finalize();
+
+        // Validate
+        for (validator, expected, kind) in self.validators {
+            let obtained = match validator {
+                Validator::PrimaryDigest => digest.raw_digest().to_string(),
+                Validator::ExtraDigest(hasher) => hex::encode(hasher.finalize()),
+            };
+
+            if expected != obtained {
+                if is_vpnless {
+                    return Err(HttpDownloadError::MaybeNotAllowedOnVpnless {
+                        kind,
+                        want: expected.to_owned(),
+                        got: obtained,
+                        url: url.to_owned(),
+                        path: abs_path.to_string(),
+                    });
+                }
+                return Err(HttpDownloadError::InvalidChecksum(
+                    kind,
+                    expected.to_owned(),
+                    obtained,
+                    url.to_owned(),
+                ));
+            }
+        }
+
+        Ok(digest)
+    }
+}
+
+/// Copy a stream into a writer while producing its digest and checksumming it.
+async fn copy_and_hash(
+    url: &str,
+    abs_path: &(impl std::fmt::Display + ?Sized),
+    mut stream: impl Stream<Item = Result<Bytes, hyper::Error>> + Unpin,
+    mut writer: impl Write,
+    digest_config: CasDigestConfig,
+    checksum: &Checksum,
+    is_vpnless: bool,
+) -> Result<FileDigest, HttpDownloadError> {
+    let mut hasher = ChecksumHasher::new(digest_config, checksum);
+
     while let Some(chunk) = stream.next().await {
         let chunk = chunk.map_err(|source| HttpError::Transfer {
-            received: digester.bytes_read(),
+            received: hasher.bytes_read(),
             url: url.to_owned(),
             source,
         })?;
@@ -311,56 +446,26 @@ async fn copy_and_hash(
             .with_context(|| format!("write({})", abs_path))
             .map_err(HttpDownloadError::IoError)?;
 
-        digester.update(&chunk);
-        for (validator, _expected, _kind) in validators.iter_mut() {
-            if let Validator::ExtraDigest(hasher) = validator {
-                hasher.update(&chunk);
-            }
-        }
+        hasher.update(&chunk);
     }
     writer
         .flush()
         .with_context(|| format!("flush({})", abs_path))
         .map_err(HttpDownloadError::IoError)?;
 
-    let digest = digester.finalize();
-
-    // Validate
-    for (validator, expected, kind) in validators {
-        let obtained = match validator {
-            Validator::PrimaryDigest => digest.raw_digest().to_string(),
-            Validator::ExtraDigest(hasher) => hex::encode(hasher.finalize()),
-        };
-
-        if expected != obtained {
-            if is_vpnless {
-                return Err(HttpDownloadError::MaybeNotAllowedOnVpnless {
-                    kind,
-                    want: expected.to_owned(),
-                    got: obtained,
-                    url: url.to_owned(),
-                    path: abs_path.to_string(),
-                });
-            }
-            return Err(HttpDownloadError::InvalidChecksum(
-                kind,
-                expected.to_owned(),
-                obtained,
-                url.to_owned(),
-            ));
-        }
-    }
-
-    Ok(digest)
+    hasher.finish(url, abs_path, is_vpnless)
 }
 
 #[cfg(test)]
 mod tests {
     use assert_matches::assert_matches;
     use buck2_common::cas_digest::testing;
+    use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+    use buck2_core::fs::project::ProjectRootTemp;
     use futures::stream;
 
     use super::*;
+    use crate::execute::blocking::testing::DummyBlockingExecutor;
 
     async fn do_test(
         digest_config: CasDigestConfig,
@@ -434,4 +539,60 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_copy_from_mirror() -> anyhow::Result<()> {
+        let fs = ProjectRootTemp::new()?;
+        let executor = DummyBlockingExecutor {
+            fs: fs.path().dupe(),
+        };
+        let mirror_path = fs.path().root().join(ForwardRelativePath::new("mirror")?);
+        let out = ProjectRelativePath::new("out/file")?;
+        let checksum = Checksum::Sha1(Arc::from("8843d7f92416211de9ebb963ff4ce28125932878"));
+
+        assert_matches!(
+            copy_from_mirror(
+                fs.path(),
+                &executor,
+                DigestConfig::testing_default(),
+                out,
+                &mirror_path,
+                &checksum,
+                false,
+            )
+            .await,
+            Ok(None)
+        );
+
+        fs_util::write(&mirror_path, "foobar")?;
+        let digest = copy_from_mirror(
+            fs.path(),
+            &executor,
+            DigestConfig::testing_default(),
+            out,
+            &mirror_path,
+            &checksum,
+            false,
+        )
+        .await?;
+        assert_eq!(digest.map(|d| d.size()), Some(6));
+        assert_eq!(fs_util::read_to_string(fs.path().resolve(out))?, "foobar");
+
+        fs_util::write(&mirror_path, "oops")?;
+        assert!(
+            copy_from_mirror(
+                fs.path(),
+                &executor,
+                DigestConfig::testing_default(),
+                out,
+                &mirror_path,
+                &checksum,
+                false,
+            )
+            .await
+            .is_err()
+        );
+
+        Ok(())
+    }
 }
@@ -229,6 +229,16 @@ pub trait Materializer: Allocative + Send + Sync + 'static {
         artifact_paths: Vec<ProjectRelativePathBuf>,
     ) -> anyhow::Result<BoxStream<'static, Result<(), MaterializationError>>>;
 
+    /// Like `materialize_many`, but artifacts that would have to be fetched over the network (from
+    /// the CAS or via HTTP) fail to materialize instead. Used for commands running with
+    /// `--offline`. Materializers that never fetch anything lazily don't need to override this.
+    async fn materialize_many_offline(
+        &self,
+        artifact_paths: Vec<ProjectRelativePathBuf>,
+    ) -> anyhow::Result<BoxStream<'static, Result<(), MaterializationError>>> {
+        self.materialize_many(artifact_paths).await
+    }
+
     /// Given a list of artifact paths, blocks until all previously declared
     /// artifacts on that list are materialized. An [`Err`] is returned if the
     /// materialization fails for one or more of these paths.
@@ -262,6 +272,15 @@ pub trait Materializer: Allocative + Send + Sync + 'static {
         artifact_path: ProjectRelativePathBuf,
     ) -> anyhow::Result<bool>;
 
+    /// Like `try_materialize_final_artifact`, but without fetching anything over the network, like
+    /// `materialize_many_offline`.
+    async fn try_materialize_final_artifact_offline(
+        &self,
+        artifact_path: ProjectRelativePathBuf,
+    ) -> anyhow::Result<bool> {
+        self.try_materialize_final_artifact(artifact_path).await
+    }
+
     /// Given a `file_path` whose contents we are interested in, *tries* to
     /// find a materialized path with the same contents. It returns [`None`] if
     /// the path leads to a file that needs to be fetched from the CAS.
@@ -541,7 +560,7 @@ impl CasDownloadInfo {
 #[derive(Debug, Display)]
 #[display("{} declared by {}", self.url, self.owner)]
 pub struct HttpDownloadInfo {
-    /// URL to download the file from.
+    /// URL to download the file from. `http.url_rewrites` have already been applied to it.
     pub url: Arc<str>,
 
     /// Size, whether the file is executable. Also contains a digest, which is a bit of a shame
@@ -670,6 +689,38 @@ pub trait DeferredMaterializerSubscription: Send + Sync {
     async fn next_materialization(&mut self) -> Option<ProjectRelativePathBuf>;
 }
 
+#[derive(Clone, Copy, Debug, Dupe, Display, PartialEq, Eq)]
+pub enum MaterializerVerifyIssueKind {
+    /// The artifact is in the materializer state but not on disk.
+    #[display("missing")]
+    Missing,
+    /// The artifact on disk does not match the materializer state.
+    #[display("modified")]
+    Modified,
+    /// The path is in `buck-out` but not in the materializer state.
+    #[display("untracked")]
+    Untracked,
+}
+
+#[derive(Debug)]
+pub struct MaterializerVerifyIssue {
+    pub path: ProjectRelativePathBuf,
+    pub kind: MaterializerVerifyIssueKind,
+    pub detail: String,
+    /// Whether the artifact was declared by the running daemon. Such artifacts are only rebuilt
+    /// after the daemon restarts, since DICE still holds their value.
+    pub active: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct MaterializerVerifyReport {
+    /// Number of materialized artifacts that were checked.
+    pub checked: u64,
+    pub issues: Vec<MaterializerVerifyIssue>,
+    /// Number of artifacts that were invalidated.
+    pub repaired: u64,
+}
+
 /// Extensions to the Materializer trait that are only available in the Deferred materializer.
 #[async_trait]
 pub trait DeferredMaterializerExtensions: Send + Sync {
@@ -681,6 +732,11 @@ pub trait DeferredMaterializerExtensions: Send + Sync {
     /// all discrepancies.
     fn fsck(&self) -> anyhow::Result<BoxStream<'static, (ProjectRelativePathBuf, anyhow::Error)>>;
 
+    /// Re-hash every materialized artifact and compare it to the materializer state, and look for
+    /// untracked files in `buck-out`. If `repair` is set, artifacts that are missing or modified
+    /// are invalidated, so that they get rematerialized or rebuilt when next needed.
+    async fn verify(&self, repair: bool) -> anyhow::Result<MaterializerVerifyReport>;
+
     async fn refresh_ttls(&self, min_ttl: i64) -> anyhow::Result<()>;
 
     async fn get_ttl_refresh_log(&self) -> anyhow::Result<String>;
@@ -0,0 +1,161 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::sync::Arc;
+
+use allocative::Allocative;
+use async_trait::async_trait;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+use buck2_events::dispatch::EventDispatcher;
+use buck2_futures::cancellation::CancellationContext;
+use futures::stream::BoxStream;
+
+use crate::artifact_value::ArtifactValue;
+use crate::materialize::materializer::ArtifactNotMaterializedReason;
+use crate::materialize::materializer::CasDownloadInfo;
+use crate::materialize::materializer::CopiedArtifact;
+use crate::materialize::materializer::DeclareMatchOutcome;
+use crate::materialize::materializer::DeferredMaterializerExtensions;
+use crate::materialize::materializer::HttpDownloadInfo;
+use crate::materialize::materializer::MaterializationError;
+use crate::materialize::materializer::Materializer;
+use crate::materialize::materializer::WriteRequest;
+
+/// The materializer of commands running with `--offline`: it shares the daemon's materializer
+/// state, but materializes through `materialize_many_offline`, so that artifacts declared by
+/// earlier commands, that would have to be fetched over the network, fail to materialize instead.
+#[derive(Allocative)]
+pub struct OfflineMaterializer {
+    inner: Arc<dyn Materializer>,
+}
+
+impl OfflineMaterializer {
+    pub fn new(inner: Arc<dyn Materializer>) -> Self {
+        Self { inner }
+    }
+}
+
+#[async_trait]
+impl Materializer for OfflineMaterializer {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    async fn declare_existing(
+        &self,
+        artifacts: Vec<(ProjectRelativePathBuf, ArtifactValue)>,
+    ) -> anyhow::Result<()> {
+        self.inner.declare_existing(artifacts).await
+    }
+
+    async fn declare_copy_impl(
+        &self,
+        path: ProjectRelativePathBuf,
+        value: ArtifactValue,
+        srcs: Vec<CopiedArtifact>,
+        cancellations: &CancellationContext,
+    ) -> anyhow::Result<()> {
+        self.inner
+            .declare_copy_impl(path, value, srcs, cancellations)
+            .await
+    }
+
+    async fn declare_cas_many_impl<'a, 'b>(
+        &self,
+        info: Arc<CasDownloadInfo>,
+        artifacts: Vec<(ProjectRelativePathBuf, ArtifactValue)>,
+        cancellations: &CancellationContext,
+    ) -> anyhow::Result<()> {
+        self.inner
+            .declare_cas_many_impl(info, artifacts, cancellations)
+            .await
+    }
+
+    async fn declare_http(
+        &self,
+        path: ProjectRelativePathBuf,
+        info: HttpDownloadInfo,
+        cancellations: &CancellationContext,
+    ) -> anyhow::Result<()> {
+        self.inner.declare_http(path, info, cancellations).await
+    }
+
+    async fn declare_write<'a>(
+        &self,
+        gen: Box<dyn FnOnce() -> anyhow::Result<Vec<WriteRequest>> + Send + 'a>,
+    ) -> anyhow::Result<Vec<ArtifactValue>> {
+        self.inner.declare_write(gen).await
+    }
+
+    async fn declare_match(
+        &self,
+        artifacts: Vec<(ProjectRelativePathBuf, ArtifactValue)>,
+    ) -> anyhow::Result<DeclareMatchOutcome> {
+        self.inner.declare_match(artifacts).await
+    }
+
+    async fn has_artifact_at(&self, path: ProjectRelativePathBuf) -> anyhow::Result<bool> {
+        self.inner.has_artifact_at(path).await
+    }
+
+    async fn invalidate_many(&self, paths: Vec<ProjectRelativePathBuf>) -> anyhow::Result<()> {
+        self.inner.invalidate_many(paths).await
+    }
+
+    async fn materialize_many(
+        &self,
+        artifact_paths: Vec<ProjectRelativePathBuf>,
+    ) -> anyhow::Result<BoxStream<'static, Result<(), MaterializationError>>> {
+        self.inner.materialize_many_offline(artifact_paths).await
+    }
+
+    async fn materialize_many_offline(
+        &self,
+        artifact_paths: Vec<ProjectRelativePathBuf>,
+    ) -> anyhow::Result<BoxStream<'static, Result<(), MaterializationError>>> {
+        self.inner.materialize_many_offline(artifact_paths).await
+    }
+
+    async fn try_materialize_final_artifact(
+        &self,
+        artifact_path: ProjectRelativePathBuf,
+    ) -> anyhow::Result<bool> {
+        self.inner
+            .try_materialize_final_artifact_offline(artifact_path)
+            .await
+    }
+
+    async fn try_materialize_final_artifact_offline(
+        &self,
+        artifact_path: ProjectRelativePathBuf,
+    ) -> anyhow::Result<bool> {
+        self.inner
+            .try_materialize_final_artifact_offline(artifact_path)
+            .await
+    }
+
+    async fn get_materialized_file_paths(
+        &self,
+        file_paths: Vec<ProjectRelativePathBuf>,
+    ) -> anyhow::Result<Vec<Result<ProjectRelativePathBuf, ArtifactNotMaterializedReason>>> {
+        self.inner.get_materialized_file_paths(file_paths).await
+    }
+
+    fn as_deferred_materializer_extension(&self) -> Option<&dyn DeferredMaterializerExtensions> {
+        self.inner.as_deferred_materializer_extension()
+    }
+
+    fn log_materializer_state(&self, events: &EventDispatcher) {
+        self.inner.log_materializer_state(events)
+    }
+
+    fn add_snapshot_stats(&self, snapshot: &mut buck2_data::Snapshot) {
+        self.inner.add_snapshot_stats(snapshot)
+    }
+}
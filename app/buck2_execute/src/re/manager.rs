@@ -179,6 +179,13 @@ impl LazyRemoteExecutionClient {
     }
 }
 
+#[derive(Debug, buck2_error::Error)]
+#[buck2(input)]
+#[error(
+    "Refusing to use remote execution or the remote cache: network access is disabled by `--offline`"
+)]
+struct OfflineError;
+
 /// The main manager for the RE connections
 #[derive(Allocative)]
 pub struct ReConnectionManager {
@@ -281,6 +288,8 @@ pub struct ReConnectionHandle {
     // after that command ended. An alternative would be to register/deregister the connection
     // handle itself as an observer on the lazy client, but that doesn't seem any simpler.
     observer: Option<Arc<dyn ReConnectionObserver>>,
+    /// Clients from this handle refuse to do anything, so we never connect.
+    offline: bool,
 }
 
 impl ReConnectionHandle {
@@ -288,9 +297,15 @@ impl ReConnectionHandle {
         Self {
             connection: Arc::new(connection),
             observer: None,
+            offline: false,
         }
     }
 
+    /// Make clients obtained from this handle fail all operations.
+    pub fn set_offline(&mut self) {
+        self.offline = true;
+    }
+
     /// Sets the connection observer. This will drop the previous observer if there is one.
     pub fn set_observer(&mut self, observer: Arc<dyn ReConnectionObserver>) {
         // We store it just to give it this handle's lifetime.
@@ -303,6 +318,7 @@ impl ReConnectionHandle {
         ManagedRemoteExecutionClient {
             data: Arc::downgrade(&self.connection),
             re_use_case_override: None,
+            offline: self.offline,
         }
     }
 }
@@ -311,6 +327,7 @@ impl ReConnectionHandle {
 pub struct ManagedRemoteExecutionClient {
     data: Weak<Arc<LazyRemoteExecutionClient>>,
     re_use_case_override: Option<RemoteExecutorUseCase>,
+    offline: bool,
 }
 
 impl ManagedRemoteExecutionClient {
@@ -320,6 +337,9 @@ impl ManagedRemoteExecutionClient {
     }
 
     fn lock(&self) -> anyhow::Result<Arc<Arc<LazyRemoteExecutionClient>>> {
+        if self.offline {
+            return Err(OfflineError.into());
+        }
         self.data
             .upgrade()
             .context("Internal error: the underlying RE connection has terminated because the corresponding guard has been dropped.")
@@ -536,6 +556,7 @@ impl ManagedRemoteExecutionClient {
         Self {
             data: Weak::new(),
             re_use_case_override: None,
+            offline: false,
         }
     }
 }
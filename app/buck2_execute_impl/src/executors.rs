@@ -9,11 +9,17 @@
 
 pub mod action_cache;
 pub mod action_cache_upload_permission_checker;
+pub(crate) mod bazel_worker;
 pub mod caching;
 pub(crate) mod empty_action_result;
 pub mod hybrid;
+pub mod hybrid_routing;
 pub mod local;
+pub mod local_input_tracer;
+pub mod local_resources;
+pub(crate) mod local_sandbox;
 pub mod re;
 pub mod stacked;
+pub(crate) mod strace;
 pub mod to_re_platform;
 pub mod worker;
@@ -0,0 +1,455 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Bazel persistent workers (`WorkerInfo(protocol = "bazel_json" | "bazel_proto")`).
+//!
+//! The worker is started with `--persistent_worker`, reads `WorkRequest`s on stdin and writes
+//! `WorkResponse`s on stdout, either as newline-delimited JSON or as length-delimited protobuf.
+//! Singleplex workers handle one request at a time, multiplex workers match responses to
+//! requests by `request_id`.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::process::Stdio;
+use std::sync::atomic::AtomicI32;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context as _;
+use buck2_common::liveliness_observer::LivelinessObserver;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_execute::execute::request::BazelWorkerEncoding;
+use buck2_forkserver::run::GatherOutputStatus;
+use buck2_util::process::background_command;
+use buck2_worker_proto::bazel::WorkRequest;
+use buck2_worker_proto::bazel::WorkResponse;
+use prost::Message;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::io::AsyncBufRead;
+use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::io::BufReader;
+use tokio::process::ChildStdin;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use crate::executors::local::apply_local_execution_environment;
+
+/// Argument that Bazel workers expect when they are started as persistent workers.
+const PERSISTENT_WORKER_ARG: &str = "--persistent_worker";
+
+#[derive(Debug, buck2_error::Error)]
+enum BazelWorkerError {
+    #[error("Worker closed its stdout, see worker logs: {0}")]
+    Exited(AbsNormPathBuf),
+    #[error("Invalid response from worker: {0}")]
+    InvalidResponse(String),
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct JsonWorkRequest<'a> {
+    arguments: &'a [String],
+    request_id: i32,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    cancel: bool,
+}
+
+/// Bazel writes proto3 JSON, which uses camelCase names, but parsers accept the original field
+/// names too, and some workers rely on that.
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct JsonWorkResponse {
+    #[serde(alias = "exit_code")]
+    exit_code: i32,
+    output: String,
+    #[serde(alias = "request_id")]
+    request_id: i32,
+    #[serde(alias = "was_cancelled")]
+    was_cancelled: bool,
+}
+
+fn encode_request(encoding: BazelWorkerEncoding, request: &WorkRequest) -> anyhow::Result<Vec<u8>> {
+    Ok(match encoding {
+        BazelWorkerEncoding::Json => {
+            let mut buf = serde_json::to_vec(&JsonWorkRequest {
+                arguments: &request.arguments,
+                request_id: request.request_id,
+                cancel: request.cancel,
+            })?;
+            buf.push(b'\n');
+            buf
+        }
+        BazelWorkerEncoding::Proto => request.encode_length_delimited_to_vec(),
+    })
+}
+
+/// Reads the next response, or `None` if the worker closed its stdout.
+async fn read_response(
+    encoding: BazelWorkerEncoding,
+    reader: &mut (impl AsyncBufRead + Unpin),
+) -> anyhow::Result<Option<WorkResponse>> {
+    match encoding {
+        BazelWorkerEncoding::Json => loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).await? == 0 {
+                return Ok(None);
+            }
+            // Workers are allowed to separate responses with blank lines.
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response: JsonWorkResponse = serde_json::from_str(&line).map_err(|e| {
+                BazelWorkerError::InvalidResponse(format!("{}: {}", e, line.trim()))
+            })?;
+            return Ok(Some(WorkResponse {
+                exit_code: response.exit_code,
+                output: response.output,
+                request_id: response.request_id,
+                was_cancelled: response.was_cancelled,
+            }));
+        },
+        BazelWorkerEncoding::Proto => {
+            let mut len: u64 = 0;
+            for shift in (0..64).step_by(7) {
+                let byte = match reader.read_u8().await {
+                    Ok(byte) => byte,
+                    Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof && shift == 0 => {
+                        return Ok(None);
+                    }
+                    Err(e) => return Err(e.into()),
+                };
+                len |= u64::from(byte & 0x7f) << shift;
+                if byte & 0x80 == 0 {
+                    let mut buf = vec![0; len.try_into()?];
+                    reader.read_exact(&mut buf).await?;
+                    return Ok(Some(
+                        WorkResponse::decode(buf.as_slice())
+                            .map_err(|e| BazelWorkerError::InvalidResponse(e.to_string()))?,
+                    ));
+                }
+            }
+            Err(BazelWorkerError::InvalidResponse("length prefix is too long".to_owned()).into())
+        }
+    }
+}
+
+/// Returns the flagfile passed as the last argument, if any. Like Bazel, we only look at the last
+/// argument, everything before it is passed through.
+fn flagfile(arg: &str) -> Option<&str> {
+    arg.strip_prefix("--flagfile=")
+        .or_else(|| arg.strip_prefix("-flagfile="))
+        .or_else(|| arg.strip_prefix('@').filter(|f| !f.starts_with('@')))
+}
+
+/// Workers expect the contents of the flagfile in the request instead of the flagfile itself.
+fn expand_flagfile(args: &[String], root: &AbsNormPathBuf) -> anyhow::Result<Vec<String>> {
+    let Some((last, rest)) = args.split_last() else {
+        return Ok(Vec::new());
+    };
+    let Some(path) = flagfile(last) else {
+        return Ok(args.to_vec());
+    };
+    let contents = fs_util::read_to_string(root.as_abs_path().join(path))
+        .with_context(|| format!("Error reading worker flagfile `{}`", path))?;
+    Ok(rest
+        .iter()
+        .cloned()
+        .chain(contents.lines().map(|l| l.to_owned()))
+        .collect())
+}
+
+type PendingResponses = Arc<parking_lot::Mutex<HashMap<i32, oneshot::Sender<WorkResponse>>>>;
+
+pub(crate) struct BazelWorker {
+    encoding: BazelWorkerEncoding,
+    multiplex: bool,
+    supports_cancellation: bool,
+    root: AbsNormPathBuf,
+    stderr_path: AbsNormPathBuf,
+    stdin: tokio::sync::Mutex<ChildStdin>,
+    pending: PendingResponses,
+    next_request_id: AtomicI32,
+    /// Held for the duration of a request on singleplex workers.
+    singleplex: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl BazelWorker {
+    /// Starts the worker. The returned handle completes when the worker exits, it is killed once
+    /// `liveliness_observer` is dead.
+    pub(crate) fn spawn(
+        exe: &[String],
+        env: Vec<(OsString, OsString)>,
+        root: &AbsNormPathBuf,
+        encoding: BazelWorkerEncoding,
+        multiplex: bool,
+        supports_cancellation: bool,
+        stderr_path: &AbsNormPathBuf,
+        liveliness_observer: impl LivelinessObserver + 'static,
+    ) -> anyhow::Result<(Self, JoinHandle<anyhow::Result<GatherOutputStatus>>)> {
+        let mut cmd = background_command(&exe[0]);
+        cmd.args(&exe[1..])
+            .arg(PERSISTENT_WORKER_ARG)
+            .current_dir(root.as_path())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(
+                std::fs::File::create(stderr_path.as_path())
+                    .with_context(|| format!("Error creating `{}`", stderr_path))?,
+            );
+        apply_local_execution_environment(&mut cmd, root, env, None);
+        let mut cmd = tokio::process::Command::from(cmd);
+        cmd.kill_on_drop(true);
+
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("Error spawning `{}`", exe[0]))?;
+        let stdin = child.stdin.take().context("Worker stdin is not piped")?;
+        let stdout = child.stdout.take().context("Worker stdout is not piped")?;
+
+        let pending = PendingResponses::default();
+        tokio::spawn({
+            let pending = pending.clone();
+            async move {
+                let mut reader = BufReader::new(stdout);
+                loop {
+                    match read_response(encoding, &mut reader).await {
+                        Ok(Some(response)) => {
+                            if let Some(tx) = pending.lock().remove(&response.request_id) {
+                                drop(tx.send(response));
+                            } else {
+                                tracing::warn!(
+                                    "Worker response for unknown request {}",
+                                    response.request_id
+                                );
+                            }
+                        }
+                        Ok(None) => break,
+                        Err(e) => {
+                            tracing::warn!("Error reading worker response: {:#}", e);
+                            break;
+                        }
+                    }
+                }
+                // Fail the requests still waiting for a response.
+                pending.lock().clear();
+            }
+        });
+
+        let exit = tokio::spawn(async move {
+            tokio::select! {
+                status = child.wait() => {
+                    let status = status?;
+                    Ok(GatherOutputStatus::Finished {
+                        exit_code: status.code().unwrap_or(-1),
+                        execution_stats: None,
+                    })
+                }
+                _ = liveliness_observer.while_alive() => {
+                    child.kill().await?;
+                    Ok(GatherOutputStatus::Cancelled)
+                }
+            }
+        });
+
+        Ok((
+            Self {
+                encoding,
+                multiplex,
+                supports_cancellation,
+                root: root.clone(),
+                stderr_path: stderr_path.clone(),
+                stdin: tokio::sync::Mutex::new(stdin),
+                pending,
+                next_request_id: AtomicI32::new(1),
+                singleplex: Arc::new(tokio::sync::Mutex::new(())),
+            },
+            exit,
+        ))
+    }
+
+    /// Sends a request and waits for its response. The `output` of the response is returned as
+    /// stderr, since that is what workers use it for.
+    pub(crate) async fn exec(
+        &self,
+        args: &[String],
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<(GatherOutputStatus, Vec<u8>)> {
+        let guard = if self.multiplex {
+            None
+        } else {
+            Some(self.singleplex.clone().lock_owned().await)
+        };
+        let request_id = if self.multiplex {
+            self.next_request_id.fetch_add(1, Ordering::Relaxed)
+        } else {
+            0
+        };
+        let request = WorkRequest {
+            arguments: expand_flagfile(args, &self.root)?,
+            request_id,
+            ..Default::default()
+        };
+
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().insert(request_id, tx);
+        if let Err(e) = self.send(&request).await {
+            self.pending.lock().remove(&request_id);
+            return Err(e);
+        }
+
+        let response = match timeout {
+            None => rx.await,
+            Some(timeout) => {
+                tokio::pin!(rx);
+                match tokio::time::timeout(timeout, &mut rx).await {
+                    Ok(response) => response,
+                    Err(_) => {
+                        if self.supports_cancellation {
+                            let cancel = WorkRequest {
+                                request_id,
+                                cancel: true,
+                                ..Default::default()
+                            };
+                            if let Err(e) = self.send(&cancel).await {
+                                tracing::warn!(
+                                    "Error cancelling worker request {}: {:#}",
+                                    request_id,
+                                    e
+                                );
+                            }
+                        }
+                        // The worker is still busy with the request, or cancelling it. A
+                        // singleplex worker can't take another one until it responds.
+                        tokio::spawn(async move {
+                            drop(rx.await);
+                            drop(guard);
+                        });
+                        return Ok((GatherOutputStatus::TimedOut(timeout), Vec::new()));
+                    }
+                }
+            }
+        };
+        let response = response.map_err(|_| BazelWorkerError::Exited(self.stderr_path.clone()))?;
+        tracing::info!(
+            "Worker response for request {}: exit code {}",
+            response.request_id,
+            response.exit_code
+        );
+
+        Ok((
+            GatherOutputStatus::Finished {
+                exit_code: response.exit_code,
+                execution_stats: None,
+            },
+            response.output.into_bytes(),
+        ))
+    }
+
+    async fn send(&self, request: &WorkRequest) -> anyhow::Result<()> {
+        let encoded = encode_request(self.encoding, request)?;
+        let mut stdin = self.stdin.lock().await;
+        let written = async {
+            stdin.write_all(&encoded).await?;
+            stdin.flush().await
+        };
+        written.await.map_err(|e| {
+            anyhow::Error::from(e).context(format!(
+                "Error sending request to worker, see worker logs: {}",
+                self.stderr_path
+            ))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_json_round_trip() -> anyhow::Result<()> {
+        let request = WorkRequest {
+            arguments: vec!["-c".to_owned(), "foo.java".to_owned()],
+            request_id: 3,
+            ..Default::default()
+        };
+        assert_eq!(
+            String::from_utf8(encode_request(BazelWorkerEncoding::Json, &request)?)?,
+            "{\"arguments\":[\"-c\",\"foo.java\"],\"requestId\":3}\n"
+        );
+        let cancel = WorkRequest {
+            request_id: 3,
+            cancel: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            String::from_utf8(encode_request(BazelWorkerEncoding::Json, &cancel)?)?,
+            "{\"arguments\":[],\"requestId\":3,\"cancel\":true}\n"
+        );
+
+        let mut responses: &[u8] =
+            b"\n{\"exitCode\":1,\"output\":\"error\",\"requestId\":3}\n{\"request_id\":4}\n";
+        let response = read_response(BazelWorkerEncoding::Json, &mut responses)
+            .await?
+            .unwrap();
+        assert_eq!(response.exit_code, 1);
+        assert_eq!(response.output, "error");
+        assert_eq!(response.request_id, 3);
+        let response = read_response(BazelWorkerEncoding::Json, &mut responses)
+            .await?
+            .unwrap();
+        assert_eq!(response.exit_code, 0);
+        assert_eq!(response.request_id, 4);
+        assert!(
+            read_response(BazelWorkerEncoding::Json, &mut responses)
+                .await?
+                .is_none()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_proto_round_trip() -> anyhow::Result<()> {
+        let response = WorkResponse {
+            exit_code: 2,
+            output: "x".repeat(200),
+            request_id: 7,
+            was_cancelled: false,
+        };
+        let mut buf = response.encode_length_delimited_to_vec();
+        buf.extend(WorkResponse::default().encode_length_delimited_to_vec());
+        let mut reader = buf.as_slice();
+        assert_eq!(
+            read_response(BazelWorkerEncoding::Proto, &mut reader).await?,
+            Some(response)
+        );
+        assert_eq!(
+            read_response(BazelWorkerEncoding::Proto, &mut reader).await?,
+            Some(WorkResponse::default())
+        );
+        assert_eq!(
+            read_response(BazelWorkerEncoding::Proto, &mut reader).await?,
+            None
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_flagfile() {
+        assert_eq!(flagfile("@args.txt"), Some("args.txt"));
+        assert_eq!(flagfile("--flagfile=args.txt"), Some("args.txt"));
+        assert_eq!(flagfile("-flagfile=args.txt"), Some("args.txt"));
+        assert_eq!(flagfile("@@escaped"), None);
+        assert_eq!(flagfile("args.txt"), None);
+    }
+}
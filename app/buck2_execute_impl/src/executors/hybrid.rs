@@ -22,6 +22,7 @@ use buck2_events::dispatch::EventDispatcher;
 use buck2_execute::execute::claim::Claim;
 use buck2_execute::execute::claim::ClaimManager;
 use buck2_execute::execute::claim::MutexClaimManager;
+use buck2_execute::execute::kind::CommandExecutionKind;
 use buck2_execute::execute::manager::CommandExecutionManager;
 use buck2_execute::execute::manager::CommandExecutionManagerExt;
 use buck2_execute::execute::prepared::PreparedCommand;
@@ -40,6 +41,8 @@ use futures::future::Future;
 use futures::FutureExt;
 use host_sharing::HostSharingRequirements;
 
+use crate::executors::hybrid_routing::HybridRouter;
+use crate::executors::hybrid_routing::RoutedExecutor;
 use crate::executors::local::LocalExecutor;
 use crate::low_pass_filter::LowPassFilter;
 
@@ -57,6 +60,9 @@ pub struct HybridExecutor<R> {
     pub low_pass_filter: Arc<LowPassFilter>,
     pub re_max_input_files_bytes: u64,
     pub fallback_tracker: Arc<FallbackTracker>,
+    /// If set, decides where to run actions without an executor preference, based on how long
+    /// they took in the past.
+    pub router: Option<Arc<HybridRouter>>,
 }
 
 impl<R> HybridExecutor<R>
@@ -107,6 +113,64 @@ where
     fn is_action_too_large_for_remote(&self, paths: &CommandExecutionPaths) -> bool {
         paths.input_files_bytes() > self.re_max_input_files_bytes
     }
+
+    /// Turn the router's decision into an executor preference, and explain it in an event.
+    fn route(
+        &self,
+        router: &HybridRouter,
+        command: &PreparedCommand<'_, '_>,
+        events: &EventDispatcher,
+    ) -> ExecutorPreference {
+        let name = command.target.as_proto_action_name();
+        let input_bytes = command.request.paths().input_files_bytes();
+        let broker = &self.local.host_sharing_broker;
+        let queue_depth = broker.queue_depth();
+
+        let decision = router.decide(
+            &command.target.re_action_key(),
+            &name,
+            input_bytes,
+            queue_depth,
+            broker.num_machine_permits(),
+        );
+        events.instant_event(decision.to_proto(name, input_bytes, queue_depth));
+
+        match decision.executor {
+            None => ExecutorPreference::Default,
+            Some(RoutedExecutor::Local) => ExecutorPreference::LocalPreferred,
+            Some(RoutedExecutor::Remote) => ExecutorPreference::RemotePreferred,
+        }
+    }
+
+    /// Learn from how long the command took on the executor that ran it.
+    fn record(
+        &self,
+        router: &HybridRouter,
+        command: &PreparedCommand<'_, '_>,
+        res: &CommandExecutionResult,
+    ) {
+        let executor = match &res.report.status {
+            CommandExecutionStatus::Success {
+                execution_kind: CommandExecutionKind::Local { .. },
+            } => RoutedExecutor::Local,
+            CommandExecutionStatus::Success {
+                execution_kind: CommandExecutionKind::Remote { .. },
+            } => RoutedExecutor::Remote,
+            // Failures and cache hits say nothing about how long running the command takes.
+            _ => return,
+        };
+        let timing = &res.report.timing;
+        let duration = timing
+            .wall_time
+            .saturating_sub(timing.queue_duration.unwrap_or_default());
+        router.record(
+            &command.target.re_action_key(),
+            &command.target.as_proto_action_name(),
+            executor,
+            duration,
+            command.request.paths().input_files_bytes(),
+        );
+    }
 }
 
 #[async_trait]
@@ -200,6 +264,21 @@ where
             return remote_result.await;
         }
 
+        // Only route actions that would otherwise race, the other levels are explicit about what
+        // should run where.
+        let router = match (&self.router, self.level) {
+            (Some(router), HybridExecutionLevel::Full { .. })
+                if matches!(executor_preference, ExecutorPreference::Default) =>
+            {
+                Some(router)
+            }
+            _ => None,
+        };
+        let executor_preference = match router {
+            Some(router) => self.route(router, command, &manager.inner.events),
+            None => executor_preference,
+        };
+
         let jobs = HybridExecutorJobs {
             local: local_result.map(|r| (r, JobPriority(1))),
             remote: remote_result.map(|r| (r, JobPriority(0))),
@@ -332,6 +411,10 @@ where
             first_res
         };
 
+        if let Some(router) = &self.router {
+            self.record(router, command, &res);
+        }
+
         res.eligible_for_full_hybrid = !fallback_only;
         res
     }
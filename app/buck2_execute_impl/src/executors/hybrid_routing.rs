@@ -0,0 +1,772 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Routing of actions between the local and remote side of a `HybridExecutor`, based on how long
+//! the same action, or actions of the same category, took on either side in the past.
+
+use std::collections::HashMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use anyhow::Context;
+use buck2_common::sqlite::VersionedSqliteDb;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use itertools::Itertools;
+use parking_lot::Mutex;
+use parking_lot::RwLock;
+use rusqlite::Connection;
+
+/// Bump this if the schema of the db changes, the existing stats will be dropped.
+const DB_SCHEMA_VERSION: u64 = 2;
+
+const DURATIONS_TABLE_NAME: &str = "hybrid_routing_durations";
+
+/// How many runs on an executor we need before trusting its estimate.
+const MIN_SAMPLES: u32 = 3;
+
+/// Weight of the latest run in the moving average.
+const ALPHA: f64 = 0.25;
+
+/// How much faster one side has to be expected to be for us to not race both.
+const MARGIN: f64 = 1.5;
+
+/// The bandwidth we assume when estimating how long uploading an action's inputs takes, i.e. 25
+/// MiB/s. Remote durations are recorded with that estimate taken out, so that they can be reused
+/// for the same action with inputs of a different size.
+const UPLOAD_BYTES_PER_MS: u64 = 25 * 1024 * 1024 / 1000;
+
+/// Every so many decisions, run the action on the side we expect to be slower. We only learn about
+/// the executor that actually ran the action, and things change (e.g. RE gets faster workers).
+const EXPLORE_EVERY: u64 = 20;
+
+/// How many actions we keep durations for. Past that, the least recently run ones are forgotten.
+const MAX_ENTRIES: usize = 100_000;
+
+/// Durations of actions that haven't run for that long are dropped when loading the db.
+const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Exponential moving average of the durations of an action on one executor.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct MovingAverage {
+    mean_ms: f64,
+    samples: u32,
+}
+
+impl MovingAverage {
+    fn record(&mut self, ms: f64) {
+        if self.samples == 0 {
+            self.mean_ms = ms;
+        } else {
+            self.mean_ms += ALPHA * (ms - self.mean_ms);
+        }
+        self.samples = self.samples.saturating_add(1);
+    }
+
+    fn estimate(&self) -> Option<f64> {
+        (self.samples >= MIN_SAMPLES).then_some(self.mean_ms)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct ExecutorDurations {
+    local: MovingAverage,
+    remote: MovingAverage,
+}
+
+/// What we persist about an action.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct StoredDurations {
+    durations: ExecutorDurations,
+    /// When the action last ran, in seconds since the epoch, for eviction.
+    last_used_secs: u64,
+}
+
+enum DbUpdate {
+    Insert(String, StoredDurations),
+    Delete(Vec<String>),
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoutedExecutor {
+    Local,
+    Remote,
+}
+
+impl RoutedExecutor {
+    fn other(self) -> Self {
+        match self {
+            RoutedExecutor::Local => RoutedExecutor::Remote,
+            RoutedExecutor::Remote => RoutedExecutor::Local,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            RoutedExecutor::Local => "locally",
+            RoutedExecutor::Remote => "remotely",
+        }
+    }
+}
+
+/// Where to run an action, and why.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoutingDecision {
+    /// `None` means race both executors.
+    pub executor: Option<RoutedExecutor>,
+    pub reason: String,
+    pub local_estimate_ms: Option<u64>,
+    pub remote_estimate_ms: Option<u64>,
+}
+
+impl RoutingDecision {
+    pub fn to_proto(
+        &self,
+        name: buck2_data::ActionName,
+        input_bytes: u64,
+        local_queue_depth: usize,
+    ) -> buck2_data::HybridRoutingDecision {
+        use buck2_data::hybrid_routing_decision::Executor;
+
+        let executor = match self.executor {
+            None => Executor::Race,
+            Some(RoutedExecutor::Local) => Executor::Local,
+            Some(RoutedExecutor::Remote) => Executor::Remote,
+        };
+
+        buck2_data::HybridRoutingDecision {
+            name: Some(name),
+            executor: executor as i32,
+            reason: self.reason.clone(),
+            local_estimate_ms: self.local_estimate_ms,
+            remote_estimate_ms: self.remote_estimate_ms,
+            input_bytes,
+            local_queue_depth: local_queue_depth as u64,
+        }
+    }
+}
+
+fn upload_ms(input_bytes: u64) -> f64 {
+    input_bytes as f64 / UPLOAD_BYTES_PER_MS as f64
+}
+
+/// Decide based on `durations`, the history of the action. Local commands that are queued behind
+/// `queue_depth` others on `local_slots` slots are expected to wait about `queue_depth /
+/// local_slots` times their own duration.
+fn route(
+    key: &str,
+    durations: Option<&ExecutorDurations>,
+    input_bytes: u64,
+    queue_depth: usize,
+    local_slots: usize,
+) -> RoutingDecision {
+    let local_estimate = durations
+        .and_then(|d| d.local.estimate())
+        .map(|local| local * (1.0 + queue_depth as f64 / local_slots.max(1) as f64));
+    let remote_estimate = durations
+        .and_then(|d| d.remote.estimate())
+        .map(|remote| remote + upload_ms(input_bytes));
+
+    let (executor, reason) = match (local_estimate, remote_estimate) {
+        (Some(local), Some(remote)) => {
+            if local * MARGIN < remote {
+                (
+                    Some(RoutedExecutor::Local),
+                    format!(
+                        "`{}` is expected to take {:.0}ms locally (with {} queued), {:.0}ms remotely",
+                        key, local, queue_depth, remote
+                    ),
+                )
+            } else if remote * MARGIN < local {
+                (
+                    Some(RoutedExecutor::Remote),
+                    format!(
+                        "`{}` is expected to take {:.0}ms remotely (with {} bytes to upload), {:.0}ms locally",
+                        key, remote, input_bytes, local
+                    ),
+                )
+            } else {
+                (
+                    None,
+                    format!(
+                        "`{}` is expected to take about as long locally ({:.0}ms) as remotely ({:.0}ms)",
+                        key, local, remote
+                    ),
+                )
+            }
+        }
+        // A race only tells us about the side that won, so run on the side we lack data for until
+        // we have enough.
+        (None, Some(_)) => (
+            Some(RoutedExecutor::Local),
+            format!("Not enough local runs of `{}`, running it locally", key),
+        ),
+        (Some(_), None) => (
+            Some(RoutedExecutor::Remote),
+            format!("Not enough remote runs of `{}`, running it remotely", key),
+        ),
+        (None, None) => (None, format!("Not enough runs of `{}`", key)),
+    };
+
+    RoutingDecision {
+        executor,
+        reason,
+        local_estimate_ms: local_estimate.map(|ms| ms as u64),
+        remote_estimate_ms: remote_estimate.map(|ms| ms as u64),
+    }
+}
+
+/// Keys we keep durations under, most specific first: the action itself, as identified by its
+/// owner, category and identifier (i.e. `CommandExecutionTarget::re_action_key`), then its
+/// category alone, for actions we haven't seen yet.
+fn keys<'a>(
+    action_key: &'a str,
+    name: &'a buck2_data::ActionName,
+) -> impl Iterator<Item = String> + 'a {
+    let action = (!action_key.is_empty()).then(|| action_key.to_owned());
+    action
+        .into_iter()
+        .chain(std::iter::once(name.category.clone()))
+}
+
+/// Lives as long as the daemon, and persists what it learns across daemons if it was created with
+/// `load`.
+pub struct HybridRouter {
+    durations: RwLock<HashMap<String, StoredDurations>>,
+    decisions: AtomicU64,
+    db: Option<Mutex<mpsc::Sender<DbUpdate>>>,
+}
+
+impl HybridRouter {
+    pub fn new_in_memory() -> Self {
+        Self {
+            durations: RwLock::new(HashMap::new()),
+            decisions: AtomicU64::new(0),
+            db: None,
+        }
+    }
+
+    /// Load the durations persisted in `state_dir`. If that fails, e.g. because the db doesn't
+    /// exist or is from another version, start over with an empty db.
+    pub fn load(state_dir: &AbsNormPath) -> anyhow::Result<Self> {
+        let (db, durations) = VersionedSqliteDb::load_or_recreate(
+            state_dir,
+            DB_SCHEMA_VERSION,
+            create_tables,
+            |connection| {
+                evict(
+                    connection,
+                    now_secs().saturating_sub(MAX_AGE.as_secs()),
+                    MAX_ENTRIES,
+                )?;
+                read_durations(connection)
+            },
+        )?;
+        let sender = db.spawn_writer("buck2-hybrid-routing", write_updates)?;
+
+        Ok(Self {
+            durations: RwLock::new(durations.unwrap_or_default()),
+            decisions: AtomicU64::new(0),
+            db: Some(Mutex::new(sender)),
+        })
+    }
+
+    /// Decide where to run the action `action_key`, named `name`.
+    pub fn decide(
+        &self,
+        action_key: &str,
+        name: &buck2_data::ActionName,
+        input_bytes: u64,
+        queue_depth: usize,
+        local_slots: usize,
+    ) -> RoutingDecision {
+        let explore =
+            self.decisions.fetch_add(1, Ordering::Relaxed) % EXPLORE_EVERY == EXPLORE_EVERY - 1;
+
+        let durations = self.durations.read();
+        // Use the most specific key we know enough about on both sides.
+        let mut decision = None;
+        for key in keys(action_key, name) {
+            let d = route(
+                &key,
+                durations.get(&key).map(|d| &d.durations),
+                input_bytes,
+                queue_depth,
+                local_slots,
+            );
+            if d.local_estimate_ms.is_some() && d.remote_estimate_ms.is_some() {
+                decision = Some(d);
+                break;
+            }
+            decision.get_or_insert(d);
+        }
+        let mut decision = decision.expect("There is always a category key");
+
+        if explore {
+            if let Some(executor) = decision.executor {
+                let other = executor.other();
+                decision.executor = Some(other);
+                decision.reason = format!(
+                    "Running {} to keep the durations up to date ({})",
+                    other.as_str(),
+                    decision.reason
+                );
+            }
+        }
+        decision
+    }
+
+    /// Record that the action `action_key`, named `name`, took `duration` on `executor`, not
+    /// counting the time it spent waiting in the local queue.
+    pub fn record(
+        &self,
+        action_key: &str,
+        name: &buck2_data::ActionName,
+        executor: RoutedExecutor,
+        duration: Duration,
+        input_bytes: u64,
+    ) {
+        let ms = duration.as_secs_f64() * 1000.0;
+        let ms = match executor {
+            RoutedExecutor::Local => ms,
+            RoutedExecutor::Remote => (ms - upload_ms(input_bytes)).max(0.0),
+        };
+
+        let now = now_secs();
+        let mut updates = Vec::new();
+        {
+            let mut durations = self.durations.write();
+            for key in keys(action_key, name) {
+                if !durations.contains_key(&key) && durations.len() >= MAX_ENTRIES {
+                    updates.push(DbUpdate::Delete(evict_least_recently_used(&mut durations)));
+                }
+                let entry = durations.entry(key.clone()).or_default();
+                match executor {
+                    RoutedExecutor::Local => entry.durations.local.record(ms),
+                    RoutedExecutor::Remote => entry.durations.remote.record(ms),
+                }
+                entry.last_used_secs = now;
+                updates.push(DbUpdate::Insert(key, *entry));
+            }
+        }
+
+        if let Some(db) = &self.db {
+            let db = db.lock();
+            for update in updates {
+                // The writer only goes away if it failed, in which case we just stop persisting.
+                let _ignored = db.send(update);
+            }
+        }
+    }
+}
+
+/// Forget the least recently run tenth of the actions, so that we don't have to do this for every
+/// new action once we are at capacity. Returns the keys that were removed.
+fn evict_least_recently_used(durations: &mut HashMap<String, StoredDurations>) -> Vec<String> {
+    let mut by_age = durations
+        .iter()
+        .map(|(key, d)| (d.last_used_secs, key.clone()))
+        .collect::<Vec<_>>();
+    let evicted = (durations.len() / 10).max(1);
+    by_age.select_nth_unstable(evicted - 1);
+    by_age.truncate(evicted);
+    by_age
+        .into_iter()
+        .map(|(_, key)| {
+            durations.remove(&key);
+            key
+        })
+        .collect()
+}
+
+fn create_tables(connection: &Connection) -> anyhow::Result<()> {
+    connection
+        .execute(
+            &format!(
+                "CREATE TABLE {} (
+                    key             TEXT PRIMARY KEY NOT NULL,
+                    local_ms        REAL NOT NULL,
+                    local_samples   INTEGER NOT NULL,
+                    remote_ms       REAL NOT NULL,
+                    remote_samples  INTEGER NOT NULL,
+                    last_used       INTEGER NOT NULL
+                )",
+                DURATIONS_TABLE_NAME
+            ),
+            [],
+        )
+        .with_context(|| format!("creating sqlite table {}", DURATIONS_TABLE_NAME))?;
+    Ok(())
+}
+
+/// Drop the actions that haven't run since `oldest_secs`, and the least recently run ones past the
+/// first `max_entries`.
+fn evict(connection: &Connection, oldest_secs: u64, max_entries: usize) -> anyhow::Result<()> {
+    connection
+        .execute(
+            &format!("DELETE FROM {} WHERE last_used < ?", DURATIONS_TABLE_NAME),
+            [oldest_secs],
+        )
+        .with_context(|| format!("evicting from sqlite table {}", DURATIONS_TABLE_NAME))?;
+    connection
+        .execute(
+            &format!(
+                "DELETE FROM {table} WHERE key NOT IN (SELECT key FROM {table} ORDER BY last_used DESC LIMIT ?)",
+                table = DURATIONS_TABLE_NAME
+            ),
+            [max_entries as u64],
+        )
+        .with_context(|| format!("evicting from sqlite table {}", DURATIONS_TABLE_NAME))?;
+    Ok(())
+}
+
+fn read_durations(connection: &Connection) -> anyhow::Result<HashMap<String, StoredDurations>> {
+    let mut stmt = connection.prepare(&format!(
+        "SELECT key, local_ms, local_samples, remote_ms, remote_samples, last_used FROM {}",
+        DURATIONS_TABLE_NAME
+    ))?;
+    let durations = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get(0)?,
+                StoredDurations {
+                    durations: ExecutorDurations {
+                        local: MovingAverage {
+                            mean_ms: row.get(1)?,
+                            samples: row.get(2)?,
+                        },
+                        remote: MovingAverage {
+                            mean_ms: row.get(3)?,
+                            samples: row.get(4)?,
+                        },
+                    },
+                    last_used_secs: row.get(5)?,
+                },
+            ))
+        })?
+        .collect::<Result<HashMap<_, _>, _>>()
+        .with_context(|| format!("reading from sqlite table {}", DURATIONS_TABLE_NAME))?;
+    Ok(durations)
+}
+
+fn insert_durations(
+    connection: &Connection,
+    updates: HashMap<String, StoredDurations>,
+) -> anyhow::Result<()> {
+    if updates.is_empty() {
+        return Ok(());
+    }
+    let sql = format!(
+        "INSERT OR REPLACE INTO {} (key, local_ms, local_samples, remote_ms, remote_samples, last_used) VALUES {}",
+        DURATIONS_TABLE_NAME,
+        updates.iter().map(|_| "(?, ?, ?, ?, ?, ?)").join(", ")
+    );
+    let params = updates
+        .into_iter()
+        .flat_map(|(key, d)| -> [Box<dyn rusqlite::ToSql>; 6] {
+            [
+                Box::new(key),
+                Box::new(d.durations.local.mean_ms),
+                Box::new(d.durations.local.samples),
+                Box::new(d.durations.remote.mean_ms),
+                Box::new(d.durations.remote.samples),
+                Box::new(d.last_used_secs),
+            ]
+        })
+        .collect::<Vec<_>>();
+    connection
+        .execute(&sql, rusqlite::params_from_iter(params))
+        .with_context(|| format!("inserting into sqlite table {}", DURATIONS_TABLE_NAME))?;
+    Ok(())
+}
+
+fn delete_durations(connection: &Connection, keys: Vec<String>) -> anyhow::Result<()> {
+    // Sqlite limits the number of parameters of a statement.
+    for chunk in keys.chunks(1000) {
+        connection
+            .execute(
+                &format!(
+                    "DELETE FROM {} WHERE key IN ({})",
+                    DURATIONS_TABLE_NAME,
+                    chunk.iter().map(|_| "?").join(", ")
+                ),
+                rusqlite::params_from_iter(chunk),
+            )
+            .with_context(|| format!("deleting from sqlite table {}", DURATIONS_TABLE_NAME))?;
+    }
+    Ok(())
+}
+
+/// Persist a batch of updates, merging the inserts between deletions into one statement.
+fn write_updates(connection: &Connection, updates: Vec<DbUpdate>) -> anyhow::Result<()> {
+    let mut inserts = HashMap::new();
+    for update in updates {
+        match update {
+            DbUpdate::Insert(key, durations) => {
+                inserts.insert(key, durations);
+            }
+            // Deletions are applied after the inserts that came before them.
+            DbUpdate::Delete(keys) => {
+                insert_durations(connection, std::mem::take(&mut inserts))?;
+                delete_durations(connection, keys)?;
+            }
+        }
+    }
+    insert_durations(connection, inserts)
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_core::fs::project::ProjectRootTemp;
+    use buck2_core::fs::project_rel_path::ProjectRelativePath;
+
+    use super::*;
+
+    fn durations(local_ms: f64, remote_ms: f64) -> ExecutorDurations {
+        ExecutorDurations {
+            local: MovingAverage {
+                mean_ms: local_ms,
+                samples: MIN_SAMPLES,
+            },
+            remote: MovingAverage {
+                mean_ms: remote_ms,
+                samples: MIN_SAMPLES,
+            },
+        }
+    }
+
+    #[test]
+    fn test_route() {
+        let key = "cxx_compile";
+
+        let d = route(key, None, 0, 0, 8);
+        assert_eq!(None, d.executor);
+        assert_eq!((None, None), (d.local_estimate_ms, d.remote_estimate_ms));
+
+        let d = route(key, Some(&durations(100.0, 1000.0)), 0, 0, 8);
+        assert_eq!(Some(RoutedExecutor::Local), d.executor);
+        assert_eq!(
+            (Some(100), Some(1000)),
+            (d.local_estimate_ms, d.remote_estimate_ms)
+        );
+
+        // A full local queue makes remote worth it.
+        let d = route(key, Some(&durations(100.0, 1000.0)), 0, 160, 8);
+        assert_eq!(Some(RoutedExecutor::Remote), d.executor);
+        assert_eq!(Some(2100), d.local_estimate_ms);
+
+        // So do large inputs make local worth it.
+        let d = route(key, Some(&durations(1000.0, 100.0)), 0, 0, 8);
+        assert_eq!(Some(RoutedExecutor::Remote), d.executor);
+        let d = route(
+            key,
+            Some(&durations(1000.0, 100.0)),
+            100 * 1024 * 1024,
+            0,
+            8,
+        );
+        assert_eq!(Some(RoutedExecutor::Local), d.executor);
+
+        let d = route(key, Some(&durations(100.0, 120.0)), 0, 0, 8);
+        assert_eq!(None, d.executor);
+
+        // Run on the side we don't know enough about yet.
+        let mut under_sampled = durations(100.0, 1000.0);
+        under_sampled.local.samples = 1;
+        let d = route(key, Some(&under_sampled), 0, 0, 8);
+        assert_eq!(Some(RoutedExecutor::Local), d.executor);
+        assert_eq!(
+            (None, Some(1000)),
+            (d.local_estimate_ms, d.remote_estimate_ms)
+        );
+    }
+
+    fn name() -> buck2_data::ActionName {
+        buck2_data::ActionName {
+            category: "cxx_compile".to_owned(),
+            identifier: "foo.cpp".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_decide_prefers_action() {
+        let router = HybridRouter::new_in_memory();
+        let slow = "root//:slow cxx_compile foo.cpp";
+        let fast = "root//:fast cxx_compile foo.cpp";
+
+        for _ in 0..MIN_SAMPLES {
+            router.record(
+                slow,
+                &name(),
+                RoutedExecutor::Local,
+                Duration::from_secs(10),
+                0,
+            );
+            router.record(
+                fast,
+                &name(),
+                RoutedExecutor::Local,
+                Duration::from_millis(10),
+                0,
+            );
+            router.record(
+                slow,
+                &name(),
+                RoutedExecutor::Remote,
+                Duration::from_secs(1),
+                0,
+            );
+        }
+
+        // Only the category has remote durations for the fast action, so use those, even though
+        // on its own it would be quicker locally.
+        let d = router.decide(fast, &name(), 0, 0, 8);
+        assert!(d.reason.contains("`cxx_compile`"), "{}", d.reason);
+        assert_eq!(Some(RoutedExecutor::Remote), d.executor);
+
+        // The slow action has the same category and identifier, but a different owner.
+        let d = router.decide(slow, &name(), 0, 0, 8);
+        assert!(d.reason.contains(&format!("`{}`", slow)), "{}", d.reason);
+        assert_eq!(Some(RoutedExecutor::Remote), d.executor);
+    }
+
+    #[test]
+    fn test_explore() {
+        let router = HybridRouter::new_in_memory();
+        let action = "root//:t cxx_compile foo.cpp";
+
+        // Racing only ever records the winner, so we run on the side we know nothing about.
+        for _ in 0..MIN_SAMPLES {
+            router.record(
+                action,
+                &name(),
+                RoutedExecutor::Local,
+                Duration::from_millis(10),
+                0,
+            );
+        }
+        assert_eq!(
+            Some(RoutedExecutor::Remote),
+            router.decide(action, &name(), 0, 0, 8).executor
+        );
+
+        for _ in 0..MIN_SAMPLES {
+            router.record(
+                action,
+                &name(),
+                RoutedExecutor::Remote,
+                Duration::from_secs(1),
+                0,
+            );
+        }
+        let executors = (0..EXPLORE_EVERY)
+            .map(|_| router.decide(action, &name(), 0, 0, 8).executor)
+            .collect::<Vec<_>>();
+        // Every so often, we run on the side we expect to be slower.
+        assert_eq!(
+            1,
+            executors
+                .iter()
+                .filter(|e| **e == Some(RoutedExecutor::Remote))
+                .count()
+        );
+        assert!(executors.iter().all(|e| e.is_some()));
+    }
+
+    #[test]
+    fn test_evict_least_recently_used() {
+        let mut durations = (0..20)
+            .map(|i| {
+                (
+                    i.to_string(),
+                    StoredDurations {
+                        durations: ExecutorDurations::default(),
+                        last_used_secs: i,
+                    },
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        let mut evicted = evict_least_recently_used(&mut durations);
+        evicted.sort();
+        assert_eq!(vec!["0".to_owned(), "1".to_owned()], evicted);
+        assert_eq!(18, durations.len());
+    }
+
+    #[test]
+    fn test_persisted() -> anyhow::Result<()> {
+        let fs = ProjectRootTemp::new()?;
+        let state_dir = fs
+            .path()
+            .resolve(ProjectRelativePath::unchecked_new("hybrid_routing"));
+        let name = buck2_data::ActionName {
+            category: "cxx_link".to_owned(),
+            identifier: String::new(),
+        };
+        let action = "root//:t cxx_link";
+
+        {
+            let router = HybridRouter::load(&state_dir)?;
+            for _ in 0..MIN_SAMPLES {
+                router.record(
+                    action,
+                    &name,
+                    RoutedExecutor::Local,
+                    Duration::from_secs(1),
+                    0,
+                );
+                router.record(
+                    action,
+                    &name,
+                    RoutedExecutor::Remote,
+                    Duration::from_secs(10),
+                    0,
+                );
+            }
+        }
+
+        // Writes are asynchronous, so wait for them to land.
+        let mut durations = HashMap::new();
+        for _ in 0..100 {
+            durations = read_durations(&VersionedSqliteDb::open(&state_dir)?.connection())?;
+            if durations.get(action).map(|d| d.durations) == Some(durations_after_samples()) {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(
+            Some(durations_after_samples()),
+            durations.get(action).map(|d| d.durations)
+        );
+
+        let router = HybridRouter::load(&state_dir)?;
+        assert_eq!(
+            Some(RoutedExecutor::Local),
+            router.decide(action, &name, 0, 0, 8).executor
+        );
+
+        // Actions that haven't run in a while are dropped.
+        let db = VersionedSqliteDb::open(&state_dir)?;
+        evict(&db.connection(), now_secs() + 1, MAX_ENTRIES)?;
+        assert!(read_durations(&db.connection())?.is_empty());
+
+        Ok(())
+    }
+
+    fn durations_after_samples() -> ExecutorDurations {
+        durations(1000.0, 10000.0)
+    }
+}
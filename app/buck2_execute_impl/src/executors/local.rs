@@ -12,6 +12,7 @@ use std::ffi::OsString;
 use std::ops::ControlFlow;
 use std::process::Command;
 use std::sync::Arc;
+use std::sync::OnceLock;
 use std::time::Duration;
 use std::time::Instant;
 use std::time::SystemTime;
@@ -56,6 +57,7 @@ use buck2_execute::execute::request::CommandExecutionOutput;
 use buck2_execute::execute::request::CommandExecutionOutputRef;
 use buck2_execute::execute::request::CommandExecutionRequest;
 use buck2_execute::execute::request::ExecutorPreference;
+use buck2_execute::execute::request::ResourceLimits;
 use buck2_execute::execute::result::CommandExecutionMetadata;
 use buck2_execute::execute::result::CommandExecutionResult;
 use buck2_execute::knobs::ExecutorGlobalKnobs;
@@ -82,6 +84,11 @@ use host_sharing::HostSharingRequirements;
 use indexmap::IndexMap;
 use tracing::info;
 
+use crate::executors::local_input_tracer::LocalInputTracer;
+use crate::executors::local_resources::LocalResourceAdmission;
+use crate::executors::local_sandbox::LocalSandbox;
+use crate::executors::local_sandbox::SandboxViolation;
+use crate::executors::local_sandbox::SandboxViolationsError;
 use crate::executors::worker::WorkerHandle;
 use crate::executors::worker::WorkerPool;
 
@@ -95,6 +102,19 @@ enum LocalExecutionError {
     RemoteOnlyAction,
 }
 
+/// Explains why a command was killed, since all it gets is a signal.
+fn memory_limit_exceeded_message(limit: Option<u64>, peak: Option<u64>) -> String {
+    const MIB: u64 = 1024 * 1024;
+    let mut message = match limit {
+        Some(limit) => format!("Action exceeded its memory limit of {} MiB", limit / MIB),
+        None => "Action exceeded the memory limit".to_owned(),
+    };
+    if let Some(peak) = peak {
+        message.push_str(&format!(" (peak usage: {} MiB)", peak / MIB));
+    }
+    message
+}
+
 #[derive(Clone)]
 pub struct LocalExecutor {
     artifact_fs: ArtifactFs,
@@ -108,6 +128,7 @@ pub struct LocalExecutor {
     knobs: ExecutorGlobalKnobs,
     #[allow(unused)]
     worker_pool: Option<Arc<WorkerPool>>,
+    local_resources: Option<Arc<LocalResourceAdmission>>,
 }
 
 impl LocalExecutor {
@@ -120,6 +141,7 @@ impl LocalExecutor {
         forkserver: Option<ForkserverClient>,
         knobs: ExecutorGlobalKnobs,
         worker_pool: Option<Arc<WorkerPool>>,
+        local_resources: Option<Arc<LocalResourceAdmission>>,
     ) -> Self {
         Self {
             artifact_fs,
@@ -130,6 +152,7 @@ impl LocalExecutor {
             forkserver,
             knobs,
             worker_pool,
+            local_resources,
         }
     }
 
@@ -145,6 +168,8 @@ impl LocalExecutor {
         env_inheritance: Option<&'a EnvironmentInheritance>,
         liveliness_observer: impl LivelinessObserver + 'static,
         disable_miniperf: bool,
+        sandbox: Option<&'a LocalSandbox>,
+        resource_limits: ResourceLimits,
     ) -> impl futures::future::Future<
         Output = anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)>,
     > + Send
@@ -165,14 +190,17 @@ impl LocalExecutor {
                             timeout,
                             env_inheritance,
                             liveliness_observer,
-                            self.knobs.enable_miniperf && !disable_miniperf,
+                            // Miniperf lives in buck-out, where the sandbox can't see it.
+                            self.knobs.enable_miniperf && !disable_miniperf && sandbox.is_none(),
+                            sandbox.map(|s| s.to_proto(self.artifact_fs.fs())),
+                            resource_limits,
                         )
                         .await
                     }
 
                     #[cfg(not(unix))]
                     {
-                        let _unused = (forkserver, disable_miniperf);
+                        let _unused = (forkserver, disable_miniperf, sandbox, resource_limits);
                         Err(anyhow::anyhow!("Forkserver is not supported off-UNIX"))
                     }
                 }
@@ -375,6 +403,30 @@ impl LocalExecutor {
             .boxed()
             .await?;
 
+        let sandbox = match self.local_sandbox(request, action_digest, worker.is_some()) {
+            Ok(sandbox) => sandbox,
+            Err(e) => return manager.error("local_sandbox_failed", e),
+        };
+        let sandbox_ref = sandbox.as_ref();
+
+        let input_tracer =
+            match self.input_tracer(request, action_digest, worker.is_some(), sandbox.is_some()) {
+                Ok(input_tracer) => input_tracer,
+                Err(e) => return manager.error("input_tracer_failed", e),
+            };
+        let traced_args = match (&input_tracer, &sandbox) {
+            (Some(input_tracer), _) => match input_tracer.wrap(self.artifact_fs.fs(), args) {
+                Ok(traced_args) => Some(traced_args),
+                Err(e) => return manager.error("input_tracer_failed", e),
+            },
+            (None, Some(sandbox)) => match sandbox.wrap(self.artifact_fs.fs(), args) {
+                Ok(traced_args) => traced_args,
+                Err(e) => return manager.error("local_sandbox_failed", e),
+            },
+            (None, None) => None,
+        };
+        let exec_args = traced_args.as_ref().unwrap_or(args);
+
         let execution_kind = match worker {
             None => CommandExecutionKind::Local {
                 digest: action_digest.dupe(),
@@ -433,14 +485,16 @@ impl LocalExecutor {
                         .await)
                 } else {
                     self.exec(
-                        &args[0],
-                        &args[1..],
+                        &exec_args[0],
+                        &exec_args[1..],
                         env,
                         request.working_directory(),
                         request.timeout(),
                         request.local_environment_inheritance(),
                         liveliness_observer,
                         request.disable_miniperf(),
+                        sandbox_ref,
+                        request.resource_limits(),
                     )
                     .await
                 };
@@ -464,16 +518,77 @@ impl LocalExecutor {
         .boxed()
         .await;
 
-        let (status, stdout, stderr) = match res {
+        let (status, stdout, mut stderr) = match res {
             Ok(res) => res,
             Err(e) => {
                 return manager.error("exec_failed", e);
             }
         };
 
+        let mut undeclared_inputs = Vec::new();
+        if let Some(sandbox) = &sandbox {
+            let failed = !matches!(status, GatherOutputStatus::Finished { exit_code: 0, .. });
+            let violations = match self
+                .blocking_executor
+                .execute_io_inline(|| sandbox.finish(self.artifact_fs.fs(), &stderr, failed))
+                .await
+            {
+                Ok(violations) => violations,
+                Err(e) => return manager.error("local_sandbox_failed", e),
+            };
+            if violations
+                .iter()
+                .any(|v| matches!(v, SandboxViolation::UndeclaredWrite(_)))
+            {
+                return manager.error(
+                    "local_sandbox_violation",
+                    SandboxViolationsError(violations),
+                );
+            }
+            if failed && !violations.is_empty() {
+                // The action failed already, explain why it might have.
+                stderr.extend(format!("\n{}\n", SandboxViolationsError(violations)).into_bytes());
+            } else {
+                // The action did without the files it could not see, but it may not do the
+                // same outside of the sandbox.
+                undeclared_inputs = violations
+                    .into_iter()
+                    .filter_map(|v| match v {
+                        SandboxViolation::UndeclaredRead(path) => Some(path),
+                        SandboxViolation::UndeclaredWrite(_) => None,
+                    })
+                    .collect();
+            }
+        }
+
+        if let Some(input_tracer) = &input_tracer {
+            undeclared_inputs = match self
+                .blocking_executor
+                .execute_io_inline(|| input_tracer.finish(self.artifact_fs.fs()))
+                .await
+            {
+                Ok(undeclared_inputs) => undeclared_inputs,
+                Err(e) => return manager.error("input_tracer_failed", e),
+            };
+        }
+
+        if let GatherOutputStatus::Finished {
+            exit_code,
+            execution_stats: Some(stats),
+        } = &status
+        {
+            if *exit_code != 0 && stats.memory_limit_exceeded {
+                let message = memory_limit_exceeded_message(
+                    request.resource_limits().memory_max_bytes,
+                    stats.memory_peak_bytes,
+                );
+                stderr.extend(format!("\n{}\n", message).into_bytes());
+            }
+        }
+
         let std_streams = CommandStdStreams::Local { stdout, stderr };
 
-        match status {
+        let mut result = match status {
             GatherOutputStatus::Finished {
                 exit_code,
                 execution_stats,
@@ -532,8 +647,11 @@ impl LocalExecutor {
                     Default::default(),
                     CommandStdStreams::Local {
                         stdout: Default::default(),
-                        stderr: format!("Spawning executable `{}` failed: {}", args[0], reason)
-                            .into_bytes(),
+                        stderr: format!(
+                            "Spawning executable `{}` failed: {}",
+                            exec_args[0], reason
+                        )
+                        .into_bytes(),
                     },
                     None,
                     *timing,
@@ -543,7 +661,65 @@ impl LocalExecutor {
                 manager.timeout(execution_kind, duration, std_streams, *timing)
             }
             GatherOutputStatus::Cancelled => manager.cancel_claim(),
+        };
+        result.report.undeclared_inputs = undeclared_inputs;
+        result
+    }
+
+    /// The sandbox to run `request` in, if `build.sandbox_local_actions` is set. Workers are
+    /// long-lived and shared between actions, so they don't get one.
+    fn local_sandbox(
+        &self,
+        request: &CommandExecutionRequest,
+        action_digest: &ActionDigest,
+        uses_worker: bool,
+    ) -> anyhow::Result<Option<LocalSandbox>> {
+        if !self.knobs.sandbox_local_actions || uses_worker {
+            return Ok(None);
+        }
+        if self.forkserver.is_none() || !cfg!(target_os = "linux") {
+            static WARN: OnceLock<()> = OnceLock::new();
+            WARN.get_or_init(|| {
+                tracing::warn!(
+                    "build.sandbox_local_actions requires Linux and the forkserver, running local actions unsandboxed"
+                )
+            });
+            return Ok(None);
+        }
+        Ok(Some(LocalSandbox::new(
+            &self.artifact_fs,
+            request,
+            action_digest,
+            !self.knobs.sandbox_allow_network,
+        )?))
+    }
+
+    /// The tracer to run `request` under, if `build.paranoid_inputs` is set. Workers are shared
+    /// between actions, and sandboxed actions can't read undeclared inputs in the first place.
+    fn input_tracer(
+        &self,
+        request: &CommandExecutionRequest,
+        action_digest: &ActionDigest,
+        uses_worker: bool,
+        sandboxed: bool,
+    ) -> anyhow::Result<Option<LocalInputTracer>> {
+        if !self.knobs.paranoid_inputs || uses_worker || sandboxed {
+            return Ok(None);
         }
+        if !cfg!(target_os = "linux") {
+            static WARN: OnceLock<()> = OnceLock::new();
+            WARN.get_or_init(|| {
+                tracing::warn!(
+                    "build.paranoid_inputs requires Linux, not tracing the inputs of local actions"
+                )
+            });
+            return Ok(None);
+        }
+        Ok(Some(LocalInputTracer::new(
+            &self.artifact_fs,
+            request,
+            action_digest,
+        )?))
     }
 
     async fn calculate_and_declare_output_values(
@@ -733,10 +909,11 @@ impl PreparedCommandExecutor for LocalExecutor {
 
         let PreparedCommand {
             request,
-            target: _,
+            target,
             prepared_action,
             digest_config,
         } = command;
+        let name = target.as_proto_action_name();
 
         let local_resource_holders = executor_stage_async(
             buck2_data::LocalStage {
@@ -757,6 +934,36 @@ impl PreparedCommandExecutor for LocalExecutor {
         )
         .await;
 
+        // Wait for the machine to have room for this command before taking a job slot, so that
+        // commands waiting for memory don't hold up smaller ones.
+        let _resources = match &self.local_resources {
+            Some(local_resources) => {
+                let (estimate, learned) =
+                    local_resources.estimate(&name, request.resource_estimate());
+                if estimate.is_empty() {
+                    None
+                } else {
+                    Some(
+                        executor_stage_async(
+                            buck2_data::LocalStage {
+                                stage: Some(
+                                    buck2_data::LocalResourceQueued {
+                                        memory_bytes: estimate.memory_bytes,
+                                        cpu_millicores: estimate.cpu_millicores,
+                                        learned,
+                                    }
+                                    .into(),
+                                ),
+                            },
+                            local_resources.acquire(&estimate),
+                        )
+                        .await,
+                    )
+                }
+            }
+            None => None,
+        };
+
         let _worker_permit = self.acquire_worker_permit(request).await;
 
         let _permit = executor_stage_async(
@@ -770,7 +977,7 @@ impl PreparedCommandExecutor for LocalExecutor {
 
         // If we start running something, we don't want this task to get dropped, because if we do
         // we might interfere with e.g. clean up.
-        cancellations
+        let result = cancellations
             .with_structured_cancellation(|cancellation| {
                 Self::exec_request(
                     self,
@@ -783,7 +990,21 @@ impl PreparedCommandExecutor for LocalExecutor {
                     &local_resource_holders,
                 )
             })
-            .await
+            .await;
+
+        if let (Some(local_resources), Some(memory_peak_bytes)) = (
+            &self.local_resources,
+            result
+                .report
+                .timing
+                .execution_stats
+                .as_ref()
+                .and_then(|stats| stats.memory_peak_bytes),
+        ) {
+            local_resources.record(&name, memory_peak_bytes);
+        }
+
+        result
     }
 
     fn is_local_execution_possible(&self, _executor_preference: ExecutorPreference) -> bool {
@@ -1086,8 +1307,16 @@ mod unix {
         env_inheritance: Option<&EnvironmentInheritance>,
         liveliness_observer: impl LivelinessObserver + 'static,
         enable_miniperf: bool,
+        sandbox: Option<buck2_forkserver_proto::SandboxConfig>,
+        resource_limits: ResourceLimits,
     ) -> anyhow::Result<(GatherOutputStatus, Vec<u8>, Vec<u8>)> {
         let exe = exe.as_ref();
+        let resource_limits = (resource_limits != ResourceLimits::default()).then_some(
+            buck2_forkserver_proto::ResourceLimits {
+                memory_max_bytes: resource_limits.memory_max_bytes,
+                cpu_max_millicores: resource_limits.cpu_max_millicores,
+            },
+        );
 
         let mut req = buck2_forkserver_proto::CommandRequest {
             exe: exe.as_bytes().to_vec(),
@@ -1103,6 +1332,8 @@ mod unix {
             enable_miniperf,
             std_redirects: None,
             graceful_shutdown_timeout_s: None,
+            sandbox,
+            resource_limits,
         };
         apply_local_execution_environment(&mut req, working_directory, env, env_inheritance);
         forkserver
@@ -1202,6 +1433,7 @@ mod tests {
             None,
             ExecutorGlobalKnobs::default(),
             None,
+            None,
         );
 
         Ok((executor, temp.path().root().to_buf(), temp))
@@ -1222,6 +1454,8 @@ mod tests {
                 None,
                 NoopLivelinessObserver::create(),
                 false,
+                None,
+                ResourceLimits::default(),
             )
             .await?;
         assert!(matches!(status, GatherOutputStatus::Finished { exit_code, .. } if exit_code == 0));
@@ -1258,6 +1492,8 @@ mod tests {
                 Some(&EnvironmentInheritance::empty()),
                 NoopLivelinessObserver::create(),
                 false,
+                None,
+                ResourceLimits::default(),
             )
             .await?;
         assert!(matches!(status, GatherOutputStatus::Finished { exit_code, .. } if exit_code == 0));
@@ -1265,4 +1501,17 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_memory_limit_exceeded_message() {
+        const MIB: u64 = 1024 * 1024;
+        assert_eq!(
+            memory_limit_exceeded_message(Some(64 * MIB), Some(64 * MIB - 1)),
+            "Action exceeded its memory limit of 64 MiB (peak usage: 63 MiB)"
+        );
+        assert_eq!(
+            memory_limit_exceeded_message(None, None),
+            "Action exceeded the memory limit"
+        );
+    }
 }
@@ -0,0 +1,235 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Paranoid inputs (`build.paranoid_inputs`): local actions run under `strace`, and the files they
+//! open or execute are compared against their declared inputs. This finds the missing `srcs` and
+//! headers that would otherwise only break the build once it moves to remote execution.
+//!
+//! Unlike the sandbox, this doesn't change what the action can see, it only reports.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::OnceLock;
+
+use buck2_core::fs::artifact_path_resolver::ArtifactFs;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_core::fs::project_rel_path::ProjectRelativePath;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+use buck2_directory::directory::directory::Directory;
+use buck2_directory::directory::directory_iterator::DirectoryIterator;
+use buck2_directory::directory::directory_iterator::DirectoryIteratorPathStack;
+use buck2_directory::directory::entry::DirectoryEntry;
+use buck2_directory::directory::walk::unordered_entry_walk;
+use buck2_execute::directory::ActionDirectoryMember;
+use buck2_execute::execute::action_digest::ActionDigest;
+use buck2_execute::execute::request::CommandExecutionInput;
+use buck2_execute::execute::request::CommandExecutionRequest;
+use buck2_forkserver::run::maybe_absolutize_exe;
+
+use crate::executors::strace::find_strace;
+use crate::executors::strace::strace_args;
+use crate::executors::strace::traced_reads;
+
+#[derive(Debug, buck2_error::Error)]
+enum LocalInputTracerError {
+    #[error(
+        "`build.paranoid_inputs` requires `strace`, which is not in the `PATH` of the buck2 daemon. Install it and restart the daemon with `buck2 kill`"
+    )]
+    #[buck2(input)]
+    StraceNotFound,
+}
+
+/// The `strace` local actions run under with paranoid inputs. Looked up once per daemon.
+pub fn paranoid_inputs_strace() -> anyhow::Result<&'static Path> {
+    static STRACE: OnceLock<Option<PathBuf>> = OnceLock::new();
+    Ok(STRACE
+        .get_or_init(find_strace)
+        .as_deref()
+        .ok_or(LocalInputTracerError::StraceNotFound)?)
+}
+
+/// The files an action is allowed to read when running with paranoid inputs.
+pub(crate) struct LocalInputTracer {
+    strace: &'static Path,
+    trace_file: ProjectRelativePathBuf,
+    /// Declared files and directories. Anything under them may be read.
+    declared: BTreeSet<ProjectRelativePathBuf>,
+    /// Symlinks among the declared inputs, e.g. in a `symlinked_dir`. What they point to may be
+    /// read too, and `strace -y` reports it by its real path.
+    declared_symlinks: Vec<ProjectRelativePathBuf>,
+    working_directory: ProjectRelativePathBuf,
+}
+
+impl LocalInputTracer {
+    pub(crate) fn new(
+        artifact_fs: &ArtifactFs,
+        request: &CommandExecutionRequest,
+        action_digest: &ActionDigest,
+    ) -> anyhow::Result<Self> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+        let strace = paranoid_inputs_strace()?;
+        let mut declared = BTreeSet::new();
+        let mut declared_symlinks = Vec::new();
+        for input in request.inputs() {
+            match input {
+                CommandExecutionInput::Artifact(group) => {
+                    for (artifact, value) in group.iter() {
+                        let path = artifact.resolve_path(artifact_fs)?;
+                        let mut walk =
+                            unordered_entry_walk(value.entry().as_ref().map_dir(Directory::as_ref));
+                        while let Some((entry_path, entry)) = walk.next() {
+                            if let DirectoryEntry::Leaf(
+                                ActionDirectoryMember::Symlink(_)
+                                | ActionDirectoryMember::ExternalSymlink(_),
+                            ) = entry
+                            {
+                                declared_symlinks.push(path.join(entry_path.get()));
+                            }
+                        }
+                        declared.insert(path);
+                    }
+                }
+                CommandExecutionInput::ActionMetadata(metadata) => {
+                    declared.insert(
+                        artifact_fs
+                            .buck_out_path_resolver()
+                            .resolve_gen(&metadata.path),
+                    );
+                }
+                CommandExecutionInput::ScratchPath(path) => {
+                    declared.insert(artifact_fs.buck_out_path_resolver().resolve_scratch(path));
+                }
+            }
+        }
+        // Actions may read back what they wrote.
+        for output in request.outputs() {
+            if let Some(path) = output.resolve(artifact_fs).path_to_create() {
+                declared.insert(path.to_owned());
+            }
+        }
+
+        let trace_file =
+            artifact_fs
+                .buck_out_path_resolver()
+                .root()
+                .join(ForwardRelativePath::unchecked_new(&format!(
+                    "paranoid_inputs/{}-{}.trace",
+                    action_digest.raw_digest(),
+                    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+                )));
+
+        Ok(Self {
+            strace,
+            trace_file,
+            declared,
+            declared_symlinks,
+            working_directory: request.working_directory().to_owned(),
+        })
+    }
+
+    /// The command line that runs `args` under `strace`.
+    pub(crate) fn wrap(&self, fs: &ProjectRoot, args: &[String]) -> anyhow::Result<Vec<String>> {
+        let trace_file = fs.resolve(&self.trace_file);
+        if let Some(dir) = trace_file.parent() {
+            fs_util::create_dir_all(dir)?;
+        }
+        // Resolve the executable the same way it would be without the tracer.
+        let exe = maybe_absolutize_exe(&args[0], &fs.resolve(&self.working_directory))?;
+
+        let mut wrapped = strace_args(self.strace, trace_file.as_path(), true);
+        wrapped.push(exe.to_string_lossy().into_owned());
+        wrapped.extend(args[1..].iter().cloned());
+        Ok(wrapped)
+    }
+
+    /// The files in the project the action read without declaring them. Removes the trace.
+    pub(crate) fn finish(&self, fs: &ProjectRoot) -> anyhow::Result<Vec<ProjectRelativePathBuf>> {
+        let trace_file = fs.resolve(&self.trace_file);
+        let Some(trace) = fs_util::read_to_string_if_exists(&trace_file)? else {
+            return Ok(Vec::new());
+        };
+        fs_util::remove_file(&trace_file)?;
+
+        // `strace -y` prints real paths, so the project root may appear resolved too.
+        let real_root = fs_util::canonicalize(fs.root())?;
+        let relativize = |path: &Path| {
+            let path = path
+                .strip_prefix(fs.root().as_path())
+                .or_else(|_| path.strip_prefix(real_root.as_path()))
+                .ok()?;
+            ProjectRelativePath::new(path.to_str()?)
+                .ok()
+                .map(|p| p.to_owned())
+        };
+
+        let working_directory = fs.resolve(&self.working_directory);
+        let mut undeclared: BTreeSet<_> = traced_reads(&trace, working_directory.as_path())
+            .into_iter()
+            .filter(|read| !read.missing)
+            .filter_map(|read| relativize(&read.path))
+            .filter(|path| !path.is_empty() && !is_under(&self.declared, path))
+            // Listing directories is fine, and the file may be gone if it was temporary.
+            .filter(|path| fs.resolve(path).is_file())
+            .collect();
+        if undeclared.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // A read through a declared symlink is reported at its target, so compare real paths.
+        let real_path = |path: &ProjectRelativePath| {
+            relativize(fs_util::canonicalize(fs.resolve(path)).ok()?.as_path())
+        };
+        let declared_real: BTreeSet<_> = self
+            .declared
+            .iter()
+            .chain(&self.declared_symlinks)
+            .filter_map(|path| real_path(path))
+            .collect();
+        undeclared.retain(|path| !real_path(path).is_some_and(|p| is_under(&declared_real, &p)));
+        Ok(undeclared.into_iter().collect())
+    }
+}
+
+/// Whether `path` is in `declared` or under a directory in it.
+fn is_under(declared: &BTreeSet<ProjectRelativePathBuf>, path: &ProjectRelativePath) -> bool {
+    let mut path = Some(path);
+    while let Some(p) = path {
+        if declared.contains(p) {
+            return true;
+        }
+        path = p.parent();
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_under() {
+        let declared = BTreeSet::from([
+            ProjectRelativePathBuf::unchecked_new("a/b".to_owned()),
+            ProjectRelativePathBuf::unchecked_new("c.h".to_owned()),
+        ]);
+        let under = |path: &str| is_under(&declared, ProjectRelativePath::unchecked_new(path));
+        assert!(under("a/b"));
+        assert!(under("a/b/d/e.h"));
+        assert!(under("c.h"));
+        assert!(!under("a"));
+        assert!(!under("a/bc"));
+        assert!(!under("c.hh"));
+    }
+}
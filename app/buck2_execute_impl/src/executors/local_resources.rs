@@ -0,0 +1,337 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Admission of local commands against the machine's memory and CPUs.
+
+use std::collections::HashMap;
+use std::sync::mpsc;
+
+use anyhow::Context;
+use buck2_common::sqlite::VersionedSqliteDb;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use dashmap::DashMap;
+use host_sharing::ResourceBudget;
+use host_sharing::ResourceBudgetGuard;
+use host_sharing::ResourceBudgetStats;
+use host_sharing::ResourceEstimate;
+use itertools::Itertools;
+use parking_lot::Mutex;
+use rusqlite::Connection;
+
+/// Bump this if the schema of the db changes, the existing estimates will be dropped.
+const DB_SCHEMA_VERSION: u64 = 1;
+
+const ESTIMATES_TABLE_NAME: &str = "local_resource_estimates";
+
+/// Keys we keep estimates under, most specific first. Identifiers are only meaningful within a
+/// category (e.g. the output of a `cxx_link`), so we fall back to the category alone for commands
+/// we haven't seen yet.
+fn keys(name: &buck2_data::ActionName) -> impl Iterator<Item = String> + '_ {
+    let with_identifier =
+        (!name.identifier.is_empty()).then(|| format!("{}:{}", name.category, name.identifier));
+    with_identifier
+        .into_iter()
+        .chain(std::iter::once(name.category.clone()))
+}
+
+/// Peak memory of previous commands, and where to persist it.
+struct LearnedMemory {
+    estimates: DashMap<String, u64>,
+    db: Option<Mutex<mpsc::Sender<(String, u64)>>>,
+}
+
+/// Lives as long as the daemon, since the budget is the machine's and the learned estimates are
+/// only useful across builds. If it was created with `load`, the estimates also persist across
+/// daemons.
+pub struct LocalResourceAdmission {
+    budget: ResourceBudget,
+    /// `None` if learning is disabled.
+    learned_memory: Option<LearnedMemory>,
+}
+
+impl LocalResourceAdmission {
+    pub fn new(memory_bytes: u64, cpu_millicores: u64, learn_estimates: bool) -> Self {
+        Self {
+            budget: ResourceBudget::new(memory_bytes, cpu_millicores),
+            learned_memory: learn_estimates.then(|| LearnedMemory {
+                estimates: DashMap::new(),
+                db: None,
+            }),
+        }
+    }
+
+    /// Learn estimates, starting from the ones persisted in `state_dir`. If loading them fails,
+    /// e.g. because the db doesn't exist or is from another version, start over with an empty db.
+    pub fn load(
+        memory_bytes: u64,
+        cpu_millicores: u64,
+        state_dir: &AbsNormPath,
+    ) -> anyhow::Result<Self> {
+        let (db, estimates) = VersionedSqliteDb::load_or_recreate(
+            state_dir,
+            DB_SCHEMA_VERSION,
+            create_tables,
+            read_estimates,
+        )?;
+        let sender = db.spawn_writer("buck2-local-resources", insert_estimates)?;
+
+        Ok(Self {
+            budget: ResourceBudget::new(memory_bytes, cpu_millicores),
+            learned_memory: Some(LearnedMemory {
+                estimates: estimates.unwrap_or_default(),
+                db: Some(Mutex::new(sender)),
+            }),
+        })
+    }
+
+    /// The estimate to admit the command named `name` with. What the rule declared wins,
+    /// otherwise we use what we learned from previous runs of the same command, or of commands in
+    /// the same category, if anything. Returns whether the memory estimate was learned.
+    pub fn estimate(
+        &self,
+        name: &buck2_data::ActionName,
+        declared: ResourceEstimate,
+    ) -> (ResourceEstimate, bool) {
+        if declared.memory_bytes.is_some() {
+            return (declared, false);
+        }
+        let Some(learned) = &self.learned_memory else {
+            return (declared, false);
+        };
+        match keys(name).find_map(|key| learned.estimates.get(&key).map(|m| *m)) {
+            Some(memory_bytes) => (
+                ResourceEstimate {
+                    memory_bytes: Some(memory_bytes),
+                    ..declared
+                },
+                true,
+            ),
+            None => (declared, false),
+        }
+    }
+
+    /// Record the peak memory of a command that ran. The estimate follows increases immediately,
+    /// since underestimating is what exhausts the machine, and decays slowly otherwise.
+    pub fn record(&self, name: &buck2_data::ActionName, memory_peak_bytes: u64) {
+        let Some(learned) = &self.learned_memory else {
+            return;
+        };
+
+        let updated = keys(name)
+            .map(|key| {
+                let estimate = *learned
+                    .estimates
+                    .entry(key.clone())
+                    .and_modify(|estimate| {
+                        *estimate =
+                            memory_peak_bytes.max((*estimate / 4) * 3 + memory_peak_bytes / 4);
+                    })
+                    .or_insert(memory_peak_bytes);
+                (key, estimate)
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(db) = &learned.db {
+            let db = db.lock();
+            for update in updated {
+                // The writer only goes away if it failed, in which case we just stop persisting.
+                let _ignored = db.send(update);
+            }
+        }
+    }
+
+    pub async fn acquire(&self, estimate: &ResourceEstimate) -> ResourceBudgetGuard {
+        self.budget.acquire(estimate).await
+    }
+
+    pub fn stats(&self) -> ResourceBudgetStats {
+        self.budget.stats()
+    }
+}
+
+fn create_tables(connection: &Connection) -> anyhow::Result<()> {
+    connection
+        .execute(
+            &format!(
+                "CREATE TABLE {} (
+                    key                 TEXT PRIMARY KEY NOT NULL,
+                    memory_peak_bytes   INTEGER NOT NULL
+                )",
+                ESTIMATES_TABLE_NAME
+            ),
+            [],
+        )
+        .with_context(|| format!("creating sqlite table {}", ESTIMATES_TABLE_NAME))?;
+    Ok(())
+}
+
+fn read_estimates(connection: &Connection) -> anyhow::Result<DashMap<String, u64>> {
+    let mut stmt = connection.prepare(&format!(
+        "SELECT key, memory_peak_bytes FROM {}",
+        ESTIMATES_TABLE_NAME
+    ))?;
+    let estimates = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<DashMap<_, _>, _>>()
+        .with_context(|| format!("reading from sqlite table {}", ESTIMATES_TABLE_NAME))?;
+    Ok(estimates)
+}
+
+fn insert_estimates(connection: &Connection, updates: Vec<(String, u64)>) -> anyhow::Result<()> {
+    // Only the latest estimate of each command matters.
+    let updates = updates.into_iter().collect::<HashMap<_, _>>();
+    let sql = format!(
+        "INSERT OR REPLACE INTO {} (key, memory_peak_bytes) VALUES {}",
+        ESTIMATES_TABLE_NAME,
+        updates.iter().map(|_| "(?, ?)").join(", ")
+    );
+    let params = updates
+        .into_iter()
+        .flat_map(|(key, memory)| -> [Box<dyn rusqlite::ToSql>; 2] {
+            [Box::new(key), Box::new(memory)]
+        })
+        .collect::<Vec<_>>();
+    connection
+        .execute(&sql, rusqlite::params_from_iter(params))
+        .with_context(|| format!("inserting into sqlite table {}", ESTIMATES_TABLE_NAME))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use buck2_core::fs::project::ProjectRootTemp;
+    use buck2_core::fs::project_rel_path::ProjectRelativePath;
+
+    use super::*;
+
+    const MIB: u64 = 1024 * 1024;
+
+    fn name(identifier: &str) -> buck2_data::ActionName {
+        buck2_data::ActionName {
+            category: "cxx_link".to_owned(),
+            identifier: identifier.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_learned_estimates() {
+        let admission = LocalResourceAdmission::new(1024 * MIB, 1000, true);
+        let declared = ResourceEstimate {
+            memory_bytes: None,
+            cpu_millicores: Some(500),
+        };
+
+        assert_eq!(
+            (declared, false),
+            admission.estimate(&name("a.so"), declared)
+        );
+
+        admission.record(&name("a.so"), 400 * MIB);
+        assert_eq!(
+            (
+                ResourceEstimate {
+                    memory_bytes: Some(400 * MIB),
+                    cpu_millicores: Some(500),
+                },
+                true
+            ),
+            admission.estimate(&name("a.so"), declared)
+        );
+
+        // Increases are taken as is, decreases decay.
+        admission.record(&name("a.so"), 800 * MIB);
+        assert_eq!(
+            Some(800 * MIB),
+            admission.estimate(&name("a.so"), declared).0.memory_bytes
+        );
+        admission.record(&name("a.so"), 0);
+        assert_eq!(
+            Some(600 * MIB),
+            admission.estimate(&name("a.so"), declared).0.memory_bytes
+        );
+
+        // Declared estimates win.
+        let declared = ResourceEstimate {
+            memory_bytes: Some(MIB),
+            cpu_millicores: None,
+        };
+        assert_eq!(
+            (declared, false),
+            admission.estimate(&name("a.so"), declared)
+        );
+    }
+
+    #[test]
+    fn test_no_learning() {
+        let admission = LocalResourceAdmission::new(1024 * MIB, 1000, false);
+        admission.record(&name("a.so"), 400 * MIB);
+        assert_eq!(
+            (ResourceEstimate::default(), false),
+            admission.estimate(&name("a.so"), ResourceEstimate::default())
+        );
+    }
+
+    #[test]
+    fn test_learned_per_identifier() {
+        let admission = LocalResourceAdmission::new(1024 * MIB, 1000, true);
+        admission.record(&name("small.so"), MIB);
+        admission.record(&name("large.so"), 800 * MIB);
+
+        let memory = |identifier| {
+            admission
+                .estimate(&name(identifier), ResourceEstimate::default())
+                .0
+                .memory_bytes
+        };
+        assert_eq!(Some(MIB), memory("small.so"));
+        assert_eq!(Some(800 * MIB), memory("large.so"));
+        // Unknown commands get the estimate of their category.
+        assert_eq!(Some(800 * MIB), memory("new.so"));
+    }
+
+    #[test]
+    fn test_persisted() -> anyhow::Result<()> {
+        let fs = ProjectRootTemp::new()?;
+        let state_dir = fs
+            .path()
+            .resolve(ProjectRelativePath::unchecked_new("local_resources"));
+
+        {
+            let admission = LocalResourceAdmission::load(1024 * MIB, 1000, &state_dir)?;
+            admission.record(&name("a.so"), 400 * MIB);
+        }
+
+        // Writes are asynchronous, so wait for them to land.
+        let mut estimates = DashMap::new();
+        for _ in 0..100 {
+            estimates = read_estimates(&VersionedSqliteDb::open(&state_dir)?.connection())?;
+            if estimates.len() == 2 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(Some(400 * MIB), estimates.get("cxx_link:a.so").map(|m| *m));
+
+        let admission = LocalResourceAdmission::load(1024 * MIB, 1000, &state_dir)?;
+        assert_eq!(
+            (
+                ResourceEstimate {
+                    memory_bytes: Some(400 * MIB),
+                    cpu_millicores: None,
+                },
+                true
+            ),
+            admission.estimate(&name("a.so"), ResourceEstimate::default())
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,310 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Sandboxed local execution (`build.sandbox_local_actions`): the forkserver runs the action in
+//! namespaces where the project root only contains its declared inputs and output directories.
+//! This module decides what to expose, and finds out what the action did outside of that.
+//!
+//! When `strace` is available, the action runs under it inside the sandbox, so that the files it
+//! failed to open because they were not exposed can be reported.
+
+use std::collections::BTreeSet;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::OnceLock;
+
+use buck2_core::fs::artifact_path_resolver::ArtifactFs;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::file_name::FileName;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_core::fs::project_rel_path::ProjectRelativePath;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+use buck2_execute::execute::action_digest::ActionDigest;
+use buck2_execute::execute::request::CommandExecutionInput;
+use buck2_execute::execute::request::CommandExecutionRequest;
+use buck2_forkserver::run::maybe_absolutize_exe;
+use itertools::Itertools;
+
+use crate::executors::strace::find_strace;
+use crate::executors::strace::strace_args;
+use crate::executors::strace::traced_reads;
+
+/// Don't flood the action error with paths found in the output of a failed action.
+const MAX_REPORTED_READS: usize = 10;
+
+/// Where `strace` writes in the sandboxed project root, which ends up at the top of the scaffold.
+const TRACE_FILE: &str = ".buck2-sandbox-trace";
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum SandboxViolation {
+    UndeclaredWrite(ProjectRelativePathBuf),
+    UndeclaredRead(ProjectRelativePathBuf),
+}
+
+impl fmt::Display for SandboxViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UndeclaredWrite(path) => {
+                write!(f, "wrote `{}`, which is not under a declared output", path)
+            }
+            Self::UndeclaredRead(path) => write!(
+                f,
+                "`{}` was referenced by the action but is not a declared input",
+                path
+            ),
+        }
+    }
+}
+
+#[derive(Debug, buck2_error::Error)]
+#[buck2(input)]
+#[error(
+    "Action violated the local sandbox (build.sandbox_local_actions):\n{}",
+    .0.iter().map(|v| format!("  {}", v)).join("\n")
+)]
+pub(crate) struct SandboxViolationsError(pub(crate) Vec<SandboxViolation>);
+
+/// The paths a sandboxed action gets to see.
+pub(crate) struct LocalSandbox {
+    scaffold_dir: ProjectRelativePathBuf,
+    readable: Vec<ProjectRelativePathBuf>,
+    writable: Vec<ProjectRelativePathBuf>,
+    working_directory: ProjectRelativePathBuf,
+    isolate_network: bool,
+    strace: Option<&'static PathBuf>,
+}
+
+impl LocalSandbox {
+    pub(crate) fn new(
+        artifact_fs: &ArtifactFs,
+        request: &CommandExecutionRequest,
+        action_digest: &ActionDigest,
+        isolate_network: bool,
+    ) -> anyhow::Result<Self> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+        let mut readable = Vec::new();
+        let mut writable = Vec::new();
+        for input in request.inputs() {
+            match input {
+                CommandExecutionInput::Artifact(group) => {
+                    for (artifact, _) in group.iter() {
+                        readable.push(artifact.resolve_path(artifact_fs)?);
+                    }
+                }
+                CommandExecutionInput::ActionMetadata(metadata) => {
+                    readable.push(
+                        artifact_fs
+                            .buck_out_path_resolver()
+                            .resolve_gen(&metadata.path),
+                    );
+                }
+                CommandExecutionInput::ScratchPath(path) => {
+                    writable.push(artifact_fs.buck_out_path_resolver().resolve_scratch(path));
+                }
+            }
+        }
+        for output in request.outputs() {
+            if let Some(path) = output.resolve(artifact_fs).path_to_create() {
+                writable.push(path.to_owned());
+            }
+        }
+
+        let scaffold_dir =
+            artifact_fs
+                .buck_out_path_resolver()
+                .root()
+                .join(ForwardRelativePath::unchecked_new(&format!(
+                    "sandbox/{}-{}",
+                    action_digest.raw_digest(),
+                    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+                )));
+
+        static STRACE: OnceLock<Option<PathBuf>> = OnceLock::new();
+        let strace = STRACE
+            .get_or_init(|| {
+                let strace = find_strace();
+                if strace.is_none() {
+                    tracing::warn!(
+                        "`strace` is not in PATH, undeclared reads of sandboxed local actions will only be guessed from the output of failed actions"
+                    );
+                }
+                strace
+            })
+            .as_ref();
+
+        Ok(Self {
+            scaffold_dir,
+            readable,
+            writable,
+            working_directory: request.working_directory().to_owned(),
+            isolate_network,
+            strace,
+        })
+    }
+
+    /// The command line that runs `args` under `strace` in the sandbox, if it is available.
+    pub(crate) fn wrap(
+        &self,
+        fs: &ProjectRoot,
+        args: &[String],
+    ) -> anyhow::Result<Option<Vec<String>>> {
+        let Some(strace) = self.strace else {
+            return Ok(None);
+        };
+        // Resolve the executable the same way it would be without `strace`.
+        let exe = maybe_absolutize_exe(&args[0], &fs.resolve(&self.working_directory))?;
+        let trace_file = fs.root().as_path().join(TRACE_FILE);
+        let mut wrapped = strace_args(strace, &trace_file, false);
+        wrapped.push(exe.to_string_lossy().into_owned());
+        wrapped.extend(args[1..].iter().cloned());
+        Ok(Some(wrapped))
+    }
+
+    fn is_undeclared_read(
+        &self,
+        fs: &ProjectRoot,
+        ancestors: &HashSet<&ProjectRelativePath>,
+        path: &ProjectRelativePath,
+    ) -> bool {
+        !path.is_empty()
+            && !self.is_exposed(path)
+            && !ancestors.contains(path)
+            && !path.starts_with(&self.scaffold_dir)
+            && fs.resolve(path).symlink_metadata().is_ok()
+    }
+
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub(crate) fn to_proto(&self, fs: &ProjectRoot) -> buck2_forkserver_proto::SandboxConfig {
+        let bytes = |p: &ProjectRelativePathBuf| p.as_str().as_bytes().to_vec();
+        buck2_forkserver_proto::SandboxConfig {
+            project_root: fs.root().as_os_str().as_encoded_bytes().to_vec(),
+            readable_paths: self.readable.iter().map(bytes).collect(),
+            writable_paths: self.writable.iter().map(bytes).collect(),
+            scaffold_dir: bytes(&self.scaffold_dir),
+            isolate_network: self.isolate_network,
+        }
+    }
+
+    fn is_exposed(&self, path: &ProjectRelativePath) -> bool {
+        self.readable
+            .iter()
+            .chain(&self.writable)
+            .any(|p| path.starts_with(p))
+    }
+
+    /// Looks for violations once the action has exited, and removes the scaffold.
+    ///
+    /// Writes are exact: whatever the action left in the scaffold. Reads are the files the action
+    /// failed to open because they exist but were not exposed. Without `strace`, they can't be
+    /// observed, so when the action failed we report the paths mentioned in its output instead.
+    pub(crate) fn finish(
+        &self,
+        fs: &ProjectRoot,
+        output: &[u8],
+        failed: bool,
+    ) -> anyhow::Result<Vec<SandboxViolation>> {
+        let mut violations = BTreeSet::new();
+        let mut trace = None;
+
+        let exposed: HashSet<&ProjectRelativePath> = self
+            .readable
+            .iter()
+            .chain(&self.writable)
+            .map(|p| &**p)
+            .collect();
+        let working_directory: &ProjectRelativePath = &self.working_directory;
+        let mut ancestors = HashSet::new();
+        for path in exposed.iter().copied().chain([working_directory]) {
+            let mut parent = path.parent();
+            while let Some(p) = parent {
+                if !ancestors.insert(p) {
+                    break;
+                }
+                parent = p.parent();
+            }
+        }
+        // The working directory gets created in the scaffold too.
+        ancestors.insert(working_directory);
+
+        let scaffold = fs.resolve(&self.scaffold_dir);
+        if fs_util::try_exists(&scaffold)? {
+            let mut queue = vec![ProjectRelativePathBuf::default()];
+            while let Some(dir) = queue.pop() {
+                for entry in fs_util::read_dir(scaffold.join(dir.as_forward_relative_path()))? {
+                    let entry = entry?;
+                    let file_name = entry.file_name();
+                    let Some(file_name) = file_name.to_str().and_then(|f| FileName::new(f).ok())
+                    else {
+                        continue;
+                    };
+                    let path = dir.join(file_name);
+                    if self.strace.is_some() && path.as_str() == TRACE_FILE {
+                        trace = Some(fs_util::read_to_string(entry.path())?);
+                        continue;
+                    }
+                    let path_ref: &ProjectRelativePath = &path;
+                    if exposed.contains(&path_ref) {
+                        // A mount point.
+                        continue;
+                    }
+                    if ancestors.contains(&path_ref) && entry.file_type()?.is_dir() {
+                        queue.push(path);
+                    } else {
+                        violations.insert(SandboxViolation::UndeclaredWrite(path));
+                    }
+                }
+            }
+            fs_util::remove_all(&scaffold)?;
+        }
+
+        if let Some(trace) = trace {
+            let working_directory = fs.resolve(&self.working_directory);
+            let reads = traced_reads(&trace, working_directory.as_path())
+                .into_iter()
+                .filter(|read| read.missing)
+                .filter_map(|read| {
+                    let path = read.path.strip_prefix(fs.root().as_path()).ok()?;
+                    ProjectRelativePath::new(path.to_str()?)
+                        .ok()
+                        .map(|p| p.to_owned())
+                })
+                .filter(|path| self.is_undeclared_read(fs, &ancestors, path))
+                .map(SandboxViolation::UndeclaredRead);
+            violations.extend(reads);
+            return Ok(violations.into_iter().collect());
+        }
+
+        if failed {
+            let root = fs.root().as_os_str().to_string_lossy().into_owned() + "/";
+            let output = String::from_utf8_lossy(output);
+            let reads = output
+                .split(|c: char| c.is_whitespace() || "'\"`:,;()[]<>=".contains(c))
+                .filter_map(|token| {
+                    let token = token.strip_prefix(&root).unwrap_or(token);
+                    // Single words are too likely to be something else.
+                    if !token.contains('/') {
+                        return None;
+                    }
+                    ProjectRelativePath::new(token.trim_end_matches(['.', '/'])).ok()
+                })
+                .unique()
+                .filter(|path| self.is_undeclared_read(fs, &ancestors, path))
+                .take(MAX_REPORTED_READS)
+                .map(|path| SandboxViolation::UndeclaredRead(path.to_owned()));
+            violations.extend(reads);
+        }
+
+        Ok(violations.into_iter().collect())
+    }
+}
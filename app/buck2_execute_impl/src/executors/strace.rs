@@ -0,0 +1,348 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Running commands under `strace`, and finding the files they read in its output.
+
+use std::collections::HashMap;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Calls that read a file, and the ones needed to know what directory relative paths are
+/// relative to.
+const TRACED_SYSCALLS: &str =
+    "trace=open,openat,openat2,execve,execveat,chdir,fchdir,clone,clone3,fork,vfork";
+
+/// `strace` in `PATH`, if any.
+pub(crate) fn find_strace() -> Option<PathBuf> {
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join("strace"))
+        .find(|path| path.is_file())
+}
+
+/// The `strace` command line, up to and including the `--` that precedes the traced command.
+/// Failed calls are needed to find out what a sandboxed action could not see.
+pub(crate) fn strace_args(strace: &Path, trace_file: &Path, only_successful: bool) -> Vec<String> {
+    let mut args = vec![
+        strace.to_string_lossy().into_owned(),
+        "-f".to_owned(),
+        "-qq".to_owned(),
+        // Print the paths of file descriptors, for `openat` relative to a directory fd.
+        "-y".to_owned(),
+    ];
+    if only_successful {
+        args.extend(["-e".to_owned(), "status=successful".to_owned()]);
+    }
+    args.extend([
+        "-e".to_owned(),
+        TRACED_SYSCALLS.to_owned(),
+        "-o".to_owned(),
+        trace_file.to_string_lossy().into_owned(),
+        "--".to_owned(),
+    ]);
+    args
+}
+
+/// A completed system call from the output of `strace -f -y`.
+#[derive(Debug, PartialEq)]
+struct Syscall {
+    pid: u32,
+    name: String,
+    args: String,
+    ret: String,
+    /// The errno name, e.g. `ENOENT`, if the call failed.
+    error: Option<String>,
+}
+
+/// A file an action opened for reading or executed.
+#[derive(Debug, PartialEq)]
+pub(crate) struct TracedRead {
+    pub(crate) path: PathBuf,
+    /// The call failed because the file does not exist.
+    pub(crate) missing: bool,
+}
+
+/// Parse one line of `strace -f` output, joining calls that were interrupted by another process.
+fn parse_line(line: &str, unfinished: &mut HashMap<u32, String>) -> Option<Syscall> {
+    let (pid, rest) = line.split_once(char::is_whitespace)?;
+    let pid = pid.parse().ok()?;
+    let rest = rest.trim_start();
+
+    if let Some(start) = rest.strip_suffix(" <unfinished ...>") {
+        unfinished.insert(pid, start.to_owned());
+        return None;
+    }
+    let joined;
+    let rest = if rest.starts_with("<... ") {
+        // `<... openat resumed>, O_RDONLY) = 3</path>`: the name and start of the arguments were
+        // printed on the unfinished line.
+        let start = unfinished.remove(&pid)?;
+        let (_, end) = rest.split_once(" resumed>")?;
+        joined = start + end;
+        joined.as_str()
+    } else {
+        rest
+    };
+    let (call, ret) = rest.rsplit_once(") = ")?;
+    let (name, args) = call.split_once('(')?;
+    let mut ret = ret.split_whitespace();
+    let value = ret.next()?;
+    Some(Syscall {
+        pid,
+        name: name.to_owned(),
+        args: args.to_owned(),
+        ret: value.to_owned(),
+        error: if value == "-1" {
+            ret.next().map(str::to_owned)
+        } else {
+            None
+        },
+    })
+}
+
+/// The value of the first string argument, unescaped.
+fn first_string_arg(args: &str) -> Option<String> {
+    let start = args.find('"')? + 1;
+    let mut out = Vec::new();
+    let mut chars = args[start..].bytes();
+    while let Some(c) = chars.next() {
+        match c {
+            b'"' => return String::from_utf8(out).ok(),
+            b'\\' => match chars.next()? {
+                b'n' => out.push(b'\n'),
+                b't' => out.push(b'\t'),
+                b'r' => out.push(b'\r'),
+                d @ b'0'..=b'7' => {
+                    let mut value = d - b'0';
+                    for _ in 0..2 {
+                        let mut peek = chars.clone();
+                        match peek.next() {
+                            Some(d @ b'0'..=b'7') => {
+                                value = value * 8 + (d - b'0');
+                                chars = peek;
+                            }
+                            _ => break,
+                        }
+                    }
+                    out.push(value);
+                }
+                other => out.push(other),
+            },
+            c => out.push(c),
+        }
+    }
+    None
+}
+
+/// The path of a file descriptor decoded by `-y`, e.g. `3</foo/bar>`.
+fn decoded_fd(s: &str) -> Option<&str> {
+    let (_, path) = s.split_once('<')?;
+    path.strip_suffix('>')
+}
+
+/// What the path argument of an `*at` call is relative to.
+fn dir_arg(args: &str, cwd: &Path) -> Option<PathBuf> {
+    let (dirfd, _) = args.split_once(',')?;
+    if dirfd == "AT_FDCWD" {
+        Some(cwd.to_owned())
+    } else {
+        decoded_fd(dirfd).map(PathBuf::from)
+    }
+}
+
+/// Resolves `.` and `..` without looking at the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The files opened for reading or executed in `trace`, or that could not be because they don't
+/// exist, for a command that started in `cwd`. Other failures are ignored.
+pub(crate) fn traced_reads(trace: &str, cwd: &Path) -> Vec<TracedRead> {
+    let mut unfinished = HashMap::new();
+    let mut cwds: HashMap<u32, PathBuf> = HashMap::new();
+    let mut reads = Vec::new();
+
+    for line in trace.lines() {
+        let Some(call) = parse_line(line, &mut unfinished) else {
+            continue;
+        };
+        let pid_cwd = cwds
+            .get(&call.pid)
+            .cloned()
+            .unwrap_or_else(|| cwd.to_owned());
+        let args = call.args.as_str();
+
+        match call.name.as_str() {
+            "clone" | "clone3" | "fork" | "vfork" => {
+                if let Ok(child) = call.ret.parse::<u32>() {
+                    cwds.insert(child, pid_cwd);
+                }
+            }
+            "chdir" | "fchdir" if call.error.is_some() => {}
+            "chdir" => {
+                if let Some(dir) = first_string_arg(args) {
+                    cwds.insert(call.pid, normalize(&pid_cwd.join(dir)));
+                }
+            }
+            "fchdir" => {
+                if let Some(dir) = decoded_fd(args) {
+                    cwds.insert(call.pid, PathBuf::from(dir));
+                }
+            }
+            "open" | "openat" | "openat2" | "execve" | "execveat" => {
+                if call.name.starts_with("open")
+                    && (args.contains("O_WRONLY") || args.contains("O_DIRECTORY"))
+                {
+                    continue;
+                }
+                let base = match call.name.as_str() {
+                    "open" | "execve" => Some(pid_cwd),
+                    _ => dir_arg(args, &pid_cwd),
+                };
+                let Some(path) = first_string_arg(args) else {
+                    continue;
+                };
+                let path = Path::new(&path);
+                let path = match base {
+                    _ if path.is_absolute() => path.to_owned(),
+                    Some(base) => base.join(path),
+                    None => continue,
+                };
+                let missing = match call.error.as_deref() {
+                    None => false,
+                    Some("ENOENT") => true,
+                    Some(_) => continue,
+                };
+                reads.push(TracedRead {
+                    path: normalize(&path),
+                    missing,
+                });
+            }
+            _ => {}
+        }
+    }
+    reads
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_string_arg() {
+        assert_eq!(
+            first_string_arg(r#"AT_FDCWD, "foo/bar.h", O_RDONLY"#).as_deref(),
+            Some("foo/bar.h")
+        );
+        assert_eq!(
+            first_string_arg(r#""a\"b\\c\040d""#).as_deref(),
+            Some("a\"b\\c d")
+        );
+        assert_eq!(first_string_arg("3</foo>"), None);
+    }
+
+    #[test]
+    fn test_parse_unfinished() {
+        let mut unfinished = HashMap::new();
+        assert_eq!(
+            parse_line(
+                r#"12  openat(AT_FDCWD, "a.h", O_RDONLY <unfinished ...>"#,
+                &mut unfinished
+            ),
+            None
+        );
+        assert_eq!(
+            parse_line(r#"13  chdir("x") = 0"#, &mut unfinished),
+            Some(Syscall {
+                pid: 13,
+                name: "chdir".to_owned(),
+                args: r#""x""#.to_owned(),
+                ret: "0".to_owned(),
+                error: None,
+            })
+        );
+        assert_eq!(
+            parse_line(
+                r#"12  <... openat resumed>|O_CLOEXEC) = 3</root/a.h>"#,
+                &mut unfinished
+            ),
+            Some(Syscall {
+                pid: 12,
+                name: "openat".to_owned(),
+                args: r#"AT_FDCWD, "a.h", O_RDONLY|O_CLOEXEC"#.to_owned(),
+                ret: "3</root/a.h>".to_owned(),
+                error: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_traced_reads() {
+        let trace = r#"100 execve("/usr/bin/sh", ["sh", "-c", "cd sub && cc ../a.c"], 0x7ffd /* 3 vars */) = 0
+100 openat(AT_FDCWD, "/etc/ld.so.cache", O_RDONLY|O_CLOEXEC) = 3</etc/ld.so.cache>
+100 chdir("sub") = 0
+100 clone(child_stack=NULL, flags=CLONE_CHILD_SETTID|SIGCHLD) = 101
+101 execve("/usr/bin/cc", ["cc", "../a.c"], 0x5581 /* 3 vars */) = 0
+101 openat(AT_FDCWD, "../a.c", O_RDONLY) = 3</repo/a.c>
+101 openat(AT_FDCWD, "inc", O_RDONLY|O_DIRECTORY) = 4</repo/sub/inc>
+101 openat(4</repo/sub/inc>, "b.h", O_RDONLY) = 5</repo/sub/inc/b.h>
+101 openat(AT_FDCWD, "a.o", O_WRONLY|O_CREAT|O_TRUNC, 0666) = 6</repo/sub/a.o>
+102 open("c.h", O_RDONLY) = 3</repo/c.h>
+"#;
+        assert_eq!(
+            traced_reads(trace, Path::new("/repo"))
+                .into_iter()
+                .map(|read| read.path)
+                .collect::<Vec<_>>(),
+            vec![
+                PathBuf::from("/usr/bin/sh"),
+                PathBuf::from("/etc/ld.so.cache"),
+                PathBuf::from("/usr/bin/cc"),
+                PathBuf::from("/repo/a.c"),
+                PathBuf::from("/repo/sub/inc/b.h"),
+                // Not a child we saw being created, so it is assumed to start in the working
+                // directory.
+                PathBuf::from("/repo/c.h"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_traced_missing_reads() {
+        let trace = r#"100 openat(AT_FDCWD, "a.h", O_RDONLY) = -1 ENOENT (No such file or directory)
+100 openat(AT_FDCWD, "b.h", O_RDONLY) = -1 EACCES (Permission denied)
+100 chdir("gone") = -1 ENOENT (No such file or directory)
+100 openat(AT_FDCWD, "c.h", O_RDONLY) = 3</repo/c.h>
+"#;
+        assert_eq!(
+            traced_reads(trace, Path::new("/repo")),
+            vec![
+                TracedRead {
+                    path: PathBuf::from("/repo/a.h"),
+                    missing: true,
+                },
+                // The failed `chdir` did not change the working directory.
+                TracedRead {
+                    path: PathBuf::from("/repo/c.h"),
+                    missing: false,
+                },
+            ]
+        );
+    }
+}
@@ -9,6 +9,7 @@
 
 use std::collections::HashMap;
 use std::ffi::OsString;
+use std::future::Future;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -26,6 +27,7 @@ use buck2_execute::execute::manager::CommandExecutionManagerWithClaim;
 use buck2_execute::execute::output::CommandStdStreams;
 use buck2_execute::execute::request::CommandExecutionRequest;
 use buck2_execute::execute::request::WorkerId;
+use buck2_execute::execute::request::WorkerProtocol;
 use buck2_execute::execute::request::WorkerSpec;
 use buck2_execute::execute::result::CommandExecutionMetadata;
 use buck2_execute::execute::result::CommandExecutionResult;
@@ -44,6 +46,8 @@ use indexmap::IndexMap;
 use tokio::task::JoinHandle;
 use tonic::transport::Channel;
 
+use crate::executors::bazel_worker::BazelWorker;
+
 #[derive(buck2_error::Error, Debug)]
 pub enum WorkerInitError {
     #[error("Worker failed to spawn: {0}")]
@@ -149,6 +153,8 @@ fn spawn_via_forkserver(
                 stderr: stderr_path.as_os_str().as_bytes().into(),
             }),
             graceful_shutdown_timeout_s,
+            sandbox: None,
+            resource_limits: None,
         };
         apply_local_execution_environment(&mut req, &working_directory, env, None);
         let res = forkserver
@@ -183,6 +189,7 @@ fn spawn_via_forkserver(
 
 async fn spawn_worker(
     worker_spec: &WorkerSpec,
+    instance: usize,
     env: impl IntoIterator<Item = (OsString, OsString)>,
     root: &AbsNormPathBuf,
     forkserver: ForkserverClient,
@@ -191,7 +198,10 @@ async fn spawn_worker(
     check_child_liveness: bool,
 ) -> Result<WorkerHandle, WorkerInitError> {
     // Use fixed length path at /tmp to avoid 108 character limit for unix domain sockets
-    let dir_name = format!("{}-{}", dispatcher.trace_id(), worker_spec.id);
+    let dir_name = match instance {
+        0 => format!("{}-{}", dispatcher.trace_id(), worker_spec.id),
+        _ => format!("{}-{}-{}", dispatcher.trace_id(), worker_spec.id, instance),
+    };
     let worker_dir = AbsNormPathBuf::from("/tmp/buck2_worker".to_owned())
         .map_err(|e| WorkerInitError::InternalError(e.into()))?
         .join(FileName::unchecked_new(&dir_name));
@@ -213,6 +223,33 @@ async fn spawn_worker(
         args.join(" ")
     );
 
+    if let WorkerProtocol::Bazel {
+        encoding,
+        multiplex,
+        supports_cancellation,
+    } = worker_spec.protocol
+    {
+        let (liveliness_observer, liveliness_guard) = LivelinessGuard::create();
+        let (worker, exit) = BazelWorker::spawn(
+            &args,
+            env.into_iter().collect(),
+            root,
+            encoding,
+            multiplex,
+            supports_cancellation,
+            &stderr_path,
+            liveliness_observer,
+        )
+        .map_err(|e| WorkerInitError::SpawnFailed(format!("{:#}", e)))?;
+        return Ok(WorkerHandle::new(
+            WorkerConnection::Bazel(worker),
+            watch_child_exit(exit, check_child_liveness),
+            stdout_path,
+            stderr_path,
+            liveliness_guard,
+        ));
+    }
+
     let worker_env = vec![("WORKER_SOCKET", socket_path.as_os_str())]
         .into_iter()
         .map(|(k, v)| (OsString::from(k), OsString::from(v)));
@@ -289,31 +326,46 @@ async fn spawn_worker(
         }?
     };
 
+    tracing::info!("Connected to socket for spawned worker: {}", socket_path);
+    let client = WorkerClient::new(channel);
+    Ok(WorkerHandle::new(
+        WorkerConnection::Grpc(client),
+        watch_child_exit(check_exit, check_child_liveness),
+        stdout_path,
+        stderr_path,
+        liveliness_guard,
+    ))
+}
+
+/// Returns an observer that dies when the worker process exits.
+fn watch_child_exit(
+    exit: impl Future + Send + 'static,
+    check_child_liveness: bool,
+) -> Arc<dyn LivelinessObserver> {
     let (child_exited_observer, child_exited_guard) = LivelinessGuard::create();
     tokio::spawn(async move {
-        drop(check_exit.await);
+        drop(exit.await);
         if check_child_liveness {
             drop(child_exited_guard);
         } else {
             child_exited_guard.forget();
         }
     });
-
-    tracing::info!("Connected to socket for spawned worker: {}", socket_path);
-    let client = WorkerClient::new(channel);
-    Ok(WorkerHandle::new(
-        client,
-        child_exited_observer,
-        stdout_path,
-        stderr_path,
-        liveliness_guard,
-    ))
+    child_exited_observer
 }
 
 type WorkerFuture = Shared<BoxFuture<'static, Result<Arc<WorkerHandle>, Arc<WorkerInitError>>>>;
 
+/// Bazel workers can't be given an environment per request, so like in Bazel, actions with
+/// different environments get different workers. Buck2 workers get theirs with each command.
+#[derive(Hash, PartialEq, Eq)]
+struct WorkerKey {
+    id: WorkerId,
+    env: Option<Vec<(OsString, OsString)>>,
+}
+
 pub struct WorkerPool {
-    workers: Arc<parking_lot::Mutex<HashMap<WorkerId, WorkerFuture>>>,
+    workers: Arc<parking_lot::Mutex<HashMap<WorkerKey, WorkerFuture>>>,
     brokers: Arc<parking_lot::Mutex<HashMap<WorkerId, Arc<HostSharingBroker>>>>,
     graceful_shutdown_timeout_s: Option<u32>,
     check_child_liveness: bool,
@@ -353,19 +405,28 @@ impl WorkerPool {
         forkserver: ForkserverClient,
         dispatcher: EventDispatcher,
     ) -> (bool, WorkerFuture) {
+        let mut env: Vec<(OsString, OsString)> = env.into_iter().collect();
+        env.sort();
+        let key = WorkerKey {
+            id: worker_spec.id,
+            env: match worker_spec.protocol {
+                WorkerProtocol::Buck2 => None,
+                WorkerProtocol::Bazel { .. } => Some(env.clone()),
+            },
+        };
         let mut workers = self.workers.lock();
-        if let Some(worker_fut) = workers.get(&worker_spec.id) {
+        if let Some(worker_fut) = workers.get(&key) {
             (false, worker_fut.clone())
         } else {
-            let worker_id = worker_spec.id;
+            let instance = workers.keys().filter(|k| k.id == worker_spec.id).count();
             let worker_spec = worker_spec.clone();
             let root = root.clone();
-            let env: Vec<(OsString, OsString)> = env.into_iter().collect();
             let graceful_shutdown_timeout_s = self.graceful_shutdown_timeout_s;
             let check_child_liveness = self.check_child_liveness;
             let fut = async move {
                 match spawn_worker(
                     &worker_spec,
+                    instance,
                     env,
                     &root,
                     forkserver,
@@ -382,14 +443,19 @@ impl WorkerPool {
             .boxed()
             .shared();
 
-            workers.insert(worker_id, fut.clone());
+            workers.insert(key, fut.clone());
             (true, fut)
         }
     }
 }
 
+enum WorkerConnection {
+    Grpc(WorkerClient<Channel>),
+    Bazel(BazelWorker),
+}
+
 pub struct WorkerHandle {
-    client: WorkerClient<Channel>,
+    connection: WorkerConnection,
     child_exited_observer: Arc<dyn LivelinessObserver>,
     stdout_path: AbsNormPathBuf,
     stderr_path: AbsNormPathBuf,
@@ -398,14 +464,14 @@ pub struct WorkerHandle {
 
 impl WorkerHandle {
     fn new(
-        client: WorkerClient<Channel>,
+        connection: WorkerConnection,
         child_exited_observer: Arc<dyn LivelinessObserver>,
         stdout_path: AbsNormPathBuf,
         stderr_path: AbsNormPathBuf,
         liveliness_guard: LivelinessGuard,
     ) -> Self {
         Self {
-            client,
+            connection,
             child_exited_observer,
             stdout_path,
             stderr_path,
@@ -436,6 +502,61 @@ impl WorkerHandle {
         args: &[String],
         env: Vec<(OsString, OsString)>,
         timeout: Option<Duration>,
+    ) -> (GatherOutputStatus, Vec<u8>, Vec<u8>) {
+        tokio::select! {
+            res = self.send(args, env, timeout) => res,
+            _ = self.child_exited_observer.while_alive() => {
+                (
+                    GatherOutputStatus::SpawnFailed(format!(
+                        "Worker exited while running command, see worker logs:\n{}\n{}",
+                        self.stdout_path, self.stderr_path,
+                    )),
+                    vec![],
+                    vec![],
+                )
+            }
+        }
+    }
+
+    async fn send(
+        &self,
+        args: &[String],
+        env: Vec<(OsString, OsString)>,
+        timeout: Option<Duration>,
+    ) -> (GatherOutputStatus, Vec<u8>, Vec<u8>) {
+        match &self.connection {
+            WorkerConnection::Grpc(client) => {
+                self.send_execute_command(client.clone(), args, env, timeout)
+                    .await
+            }
+            WorkerConnection::Bazel(worker) => {
+                // Bazel workers get their environment when they are started, and the pool
+                // starts another worker for actions with a different one.
+                tracing::info!(
+                    "Sending worker command:\nWorkRequest {{ arguments: {:?} }}\n",
+                    args
+                );
+                match worker.exec(args, timeout).await {
+                    Ok((status, stderr)) => (status, vec![], stderr),
+                    Err(e) => (
+                        GatherOutputStatus::SpawnFailed(format!(
+                            "Error sending WorkRequest to worker: {:#}, see worker logs:\n{}",
+                            e, self.stderr_path,
+                        )),
+                        vec![],
+                        vec![],
+                    ),
+                }
+            }
+        }
+    }
+
+    async fn send_execute_command(
+        &self,
+        mut client: WorkerClient<Channel>,
+        args: &[String],
+        env: Vec<(OsString, OsString)>,
+        timeout: Option<Duration>,
     ) -> (GatherOutputStatus, Vec<u8>, Vec<u8>) {
         tracing::info!(
             "Sending worker command:\nExecuteCommand {{ argv: {:?}, env: {:?} }}\n",
@@ -451,49 +572,34 @@ impl WorkerHandle {
             timeout_s: timeout.map(|v| v.as_secs()),
         };
 
-        let mut client = self.client.clone();
-        tokio::select! {
-            response = client.execute(request) => {
-                match response {
-                    Ok(response) => {
-                        let exec_response: ExecuteResponse = response.into_inner();
-                        tracing::info!("Worker response:\n{:?}\n", exec_response);
-                        if let Some(timeout) = exec_response.timed_out_after_s {
-                            (
-                                GatherOutputStatus::TimedOut(Duration::from_secs(timeout)),
-                                vec![],
-                                exec_response.stderr.into(),
-                            )
-                        } else {
-                            (
-                                GatherOutputStatus::Finished {
-                                    exit_code: exec_response.exit_code,
-                                    execution_stats: None,
-                                },
-                                vec![],
-                                exec_response.stderr.into(),
-                            )
-                        }
-                    }
-                    Err(err) => {
-                        (
-                            GatherOutputStatus::SpawnFailed(format!(
-                                "Error sending ExecuteCommand to worker: {:?}, see worker logs:\n{}\n{}",
-                                err, self.stdout_path, self.stderr_path,
-                            )),
-                            // stdout/stderr logs for worker are for multiple commands, probably do not want to dump contents here
-                            vec![],
-                            vec![],
-                        )
-                    }
+        match client.execute(request).await {
+            Ok(response) => {
+                let exec_response: ExecuteResponse = response.into_inner();
+                tracing::info!("Worker response:\n{:?}\n", exec_response);
+                if let Some(timeout) = exec_response.timed_out_after_s {
+                    (
+                        GatherOutputStatus::TimedOut(Duration::from_secs(timeout)),
+                        vec![],
+                        exec_response.stderr.into(),
+                    )
+                } else {
+                    (
+                        GatherOutputStatus::Finished {
+                            exit_code: exec_response.exit_code,
+                            execution_stats: None,
+                        },
+                        vec![],
+                        exec_response.stderr.into(),
+                    )
                 }
             }
-            _ = self.child_exited_observer.while_alive() => {
+            Err(err) => {
                 (
                     GatherOutputStatus::SpawnFailed(format!(
-                        "Worker exited while running command, see worker logs:\n{}\n{}",
-                        self.stdout_path, self.stderr_path,
+                        "Error sending ExecuteCommand to worker: {:?}, see worker logs:\n{}\n{}",
+                        err, self.stdout_path, self.stderr_path,
                     )),
+                    // stdout/stderr logs for worker are for multiple commands, probably do not want to dump contents here
                     vec![],
                     vec![],
                 )
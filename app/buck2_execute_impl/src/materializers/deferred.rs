@@ -11,7 +11,9 @@ pub mod clean_stale;
 mod extension;
 mod file_tree;
 mod io_handler;
+pub mod local_cas;
 mod subscriptions;
+mod verify;
 
 #[cfg(test)]
 mod tests;
@@ -94,6 +96,7 @@ use futures::stream::BoxStream;
 use futures::stream::FuturesOrdered;
 use futures::stream::Stream;
 use futures::stream::StreamExt;
+use futures::stream::TryStreamExt;
 use futures::Future;
 use gazebo::prelude::*;
 use itertools::Itertools;
@@ -116,6 +119,8 @@ use crate::materializers::deferred::extension::ExtensionCommand;
 use crate::materializers::deferred::file_tree::FileTree;
 use crate::materializers::deferred::io_handler::DefaultIoHandler;
 use crate::materializers::deferred::io_handler::IoHandler;
+use crate::materializers::deferred::local_cas::LocalCasConfig;
+use crate::materializers::deferred::local_cas::LocalCasStore;
 use crate::materializers::deferred::subscriptions::MaterializerSubscriptionOperation;
 use crate::materializers::deferred::subscriptions::MaterializerSubscriptions;
 use crate::materializers::sqlite::MaterializerState;
@@ -166,6 +171,9 @@ pub struct DeferredMaterializerAccessor<T: IoHandler + 'static> {
 
     /// Logs verbose events about materializer to the event log when enabled.
     verbose_materializer_log: bool,
+
+    /// The `buck2.buck_out_max_size_gb` budget, also enforced by `buck2 clean --stale`.
+    buck_out_max_size_bytes: Option<u64>,
 }
 
 pub type DeferredMaterializer = DeferredMaterializerAccessor<DefaultIoHandler>;
@@ -182,6 +190,9 @@ impl<T: IoHandler> Drop for DeferredMaterializerAccessor<T> {
 pub struct DeferredMaterializerStats {
     declares: AtomicU64,
     declares_reused: AtomicU64,
+    local_cas_hits: AtomicU64,
+    local_cas_misses: AtomicU64,
+    local_cas_import_mismatches: AtomicU64,
 }
 
 fn access_time_update_max_buffer_size() -> anyhow::Result<usize> {
@@ -195,6 +206,7 @@ pub struct DeferredMaterializerConfigs {
     pub update_access_times: AccessTimesUpdates,
     pub verbose_materializer_log: bool,
     pub clean_stale_config: Option<CleanStaleConfig>,
+    pub local_cas: Option<LocalCasConfig>,
 }
 
 pub struct TtlRefreshConfiguration {
@@ -420,10 +432,13 @@ enum MaterializerCommand<T: 'static> {
     /// list that have been declared but not yet been materialized. When the
     /// materialization starts, a future is sent back through the provided
     /// Sender; this future will be resolved when the materialization
-    /// concludes (whether successfully or not).
+    /// concludes (whether successfully or not). If the flag is set, the
+    /// request comes from an `--offline` command, and artifacts that would
+    /// need to be downloaded fail to materialize instead.
     Ensure(
         Vec<ProjectRelativePathBuf>,
         EventDispatcher,
+        bool,
         oneshot::Sender<BoxStream<'static, Result<(), MaterializationError>>>,
     ),
 
@@ -461,7 +476,7 @@ impl<T> std::fmt::Debug for MaterializerCommand<T> {
             MaterializerCommand::InvalidateFilePaths(paths, ..) => {
                 write!(f, "InvalidateFilePaths({:?})", paths)
             }
-            MaterializerCommand::Ensure(paths, _, _) => write!(f, "Ensure({:?}, _)", paths,),
+            MaterializerCommand::Ensure(paths, _, _, _) => write!(f, "Ensure({:?}, _)", paths,),
             MaterializerCommand::Subscription(op) => write!(f, "Subscription({:?})", op,),
             MaterializerCommand::Extension(ext) => write!(f, "Extension({:?})", ext),
             MaterializerCommand::Abort => write!(f, "Abort"),
@@ -914,21 +929,14 @@ impl<T: IoHandler + Allocative> Materializer for DeferredMaterializerAccessor<T>
         &self,
         artifact_paths: Vec<ProjectRelativePathBuf>,
     ) -> anyhow::Result<BoxStream<'static, Result<(), MaterializationError>>> {
-        let event_dispatcher = get_dispatcher();
+        self.ensure(artifact_paths, false).await
+    }
 
-        // TODO: display [materializing] in superconsole
-        let (sender, recv) = oneshot::channel();
-        self.command_sender
-            .send(MaterializerCommand::Ensure(
-                artifact_paths,
-                event_dispatcher,
-                sender,
-            ))
-            .context("Sending Ensure() command.")?;
-        let materialization_fut = recv
-            .await
-            .context("Receiving materialization future from command thread.")?;
-        Ok(materialization_fut)
+    async fn materialize_many_offline(
+        &self,
+        artifact_paths: Vec<ProjectRelativePathBuf>,
+    ) -> anyhow::Result<BoxStream<'static, Result<(), MaterializationError>>> {
+        self.ensure(artifact_paths, true).await
     }
 
     async fn try_materialize_final_artifact(
@@ -943,6 +951,21 @@ impl<T: IoHandler + Allocative> Materializer for DeferredMaterializerAccessor<T>
         }
     }
 
+    async fn try_materialize_final_artifact_offline(
+        &self,
+        artifact_path: ProjectRelativePathBuf,
+    ) -> anyhow::Result<bool> {
+        if self.materialize_final_artifacts {
+            self.materialize_many_offline(vec![artifact_path])
+                .await?
+                .try_collect::<()>()
+                .await?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
     async fn get_materialized_file_paths(
         &self,
         paths: Vec<ProjectRelativePathBuf>,
@@ -969,6 +992,39 @@ impl<T: IoHandler + Allocative> Materializer for DeferredMaterializerAccessor<T>
         snapshot.deferred_materializer_declares_reused =
             self.stats.declares_reused.load(Ordering::Relaxed);
         snapshot.deferred_materializer_queue_size = self.command_sender.counters.queue_size() as _;
+        snapshot.deferred_materializer_local_cas_hits =
+            self.stats.local_cas_hits.load(Ordering::Relaxed);
+        snapshot.deferred_materializer_local_cas_misses =
+            self.stats.local_cas_misses.load(Ordering::Relaxed);
+        snapshot.deferred_materializer_local_cas_import_mismatches = self
+            .stats
+            .local_cas_import_mismatches
+            .load(Ordering::Relaxed);
+    }
+}
+
+impl<T: IoHandler + Allocative> DeferredMaterializerAccessor<T> {
+    async fn ensure(
+        &self,
+        artifact_paths: Vec<ProjectRelativePathBuf>,
+        offline: bool,
+    ) -> anyhow::Result<BoxStream<'static, Result<(), MaterializationError>>> {
+        let event_dispatcher = get_dispatcher();
+
+        // TODO: display [materializing] in superconsole
+        let (sender, recv) = oneshot::channel();
+        self.command_sender
+            .send(MaterializerCommand::Ensure(
+                artifact_paths,
+                event_dispatcher,
+                offline,
+                sender,
+            ))
+            .context("Sending Ensure() command.")?;
+        let materialization_fut = recv
+            .await
+            .context("Receiving materialization future from command thread.")?;
+        Ok(materialization_fut)
     }
 }
 
@@ -1025,6 +1081,9 @@ impl DeferredMaterializerAccessor<DefaultIoHandler> {
             re_client_manager,
             io_executor,
             http_client,
+            configs
+                .local_cas
+                .map(|config| Arc::new(LocalCasStore::new(config, digest_config, stats.dupe()))),
         ));
 
         let command_processor = {
@@ -1053,6 +1112,10 @@ impl DeferredMaterializerAccessor<DefaultIoHandler> {
         };
 
         let access_time_update_max_buffer_size = access_time_update_max_buffer_size()?;
+        let buck_out_max_size_bytes = configs
+            .clean_stale_config
+            .as_ref()
+            .and_then(|config| config.max_size_bytes);
 
         let command_thread = thread_spawn("buck2-dm", {
             move || {
@@ -1083,6 +1146,7 @@ impl DeferredMaterializerAccessor<DefaultIoHandler> {
             materializer_state_info,
             stats,
             verbose_materializer_log: configs.verbose_materializer_log,
+            buck_out_max_size_bytes,
         })
     }
 }
@@ -1326,10 +1390,15 @@ impl<T: IoHandler> DeferredMaterializerCommandProcessor<T> {
                 Op::CleanStaleRequest => {
                     if let Some(config) = clean_stale_config.as_ref() {
                         let dispatcher = self.daemon_dispatcher.dupe();
+                        let keep_since_time = match config.artifact_ttl {
+                            Some(artifact_ttl) => chrono::Utc::now() - artifact_ttl,
+                            None => DateTime::<Utc>::MIN_UTC,
+                        };
                         let cmd = CleanStaleArtifactsCommand {
-                            keep_since_time: chrono::Utc::now() - config.artifact_ttl,
+                            keep_since_time,
                             dry_run: config.dry_run,
                             tracked_only: false,
+                            max_size_bytes: config.max_size_bytes,
                             dispatcher,
                         };
                         stream.clean_stale_fut = Some(cmd.create_clean_fut(&mut self, None));
@@ -1416,7 +1485,7 @@ impl<T: IoHandler> DeferredMaterializerCommandProcessor<T> {
                     .ok();
             }
             // Entry point for `ensure_materialized` calls
-            MaterializerCommand::Ensure(paths, event_dispatcher, fut_sender) => {
+            MaterializerCommand::Ensure(paths, event_dispatcher, offline, fut_sender) => {
                 self.maybe_log_command(&event_dispatcher, || {
                     buck2_data::materializer_command::Data::Ensure(
                         buck2_data::materializer_command::Ensure {
@@ -1426,7 +1495,7 @@ impl<T: IoHandler> DeferredMaterializerCommandProcessor<T> {
                 });
 
                 fut_sender
-                    .send(self.materialize_many_artifacts(paths, event_dispatcher))
+                    .send(self.materialize_many_artifacts(paths, event_dispatcher, offline))
                     .ok();
             }
             MaterializerCommand::Subscription(sub) => sub.execute(self),
@@ -1535,20 +1604,26 @@ impl<T: IoHandler> DeferredMaterializerCommandProcessor<T> {
         &mut self,
         paths: Vec<ProjectRelativePathBuf>,
         event_dispatcher: EventDispatcher,
+        offline: bool,
     ) -> BoxStream<'static, Result<(), MaterializationError>> {
         let tasks = paths.into_iter().filter_map(|path| {
-            self.materialize_artifact(path.as_ref(), event_dispatcher.dupe())
-                .map(move |fut| {
-                    fut.map_err(move |e| match e {
-                        SharedMaterializingError::Error(source) => MaterializationError::Error {
-                            path,
-                            source: source.into(),
-                        },
-                        SharedMaterializingError::NotFound(source) => {
-                            MaterializationError::NotFound { source }
-                        }
-                    })
+            self.materialize_artifact_recurse(
+                MaterializeStack::Empty,
+                path.as_ref(),
+                event_dispatcher.dupe(),
+                offline,
+            )
+            .map(move |fut| {
+                fut.map_err(move |e| match e {
+                    SharedMaterializingError::Error(source) => MaterializationError::Error {
+                        path,
+                        source: source.into(),
+                    },
+                    SharedMaterializingError::NotFound(source) => {
+                        MaterializationError::NotFound { source }
+                    }
                 })
+            })
         });
 
         tasks.collect::<FuturesOrdered<_>>().boxed()
@@ -1788,19 +1863,21 @@ impl<T: IoHandler> DeferredMaterializerCommandProcessor<T> {
         path: &ProjectRelativePath,
         event_dispatcher: EventDispatcher,
     ) -> Option<MaterializingFuture> {
-        self.materialize_artifact_recurse(MaterializeStack::Empty, path, event_dispatcher)
+        self.materialize_artifact_recurse(MaterializeStack::Empty, path, event_dispatcher, false)
     }
 
+    /// `offline` is set for requests from `--offline` commands, which must not download anything.
     fn materialize_artifact_recurse(
         &mut self,
         stack: MaterializeStack<'_>,
         path: &ProjectRelativePath,
         event_dispatcher: EventDispatcher,
+        offline: bool,
     ) -> Option<MaterializingFuture> {
         let stack = MaterializeStack::Child(&stack, path);
         // We only add context to outer error, because adding context to the future
         // is expensive. Errors in futures should add stack context themselves.
-        match self.materialize_artifact_inner(stack, path, event_dispatcher) {
+        match self.materialize_artifact_inner(stack, path, event_dispatcher, offline) {
             Ok(res) => res,
             Err(e) => Some(
                 future::err(SharedMaterializingError::Error(
@@ -1817,6 +1894,7 @@ impl<T: IoHandler> DeferredMaterializerCommandProcessor<T> {
         stack: MaterializeStack<'_>,
         path: &ProjectRelativePath,
         event_dispatcher: EventDispatcher,
+        offline: bool,
     ) -> anyhow::Result<Option<MaterializingFuture>> {
         // TODO(nga): rewrite without recursion or figure out why we overflow stack here.
         check_stack_overflow().tag_anyhow(ErrorTag::ServerStackOverflow)?;
@@ -1912,6 +1990,7 @@ impl<T: IoHandler> DeferredMaterializerCommandProcessor<T> {
                             MaterializeStack::Child(&stack, path),
                             a.src.as_ref(),
                             event_dispatcher.dupe(),
+                            offline,
                         )
                     })
                     .collect::<Vec<_>>(),
@@ -1934,6 +2013,7 @@ impl<T: IoHandler> DeferredMaterializerCommandProcessor<T> {
                         MaterializeStack::Child(&stack, path),
                         p.as_ref(),
                         event_dispatcher.dupe(),
+                        offline,
                     )
                 })
                 .collect::<Vec<_>>(),
@@ -1975,6 +2055,7 @@ impl<T: IoHandler> DeferredMaterializerCommandProcessor<T> {
                                 method,
                                 entry.dupe(),
                                 event_dispatcher.dupe(),
+                                offline,
                                 cancellations,
                             )
                         };
@@ -17,6 +17,7 @@ use buck2_common::legacy_configs::configs::LegacyBuckConfig;
 use buck2_common::legacy_configs::key::BuckconfigKeyRef;
 use buck2_common::liveliness_observer::LivelinessGuard;
 use buck2_common::liveliness_observer::LivelinessObserverSync;
+use buck2_common::liveliness_observer::NoopLivelinessObserver;
 use buck2_core::fs::fs_util;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
 use buck2_core::fs::paths::file_name::FileName;
@@ -52,11 +53,16 @@ use crate::materializers::deferred::ArtifactTree;
 use crate::materializers::deferred::DeferredMaterializerCommandProcessor;
 use crate::materializers::sqlite::MaterializerStateSqliteDb;
 
+/// Maximum number of `UntrackedFile` and `EvictedArtifact` events a clean emits of each.
+const MAX_LOGGED_PATHS: usize = 2000;
+
 #[derive(Debug, Clone)]
 pub struct CleanStaleArtifactsCommand {
     pub keep_since_time: DateTime<Utc>,
     pub dry_run: bool,
     pub tracked_only: bool,
+    /// If set, also evict the least recently accessed artifacts until `buck-out` fits.
+    pub max_size_bytes: Option<u64>,
     pub dispatcher: EventDispatcher,
 }
 
@@ -245,6 +251,10 @@ impl CleanStaleArtifactsCommand {
             .visit_recursively(gen_path, gen_subtree)?;
         };
 
+        if let Some(max_size_bytes) = self.max_size_bytes {
+            evict_for_size(&mut found_paths, max_size_bytes);
+        }
+
         let mut stats = stats_for_paths(&found_paths);
         stats.scan_duration_s = (Instant::now() - start_time).as_secs();
 
@@ -255,7 +265,7 @@ impl CleanStaleArtifactsCommand {
                 FoundPath::Untracked(path, file_type, _) => Some((path, file_type)),
                 _ => None,
             })
-            .take(2000)
+            .take(MAX_LOGGED_PATHS)
         {
             self.dispatcher.instant_event(buck2_data::UntrackedFile {
                 path: path.to_string(),
@@ -263,6 +273,27 @@ impl CleanStaleArtifactsCommand {
             });
         }
 
+        for (path, size, last_access_time) in found_paths
+            .iter()
+            .filter_map(|x| match x {
+                FoundPath::Evicted(path, size, last_access_time) => {
+                    Some((path, size, last_access_time))
+                }
+                _ => None,
+            })
+            .take(MAX_LOGGED_PATHS)
+        {
+            self.dispatcher.instant_event(buck2_data::EvictedArtifact {
+                path: path.to_string(),
+                size: *size,
+                last_access_time: last_access_time.timestamp(),
+            });
+        }
+        // The result event carries how many more were evicted than logged.
+        stats.unlogged_evicted_artifact_count = stats
+            .evicted_artifact_count
+            .saturating_sub(MAX_LOGGED_PATHS as u64);
+
         if !liveliness_observer.is_alive_sync() {
             return Ok(PendingCleanResult::Finished(CleanResult {
                 kind: CleanStaleResultKind::Interrupted,
@@ -271,7 +302,9 @@ impl CleanStaleArtifactsCommand {
         }
 
         // If no stale or retained artifact founds, the db should be empty.
-        if stats.stale_artifact_count + stats.retained_artifact_count == 0 {
+        if stats.stale_artifact_count + stats.retained_artifact_count + stats.evicted_artifact_count
+            == 0
+        {
             // Just need to know if any entries exist, could be a simpler query.
             // Checking the db directly in case tree is somehow not in sync.
             let materializer_state = sqlite_db
@@ -328,10 +361,14 @@ fn stats_for_paths(paths: &Vec<FoundPath>) -> buck2_data::CleanStaleStats {
                 stats.stale_artifact_count += 1;
                 stats.stale_bytes += *size;
             }
-            FoundPath::Retained(size) => {
+            FoundPath::Retained(size, _) => {
                 stats.retained_artifact_count += 1;
                 stats.retained_bytes += *size;
             }
+            FoundPath::Evicted(_, size, _) => {
+                stats.evicted_artifact_count += 1;
+                stats.evicted_bytes += *size;
+            }
         }
     }
     stats
@@ -351,7 +388,7 @@ fn create_clean_fut<T: IoHandler>(
     let paths_to_invalidate: Vec<ProjectRelativePathBuf> = found_paths
         .iter()
         .filter_map(|x| match x {
-            FoundPath::Stale(p, ..) => Some(p.clone()),
+            FoundPath::Stale(p, ..) | FoundPath::Evicted(p, ..) => Some(p.clone()),
             _ => None,
         })
         .collect();
@@ -388,6 +425,7 @@ fn create_clean_fut<T: IoHandler>(
                 .filter_map(|x| match x {
                     FoundPath::Untracked(p, _, size) => Some((p, size)),
                     FoundPath::Stale(p, size) => Some((p, size)),
+                    FoundPath::Evicted(p, size, _) => Some((p, size)),
                     _ => None,
                 })
                 .map(|(path, size)| {
@@ -484,7 +522,11 @@ enum FoundPath {
     Untracked(ProjectRelativePathBuf, FileType, u64),
     /// These will be invalidated in the materiaizer.
     Stale(ProjectRelativePathBuf, u64),
-    Retained(u64),
+    /// Path and last access time are set if the artifact can be evicted to respect the size
+    /// budget, i.e. it is not used by this daemon.
+    Retained(u64, Option<(ProjectRelativePathBuf, DateTime<Utc>)>),
+    /// These will be invalidated in the materializer to get under the size budget.
+    Evicted(ProjectRelativePathBuf, u64, DateTime<Utc>),
 }
 
 impl<'a, T: IoHandler> StaleFinder<'a, T> {
@@ -570,11 +612,18 @@ impl<'a, T: IoHandler> StaleFinder<'a, T> {
                         .push(FoundPath::Stale(path, metadata.size()));
                 }
                 ArtifactTree::Data(box ArtifactMaterializationData {
-                    stage: ArtifactMaterializationStage::Materialized { metadata, .. },
+                    stage:
+                        ArtifactMaterializationStage::Materialized {
+                            active,
+                            last_access_time,
+                            metadata,
+                        },
                     ..
                 }) => {
                     tracing::trace!(path = %path, file_type = ?file_type, "marking as retained");
-                    self.found_paths.push(FoundPath::Retained(metadata.size()));
+                    let evictable = (!active).then_some((path, *last_access_time));
+                    self.found_paths
+                        .push(FoundPath::Retained(metadata.size(), evictable));
                 }
                 _ => {
                     // What we have on disk does not match what we have in the materializer (which is
@@ -588,6 +637,80 @@ impl<'a, T: IoHandler> StaleFinder<'a, T> {
     }
 }
 
+/// Returns the paths under `buck-out/v2/gen` that are not tracked by the materializer.
+pub(super) fn find_untracked<T: IoHandler>(
+    io: &Arc<T>,
+    tree: &ArtifactTree,
+) -> anyhow::Result<Vec<ProjectRelativePathBuf>> {
+    let gen_path = io
+        .buck_out_path()
+        .join(ProjectRelativePathBuf::unchecked_new("gen".to_owned()));
+    if !fs_util::try_exists(io.fs().resolve(&gen_path))? {
+        return Ok(Vec::new());
+    }
+    let empty = HashMap::new();
+    let gen_subtree = tree
+        .get_subtree(&mut gen_path.iter())
+        .context("Found a file where gen dir expected")?
+        .unwrap_or(&empty);
+
+    let mut found_paths = Vec::new();
+    StaleFinder {
+        io: io.dupe(),
+        // Nothing is older than this, so tracked artifacts are all retained.
+        keep_since_time: DateTime::<Utc>::MIN_UTC,
+        found_paths: &mut found_paths,
+        liveliness_observer: Arc::new(NoopLivelinessObserver),
+    }
+    .visit_recursively(gen_path, gen_subtree)?;
+
+    Ok(found_paths
+        .into_iter()
+        .filter_map(|p| match p {
+            FoundPath::Untracked(path, ..) => Some(path),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Evict the least recently accessed retained artifacts until the remaining ones fit in
+/// `max_size_bytes`. Untracked and stale paths are deleted anyway so they don't count.
+fn evict_for_size(found_paths: &mut [FoundPath], max_size_bytes: u64) {
+    let mut retained_bytes: u64 = found_paths
+        .iter()
+        .map(|x| match x {
+            FoundPath::Retained(size, _) => *size,
+            _ => 0,
+        })
+        .sum();
+    if retained_bytes <= max_size_bytes {
+        return;
+    }
+
+    let mut candidates: Vec<(DateTime<Utc>, usize)> = found_paths
+        .iter()
+        .enumerate()
+        .filter_map(|(i, x)| match x {
+            FoundPath::Retained(_, Some((_, last_access_time))) => Some((*last_access_time, i)),
+            _ => None,
+        })
+        .collect();
+    candidates.sort();
+
+    for (_, i) in candidates {
+        if retained_bytes <= max_size_bytes {
+            break;
+        }
+        if let FoundPath::Retained(size, Some((path, last_access_time))) =
+            std::mem::replace(&mut found_paths[i], FoundPath::Retained(0, None))
+        {
+            tracing::trace!(path = %path, "evicting to respect size budget");
+            retained_bytes -= size;
+            found_paths[i] = FoundPath::Evicted(path, size, last_access_time);
+        }
+    }
+}
+
 fn find_stale_tracked_only(
     tree: &ArtifactTree,
     keep_since_time: DateTime<Utc>,
@@ -606,7 +729,7 @@ fn find_stale_tracked_only(
                 found_paths.push(FoundPath::Stale(path, 0));
             } else {
                 tracing::trace!(path = %path, "retaining artifact");
-                found_paths.push(FoundPath::Retained(0));
+                found_paths.push(FoundPath::Retained(0, None));
             }
         }
     }
@@ -617,7 +740,9 @@ pub struct CleanStaleConfig {
     // Time before running first clean, after daemon start
     pub start_offset: std::time::Duration,
     pub clean_period: std::time::Duration,
-    pub artifact_ttl: std::time::Duration,
+    // None if only size based eviction is enabled
+    pub artifact_ttl: Option<std::time::Duration>,
+    pub max_size_bytes: Option<u64>,
     pub dry_run: bool,
 }
 
@@ -635,18 +760,28 @@ impl CleanStaleConfig {
                 property: "clean_stale_artifact_ttl_hours",
             })?
             .unwrap_or(24.0 * 7.0);
+        let buck_out_max_size_gb: Option<f64> = root_config.parse(BuckconfigKeyRef {
+            section: "buck2",
+            property: "buck_out_max_size_gb",
+        })?;
+        // Check a size budget often, since a build can fill buck-out well within a day.
+        let (default_period_hours, default_start_offset_hours) = if buck_out_max_size_gb.is_some() {
+            (1.0, 0.25)
+        } else {
+            (24.0, 12.0)
+        };
         let clean_stale_period_hours = root_config
             .parse(BuckconfigKeyRef {
                 section: "buck2",
                 property: "clean_stale_period_hours",
             })?
-            .unwrap_or(24.0);
+            .unwrap_or(default_period_hours);
         let clean_stale_start_offset_hours = root_config
             .parse(BuckconfigKeyRef {
                 section: "buck2",
                 property: "clean_stale_start_offset_hours",
             })?
-            .unwrap_or(12.0);
+            .unwrap_or(default_start_offset_hours);
         let clean_stale_dry_run = root_config
             .parse(BuckconfigKeyRef {
                 section: "buck2",
@@ -655,14 +790,18 @@ impl CleanStaleConfig {
             .unwrap_or(false);
 
         let secs_in_hour = 60.0 * 60.0;
-        let clean_stale_config = if clean_stale_enabled {
+        let clean_stale_config = if clean_stale_enabled || buck_out_max_size_gb.is_some() {
             Some(Self {
                 clean_period: std::time::Duration::from_secs_f64(
                     secs_in_hour * clean_stale_period_hours,
                 ),
-                artifact_ttl: std::time::Duration::from_secs_f64(
-                    secs_in_hour * clean_stale_artifact_ttl_hours,
-                ),
+                artifact_ttl: clean_stale_enabled.then(|| {
+                    std::time::Duration::from_secs_f64(
+                        secs_in_hour * clean_stale_artifact_ttl_hours,
+                    )
+                }),
+                max_size_bytes: buck_out_max_size_gb
+                    .map(|gb| (gb * 1024.0 * 1024.0 * 1024.0) as u64),
                 start_offset: std::time::Duration::from_secs_f64(
                     secs_in_hour * clean_stale_start_offset_hours,
                 ),
@@ -674,3 +813,44 @@ impl CleanStaleConfig {
         Ok(clean_stale_config)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use buck2_common::legacy_configs::configs::testing::parse;
+
+    use super::*;
+
+    fn parse_config(buckconfig: &str) -> anyhow::Result<Option<CleanStaleConfig>> {
+        CleanStaleConfig::from_buck_config(&parse(&[("config", buckconfig)], "config")?)
+    }
+
+    #[test]
+    fn test_size_budget_checked_hourly() -> anyhow::Result<()> {
+        let config = parse_config("[buck2]\nbuck_out_max_size_gb = 2\n")?.unwrap();
+        assert_eq!(config.clean_period, Duration::from_secs(60 * 60));
+        assert_eq!(config.start_offset, Duration::from_secs(15 * 60));
+        assert_eq!(config.artifact_ttl, None);
+        assert_eq!(config.max_size_bytes, Some(2 * 1024 * 1024 * 1024));
+
+        let config = parse_config(
+            "[buck2]\nbuck_out_max_size_gb = 2\nclean_stale_period_hours = 6\nclean_stale_start_offset_hours = 1\n",
+        )?
+        .unwrap();
+        assert_eq!(config.clean_period, Duration::from_secs(6 * 60 * 60));
+        assert_eq!(config.start_offset, Duration::from_secs(60 * 60));
+        Ok(())
+    }
+
+    #[test]
+    fn test_ttl_only_checked_daily() -> anyhow::Result<()> {
+        assert!(parse_config("")?.is_none());
+
+        let config = parse_config("[buck2]\nclean_stale_enabled = true\n")?.unwrap();
+        assert_eq!(config.clean_period, Duration::from_secs(24 * 60 * 60));
+        assert_eq!(config.start_offset, Duration::from_secs(12 * 60 * 60));
+        assert_eq!(config.max_size_bytes, None);
+        Ok(())
+    }
+}
@@ -24,6 +24,7 @@ use buck2_execute::materialize::materializer::DeferredMaterializerEntry;
 use buck2_execute::materialize::materializer::DeferredMaterializerExtensions;
 use buck2_execute::materialize::materializer::DeferredMaterializerIterItem;
 use buck2_execute::materialize::materializer::DeferredMaterializerSubscription;
+use buck2_execute::materialize::materializer::MaterializerVerifyReport;
 use chrono::DateTime;
 use chrono::Duration;
 use chrono::TimeZone;
@@ -44,6 +45,8 @@ use crate::materializers::deferred::clean_stale::CleanStaleArtifactsExtensionCom
 use crate::materializers::deferred::io_handler::create_ttl_refresh;
 use crate::materializers::deferred::io_handler::IoHandler;
 use crate::materializers::deferred::subscriptions::MaterializerSubscriptionOperation;
+use crate::materializers::deferred::verify;
+use crate::materializers::deferred::verify::SnapshotForVerify;
 use crate::materializers::deferred::ArtifactMaterializationMethod;
 use crate::materializers::deferred::ArtifactMaterializationStage;
 use crate::materializers::deferred::DeferredMaterializerAccessor;
@@ -382,6 +385,48 @@ impl<T: IoHandler> DeferredMaterializerExtensions for DeferredMaterializerAccess
         Ok(UnboundedReceiverStream::new(receiver).boxed())
     }
 
+    async fn verify(&self, repair: bool) -> anyhow::Result<MaterializerVerifyReport> {
+        let (sender, receiver) = oneshot::channel();
+        self.command_sender
+            .send(MaterializerCommand::Extension(
+                Box::new(SnapshotForVerify { sender }) as _,
+            ))?;
+        let snapshot = receiver.await.context("No response from materializer")??;
+
+        let mut report = verify::verify(&self.io, snapshot).await?;
+        if repair {
+            let paths = verify::paths_to_repair(&report);
+            report.repaired = paths.len() as u64;
+            if !paths.is_empty() {
+                let (sender, receiver) = oneshot::channel();
+                self.command_sender
+                    .send(MaterializerCommand::InvalidateFilePaths(
+                        paths.clone(),
+                        sender,
+                        get_dispatcher(),
+                    ))?;
+                receiver
+                    .await
+                    .context("No response from materializer")?
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .context("Failed to invalidate artifacts")?;
+                // Invalidating doesn't touch the disk, but we don't want anything to read the
+                // corrupted outputs until they are rebuilt.
+                self.io
+                    .io_executor()
+                    .execute_io_inline(|| {
+                        for path in &paths {
+                            fs_util::remove_all(self.io.fs().resolve(path))?;
+                        }
+                        Ok(())
+                    })
+                    .await?;
+            }
+        }
+        Ok(report)
+    }
+
     async fn refresh_ttls(&self, min_ttl: i64) -> anyhow::Result<()> {
         let (sender, receiver) = oneshot::channel();
         self.command_sender
@@ -422,6 +467,7 @@ impl<T: IoHandler> DeferredMaterializerExtensions for DeferredMaterializerAccess
                         keep_since_time,
                         dry_run,
                         tracked_only,
+                        max_size_bytes: self.buck_out_max_size_bytes,
                         dispatcher,
                     },
                     sender,
@@ -63,6 +63,8 @@ use remote_execution::TDigest;
 use tracing::instrument;
 
 use crate::materializers::deferred::clean_stale::CleanInvalidatedPathRequest;
+use crate::materializers::deferred::local_cas::LocalCasPlacement;
+use crate::materializers::deferred::local_cas::LocalCasStore;
 use crate::materializers::deferred::ArtifactMaterializationMethod;
 use crate::materializers::deferred::ArtifactMaterializationStage;
 use crate::materializers::deferred::ArtifactTree;
@@ -77,6 +79,13 @@ use crate::materializers::immediate;
 use crate::materializers::io::materialize_files;
 use crate::materializers::io::MaterializeTreeStructure;
 
+#[derive(Debug, buck2_error::Error)]
+#[buck2(input)]
+#[error("Refusing to download `{path}`: network access is disabled by `--offline`")]
+struct OfflineDownloadError {
+    path: ProjectRelativePathBuf,
+}
+
 #[derive(Allocative)]
 pub struct DefaultIoHandler {
     fs: ProjectRoot,
@@ -86,11 +95,15 @@ pub struct DefaultIoHandler {
     /// Executor for blocking IO operations
     io_executor: Arc<dyn BlockingExecutor>,
     http_client: HttpClient,
+    /// Local content-addressed store checked before downloading files, if enabled.
+    local_cas: Option<Arc<LocalCasStore>>,
 }
 
 struct MaterializationStat {
     file_count: u64,
     total_bytes: u64,
+    /// Files that were placed from the local CAS instead of being downloaded.
+    local_cas_file_count: u64,
 }
 
 #[async_trait]
@@ -123,12 +136,15 @@ pub trait IoHandler: Sized + Sync + Send + 'static {
         cancellations: &'a CancellationContext,
     ) -> anyhow::Result<()>;
 
+    /// Materializes `entry` at `path`. If `offline` is set, the materialization fails instead of
+    /// downloading anything.
     async fn materialize_entry(
         self: &Arc<Self>,
         path: ProjectRelativePathBuf,
         method: Arc<ArtifactMaterializationMethod>,
         entry: ActionDirectoryEntry<ActionSharedDirectory>,
         event_dispatcher: EventDispatcher,
+        offline: bool,
         cancellations: &CancellationContext,
     ) -> Result<(), MaterializeEntryError>;
 
@@ -143,6 +159,7 @@ pub trait IoHandler: Sized + Sync + Send + 'static {
     fn re_client_manager(&self) -> &Arc<ReConnectionManager>;
     fn fs(&self) -> &ProjectRoot;
     fn digest_config(&self) -> DigestConfig;
+    fn io_executor(&self) -> &Arc<dyn BlockingExecutor>;
 }
 
 impl DefaultIoHandler {
@@ -153,6 +170,7 @@ impl DefaultIoHandler {
         re_client_manager: Arc<ReConnectionManager>,
         io_executor: Arc<dyn BlockingExecutor>,
         http_client: HttpClient,
+        local_cas: Option<Arc<LocalCasStore>>,
     ) -> Self {
         Self {
             fs,
@@ -161,6 +179,7 @@ impl DefaultIoHandler {
             re_client_manager,
             io_executor,
             http_client,
+            local_cas,
         }
     }
     /// Materializes an `entry` at `path`, using the materialization `method`
@@ -170,6 +189,7 @@ impl DefaultIoHandler {
         path: ProjectRelativePathBuf,
         method: Arc<ArtifactMaterializationMethod>,
         entry: ActionDirectoryEntry<ActionSharedDirectory>,
+        offline: bool,
         stat: &mut MaterializationStat,
         cancellations: &CancellationContext<'_>,
     ) -> Result<(), MaterializeEntryError> {
@@ -188,6 +208,8 @@ impl DefaultIoHandler {
         match method.as_ref() {
             ArtifactMaterializationMethod::CasDownload { info } => {
                 let mut files = Vec::new();
+                // For each file in `files`: where it goes and what it contains, for the local CAS.
+                let mut local_files = Vec::new();
 
                 {
                     let mut walk = unordered_entry_walk(entry.as_ref().map_dir(Directory::as_ref));
@@ -195,7 +217,15 @@ impl DefaultIoHandler {
                     while let Some((entry_path, entry)) = walk.next() {
                         if let DirectoryEntry::Leaf(ActionDirectoryMember::File(f)) = entry {
                             let name = path.join(entry_path.get());
-                            let digest = maybe_tombstone_digest(f.digest.data())?.to_re();
+                            let file_digest = maybe_tombstone_digest(f.digest.data())?;
+                            let digest = file_digest.to_re();
+                            if self.local_cas.is_some() {
+                                local_files.push((
+                                    self.fs.resolve(&name),
+                                    file_digest.dupe(),
+                                    f.is_executable,
+                                ));
+                            }
 
                             tracing::trace!(name = %name, digest = %digest, "push download");
                             let name = self
@@ -222,6 +252,50 @@ impl DefaultIoHandler {
                     .map(|x| u64::try_from(x.named_digest.digest.size_in_bytes).unwrap_or_default())
                     .sum();
 
+                if let Some(local_cas) = &self.local_cas {
+                    let placements = self
+                        .io_executor
+                        .execute_io_inline(|| {
+                            Ok(local_files
+                                .iter()
+                                .map(|(dest, digest, is_executable)| {
+                                    // The local CAS is best-effort: fall back to downloading.
+                                    local_cas
+                                        .place(digest, *is_executable, dest)
+                                        .unwrap_or_else(|e| {
+                                            tracing::warn!(
+                                                "Error placing `{}` from local CAS: {:#}",
+                                                dest,
+                                                e
+                                            );
+                                            LocalCasPlacement::Miss
+                                        })
+                                })
+                                .collect::<Vec<_>>())
+                        })
+                        .await?;
+
+                    let mut to_download = Vec::with_capacity(files.len());
+                    let mut to_insert = Vec::new();
+                    for ((file, local_file), placement) in
+                        files.into_iter().zip(local_files).zip(placements)
+                    {
+                        match placement {
+                            LocalCasPlacement::Hit => stat.local_cas_file_count += 1,
+                            LocalCasPlacement::Miss => {
+                                to_download.push(file);
+                                to_insert.push(local_file);
+                            }
+                        }
+                    }
+                    files = to_download;
+                    local_files = to_insert;
+                }
+
+                if offline && !files.is_empty() {
+                    return Err(anyhow::Error::from(OfflineDownloadError { path }).into());
+                }
+
                 let connection = self.re_client_manager.get_re_connection();
                 let re_client = connection.get_client();
 
@@ -251,11 +325,45 @@ impl DefaultIoHandler {
                             ),
                         }
                     })?;
+
+                if let Some(local_cas) = &self.local_cas {
+                    self.insert_into_local_cas(local_cas, local_files).await;
+                }
             }
             ArtifactMaterializationMethod::HttpDownload { info } => {
+                if let Some(local_cas) = &self.local_cas {
+                    let dest = self.fs.resolve(&path);
+                    let digest = info.metadata.digest.data();
+                    let placement = self
+                        .io_executor
+                        .execute_io_inline(|| {
+                            Ok(local_cas
+                                .place(digest, info.metadata.is_executable, &dest)
+                                .unwrap_or_else(|e| {
+                                    tracing::warn!(
+                                        "Error placing `{}` from local CAS: {:#}",
+                                        dest,
+                                        e
+                                    );
+                                    LocalCasPlacement::Miss
+                                }))
+                        })
+                        .await?;
+                    if placement == LocalCasPlacement::Hit {
+                        stat.file_count = 1;
+                        stat.total_bytes = info.metadata.digest.size();
+                        stat.local_cas_file_count = 1;
+                        return Ok(());
+                    }
+                }
+
+                if offline {
+                    return Err(anyhow::Error::from(OfflineDownloadError { path }).into());
+                }
+
                 async {
                     let downloaded = http_download(
-                        &self.http_client,
+                        &self.http_client.without_url_rewrites(),
                         &self.fs,
                         self.digest_config,
                         &path,
@@ -288,6 +396,18 @@ impl DefaultIoHandler {
                         info.owner
                     )
                 })?;
+
+                if let Some(local_cas) = &self.local_cas {
+                    self.insert_into_local_cas(
+                        local_cas,
+                        vec![(
+                            self.fs.resolve(&path),
+                            info.metadata.digest.data().dupe(),
+                            info.metadata.is_executable,
+                        )],
+                    )
+                    .await;
+                }
             }
             ArtifactMaterializationMethod::LocalCopy(_, copied_artifacts) => {
                 self.io_executor
@@ -324,6 +444,32 @@ impl DefaultIoHandler {
         };
         Ok(())
     }
+
+    /// Adds freshly downloaded files to the local CAS. Failures are logged and otherwise ignored,
+    /// since the files were materialized successfully.
+    async fn insert_into_local_cas(
+        &self,
+        local_cas: &LocalCasStore,
+        files: Vec<(AbsNormPathBuf, FileDigest, bool)>,
+    ) {
+        if files.is_empty() {
+            return;
+        }
+        let res = self
+            .io_executor
+            .execute_io_inline(|| {
+                for (src, digest, is_executable) in &files {
+                    local_cas
+                        .insert(src, digest, *is_executable)
+                        .with_context(|| format!("Error adding `{}` to local CAS", src))?;
+                }
+                Ok(())
+            })
+            .await;
+        if let Err(e) = res {
+            tracing::warn!("{:#}", e);
+        }
+    }
 }
 
 #[async_trait]
@@ -402,6 +548,7 @@ impl IoHandler for DefaultIoHandler {
         method: Arc<ArtifactMaterializationMethod>,
         entry: ActionDirectoryEntry<ActionSharedDirectory>,
         event_dispatcher: EventDispatcher,
+        offline: bool,
         cancellations: &CancellationContext,
     ) -> Result<(), MaterializeEntryError> {
         let materialization_start = buck2_data::MaterializationStart {
@@ -418,9 +565,17 @@ impl IoHandler for DefaultIoHandler {
                 let mut stat = MaterializationStat {
                     file_count: 0,
                     total_bytes: 0,
+                    local_cas_file_count: 0,
                 };
                 let res = self
-                    .materialize_entry_span(path, method.dupe(), entry, &mut stat, cancellations)
+                    .materialize_entry_span(
+                        path,
+                        method.dupe(),
+                        entry,
+                        offline,
+                        &mut stat,
+                        cancellations,
+                    )
                     .await;
                 let error = res.as_ref().err().map(|e| format!("{:#}", e));
 
@@ -439,6 +594,7 @@ impl IoHandler for DefaultIoHandler {
                         success: error.is_none(),
                         error,
                         method: Some(method.to_proto() as i32),
+                        local_cas_file_count: stat.local_cas_file_count,
                     },
                 )
             })
@@ -474,6 +630,10 @@ impl IoHandler for DefaultIoHandler {
     fn digest_config(&self) -> DigestConfig {
         self.digest_config
     }
+
+    fn io_executor(&self) -> &Arc<dyn BlockingExecutor> {
+        &self.io_executor
+    }
 }
 
 /// This is used for testing to ingest digests (via BUCK2_TEST_TOMBSTONED_DIGESTS).
@@ -0,0 +1,470 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A local content-addressed store that the deferred materializer checks before downloading
+//! files.
+//!
+//! The store is a directory of files named after their digest. Files are placed into `buck-out`
+//! with a reflink (`FICLONE`, on filesystems that support it, such as btrfs or XFS) or a read-only
+//! hardlink, falling back to a copy. Since the store is typically shared by several checkouts, the
+//! files it contains are never modified in place.
+//!
+//! On top of that, the store can import the materializer state of other `buck-out` directories
+//! (e.g. those of other worktrees). Those files are not owned by the store and might have been
+//! modified, so their content is verified after being copied and before being used. The index of
+//! imported files is reloaded every [`IMPORT_REFRESH_INTERVAL`], since the other daemons keep
+//! materializing files.
+
+use std::collections::HashMap;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
+
+use allocative::Allocative;
+use anyhow::Context;
+use buck2_common::file_ops::FileDigest;
+use buck2_common::file_ops::FileDigestConfig;
+use buck2_common::legacy_configs::configs::LegacyBuckConfig;
+use buck2_common::legacy_configs::key::BuckconfigKeyRef;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::file_name::FileName;
+use buck2_directory::directory::entry::DirectoryEntry;
+use buck2_execute::digest_config::DigestConfig;
+use buck2_execute::directory::ActionDirectoryMember;
+use dupe::Dupe;
+use parking_lot::Mutex;
+
+use crate::materializers::deferred::DeferredMaterializerStats;
+use crate::materializers::sqlite::read_materializer_state_readonly;
+
+/// How long the index of files materialized in other `buck-out` directories is used before it is
+/// loaded again.
+const IMPORT_REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+
+#[derive(Debug, buck2_error::Error)]
+pub enum LocalCasConfigError {
+    #[error(
+        "Invalid value for buckconfig `[buck2] local_cas_link_mode`. Got `{0}`. Expected one of `reflink`, `hardlink` or `copy`."
+    )]
+    InvalidLinkMode(String),
+    #[error("`[buck2] local_cas_dir` must be an absolute path, got `{0}`")]
+    RelativeDir(String),
+}
+
+/// How files are placed from the local CAS into `buck-out`.
+#[derive(Clone, Copy, Debug, Dupe, PartialEq, Eq, Allocative)]
+pub enum LocalCasLinkMode {
+    /// Clone the file (copy-on-write), falling back to a copy.
+    Reflink,
+    /// Hardlink the file, falling back to a copy. Since the file in `buck-out` and the entry in the
+    /// store are the same inode, both are made read-only: anything that writes to files in
+    /// `buck-out` in place will fail with this mode.
+    Hardlink,
+    /// Always copy the file.
+    Copy,
+}
+
+#[derive(Clone, Debug, Allocative)]
+pub struct LocalCasConfig {
+    pub dir: AbsNormPathBuf,
+    pub link_mode: LocalCasLinkMode,
+    /// Other `buck-out` isolation directories (e.g. `/src/worktree/buck-out/v2`) whose materialized
+    /// files are used as a source of content.
+    pub import_buck_outs: Vec<AbsNormPathBuf>,
+}
+
+impl LocalCasConfig {
+    pub fn from_buck_config(root_config: &LegacyBuckConfig) -> anyhow::Result<Option<Self>> {
+        let Some(dir) = root_config.get(BuckconfigKeyRef {
+            section: "buck2",
+            property: "local_cas_dir",
+        }) else {
+            return Ok(None);
+        };
+        let dir = AbsNormPathBuf::try_from(dir.to_owned())
+            .map_err(|_| LocalCasConfigError::RelativeDir(dir.to_owned()))?;
+
+        let link_mode = match root_config.get(BuckconfigKeyRef {
+            section: "buck2",
+            property: "local_cas_link_mode",
+        }) {
+            None | Some("reflink") => LocalCasLinkMode::Reflink,
+            Some("hardlink") => LocalCasLinkMode::Hardlink,
+            Some("copy") => LocalCasLinkMode::Copy,
+            Some(other) => {
+                return Err(LocalCasConfigError::InvalidLinkMode(other.to_owned()).into());
+            }
+        };
+
+        let import_buck_outs = root_config
+            .get(BuckconfigKeyRef {
+                section: "buck2",
+                property: "local_cas_import_buck_outs",
+            })
+            .unwrap_or("")
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                AbsNormPathBuf::try_from(s.to_owned()).with_context(|| {
+                    format!("Invalid `[buck2] local_cas_import_buck_outs`: `{}`", s)
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Some(Self {
+            dir,
+            link_mode,
+            import_buck_outs,
+        }))
+    }
+}
+
+/// What the store did for a single file.
+#[derive(Clone, Copy, Debug, Dupe, PartialEq, Eq)]
+pub(crate) enum LocalCasPlacement {
+    /// The file was not in the store.
+    Miss,
+    /// The file was placed from the store.
+    Hit,
+}
+
+type ImportedIndex = HashMap<FileDigest, Vec<AbsNormPathBuf>>;
+
+#[derive(Allocative)]
+pub(crate) struct LocalCasStore {
+    config: LocalCasConfig,
+    digest_config: DigestConfig,
+    /// Files materialized in other `buck-out` directories, indexed by digest, and when they were
+    /// loaded. Loaded on first use.
+    #[allocative(skip)]
+    imported: Mutex<Option<(Instant, Arc<ImportedIndex>)>>,
+    /// Hits, misses and mismatches are reported in the materializer's snapshot stats.
+    stats: Arc<DeferredMaterializerStats>,
+}
+
+impl LocalCasStore {
+    pub(crate) fn new(
+        config: LocalCasConfig,
+        digest_config: DigestConfig,
+        stats: Arc<DeferredMaterializerStats>,
+    ) -> Self {
+        Self {
+            config,
+            digest_config,
+            imported: Mutex::new(None),
+            stats,
+        }
+    }
+
+    fn entry_path(&self, digest: &FileDigest, is_executable: bool) -> AbsNormPathBuf {
+        // The executable bit is part of the inode, so hardlinked executable and non-executable
+        // copies of the same content must be distinct entries.
+        let hex = hex_digest(digest);
+        let name = format!(
+            "{}:{}{}",
+            hex,
+            digest.size(),
+            if is_executable { "-x" } else { "" }
+        );
+        self.config
+            .dir
+            .join(FileName::unchecked_new(
+                &digest.raw_digest().algorithm().to_string().to_lowercase(),
+            ))
+            .join(FileName::unchecked_new(&hex[..2]))
+            .join(FileName::unchecked_new(&name))
+    }
+
+    fn imported(&self) -> Arc<ImportedIndex> {
+        if self.config.import_buck_outs.is_empty() {
+            return Arc::default();
+        }
+        let mut imported = self.imported.lock();
+        match &*imported {
+            Some((loaded, index)) if loaded.elapsed() < IMPORT_REFRESH_INTERVAL => index.dupe(),
+            _ => {
+                let index = Arc::new(self.load_imported());
+                *imported = Some((Instant::now(), index.dupe()));
+                index
+            }
+        }
+    }
+
+    fn load_imported(&self) -> ImportedIndex {
+        let mut res: ImportedIndex = HashMap::new();
+        for buck_out in &self.config.import_buck_outs {
+            // Paths in the materializer state are relative to the project root, which is
+            // two levels above the isolation dir (`<root>/buck-out/v2`).
+            let Some(project_root) = buck_out.parent().and_then(|p| p.parent()) else {
+                continue;
+            };
+            let db = buck_out
+                .join(FileName::unchecked_new("cache"))
+                .join(FileName::unchecked_new("materializer_state"))
+                .join(FileName::unchecked_new("db.sqlite"));
+            let state = match read_materializer_state_readonly(&db, self.digest_config) {
+                Ok(state) => state,
+                Err(e) => {
+                    tracing::warn!("Not importing materializer state from `{}`: {:#}", db, e);
+                    continue;
+                }
+            };
+            for (path, (metadata, _)) in state {
+                if let DirectoryEntry::Leaf(ActionDirectoryMember::File(file)) = metadata.0 {
+                    res.entry(file.digest.data().dupe())
+                        .or_default()
+                        .push(project_root.join(&path));
+                }
+            }
+        }
+        res
+    }
+
+    /// Places the file with `digest` at `dest` if the store has it. Must be called on a blocking
+    /// I/O thread.
+    pub(crate) fn place(
+        &self,
+        digest: &FileDigest,
+        is_executable: bool,
+        dest: &AbsNormPath,
+    ) -> anyhow::Result<LocalCasPlacement> {
+        let entry = self.entry_path(digest, is_executable);
+        if fs_util::try_exists(&entry)? {
+            fs_util::remove_all(dest)?;
+            link_or_copy(&entry, dest, self.config.link_mode)?;
+            if self.config.link_mode != LocalCasLinkMode::Hardlink {
+                set_mode(dest, is_executable, false)?;
+            }
+            self.stats.local_cas_hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(LocalCasPlacement::Hit);
+        }
+
+        if let Some(candidates) = self.imported().get(digest) {
+            for candidate in candidates {
+                if !fs_util::try_exists(candidate)? {
+                    continue;
+                }
+                fs_util::remove_all(dest)?;
+                // Never hardlink files we don't own, they might be modified later.
+                let mode = match self.config.link_mode {
+                    LocalCasLinkMode::Hardlink => LocalCasLinkMode::Copy,
+                    mode => mode,
+                };
+                link_or_copy(candidate, dest, mode)?;
+                let actual = FileDigest::from_file_disk(
+                    dest,
+                    FileDigestConfig::build(self.digest_config.cas_digest_config()),
+                )?;
+                if actual != *digest {
+                    self.stats
+                        .local_cas_import_mismatches
+                        .fetch_add(1, Ordering::Relaxed);
+                    fs_util::remove_all(dest)?;
+                    continue;
+                }
+                set_mode(dest, is_executable, false)?;
+                self.insert(dest, digest, is_executable)?;
+                self.stats.local_cas_hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(LocalCasPlacement::Hit);
+            }
+        }
+
+        self.stats.local_cas_misses.fetch_add(1, Ordering::Relaxed);
+        Ok(LocalCasPlacement::Miss)
+    }
+
+    /// Adds the file at `src`, whose content is known to be `digest`, to the store. Must be called
+    /// on a blocking I/O thread.
+    ///
+    /// In hardlink mode, this makes `src` read-only.
+    pub(crate) fn insert(
+        &self,
+        src: &AbsNormPath,
+        digest: &FileDigest,
+        is_executable: bool,
+    ) -> anyhow::Result<()> {
+        let entry = self.entry_path(digest, is_executable);
+        if fs_util::try_exists(&entry)? {
+            return Ok(());
+        }
+        let parent = entry.parent().context("Local CAS entry has no parent")?;
+        fs_util::create_dir_all(parent)?;
+
+        // Write to a temporary file and rename so that concurrent readers (possibly from other
+        // daemons) never see partial files.
+        static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+        let tmp = parent.join(FileName::unchecked_new(&format!(
+            ".tmp.{}.{}",
+            std::process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        )));
+        fs_util::remove_all(&tmp)?;
+        link_or_copy(src, &tmp, self.config.link_mode)?;
+        set_mode(&tmp, is_executable, true)?;
+        fs_util::rename(&tmp, &entry)?;
+        Ok(())
+    }
+}
+
+fn hex_digest(digest: &FileDigest) -> String {
+    digest
+        .raw_digest()
+        .as_bytes()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Links `src` to `dest` (which must not exist) using `mode`, falling back to a copy.
+fn link_or_copy(
+    src: &AbsNormPath,
+    dest: &AbsNormPath,
+    mode: LocalCasLinkMode,
+) -> anyhow::Result<()> {
+    match mode {
+        LocalCasLinkMode::Hardlink => {
+            if std::fs::hard_link(src.as_path(), dest.as_path()).is_ok() {
+                return Ok(());
+            }
+        }
+        LocalCasLinkMode::Reflink => {
+            if reflink(src, dest).is_ok() {
+                return Ok(());
+            }
+            // A failed clone may have left an empty file behind.
+            fs_util::remove_all(dest)?;
+        }
+        LocalCasLinkMode::Copy => {}
+    }
+    fs_util::copy(src, dest)?;
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn reflink(src: &AbsNormPath, dest: &AbsNormPath) -> anyhow::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    // `_IOW(0x94, 9, int)`, from `linux/fs.h`.
+    const FICLONE: libc::c_ulong = 0x40049409;
+
+    let src =
+        std::fs::File::open(src.as_path()).with_context(|| format!("Error opening `{}`", src))?;
+    let dest = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(dest.as_path())
+        .with_context(|| format!("Error creating `{}`", dest))?;
+    // SAFETY: both file descriptors are valid for the duration of the call.
+    let res = unsafe { libc::ioctl(dest.as_raw_fd(), FICLONE as _, src.as_raw_fd()) };
+    if res != 0 {
+        return Err(std::io::Error::last_os_error()).context("FICLONE failed");
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_src: &AbsNormPath, _dest: &AbsNormPath) -> anyhow::Result<()> {
+    Err(anyhow::anyhow!("Reflinks are only supported on Linux"))
+}
+
+#[cfg(unix)]
+fn set_mode(path: &AbsNormPath, is_executable: bool, read_only: bool) -> anyhow::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = match (is_executable, read_only) {
+        (true, true) => 0o555,
+        (false, true) => 0o444,
+        (true, false) => 0o755,
+        (false, false) => 0o644,
+    };
+    fs_util::set_permissions(path, std::fs::Permissions::from_mode(mode))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_mode(path: &AbsNormPath, _is_executable: bool, read_only: bool) -> anyhow::Result<()> {
+    let mut perms = fs_util::symlink_metadata(path)?.permissions();
+    perms.set_readonly(read_only);
+    fs_util::set_permissions(path, perms)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use buck2_common::file_ops::FileDigest;
+    use buck2_common::file_ops::FileDigestConfig;
+    use buck2_core::fs::fs_util;
+    use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+    use buck2_execute::digest_config::DigestConfig;
+
+    use crate::materializers::deferred::local_cas::LocalCasConfig;
+    use crate::materializers::deferred::local_cas::LocalCasLinkMode;
+    use crate::materializers::deferred::local_cas::LocalCasPlacement;
+    use crate::materializers::deferred::local_cas::LocalCasStore;
+
+    fn test_roundtrip(link_mode: LocalCasLinkMode) -> anyhow::Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let root = AbsNormPathBuf::new(tempdir.path().to_owned())?;
+        let digest_config = DigestConfig::testing_default();
+
+        let store = LocalCasStore::new(
+            LocalCasConfig {
+                dir: root.join_normalized("cas")?,
+                link_mode,
+                import_buck_outs: Vec::new(),
+            },
+            digest_config,
+            Default::default(),
+        );
+
+        let src = root.join_normalized("src")?;
+        fs_util::write(&src, "hello")?;
+        let digest = FileDigest::from_file_disk(
+            src.as_abs_path(),
+            FileDigestConfig::build(digest_config.cas_digest_config()),
+        )?;
+
+        let dest = root.join_normalized("dest")?;
+        assert_eq!(store.place(&digest, false, &dest)?, LocalCasPlacement::Miss);
+        assert!(!fs_util::try_exists(&dest)?);
+
+        store.insert(&src, &digest, false)?;
+        assert_eq!(store.place(&digest, false, &dest)?, LocalCasPlacement::Hit);
+        assert_eq!(fs_util::read_to_string(&dest)?, "hello");
+
+        // The executable bit is part of the key.
+        let dest_x = root.join_normalized("dest_x")?;
+        assert_eq!(
+            store.place(&digest, true, &dest_x)?,
+            LocalCasPlacement::Miss
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_roundtrip_copy() -> anyhow::Result<()> {
+        test_roundtrip(LocalCasLinkMode::Copy)
+    }
+
+    #[test]
+    fn test_roundtrip_hardlink() -> anyhow::Result<()> {
+        test_roundtrip(LocalCasLinkMode::Hardlink)
+    }
+
+    #[test]
+    fn test_roundtrip_reflink() -> anyhow::Result<()> {
+        // Falls back to a copy on filesystems without reflink support.
+        test_roundtrip(LocalCasLinkMode::Reflink)
+    }
+}
@@ -105,6 +105,8 @@ mod state_machine {
     use buck2_events::source::ChannelEventSource;
     use buck2_execute::directory::Symlink;
     use buck2_execute::directory::INTERNER;
+    use buck2_execute::execute::blocking::testing::DummyBlockingExecutor;
+    use buck2_execute::execute::blocking::BlockingExecutor;
     use buck2_execute::execute::blocking::IoRequest;
     use buck2_util::threads::ignore_stack_overflow_checks_for_future;
     use tokio::time::sleep;
@@ -136,6 +138,8 @@ mod state_machine {
         digest_config: DigestConfig,
         buck_out_path: ProjectRelativePathBuf,
         fs: ProjectRoot,
+        #[allocative(skip)]
+        io_executor: Arc<dyn BlockingExecutor>,
     }
 
     impl DeferredMaterializerAccessor<StubIoHandler> {
@@ -173,6 +177,7 @@ mod state_machine {
                 clean_barriers: None,
                 digest_config: DigestConfig::testing_default(),
                 buck_out_path: make_path("buck-out/v2"),
+                io_executor: Arc::new(DummyBlockingExecutor { fs: fs.dupe() }),
                 fs,
             }
         }
@@ -281,6 +286,7 @@ mod state_machine {
             _method: Arc<ArtifactMaterializationMethod>,
             _entry: ActionDirectoryEntry<ActionSharedDirectory>,
             _event_dispatcher: EventDispatcher,
+            _offline: bool,
             _cancellations: &CancellationContext,
         ) -> Result<(), MaterializeEntryError> {
             // Simulate a non-immediate materialization if configured
@@ -338,6 +344,10 @@ mod state_machine {
         fn digest_config(&self) -> DigestConfig {
             self.digest_config
         }
+
+        fn io_executor(&self) -> &Arc<dyn BlockingExecutor> {
+            &self.io_executor
+        }
     }
 
     /// A stub command sender. We are calling materializer methods directly so that's all we need.
@@ -487,6 +497,9 @@ mod state_machine {
             recv.await.unwrap()
         };
 
+        let buck_out_max_size_bytes = clean_stale_config
+            .as_ref()
+            .and_then(|config| config.max_size_bytes);
         let command_thread = thread_spawn("buck2-dm", {
             move || {
                 let rt = tokio::runtime::Builder::new_current_thread()
@@ -522,6 +535,7 @@ mod state_machine {
                 },
                 stats: Arc::new(DeferredMaterializerStats::default()),
                 verbose_materializer_log: true,
+                buck_out_max_size_bytes,
             },
             handle,
             daemon_dispatcher_events,
@@ -1241,7 +1255,8 @@ mod state_machine {
             // dry run because it's easier and since this is only testing that cleans are triggered by the materializer
             let clean_stale_config = CleanStaleConfig {
                 clean_period: std::time::Duration::from_secs(1),
-                artifact_ttl: std::time::Duration::from_secs(0),
+                artifact_ttl: Some(std::time::Duration::from_secs(0)),
+                max_size_bytes: None,
                 start_offset: std::time::Duration::from_secs(0),
                 dry_run: true,
             };
@@ -1298,6 +1313,76 @@ mod state_machine {
         .await
     }
 
+    #[tokio::test]
+    async fn test_clean_stale_max_size() -> anyhow::Result<()> {
+        ignore_stack_overflow_checks_for_future(async {
+            let project_root = temp_root();
+            let io = Arc::new(StubIoHandler::new(project_root.dupe()));
+            let (dm, mut handle, _) = make_materializer(io.dupe(), None).await;
+            materialize_write(
+                &make_path("buck-out/v2/gen/foo/a"),
+                b"contents",
+                &mut handle,
+                &dm,
+            )
+            .await?;
+            materialize_write(
+                &make_path("buck-out/v2/gen/foo/b"),
+                b"contents",
+                &mut handle,
+                &dm,
+            )
+            .await?;
+            // Drop dm and flush sqlite connection.
+            dm.abort();
+
+            // Only size based eviction: both artifacts are recent but only one fits.
+            let clean_stale_config = CleanStaleConfig {
+                clean_period: std::time::Duration::from_secs(1),
+                artifact_ttl: None,
+                max_size_bytes: Some(8),
+                start_offset: std::time::Duration::from_secs(0),
+                dry_run: false,
+            };
+            // Create new materializer from db state so that artifacts are not active
+            let (_dm, _, mut daemon_dispatcher_events) =
+                make_materializer(io, Some(clean_stale_config)).await;
+
+            let mut evicted = Vec::new();
+            let stats = loop {
+                let event = daemon_dispatcher_events.receive().unwrap();
+                if let buck2_data::buck_event::Data::Instant(instant) =
+                    event.unpack_buck().unwrap().data()
+                {
+                    match instant.data.as_ref() {
+                        Some(buck2_data::instant_event::Data::EvictedArtifact(artifact)) => {
+                            evicted.push(artifact.path.clone());
+                        }
+                        Some(buck2_data::instant_event::Data::CleanStaleResult(res)) => {
+                            break res.stats.clone().unwrap();
+                        }
+                        _ => {}
+                    }
+                }
+            };
+
+            assert_eq!(evicted.len(), 1);
+            assert_eq!(
+                (
+                    stats.stale_artifact_count,
+                    stats.evicted_artifact_count,
+                    stats.evicted_bytes,
+                    stats.retained_artifact_count,
+                    stats.cleaned_artifact_count,
+                ),
+                (0, 1, 8, 1, 1)
+            );
+            assert!(!project_root.resolve(&make_path(&evicted[0])).exists());
+            Ok(())
+        })
+        .await
+    }
+
     #[tokio::test]
     async fn test_has_artifact_at() -> anyhow::Result<()> {
         ignore_stack_overflow_checks_for_future(async {
@@ -0,0 +1,197 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Verify that what's on disk in `buck-out` matches the materializer state.
+
+use std::sync::Arc;
+
+use anyhow::Context;
+use buck2_common::file_ops::FileDigestConfig;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+use buck2_directory::directory::entry::DirectoryEntry;
+use buck2_execute::directory::ActionDirectoryEntry;
+use buck2_execute::directory::ActionDirectoryMember;
+use buck2_execute::directory::ActionSharedDirectory;
+use buck2_execute::directory::INTERNER;
+use buck2_execute::entry::build_entry_from_disk;
+use buck2_execute::materialize::materializer::MaterializerVerifyIssue;
+use buck2_execute::materialize::materializer::MaterializerVerifyIssueKind;
+use buck2_execute::materialize::materializer::MaterializerVerifyReport;
+use derivative::Derivative;
+use futures::StreamExt;
+use futures::TryStreamExt;
+use tokio::sync::oneshot::Sender;
+
+use crate::materializers::deferred::clean_stale::find_untracked;
+use crate::materializers::deferred::extension::ExtensionCommand;
+use crate::materializers::deferred::io_handler::IoHandler;
+use crate::materializers::deferred::ArtifactMaterializationStage;
+use crate::materializers::deferred::ArtifactMetadata;
+use crate::materializers::deferred::DeferredMaterializerCommandProcessor;
+use crate::materializers::deferred::DirectoryMetadata;
+
+/// How many artifacts are checked at once. Reading and hashing happen on the blocking executor,
+/// which bounds the actual I/O concurrency.
+const VERIFY_CONCURRENCY: usize = 64;
+
+/// The state of the materializer at the time verification started.
+pub(super) struct VerifySnapshot {
+    /// Materialized artifacts, and whether they were declared by this daemon.
+    materialized: Vec<(ProjectRelativePathBuf, ArtifactMetadata, bool)>,
+    untracked: Vec<ProjectRelativePathBuf>,
+}
+
+/// Takes a snapshot of the materialized artifacts. Hashing happens off the command thread,
+/// since it's much slower than listing artifacts.
+#[derive(Derivative)]
+#[derivative(Debug)]
+pub(super) struct SnapshotForVerify {
+    #[derivative(Debug = "ignore")]
+    pub(super) sender: Sender<anyhow::Result<VerifySnapshot>>,
+}
+
+impl<T: IoHandler> ExtensionCommand<T> for SnapshotForVerify {
+    fn execute(self: Box<Self>, processor: &mut DeferredMaterializerCommandProcessor<T>) {
+        let materialized = processor
+            .tree
+            .iter_with_paths()
+            .filter_map(|(path, data)| match &data.stage {
+                ArtifactMaterializationStage::Declared { .. } => None,
+                ArtifactMaterializationStage::Materialized {
+                    metadata, active, ..
+                } => Some((
+                    ProjectRelativePathBuf::from(path),
+                    metadata.clone(),
+                    *active,
+                )),
+            })
+            .collect();
+        let snapshot =
+            find_untracked(&processor.io, &processor.tree).map(|untracked| VerifySnapshot {
+                materialized,
+                untracked,
+            });
+        let _ignored = self.sender.send(snapshot);
+    }
+}
+
+fn describe(entry: &DirectoryEntry<impl DescribeDir, ActionDirectoryMember>) -> String {
+    match entry {
+        DirectoryEntry::Dir(d) => format!("directory {}", d.describe()),
+        DirectoryEntry::Leaf(ActionDirectoryMember::File(f)) => format!(
+            "file {}{}",
+            f.digest,
+            if f.is_executable { " (executable)" } else { "" }
+        ),
+        DirectoryEntry::Leaf(ActionDirectoryMember::Symlink(s)) => format!("symlink to {}", s),
+        DirectoryEntry::Leaf(ActionDirectoryMember::ExternalSymlink(s)) => {
+            format!("external symlink to {}", s)
+        }
+    }
+}
+
+trait DescribeDir {
+    fn describe(&self) -> String;
+}
+
+impl DescribeDir for DirectoryMetadata {
+    fn describe(&self) -> String {
+        self.fingerprint.to_string()
+    }
+}
+
+impl DescribeDir for ActionSharedDirectory {
+    fn describe(&self) -> String {
+        self.fingerprint().to_string()
+    }
+}
+
+/// Checks a single artifact on disk, returning an issue if it doesn't match.
+async fn verify_one<T: IoHandler>(
+    io: &T,
+    path: ProjectRelativePathBuf,
+    expected: &ArtifactMetadata,
+    active: bool,
+) -> anyhow::Result<Option<MaterializerVerifyIssue>> {
+    let digest_config = io.digest_config();
+    let (actual, _hashing_info) = build_entry_from_disk(
+        io.fs().resolve(&path),
+        FileDigestConfig::build(digest_config.cas_digest_config()),
+        io.io_executor().as_ref(),
+        io.fs().root(),
+    )
+    .await
+    .with_context(|| format!("Error reading `{}`", path))?;
+
+    let Some(actual) = actual else {
+        return Ok(Some(MaterializerVerifyIssue {
+            path,
+            kind: MaterializerVerifyIssueKind::Missing,
+            detail: format!("expected {}", describe(&expected.0)),
+            active,
+        }));
+    };
+    let actual: ActionDirectoryEntry<ActionSharedDirectory> = actual.map_dir(|dir| {
+        dir.fingerprint(digest_config.as_directory_serializer())
+            .shared(&*INTERNER)
+    });
+
+    if expected.matches_entry(&actual) {
+        return Ok(None);
+    }
+    Ok(Some(MaterializerVerifyIssue {
+        path,
+        kind: MaterializerVerifyIssueKind::Modified,
+        detail: format!(
+            "expected {}, found {}",
+            describe(&expected.0),
+            describe(&actual)
+        ),
+        active,
+    }))
+}
+
+pub(super) async fn verify<T: IoHandler>(
+    io: &Arc<T>,
+    snapshot: VerifySnapshot,
+) -> anyhow::Result<MaterializerVerifyReport> {
+    let mut report = MaterializerVerifyReport::default();
+
+    let io = &**io;
+    let results: Vec<Option<MaterializerVerifyIssue>> = futures::stream::iter(
+        snapshot.materialized,
+    )
+    .map(|(path, metadata, active)| async move { verify_one(io, path, &metadata, active).await })
+    .buffered(VERIFY_CONCURRENCY)
+    .try_collect()
+    .await?;
+    report.checked = results.len() as u64;
+    report.issues.extend(results.into_iter().flatten());
+
+    for path in snapshot.untracked {
+        report.issues.push(MaterializerVerifyIssue {
+            path,
+            kind: MaterializerVerifyIssueKind::Untracked,
+            detail: "not in materializer state".to_owned(),
+            active: false,
+        });
+    }
+
+    Ok(report)
+}
+
+/// Paths that `repair` should invalidate: untracked paths are left to `buck2 clean --stale`.
+pub(super) fn paths_to_repair(report: &MaterializerVerifyReport) -> Vec<ProjectRelativePathBuf> {
+    report
+        .issues
+        .iter()
+        .filter(|issue| issue.kind != MaterializerVerifyIssueKind::Untracked)
+        .map(|issue| issue.path.clone())
+        .collect()
+}
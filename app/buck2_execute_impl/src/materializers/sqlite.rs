@@ -705,6 +705,21 @@ impl MaterializerStateTables {
     }
 }
 
+/// Reads the materializer state from the db at `path` without modifying it. This is used to read
+/// the state of other daemons (which may be concurrently writing to it), so the versions are not
+/// checked: entries that fail to convert cause an error, and the caller is expected to ignore it.
+pub(crate) fn read_materializer_state_readonly(
+    path: &AbsNormPath,
+    digest_config: DigestConfig,
+) -> anyhow::Result<MaterializerState> {
+    let connection = Connection::open_with_flags(
+        path,
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .with_context(|| format!("opening materializer state at `{}`", path))?;
+    MaterializerStateSqliteTable::new(Arc::new(Mutex::new(connection))).read_all(digest_config)
+}
+
 #[allow(unused)] // Used by test modules
 pub(crate) fn testing_materializer_state_sqlite_db(
     fs: &ProjectRoot,
@@ -0,0 +1,342 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! External cells whose contents are fetched into buck-out once, and read from there.
+
+use std::collections::hash_map;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+
+use anyhow::Context;
+use buck2_build_api::actions::artifact::get_artifact_fs::GetArtifactFs;
+use buck2_build_api::actions::impls::run_action_knobs::HasRunActionKnobs;
+use buck2_common::dice::data::HasIoProvider;
+use buck2_common::dice::file_ops::delegate::FileOpsDelegate;
+use buck2_common::file_ops::FileDigestConfig;
+use buck2_common::file_ops::RawDirEntry;
+use buck2_common::file_ops::RawPathMetadata;
+use buck2_common::io::fs::FsIoProvider;
+use buck2_common::io::IoProvider;
+use buck2_core::cells::cell_path::CellPath;
+use buck2_core::cells::external::ExternalCellOrigin;
+use buck2_core::cells::name::CellName;
+use buck2_core::cells::paths::CellRelativePath;
+use buck2_core::fs::buck_out_path::BuckOutPathResolver;
+use buck2_core::fs::project_rel_path::ProjectRelativePath;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+use buck2_directory::directory::directory::Directory;
+use buck2_error::internal_error_anyhow;
+use buck2_execute::artifact_value::ArtifactValue;
+use buck2_execute::digest_config::HasDigestConfig;
+use buck2_execute::directory::INTERNER;
+use buck2_execute::entry::build_entry_from_disk;
+use buck2_execute::execute::blocking::HasBlockingExecutor;
+use buck2_execute::execute::clean_output_paths::CleanOutputPaths;
+use buck2_execute::materialize::materializer::HasMaterializer;
+use buck2_execute::materialize::materializer::Materializer;
+use cmp_any::PartialEqAny;
+use dice::CancellationContext;
+use dice::DiceComputations;
+use dice::Key;
+use dupe::Dupe;
+use tokio::sync::Semaphore;
+
+use crate::git;
+use crate::http_archive;
+
+#[derive(buck2_error::Error, Debug)]
+enum FetchError {
+    #[error("Expected fetching the external cell to create a directory at `{0}`")]
+    NoDirectory(ProjectRelativePathBuf),
+    #[error(
+        "Refusing to fetch external cell from `{origin}` into `{path}`: network access is disabled by `--offline`"
+    )]
+    #[buck2(input)]
+    Offline {
+        origin: ExternalCellOrigin,
+        path: ProjectRelativePathBuf,
+    },
+}
+
+async fn fetch_impl(
+    ctx: &mut DiceComputations<'_>,
+    origin: &ExternalCellOrigin,
+    path: &ProjectRelativePath,
+    materializer: &dyn Materializer,
+    cancellations: &CancellationContext<'_>,
+) -> anyhow::Result<()> {
+    let io = ctx.get_blocking_executor();
+    io.execute_io(
+        Box::new(CleanOutputPaths {
+            paths: vec![path.to_owned()],
+        }),
+        cancellations,
+    )
+    .await?;
+
+    match origin {
+        ExternalCellOrigin::Git(setup) => git::fetch(ctx, setup, path, cancellations).await?,
+        ExternalCellOrigin::HttpArchive(setup) => {
+            http_archive::fetch(ctx, setup, path, cancellations).await?
+        }
+        ExternalCellOrigin::Bundled(_) | ExternalCellOrigin::Local(_) => {
+            return Err(internal_error_anyhow!(
+                "Bundled and local cells are not fetched, but tried to fetch `{}`",
+                origin
+            ));
+        }
+    }
+
+    // Read and hash the contents. We have to do this because the materializer requires an artifact
+    // value. This work is kind of duplicated with the reading in the fileops, but only the first
+    // time the contents are downloaded. On subsequent invocations of the daemon, we won't rerun
+    // this however, so that case will still avoid doing unnecessary work.
+    let io_prov = ctx.global_data().get_io_provider();
+    let proj_root = io_prov.project_root().root();
+    let abs_path = proj_root.join(path);
+    let digest_config = ctx.global_data().get_digest_config();
+    let file_digest_config = FileDigestConfig::build(digest_config.cas_digest_config());
+    let entry = build_entry_from_disk(abs_path, file_digest_config, &*io, proj_root)
+        .await?
+        .0
+        .ok_or_else(|| FetchError::NoDirectory(path.to_owned()))?;
+    let entry = entry.map_dir(|d| {
+        d.to_builder()
+            .fingerprint(digest_config.as_directory_serializer())
+            .shared(&*INTERNER)
+    });
+
+    materializer
+        .declare_existing(vec![(path.to_owned(), ArtifactValue::new(entry, None))])
+        .await?;
+
+    Ok(())
+}
+
+async fn fetch_and_materialize(
+    ctx: &mut DiceComputations<'_>,
+    path: &ProjectRelativePath,
+    origin: &ExternalCellOrigin,
+    cancellations: &CancellationContext<'_>,
+) -> anyhow::Result<()> {
+    let materializer = ctx.per_transaction_data().get_materializer();
+
+    // Origins are identified by a hash of their contents, so what we have is up to date.
+    if materializer.has_artifact_at(path.to_owned()).await? {
+        return Ok(());
+    }
+
+    if ctx.per_transaction_data().get_run_action_knobs().offline {
+        return Err(FetchError::Offline {
+            origin: origin.dupe(),
+            path: path.to_owned(),
+        }
+        .into());
+    }
+
+    // A map of directories to semaphores that are actually condvars which protect access to them
+    static DIRECTORY_LICENSES: OnceLock<Mutex<HashMap<ProjectRelativePathBuf, Arc<Semaphore>>>> =
+        OnceLock::new();
+
+    // We have to write this in a slightly funny way to convince the compiler that there's no
+    // `map_guard` being held across an await point
+    let semaphore;
+    let semaphore_guard;
+    'populate: {
+        'wait: {
+            let mut map_guard = DIRECTORY_LICENSES
+                .get_or_init(Default::default)
+                .lock()
+                .unwrap();
+            let entry = map_guard.entry(path.to_owned());
+
+            match entry {
+                hash_map::Entry::Occupied(entry) => {
+                    // There's another key simultaneously populating this directory. Just wait for
+                    // it to finish and then return. We don't need to check the contents of the
+                    // directory, since we assume that the origin uniquely identifies those.
+                    semaphore = entry.get().dupe();
+                    break 'wait;
+                }
+                hash_map::Entry::Vacant(entry) => {
+                    // It's on us to populate this directory. Make a condvar so that we block other accesses
+                    semaphore = Arc::new(Semaphore::new(1));
+                    semaphore_guard = semaphore.try_acquire().unwrap(); // we know there's a permit available
+                    entry.insert(semaphore.dupe());
+                    break 'populate;
+                }
+            }
+        }
+
+        drop(semaphore.acquire().await.unwrap());
+        return Ok(());
+    }
+
+    // Don't allow the actual download step to be cancelled. In principle it might be possible to
+    // properly clean up after a cancellation within the execution of this key, but we'd also have
+    // to deal with another key that might be waiting on this download to finish, which would be
+    // pretty complicated to deal with.
+    let res = cancellations
+        .critical_section(|| fetch_impl(ctx, origin, path, &*materializer, cancellations))
+        .await;
+
+    // Give up our lock
+    drop(semaphore_guard);
+    DIRECTORY_LICENSES
+        .get()
+        .unwrap()
+        .lock()
+        .unwrap()
+        .remove(path)
+        .unwrap();
+
+    res
+}
+
+#[derive(allocative::Allocative)]
+pub(crate) struct FetchedFileOpsDelegate {
+    buck_out_resolver: BuckOutPathResolver,
+    cell: CellName,
+    origin: ExternalCellOrigin,
+    // The fs accesses in this code are sort of a mix between source file accesses and buck-out
+    // accesses. Unconditionally using an `FsIoProvider` turns out to give all the right behavior
+    io: FsIoProvider,
+}
+
+impl FetchedFileOpsDelegate {
+    fn resolve(&self, path: &CellRelativePath) -> ProjectRelativePathBuf {
+        self.buck_out_resolver
+            .resolve_external_cell_source(path, self.origin.dupe())
+    }
+
+    fn get_base_path(&self) -> ProjectRelativePathBuf {
+        self.resolve(CellRelativePath::empty())
+    }
+}
+
+#[async_trait::async_trait]
+impl FileOpsDelegate for FetchedFileOpsDelegate {
+    async fn read_file_if_exists(
+        &self,
+        path: &'async_trait CellRelativePath,
+    ) -> anyhow::Result<Option<String>> {
+        let project_path = self.resolve(path);
+        (&self.io as &dyn IoProvider)
+            .read_file_if_exists(project_path)
+            .await
+    }
+
+    async fn read_dir(
+        &self,
+        path: &'async_trait CellRelativePath,
+    ) -> anyhow::Result<Vec<RawDirEntry>> {
+        let project_path = self.resolve(path);
+        let mut entries = (&self.io as &dyn IoProvider)
+            .read_dir(project_path)
+            .await
+            .with_context(|| format!("Error listing dir `{}`", path))?;
+
+        // Make sure entries are deterministic, since read_dir isn't.
+        entries.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+
+        Ok(entries)
+    }
+
+    async fn read_path_metadata_if_exists(
+        &self,
+        path: &'async_trait CellRelativePath,
+    ) -> anyhow::Result<Option<RawPathMetadata>> {
+        let project_path = self.resolve(path);
+
+        let Some(metadata) = (&self.io as &dyn IoProvider)
+            .read_path_metadata_if_exists(project_path)
+            .await
+            .with_context(|| format!("Error accessing metadata for path `{}`", path))?
+        else {
+            return Ok(None);
+        };
+        Ok(Some(metadata.try_map(
+            |path| match path.strip_prefix_opt(&self.get_base_path()) {
+                Some(path) => Ok(Arc::new(CellPath::new(self.cell, path.to_owned().into()))),
+                None => Err(internal_error_anyhow!(
+                    "Non-cell internal symlink at `{}` in cell `{}`",
+                    path,
+                    self.cell
+                )),
+            },
+        )?))
+    }
+
+    fn eq_token(&self) -> PartialEqAny {
+        PartialEqAny::always_false()
+    }
+}
+
+pub(crate) async fn get_file_ops_delegate(
+    ctx: &mut DiceComputations<'_>,
+    cell: CellName,
+    origin: ExternalCellOrigin,
+) -> anyhow::Result<Arc<FetchedFileOpsDelegate>> {
+    #[derive(
+        dupe::Dupe,
+        Clone,
+        Debug,
+        derive_more::Display,
+        PartialEq,
+        Eq,
+        Hash,
+        allocative::Allocative
+    )]
+    #[display("({}, {})", _0, _1)]
+    struct FetchedFileOpsDelegateKey(CellName, ExternalCellOrigin);
+
+    #[async_trait::async_trait]
+    impl Key for FetchedFileOpsDelegateKey {
+        type Value = buck2_error::Result<Arc<FetchedFileOpsDelegate>>;
+
+        async fn compute(
+            &self,
+            ctx: &mut DiceComputations,
+            cancellations: &CancellationContext,
+        ) -> Self::Value {
+            let artifact_fs = ctx.get_artifact_fs().await?;
+            let ops = FetchedFileOpsDelegate {
+                buck_out_resolver: artifact_fs.buck_out_path_resolver().clone(),
+                cell: self.0,
+                origin: self.1.dupe(),
+                io: FsIoProvider::new(
+                    artifact_fs.fs().dupe(),
+                    ctx.global_data().get_digest_config().cas_digest_config(),
+                ),
+            };
+            fetch_and_materialize(ctx, &ops.get_base_path(), &self.1, cancellations).await?;
+            Ok(Arc::new(ops))
+        }
+
+        fn equality(_x: &Self::Value, _y: &Self::Value) -> bool {
+            false
+        }
+    }
+
+    Ok(ctx
+        .compute(&FetchedFileOpsDelegateKey(cell, origin))
+        .await??)
+}
+
+pub(crate) async fn materialize_all(
+    ctx: &mut DiceComputations<'_>,
+    cell: CellName,
+    origin: ExternalCellOrigin,
+) -> anyhow::Result<ProjectRelativePathBuf> {
+    // Get the `FetchedFileOpsDelegate` instance to make sure all the data is materialized.
+    let ops = get_file_ops_delegate(ctx, cell, origin).await?;
+    Ok(ops.get_base_path())
+}
@@ -0,0 +1,226 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::io::Read;
+use std::sync::Arc;
+
+use anyhow::Context;
+use buck2_common::dice::data::HasIoProvider;
+use buck2_common::http::HasHttpClient;
+use buck2_core::cells::external::HttpArchiveCellSetup;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_core::fs::project_rel_path::ProjectRelativePath;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+use buck2_execute::digest_config::HasDigestConfig;
+use buck2_execute::execute::blocking::HasBlockingExecutor;
+use buck2_execute::execute::blocking::IoRequest;
+use buck2_execute::execute::clean_output_paths::CleanOutputPaths;
+use buck2_execute::materialize::http::http_download;
+use buck2_execute::materialize::http::Checksum;
+use dice::CancellationContext;
+use dice::DiceComputations;
+use dupe::Dupe;
+
+#[derive(buck2_error::Error, Debug)]
+enum HttpArchiveError {
+    #[error(
+        "Can't tell the format of the archive at `{0}`, expected it to end in `.tar`, `.tar.gz`, `.tgz`, `.tar.zst` or `.tzst`"
+    )]
+    #[buck2(input)]
+    UnknownFormat(Arc<str>),
+    #[error("Archive from `{url}` has no directory `{strip_prefix}` to strip")]
+    #[buck2(input)]
+    MissingStripPrefix {
+        url: Arc<str>,
+        strip_prefix: Arc<str>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveFormat {
+    Tar,
+    TarGz,
+    TarZst,
+}
+
+impl ArchiveFormat {
+    fn from_url(url: &Arc<str>) -> anyhow::Result<Self> {
+        let path = url.split(['?', '#']).next().unwrap_or_default();
+        if path.ends_with(".tar") {
+            Ok(Self::Tar)
+        } else if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+            Ok(Self::TarGz)
+        } else if path.ends_with(".tar.zst") || path.ends_with(".tzst") {
+            Ok(Self::TarZst)
+        } else {
+            Err(HttpArchiveError::UnknownFormat(url.dupe()).into())
+        }
+    }
+}
+
+struct ExtractIoRequest {
+    url: Arc<str>,
+    format: ArchiveFormat,
+    archive: ProjectRelativePathBuf,
+    strip_prefix: Option<Arc<str>>,
+    path: ProjectRelativePathBuf,
+}
+
+impl ExtractIoRequest {
+    fn unpack(
+        &self,
+        archive: impl Read,
+        dest: &ProjectRelativePath,
+        project_fs: &ProjectRoot,
+    ) -> anyhow::Result<()> {
+        let dest = project_fs.resolve(dest);
+        fs_util::create_dir_all(&dest)?;
+        // `unpack` refuses to write outside of `dest`.
+        tar::Archive::new(archive)
+            .unpack(&dest)
+            .with_context(|| format!("Error extracting archive from `{}`", self.url))
+    }
+}
+
+impl IoRequest for ExtractIoRequest {
+    fn execute(self: Box<Self>, project_fs: &ProjectRoot) -> anyhow::Result<()> {
+        let extract_to = match &self.strip_prefix {
+            Some(_) => ProjectRelativePathBuf::unchecked_new(format!("{}.extract", self.path)),
+            None => self.path.clone(),
+        };
+
+        let archive = fs_util::open_file(project_fs.resolve(&self.archive))?;
+        match self.format {
+            ArchiveFormat::Tar => self.unpack(archive, &extract_to, project_fs)?,
+            ArchiveFormat::TarGz => self.unpack(
+                flate2::read::GzDecoder::new(archive),
+                &extract_to,
+                project_fs,
+            )?,
+            ArchiveFormat::TarZst => self.unpack(
+                zstd::stream::read::Decoder::new(archive)?,
+                &extract_to,
+                project_fs,
+            )?,
+        }
+
+        if let Some(strip_prefix) = &self.strip_prefix {
+            let prefix = extract_to.join(ForwardRelativePath::new(strip_prefix.as_ref())?);
+            if !project_fs.resolve(&prefix).as_path().is_dir() {
+                return Err(HttpArchiveError::MissingStripPrefix {
+                    url: self.url.dupe(),
+                    strip_prefix: strip_prefix.dupe(),
+                }
+                .into());
+            }
+            fs_util::rename(project_fs.resolve(&prefix), project_fs.resolve(&self.path))?;
+            fs_util::remove_all(project_fs.resolve(&extract_to))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Download the archive and extract it into `path`, which must not exist.
+pub(crate) async fn fetch(
+    ctx: &mut DiceComputations<'_>,
+    setup: &HttpArchiveCellSetup,
+    path: &ProjectRelativePath,
+    cancellations: &CancellationContext<'_>,
+) -> anyhow::Result<()> {
+    let client = ctx.per_transaction_data().get_http_client();
+    let io = ctx.get_blocking_executor();
+    let io_provider = ctx.global_data().get_io_provider();
+    let project_fs = io_provider.project_root();
+    let digest_config = ctx.global_data().get_digest_config();
+
+    let archive = ProjectRelativePathBuf::unchecked_new(format!("{}.archive", path));
+    let extract = ProjectRelativePathBuf::unchecked_new(format!("{}.extract", path));
+    io.execute_io(
+        Box::new(CleanOutputPaths {
+            paths: vec![archive.clone(), extract],
+        }),
+        cancellations,
+    )
+    .await?;
+
+    // Each URL is expected to serve the same archive, so we stop at the first that works.
+    let checksum = Checksum::Sha256(setup.sha256.dupe());
+    let mut result = None;
+    for url in setup.urls.iter() {
+        let format = ArchiveFormat::from_url(url)?;
+        match http_download(
+            &client,
+            project_fs,
+            digest_config,
+            &archive,
+            url,
+            &checksum,
+            false,
+        )
+        .await
+        {
+            Ok(_) => {
+                result = Some(Ok((url.dupe(), format)));
+                break;
+            }
+            Err(e) => {
+                tracing::debug!("Failed to download external cell from `{}`: {:#}", url, e);
+                result = Some(Err(e));
+            }
+        }
+    }
+    let (url, format) = result.context("External cell has no URLs to download from")??;
+
+    io.execute_io(
+        Box::new(ExtractIoRequest {
+            url,
+            format,
+            archive: archive.clone(),
+            strip_prefix: setup.strip_prefix.dupe(),
+            path: path.to_owned(),
+        }),
+        cancellations,
+    )
+    .await?;
+
+    io.execute_io(
+        Box::new(CleanOutputPaths {
+            paths: vec![archive],
+        }),
+        cancellations,
+    )
+    .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_format_from_url() {
+        let format = |url: &str| ArchiveFormat::from_url(&Arc::from(url)).ok();
+
+        assert_eq!(Some(ArchiveFormat::Tar), format("https://x/a.tar"));
+        assert_eq!(Some(ArchiveFormat::TarGz), format("https://x/a.tar.gz"));
+        assert_eq!(
+            Some(ArchiveFormat::TarGz),
+            format("https://x/a.tgz?raw=true")
+        );
+        assert_eq!(
+            Some(ArchiveFormat::TarZst),
+            format("https://x/a.tar.zst#frag")
+        );
+        assert_eq!(None, format("https://x/a.zip"));
+    }
+}
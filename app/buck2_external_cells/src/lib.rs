@@ -25,7 +25,12 @@ use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
 use dice::DiceComputations;
 
 mod bundled;
+mod fetched;
 mod git;
+mod http_archive;
+mod local;
+mod resolve;
+mod versions;
 
 struct ConcreteExternalCellsImpl;
 
@@ -47,8 +52,11 @@ impl buck2_common::external_cells::ExternalCellsImpl for ConcreteExternalCellsIm
             ExternalCellOrigin::Bundled(cell_name) => {
                 Ok(bundled::get_file_ops_delegate(ctx, cell_name).await? as _)
             }
-            ExternalCellOrigin::Git(setup) => {
-                Ok(git::get_file_ops_delegate(ctx, cell_name, setup).await? as _)
+            ExternalCellOrigin::Git(_) | ExternalCellOrigin::HttpArchive(_) => {
+                Ok(fetched::get_file_ops_delegate(ctx, cell_name, origin).await? as _)
+            }
+            ExternalCellOrigin::Local(setup) => {
+                Ok(local::get_file_ops_delegate(ctx, &setup).await? as _)
             }
         }
     }
@@ -89,11 +97,25 @@ impl buck2_common::external_cells::ExternalCellsImpl for ConcreteExternalCellsIm
         // now.
         let materialized_path = match origin {
             ExternalCellOrigin::Bundled(cell) => bundled::materialize_all(ctx, cell).await?,
-            ExternalCellOrigin::Git(setup) => git::materialize_all(ctx, cell, setup).await?,
+            ExternalCellOrigin::Git(_) | ExternalCellOrigin::HttpArchive(_) => {
+                fetched::materialize_all(ctx, cell, origin).await?
+            }
+            // Already on disk, so copy it directly.
+            ExternalCellOrigin::Local(setup) => {
+                return local::expand(ctx, &setup, &dest_path).await;
+            }
         };
 
         io.project_root().copy(&materialized_path, &dest_path)
     }
+
+    async fn resolve_lockfile(
+        &self,
+        ctx: &mut DiceComputations<'_>,
+        update: bool,
+    ) -> anyhow::Result<Vec<String>> {
+        resolve::resolve_lockfile(ctx, update).await
+    }
 }
 
 pub fn init_late_bindings() {
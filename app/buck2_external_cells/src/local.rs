@@ -0,0 +1,179 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! External cells read in place from a local directory.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Context;
+use buck2_common::dice::cells::HasCellResolver;
+use buck2_common::dice::data::HasIoProvider;
+use buck2_common::dice::file_ops::delegate::FileOpsDelegate;
+use buck2_common::file_ops::RawDirEntry;
+use buck2_common::file_ops::RawPathMetadata;
+use buck2_common::io::fs::FsIoProvider;
+use buck2_common::io::IoProvider;
+use buck2_core::cells::cell_path::CellPath;
+use buck2_core::cells::external::LocalCellSetup;
+use buck2_core::cells::name::CellName;
+use buck2_core::cells::paths::CellRelativePath;
+use buck2_core::cells::CellResolver;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::paths::RelativePath;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_core::fs::project_rel_path::ProjectRelativePath;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+use buck2_execute::digest_config::HasDigestConfig;
+use cmp_any::PartialEqAny;
+use dice::DiceComputations;
+use dupe::Dupe;
+
+#[derive(buck2_error::Error, Debug)]
+enum LocalError {
+    #[error("Local directory `{0}` for external cell `{1}` does not exist or is not a directory")]
+    #[buck2(input)]
+    NotADirectory(AbsNormPathBuf, String),
+}
+
+fn resolve_dir(
+    project_root: &ProjectRoot,
+    setup: &LocalCellSetup,
+) -> anyhow::Result<AbsNormPathBuf> {
+    let dir = if Path::new(setup.path.as_ref()).is_absolute() {
+        AbsNormPathBuf::new(setup.path.as_ref().into())?
+    } else {
+        project_root
+            .root()
+            .join_normalized(RelativePath::new(setup.path.as_ref()))?
+    };
+    if !dir.as_path().is_dir() {
+        return Err(LocalError::NotADirectory(dir, setup.cell.to_string()).into());
+    }
+    Ok(dir)
+}
+
+/// Reads the files of the cell from the directory itself. When the directory is inside the
+/// project, the file watcher reports changes to it, which `FileChangeTracker` also applies to the
+/// cell.
+#[derive(allocative::Allocative)]
+pub(crate) struct LocalFileOpsDelegate {
+    cell: CellName,
+    /// The directory, relative to the root of `io`. That's the project root if the directory is
+    /// inside the project, and the directory itself otherwise.
+    base: ProjectRelativePathBuf,
+    io: FsIoProvider,
+    cells: CellResolver,
+}
+
+impl LocalFileOpsDelegate {
+    fn resolve(&self, path: &CellRelativePath) -> ProjectRelativePathBuf {
+        self.base.join(path)
+    }
+}
+
+#[async_trait::async_trait]
+impl FileOpsDelegate for LocalFileOpsDelegate {
+    async fn read_file_if_exists(
+        &self,
+        path: &'async_trait CellRelativePath,
+    ) -> anyhow::Result<Option<String>> {
+        let project_path = self.resolve(path);
+        (&self.io as &dyn IoProvider)
+            .read_file_if_exists(project_path)
+            .await
+    }
+
+    async fn read_dir(
+        &self,
+        path: &'async_trait CellRelativePath,
+    ) -> anyhow::Result<Vec<RawDirEntry>> {
+        let project_path = self.resolve(path);
+        let mut entries = (&self.io as &dyn IoProvider)
+            .read_dir(project_path)
+            .await
+            .with_context(|| format!("Error listing dir `{}`", path))?;
+
+        // Make sure entries are deterministic, since read_dir isn't.
+        entries.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+
+        Ok(entries)
+    }
+
+    async fn read_path_metadata_if_exists(
+        &self,
+        path: &'async_trait CellRelativePath,
+    ) -> anyhow::Result<Option<RawPathMetadata>> {
+        let project_path = self.resolve(path);
+
+        let Some(metadata) = (&self.io as &dyn IoProvider)
+            .read_path_metadata_if_exists(project_path)
+            .await
+            .with_context(|| format!("Error accessing metadata for path `{}`", path))?
+        else {
+            return Ok(None);
+        };
+        Ok(Some(metadata.try_map(|path| {
+            match path.strip_prefix_opt(&self.base) {
+                Some(path) => Ok(Arc::new(CellPath::new(self.cell, path.to_owned().into()))),
+                // A symlink to elsewhere in the project.
+                None => Ok(Arc::new(self.cells.get_cell_path(path)?)),
+            }
+        })?))
+    }
+
+    fn eq_token(&self) -> PartialEqAny {
+        PartialEqAny::always_false()
+    }
+}
+
+pub(crate) async fn get_file_ops_delegate(
+    ctx: &mut DiceComputations<'_>,
+    setup: &LocalCellSetup,
+) -> anyhow::Result<Arc<LocalFileOpsDelegate>> {
+    let project_root = ctx.global_data().get_io_provider().project_root().dupe();
+    let cas_digest_config = ctx.global_data().get_digest_config().cas_digest_config();
+    let cells = ctx.get_cell_resolver().await?;
+
+    let dir = resolve_dir(&project_root, setup)?;
+    let (root, base) = match project_root.relativize(&dir) {
+        Ok(base) => (project_root, base.into_owned()),
+        Err(_) => (
+            ProjectRoot::new(fs_util::canonicalize(&dir)?)?,
+            ProjectRelativePath::empty().to_buf(),
+        ),
+    };
+
+    Ok(Arc::new(LocalFileOpsDelegate {
+        cell: setup.cell,
+        base,
+        io: FsIoProvider::new(root, cas_digest_config),
+        cells,
+    }))
+}
+
+/// Copy the local directory into `dest`, which must not contain data.
+pub(crate) async fn expand(
+    ctx: &mut DiceComputations<'_>,
+    setup: &LocalCellSetup,
+    dest: &ProjectRelativePath,
+) -> anyhow::Result<()> {
+    let project_root = ctx.global_data().get_io_provider().project_root().dupe();
+    let src = resolve_dir(&project_root, setup)?;
+    project_root.copy_from_external(&src, dest)?;
+
+    // Like for git cells, the history is of no use to us.
+    fs_util::remove_all(
+        project_root.resolve(dest.join(ForwardRelativePath::new(".git").unwrap())),
+    )?;
+
+    Ok(())
+}
@@ -0,0 +1,544 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Resolution of the external cells that cells' manifests ask for to a lockfile.
+//!
+//! This uses minimal version selection: every external cell gets the lowest version (among the
+//! tags of its git repo) that satisfies all the constraints on it, including those in the manifests
+//! of the selected versions of other external cells. That makes the result reproducible, and means
+//! a dependency is only upgraded when some manifest asks for it.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::process::Command;
+use std::process::ExitStatus;
+use std::process::Stdio;
+
+use anyhow::Context;
+use buck2_build_api::actions::artifact::get_artifact_fs::GetArtifactFs;
+use buck2_common::dice::cells::HasCellResolver;
+use buck2_common::dice::data::HasIoProvider;
+use buck2_common::external_cells::lockfile::ExternalCellsLockfile;
+use buck2_common::external_cells::lockfile::LockedCell;
+use buck2_common::external_cells::lockfile::EXTERNAL_CELLS_LOCKFILE;
+use buck2_common::external_cells::lockfile::EXTERNAL_CELLS_MANIFEST;
+use buck2_core::fs::fs_util;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::project_rel_path::ProjectRelativePath;
+use buck2_util::process::background_command;
+use dice::DiceComputations;
+use dupe::Dupe;
+use serde::Deserialize;
+
+use crate::versions::Version;
+use crate::versions::VersionReq;
+
+/// Minimal version selection converges quickly in practice, so hitting this means the manifests
+/// keep flipping between versions.
+const MAX_ROUNDS: usize = 100;
+
+#[derive(buck2_error::Error, Debug)]
+enum ResolveError {
+    #[error(
+        "Error running git to resolve external cells, exit code: {exit_code:?}, stderr:\n{stderr}"
+    )]
+    Git {
+        exit_code: ExitStatus,
+        stderr: String,
+    },
+    #[error("Error parsing `external_cells.toml` of {1}")]
+    #[buck2(input)]
+    InvalidManifest(#[source] toml::de::Error, String),
+    #[error(
+        "External cell `{cell}` is required from `{first}` by {first_by}, but from `{second}` by {second_by}"
+    )]
+    #[buck2(input)]
+    ConflictingOrigins {
+        cell: String,
+        first: String,
+        first_by: String,
+        second: String,
+        second_by: String,
+    },
+    #[error(
+        "No version of external cell `{cell}` satisfies all constraints on it:\n{constraints}\nAvailable versions: {available}"
+    )]
+    #[buck2(input)]
+    NoMatchingVersion {
+        cell: String,
+        constraints: String,
+        available: String,
+    },
+    #[error("Tag `{tag}` of `{git}` moved while resolving external cells")]
+    TagMoved { git: String, tag: String },
+    #[error("Resolving external cells did not converge after {0} rounds")]
+    #[buck2(input)]
+    DidNotConverge(usize),
+}
+
+/// The `external_cells.toml` at the root of a cell.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    #[serde(default)]
+    cells: BTreeMap<String, ManifestEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ManifestEntry {
+    git: String,
+    version: String,
+}
+
+#[derive(Debug, Clone)]
+struct Requirement {
+    cell: String,
+    git: String,
+    req: VersionReq,
+    /// For error messages.
+    required_by: String,
+}
+
+fn parse_manifest(contents: &str, required_by: &str) -> anyhow::Result<Vec<Requirement>> {
+    let manifest: Manifest = toml::from_str(contents)
+        .map_err(|e| ResolveError::InvalidManifest(e, required_by.to_owned()))?;
+    manifest
+        .cells
+        .into_iter()
+        .map(|(cell, entry)| {
+            anyhow::Ok(Requirement {
+                req: entry.version.parse().with_context(|| {
+                    format!("Error parsing the version of `{}` in {}", cell, required_by)
+                })?,
+                cell,
+                git: entry.git,
+                required_by: required_by.to_owned(),
+            })
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Tag {
+    version: Version,
+    name: String,
+    commit: String,
+}
+
+pub(crate) struct Resolver {
+    /// A git repo to fetch manifests into.
+    scratch: AbsNormPathBuf,
+    /// Version tags of each git repo, sorted by version.
+    tags: HashMap<String, Vec<Tag>>,
+    /// Manifest contents at each `(git repo, commit)`.
+    manifests: HashMap<(String, String), Option<String>>,
+}
+
+impl Resolver {
+    pub(crate) fn new(scratch: AbsNormPathBuf) -> anyhow::Result<Self> {
+        fs_util::create_dir_all(&scratch)?;
+        let resolver = Self {
+            scratch,
+            tags: HashMap::new(),
+            manifests: HashMap::new(),
+        };
+        if !fs_util::try_exists(resolver.scratch.join(ForwardRelativePath::new(".git")?))? {
+            resolver.git(|c| {
+                c.arg("init").arg("--quiet");
+            })?;
+        }
+        Ok(resolver)
+    }
+
+    fn git_output(&self, f: impl FnOnce(&mut Command)) -> anyhow::Result<std::process::Output> {
+        let mut cmd = background_command("git");
+        f(&mut cmd);
+        cmd.current_dir(&self.scratch)
+            .stdin(Stdio::null())
+            .output()
+            .context("Could not run git to resolve external cells")
+    }
+
+    fn git(&self, f: impl FnOnce(&mut Command)) -> anyhow::Result<String> {
+        let output = self.git_output(f)?;
+        if !output.status.success() {
+            return Err(ResolveError::Git {
+                exit_code: output.status,
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            }
+            .into());
+        }
+        String::from_utf8(output.stdout).context("git output is not UTF-8")
+    }
+
+    fn tags(&mut self, git: &str) -> anyhow::Result<&[Tag]> {
+        if !self.tags.contains_key(git) {
+            let output = self.git(|c| {
+                c.arg("ls-remote").arg("--tags").arg(git);
+            })?;
+            // Annotated tags are listed twice, the second time as `<tag>^{}` with the commit they
+            // point to, which is the one we want.
+            let mut commits = BTreeMap::new();
+            for line in output.lines() {
+                let Some((commit, name)) = line.split_once('\t') else {
+                    continue;
+                };
+                let Some(name) = name.strip_prefix("refs/tags/") else {
+                    continue;
+                };
+                match name.strip_suffix("^{}") {
+                    Some(name) => {
+                        commits.insert(name.to_owned(), commit.to_owned());
+                    }
+                    None => {
+                        commits
+                            .entry(name.to_owned())
+                            .or_insert_with(|| commit.to_owned());
+                    }
+                }
+            }
+            let mut tags: Vec<Tag> = commits
+                .into_iter()
+                .filter_map(|(name, commit)| {
+                    Some(Tag {
+                        version: Version::from_tag(&name)?,
+                        name,
+                        commit,
+                    })
+                })
+                .collect();
+            tags.sort_by(|a, b| (a.version, &a.name).cmp(&(b.version, &b.name)));
+            self.tags.insert(git.to_owned(), tags);
+        }
+        Ok(&self.tags[git])
+    }
+
+    fn manifest(&mut self, git: &str, tag: &Tag) -> anyhow::Result<Option<String>> {
+        let key = (git.to_owned(), tag.commit.clone());
+        if let Some(manifest) = self.manifests.get(&key) {
+            return Ok(manifest.clone());
+        }
+
+        // Fetch the tag rather than the commit, since not every server lets you fetch commits that
+        // aren't at the tip of a ref.
+        self.git(|c| {
+            c.arg("fetch")
+                .arg("--quiet")
+                .arg("--depth=1")
+                .arg(git)
+                .arg(format!("refs/tags/{}", tag.name));
+        })?;
+        let fetched = self.git(|c| {
+            c.arg("rev-parse").arg("FETCH_HEAD^{commit}");
+        })?;
+        if fetched.trim() != tag.commit {
+            return Err(ResolveError::TagMoved {
+                git: git.to_owned(),
+                tag: tag.name.clone(),
+            }
+            .into());
+        }
+
+        let object = format!("{}:{}", tag.commit, EXTERNAL_CELLS_MANIFEST);
+        let manifest = if self
+            .git_output(|c| {
+                c.arg("cat-file").arg("-e").arg(&object);
+            })?
+            .status
+            .success()
+        {
+            Some(self.git(|c| {
+                c.arg("show").arg(&object);
+            })?)
+        } else {
+            None
+        };
+        self.manifests.insert(key, manifest.clone());
+        Ok(manifest)
+    }
+
+    /// Resolve the manifests of the cells in the project, given as `(cell, contents)`.
+    pub(crate) fn resolve(
+        &mut self,
+        manifests: &[(String, String)],
+    ) -> anyhow::Result<ExternalCellsLockfile> {
+        let mut root_requirements = Vec::new();
+        for (cell, contents) in manifests {
+            root_requirements.extend(parse_manifest(contents, &format!("cell `{}`", cell))?);
+        }
+
+        let mut selected: BTreeMap<String, (String, Tag)> = BTreeMap::new();
+        for _ in 0..MAX_ROUNDS {
+            let mut requirements = root_requirements.clone();
+            for (cell, (git, tag)) in &selected {
+                if let Some(contents) = self.manifest(git, tag)? {
+                    requirements.extend(parse_manifest(
+                        &contents,
+                        &format!("external cell `{}` {}", cell, tag.version),
+                    )?);
+                }
+            }
+
+            let mut by_cell: BTreeMap<&str, Vec<&Requirement>> = BTreeMap::new();
+            for requirement in &requirements {
+                by_cell
+                    .entry(requirement.cell.as_str())
+                    .or_default()
+                    .push(requirement);
+            }
+
+            let mut next = BTreeMap::new();
+            for (cell, requirements) in by_cell {
+                let first = requirements[0];
+                if let Some(other) = requirements.iter().find(|r| r.git != first.git) {
+                    return Err(ResolveError::ConflictingOrigins {
+                        cell: cell.to_owned(),
+                        first: first.git.clone(),
+                        first_by: first.required_by.clone(),
+                        second: other.git.clone(),
+                        second_by: other.required_by.clone(),
+                    }
+                    .into());
+                }
+
+                let tags = self.tags(&first.git)?;
+                let Some(tag) = tags
+                    .iter()
+                    .find(|tag| requirements.iter().all(|r| r.req.matches(&tag.version)))
+                else {
+                    return Err(ResolveError::NoMatchingVersion {
+                        cell: cell.to_owned(),
+                        constraints: requirements
+                            .iter()
+                            .map(|r| format!("  `{}` from {}", r.req, r.required_by))
+                            .collect::<Vec<_>>()
+                            .join("\n"),
+                        available: if tags.is_empty() {
+                            "none".to_owned()
+                        } else {
+                            tags.iter()
+                                .map(|t| t.version.to_string())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        },
+                    }
+                    .into());
+                };
+                next.insert(cell.to_owned(), (first.git.clone(), tag.clone()));
+            }
+
+            if next == selected {
+                return Ok(ExternalCellsLockfile {
+                    version: ExternalCellsLockfile::VERSION,
+                    cells: selected
+                        .into_iter()
+                        .map(|(cell, (git, tag))| {
+                            (
+                                cell,
+                                LockedCell {
+                                    git,
+                                    version: tag.version.to_string(),
+                                    tag: tag.name,
+                                    commit: tag.commit,
+                                },
+                            )
+                        })
+                        .collect(),
+                });
+            }
+            selected = next;
+        }
+
+        Err(ResolveError::DidNotConverge(MAX_ROUNDS).into())
+    }
+}
+
+pub(crate) async fn resolve_lockfile(
+    ctx: &mut DiceComputations<'_>,
+    update: bool,
+) -> anyhow::Result<Vec<String>> {
+    let cells = ctx.get_cell_resolver().await?;
+    let artifact_fs = ctx.get_artifact_fs().await?;
+    let project_fs = ctx.global_data().get_io_provider().project_root().dupe();
+
+    // External cells can't declare further external cells to the build, but their manifests are
+    // still taken into account by the resolution.
+    let mut manifests = Vec::new();
+    for (name, instance) in cells.cells() {
+        if instance.external().is_some() {
+            continue;
+        }
+        let path = instance
+            .path()
+            .as_project_relative_path()
+            .join(ForwardRelativePath::new(EXTERNAL_CELLS_MANIFEST)?);
+        if let Some(contents) = fs_util::read_to_string_if_exists(project_fs.resolve(&path))? {
+            manifests.push((name.to_string(), contents));
+        }
+    }
+
+    let scratch = project_fs.resolve(
+        artifact_fs
+            .buck_out_path_resolver()
+            .root()
+            .join(ForwardRelativePath::new("external_cells/resolve")?),
+    );
+    let lockfile_path = project_fs.resolve(ProjectRelativePath::new(EXTERNAL_CELLS_LOCKFILE)?);
+
+    tokio::task::spawn_blocking(move || {
+        let new = Resolver::new(scratch)?.resolve(&manifests)?;
+
+        let old = fs_util::read_to_string_if_exists(&lockfile_path)?
+            .map(|contents| ExternalCellsLockfile::parse(&contents))
+            .transpose();
+        let (old, rewrite) = match old {
+            Ok(old) => (old.unwrap_or_default(), false),
+            // A broken lockfile is what `update` is for.
+            Err(_) if update => (ExternalCellsLockfile::default(), true),
+            Err(e) => return Err(e),
+        };
+
+        let diff = old.diff(&new);
+        if update && (rewrite || !diff.is_empty()) {
+            fs_util::write(&lockfile_path, new.serialize()?)?;
+        }
+        Ok(diff)
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn run_git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args([
+                "-c",
+                "user.name=test",
+                "-c",
+                "user.email=test@example.com",
+                "-c",
+                "commit.gpgsign=false",
+                "-c",
+                "tag.gpgsign=false",
+            ])
+            .args(args)
+            .current_dir(dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    /// Create a repo with one commit per `(tag, manifest)`.
+    fn fixture_repo(dir: &Path, name: &str, versions: &[(&str, Option<&str>)]) -> String {
+        let repo = dir.join(name);
+        std::fs::create_dir_all(&repo).unwrap();
+        run_git(&repo, &["init", "--quiet"]);
+        for (tag, manifest) in versions {
+            std::fs::write(repo.join("BUCK"), format!("# {}\n", tag)).unwrap();
+            std::fs::write(
+                repo.join(EXTERNAL_CELLS_MANIFEST),
+                manifest.unwrap_or_default(),
+            )
+            .unwrap();
+            run_git(&repo, &["add", "."]);
+            run_git(&repo, &["commit", "--quiet", "-m", tag]);
+            run_git(&repo, &["tag", "-a", "-m", tag, tag]);
+        }
+        repo.to_str().unwrap().to_owned()
+    }
+
+    fn resolver(dir: &Path) -> Resolver {
+        Resolver::new(AbsNormPathBuf::new(dir.join("scratch")).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_raises_shared_dependency() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let rules = fixture_repo(
+            dir.path(),
+            "rules",
+            &[("v1.0.0", None), ("v1.1.0", None), ("v2.0.0", None)],
+        );
+        let libfoo_manifest = format!(
+            "[cells.rules]\ngit = {:?}\nversion = \">=1.1, <2\"\n",
+            rules
+        );
+        let libfoo = fixture_repo(
+            dir.path(),
+            "libfoo",
+            &[("v1.0.0", Some(libfoo_manifest.as_str()))],
+        );
+
+        let root = format!(
+            "[cells.rules]\ngit = {:?}\nversion = \"1.0\"\n[cells.libfoo]\ngit = {:?}\nversion = \"1\"\n",
+            rules, libfoo
+        );
+        let lockfile = resolver(dir.path()).resolve(&[("root".to_owned(), root)])?;
+
+        // `rules` is raised to what `libfoo` needs, but no further.
+        assert_eq!(
+            vec![("libfoo", "1.0.0"), ("rules", "1.1.0")],
+            lockfile
+                .cells
+                .iter()
+                .map(|(name, cell)| (name.as_str(), cell.version.as_str()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!("v1.1.0", lockfile.cells["rules"].tag);
+        assert_eq!(40, lockfile.cells["rules"].commit.len());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_conflicts() -> anyhow::Result<()> {
+        let dir = tempfile::tempdir()?;
+        let rules = fixture_repo(dir.path(), "rules", &[("v1.0.0", None), ("v2.0.0", None)]);
+        let mut resolver = resolver(dir.path());
+
+        let incompatible = [
+            (
+                "a".to_owned(),
+                format!("[cells.rules]\ngit = {:?}\nversion = \"1\"\n", rules),
+            ),
+            (
+                "b".to_owned(),
+                format!("[cells.rules]\ngit = {:?}\nversion = \">=2\"\n", rules),
+            ),
+        ];
+        let err = resolver.resolve(&incompatible).unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("Available versions: 1.0.0, 2.0.0"),
+            "{:#}",
+            err
+        );
+
+        let different_origins = [
+            (
+                "a".to_owned(),
+                format!("[cells.rules]\ngit = {:?}\nversion = \"1\"\n", rules),
+            ),
+            (
+                "b".to_owned(),
+                "[cells.rules]\ngit = \"https://example.com/rules\"\nversion = \"1\"\n".to_owned(),
+            ),
+        ];
+        assert!(resolver.resolve(&different_origins).is_err());
+
+        Ok(())
+    }
+}
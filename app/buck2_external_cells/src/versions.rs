@@ -0,0 +1,187 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Versions of external cells, as taken from git tags, and the constraints manifests put on them.
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(buck2_error::Error, Debug)]
+enum VersionError {
+    #[error("Invalid version `{0}`, expected up to three dot separated numbers")]
+    #[buck2(input)]
+    InvalidVersion(String),
+    #[error("Invalid version constraint `{0}`")]
+    #[buck2(input)]
+    InvalidConstraint(String),
+}
+
+/// A `major.minor.patch` version. Missing components are zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) struct Version {
+    major: u64,
+    minor: u64,
+    patch: u64,
+}
+
+impl Version {
+    /// The version a git tag names, if any. Tags may have a leading `v`.
+    pub(crate) fn from_tag(tag: &str) -> Option<Self> {
+        tag.strip_prefix('v').unwrap_or(tag).parse().ok()
+    }
+}
+
+impl FromStr for Version {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut parts = [0; 3];
+        let mut count = 0;
+        for part in s.split('.') {
+            if count == parts.len() || part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit())
+            {
+                return Err(VersionError::InvalidVersion(s.to_owned()).into());
+            }
+            parts[count] = part
+                .parse()
+                .map_err(|_| VersionError::InvalidVersion(s.to_owned()))?;
+            count += 1;
+        }
+        Ok(Self {
+            major: parts[0],
+            minor: parts[1],
+            patch: parts[2],
+        })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+    /// At least this version, and below the next major version (or the next minor version for
+    /// `0.x`), like Cargo's default.
+    Compatible,
+}
+
+/// A comma separated list of constraints that must all hold, like `>=1.2, <2`. A bare version
+/// means `^version`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct VersionReq {
+    text: String,
+    constraints: Vec<(Op, Version)>,
+}
+
+impl VersionReq {
+    pub(crate) fn matches(&self, version: &Version) -> bool {
+        self.constraints.iter().all(|(op, v)| match op {
+            Op::Exact => version == v,
+            Op::Greater => version > v,
+            Op::GreaterEq => version >= v,
+            Op::Less => version < v,
+            Op::LessEq => version <= v,
+            Op::Compatible => {
+                version >= v
+                    && if v.major == 0 {
+                        version.major == 0 && version.minor == v.minor
+                    } else {
+                        version.major == v.major
+                    }
+            }
+        })
+    }
+}
+
+impl FromStr for VersionReq {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let constraints = s
+            .split(',')
+            .map(|c| {
+                let c = c.trim();
+                let (op, version) = [
+                    (">=", Op::GreaterEq),
+                    ("<=", Op::LessEq),
+                    (">", Op::Greater),
+                    ("<", Op::Less),
+                    ("=", Op::Exact),
+                    ("^", Op::Compatible),
+                ]
+                .into_iter()
+                .find_map(|(prefix, op)| c.strip_prefix(prefix).map(|v| (op, v)))
+                .unwrap_or((Op::Compatible, c));
+                let version = version
+                    .trim()
+                    .parse()
+                    .map_err(|_| VersionError::InvalidConstraint(s.to_owned()))?;
+                anyhow::Ok((op, version))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            text: s.to_owned(),
+            constraints,
+        })
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(req: &str, version: &str) -> bool {
+        req.parse::<VersionReq>()
+            .unwrap()
+            .matches(&version.parse().unwrap())
+    }
+
+    #[test]
+    fn test_version() {
+        assert_eq!(
+            Some("1.2.0".parse::<Version>().unwrap()),
+            Version::from_tag("v1.2")
+        );
+        assert_eq!(None, Version::from_tag("release-1.2"));
+        assert_eq!(None, Version::from_tag("1.2.3.4"));
+        assert_eq!(None, Version::from_tag("1..2"));
+        assert!(Version::from_tag("1.10").unwrap() > Version::from_tag("1.9.9").unwrap());
+    }
+
+    #[test]
+    fn test_version_req() {
+        assert!(matches(">=1.2, <2", "1.2.0"));
+        assert!(matches(">=1.2, <2", "1.9.3"));
+        assert!(!matches(">=1.2, <2", "2.0.0"));
+        assert!(!matches(">=1.2, <2", "1.1.9"));
+
+        assert!(matches("1.2", "1.3.0"));
+        assert!(!matches("1.2", "2.0.0"));
+        assert!(!matches("^0.2", "0.3.0"));
+        assert!(matches("=1.2.3", "1.2.3"));
+        assert!(!matches("=1.2.3", "1.2.4"));
+
+        assert!("".parse::<VersionReq>().is_err());
+        assert!(">=x".parse::<VersionReq>().is_err());
+    }
+}
@@ -23,6 +23,7 @@ use buck2_core::is_open_source;
 use dice::DiceTransactionUpdater;
 
 use crate::fs_hash_crawler::FsHashCrawler;
+use crate::git::GitFileWatcher;
 use crate::mergebase::Mergebase;
 use crate::notify::NotifyFileWatcher;
 use crate::watchman::interface::WatchmanFileWatcher;
@@ -33,6 +34,13 @@ pub trait FileWatcher: Allocative + Send + Sync + 'static {
         &self,
         dice: DiceTransactionUpdater,
     ) -> anyhow::Result<(DiceTransactionUpdater, Mergebase)>;
+
+    /// Marked changed whenever the watcher sees changes that the next `sync` would pick up, so that
+    /// commands can wait for changes without syncing. `None` if the watcher only finds changes by
+    /// syncing.
+    fn changes(&self) -> Option<tokio::sync::watch::Receiver<()>> {
+        None
+    }
 }
 
 impl dyn FileWatcher {
@@ -69,6 +77,10 @@ impl dyn FileWatcher {
                 FsHashCrawler::new(project_root, cells, ignore_specs)
                     .context("Creating fs_crawler file watcher")?,
             )),
+            "git" => Ok(Arc::new(
+                GitFileWatcher::new(project_root, root_config, cells, ignore_specs)
+                    .context("Creating git file watcher")?,
+            )),
             other => Err(anyhow::anyhow!("Invalid buck2.file_watcher: {}", other)),
         }
     }
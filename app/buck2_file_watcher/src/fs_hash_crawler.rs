@@ -79,7 +79,7 @@ impl FsHashCrawler {
         let mut guard = self.snapshot.lock().unwrap();
         let old_snapshot = mem::replace(&mut *guard, new_snapshot);
         let (stats, changes) = old_snapshot.get_updates_for_dice(&guard, &self.ignore_specs)?;
-        changes.write_to_dice(&mut dice)?;
+        changes.write_to_dice(&mut dice, &self.cells)?;
         Ok((stats, dice))
     }
 }
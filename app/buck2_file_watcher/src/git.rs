@@ -0,0 +1,804 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A file watcher that asks Git what changed instead of subscribing to file system events.
+//!
+//! On every sync we record the `HEAD` commit and the set of paths that `git status` reports as
+//! differing from `HEAD` (modified, deleted or untracked), together with their current `stat`
+//! information. Changes between two syncs are then:
+//!
+//! * the files that differ between the old and the new `HEAD` (if `HEAD` moved), and
+//! * the files whose dirty state or `stat` information changed.
+//!
+//! Git's own index stat cache makes `git status` cheap on large checkouts, and since we never
+//! register any watches this is not subject to inotify limits.
+//!
+//! Note that Git does not report changes to ignored files, so files matched by `.gitignore` are
+//! not tracked by this watcher.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use allocative::Allocative;
+use anyhow::Context;
+use async_trait::async_trait;
+use buck2_common::dice::file_ops::FileChangeTracker;
+use buck2_common::ignores::ignore_set::IgnoreSet;
+use buck2_common::invocation_paths::InvocationPaths;
+use buck2_common::legacy_configs::configs::LegacyBuckConfig;
+use buck2_common::legacy_configs::key::BuckconfigKeyRef;
+use buck2_core::cells::name::CellName;
+use buck2_core::cells::CellResolver;
+use buck2_core::fs::paths::forward_rel_path::ForwardRelativePath;
+use buck2_core::fs::project::ProjectRoot;
+use buck2_core::fs::project_rel_path::ProjectRelativePath;
+use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+use buck2_data::FileWatcherEventType;
+use buck2_data::FileWatcherKind;
+use buck2_events::dispatch::span_async;
+use buck2_util::process::async_background_command;
+use dice::DiceTransactionUpdater;
+use dupe::Dupe;
+use starlark_map::ordered_set::OrderedSet;
+use tracing::info;
+
+use crate::file_watcher::FileWatcher;
+use crate::mergebase::Mergebase;
+use crate::stats::FileWatcherStats;
+
+/// If `HEAD` moves across more commits than this, we treat the sync as a fresh instance and drop
+/// the DICE state instead of invalidating every changed file individually.
+const DEFAULT_FRESH_INSTANCE_COMMIT_THRESHOLD: u64 = 1000;
+
+#[derive(Debug, buck2_error::Error)]
+enum GitFileWatcherError {
+    #[error("`git {}` failed with {}: {}", args, status, stderr.trim())]
+    CommandFailed {
+        args: String,
+        status: std::process::ExitStatus,
+        stderr: String,
+    },
+    #[error("Malformed `git status` output: `{0}`")]
+    MalformedStatus(String),
+}
+
+/// The stat information we compare between syncs for a dirty path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileStat {
+    is_dir: bool,
+    len: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileStat {
+    fn read(path: &std::path::Path) -> Option<Self> {
+        let metadata = std::fs::symlink_metadata(path).ok()?;
+        Some(FileStat {
+            is_dir: metadata.is_dir(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+/// State of a path that `git status` reported as differing from `HEAD`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DirtyPath {
+    /// The path is not tracked by Git.
+    untracked: bool,
+    /// `None` if the path does not exist on disk.
+    stat: Option<FileStat>,
+}
+
+/// How a path changed between two `HEAD` commits, from `git diff --name-status`.
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq)]
+enum HeadChange {
+    Added,
+    Deleted,
+    Modified,
+}
+
+#[derive(Debug, Clone, Copy, Dupe, PartialEq, Eq, Ord, PartialOrd)]
+enum GitChange {
+    Created,
+    Modified,
+    Deleted,
+}
+
+#[derive(Debug, Default)]
+struct GitSnapshot {
+    /// `None` if the repository has no commits yet.
+    head: Option<String>,
+    dirty: HashMap<ProjectRelativePathBuf, DirtyPath>,
+}
+
+impl GitSnapshot {
+    /// Compute the paths that changed between `self` and `new`, given the changes between the
+    /// two `HEAD` commits.
+    fn changes(
+        &self,
+        new: &GitSnapshot,
+        head_changes: &HashMap<ProjectRelativePathBuf, HeadChange>,
+    ) -> BTreeMap<ProjectRelativePathBuf, GitChange> {
+        let candidates = self
+            .dirty
+            .keys()
+            .chain(new.dirty.keys())
+            .chain(head_changes.keys());
+
+        let mut res = BTreeMap::new();
+        for path in candidates {
+            if res.contains_key(path) {
+                continue;
+            }
+
+            let old_state = self.dirty.get(path);
+            let new_state = new.dirty.get(path);
+            let head_change = head_changes.get(path).copied();
+
+            if head_change.is_none() && old_state == new_state {
+                continue;
+            }
+
+            // A path that is not dirty matches `HEAD`: it exists iff `HEAD` has it. If `HEAD` did
+            // not move, the only way to tell whether it's in `HEAD` is whether the other snapshot
+            // knows it as untracked.
+            let old_exists = match old_state {
+                Some(s) => s.stat.is_some(),
+                None => match head_change {
+                    Some(HeadChange::Added) => false,
+                    Some(HeadChange::Deleted | HeadChange::Modified) => true,
+                    None => !new_state.map_or(false, |s| s.untracked),
+                },
+            };
+            let new_exists = match new_state {
+                Some(s) => s.stat.is_some(),
+                None => match head_change {
+                    Some(HeadChange::Deleted) => false,
+                    Some(HeadChange::Added | HeadChange::Modified) => true,
+                    None => !old_state.map_or(false, |s| s.untracked),
+                },
+            };
+
+            let change = match (old_exists, new_exists) {
+                (false, false) => continue,
+                (false, true) => GitChange::Created,
+                (true, false) => GitChange::Deleted,
+                (true, true) => GitChange::Modified,
+            };
+            res.insert(path.clone(), change);
+        }
+        res
+    }
+}
+
+#[derive(Default)]
+struct GitWatcherState {
+    /// `None` until the first sync.
+    snapshot: Option<GitSnapshot>,
+    mergebase: Option<String>,
+}
+
+#[derive(Allocative)]
+pub(crate) struct GitFileWatcher {
+    root: ProjectRoot,
+    cells: CellResolver,
+    ignore_specs: HashMap<CellName, IgnoreSet>,
+    /// The ref we compute the mergebase against (e.g. `origin/main`), if any.
+    merge_base: Option<String>,
+    fresh_instance_commit_threshold: u64,
+    #[allocative(skip)]
+    state: tokio::sync::Mutex<GitWatcherState>,
+}
+
+impl GitFileWatcher {
+    pub(crate) fn new(
+        root: &ProjectRoot,
+        root_config: &LegacyBuckConfig,
+        cells: CellResolver,
+        ignore_specs: HashMap<CellName, IgnoreSet>,
+    ) -> anyhow::Result<Self> {
+        let merge_base = root_config
+            .get(BuckconfigKeyRef {
+                section: "project",
+                property: "git_merge_base",
+            })
+            .map(|s| s.to_owned());
+
+        let fresh_instance_commit_threshold = root_config
+            .parse::<u64>(BuckconfigKeyRef {
+                section: "buck2",
+                property: "git_file_watcher_fresh_instance_threshold",
+            })?
+            .unwrap_or(DEFAULT_FRESH_INSTANCE_COMMIT_THRESHOLD);
+
+        Ok(Self {
+            root: root.dupe(),
+            cells,
+            ignore_specs,
+            merge_base,
+            fresh_instance_commit_threshold,
+            state: tokio::sync::Mutex::new(GitWatcherState::default()),
+        })
+    }
+
+    async fn git<I, S>(&self, args: I) -> anyhow::Result<Vec<u8>>
+    where
+        I: IntoIterator<Item = S> + Clone,
+        S: AsRef<OsStr>,
+    {
+        let output = async_background_command("git")
+            .current_dir(self.root.root().as_path())
+            // Don't take the index lock just to refresh stat information, since the user might be
+            // running git commands concurrently.
+            .env("GIT_OPTIONAL_LOCKS", "0")
+            .args(args.clone())
+            .output()
+            .await
+            .context("Failed to spawn `git`")?;
+        if !output.status.success() {
+            return Err(GitFileWatcherError::CommandFailed {
+                args: args
+                    .into_iter()
+                    .map(|a| a.as_ref().to_string_lossy().into_owned())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                status: output.status,
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            }
+            .into());
+        }
+        Ok(output.stdout)
+    }
+
+    async fn git_line(&self, args: &[&str]) -> anyhow::Result<String> {
+        let stdout = self.git(args).await?;
+        Ok(String::from_utf8(stdout)
+            .context("`git` output is not UTF-8")?
+            .trim()
+            .to_owned())
+    }
+
+    async fn head(&self) -> anyhow::Result<Option<String>> {
+        // Fails on a repository without commits, in which case there is no `HEAD` to diff against.
+        Ok(self
+            .git_line(&["rev-parse", "--verify", "--quiet", "HEAD"])
+            .await
+            .ok())
+    }
+
+    /// Converts a path relative to the project root, as printed by Git, skipping paths we never
+    /// want to report.
+    fn project_path(&self, path: &str) -> anyhow::Result<Option<ProjectRelativePathBuf>> {
+        let path = ProjectRelativePath::new(path.trim_end_matches('/'))?;
+        if path.starts_with(InvocationPaths::buck_out_dir_prefix()) {
+            return Ok(None);
+        }
+        Ok(Some(path.to_owned()))
+    }
+
+    async fn snapshot(&self) -> anyhow::Result<GitSnapshot> {
+        let head = self.head().await?;
+
+        // We run in the project root, and `status.relativePaths` makes Git print paths relative
+        // to it. The `.` pathspec excludes anything outside of the project.
+        let status = self
+            .git([
+                "-c",
+                "status.relativePaths=true",
+                "status",
+                "--porcelain=v1",
+                "-z",
+                "--untracked-files=all",
+                "--no-renames",
+                "--ignore-submodules=all",
+                "--",
+                ".",
+            ])
+            .await?;
+
+        let mut dirty = HashMap::new();
+        for (code, path) in parse_porcelain_status(&status)? {
+            let Some(path) = self.project_path(&path)? else {
+                continue;
+            };
+            let stat = FileStat::read(self.root.resolve(&path).as_path());
+            dirty.insert(
+                path,
+                DirtyPath {
+                    untracked: code == "??",
+                    stat,
+                },
+            );
+        }
+
+        Ok(GitSnapshot { head, dirty })
+    }
+
+    async fn head_changes(
+        &self,
+        old: &str,
+        new: &str,
+    ) -> anyhow::Result<HashMap<ProjectRelativePathBuf, HeadChange>> {
+        let diff = self
+            .git([
+                "diff",
+                "--name-status",
+                "-z",
+                "--no-renames",
+                "--relative",
+                old,
+                new,
+                "--",
+                ".",
+            ])
+            .await?;
+
+        let mut res = HashMap::new();
+        for (status, path) in parse_name_status(&diff)? {
+            let Some(path) = self.project_path(&path)? else {
+                continue;
+            };
+            let change = match status.chars().next() {
+                Some('A') => HeadChange::Added,
+                Some('D') => HeadChange::Deleted,
+                _ => HeadChange::Modified,
+            };
+            res.insert(path, change);
+        }
+        Ok(res)
+    }
+
+    /// Returns `true` if `HEAD` moved across so many commits (or to an unrelated commit) that we
+    /// should start from a clean DICE state.
+    async fn is_fresh_instance(&self, old: &str, new: &str) -> bool {
+        let range = format!("{}...{}", old, new);
+        match self.git_line(&["rev-list", "--count", &range]).await {
+            Ok(count) => count
+                .parse::<u64>()
+                .map_or(true, |count| count > self.fresh_instance_commit_threshold),
+            // The old commit might have been garbage collected.
+            Err(_) => true,
+        }
+    }
+
+    async fn mergebase(&self) -> Option<String> {
+        let merge_base = self.merge_base.as_ref()?;
+        // Just like the `hg` global revision for Watchman, this is informational: don't fail builds
+        // if it can't be computed (e.g. the ref does not exist locally).
+        self.git_line(&["merge-base", "HEAD", merge_base])
+            .await
+            .ok()
+    }
+
+    async fn update(
+        &self,
+        mut dice: DiceTransactionUpdater,
+    ) -> anyhow::Result<(buck2_data::FileWatcherStats, DiceTransactionUpdater)> {
+        let mut state = self.state.lock().await;
+
+        let new_snapshot = self.snapshot().await?;
+        let old_snapshot = match state.snapshot.take() {
+            Some(old) => old,
+            None => {
+                // This is the first sync, there is nothing in DICE to invalidate yet.
+                state.mergebase = self.mergebase().await;
+                state.snapshot = Some(new_snapshot);
+                let stats = buck2_data::FileWatcherStats {
+                    branched_from_revision: state.mergebase.clone(),
+                    ..Default::default()
+                };
+                return Ok((stats, dice));
+            }
+        };
+
+        let mut new_mergebase = false;
+        if old_snapshot.head != new_snapshot.head {
+            let mergebase = self.mergebase().await;
+            new_mergebase = mergebase != state.mergebase;
+            if new_mergebase {
+                // See the comment in the Watchman file watcher for why we do this.
+                crate::dep_files::flush_non_local_dep_files();
+            }
+            state.mergebase = mergebase;
+        }
+
+        let head_changes = match (&old_snapshot.head, &new_snapshot.head) {
+            (Some(old), Some(new)) if old != new => {
+                if self.is_fresh_instance(old, new).await {
+                    info!(
+                        "GitFileWatcher: HEAD moved from {} to {}, fresh instance",
+                        old, new
+                    );
+                    let stats = fresh_instance_stats(state.mergebase.clone(), new_mergebase);
+                    state.snapshot = Some(new_snapshot);
+                    return Ok((stats, dice.unstable_take()));
+                }
+                match self.head_changes(old, new).await {
+                    Ok(head_changes) => head_changes,
+                    Err(e) => {
+                        // Keep diffing against the old state on the next sync.
+                        state.snapshot = Some(old_snapshot);
+                        return Err(e);
+                    }
+                }
+            }
+            (None, Some(_)) | (Some(_), None) => {
+                // The repository gained its first commit, or `HEAD` became unborn: we can't diff.
+                let stats = fresh_instance_stats(state.mergebase.clone(), new_mergebase);
+                state.snapshot = Some(new_snapshot);
+                return Ok((stats, dice.unstable_take()));
+            }
+            _ => HashMap::new(),
+        };
+
+        let changes = old_snapshot.changes(&new_snapshot, &head_changes);
+        state.snapshot = Some(new_snapshot);
+
+        let mut tracker = FileChangeTracker::new();
+        let mut stats = FileWatcherStats::new(
+            buck2_data::FileWatcherStats {
+                branched_from_revision: state.mergebase.clone(),
+                ..Default::default()
+            },
+            changes.len(),
+        );
+        let mut ignored = 0;
+        // Git only knows about files. Adding or removing a file dirties the listing of its
+        // parent, like the Watchman watcher does, and the directories that appeared or vanished
+        // along with it dirty the listing of their own parent.
+        let mut dirs = OrderedSet::new();
+        let created: HashSet<ProjectRelativePathBuf> = changes
+            .iter()
+            .filter(|(_, change)| **change == GitChange::Created)
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        for (path, change) in changes {
+            let cell_path = self.cells.get_cell_path(&path)?;
+            let ignore = self
+                .ignore_specs
+                .get(&cell_path.cell())
+                .map_or(false, |i| i.is_match(cell_path.path()));
+
+            info!(
+                "GitFileWatcher: {:?} {:?} (ignore = {})",
+                path, change, ignore
+            );
+
+            if ignore {
+                ignored += 1;
+                continue;
+            }
+
+            let event = match change {
+                GitChange::Created => FileWatcherEventType::Create,
+                GitChange::Modified => FileWatcherEventType::Modify,
+                GitChange::Deleted => FileWatcherEventType::Delete,
+            };
+            stats.add(cell_path.to_string(), event, FileWatcherKind::File);
+
+            match change {
+                GitChange::Modified => tracker.file_changed(cell_path),
+                GitChange::Created | GitChange::Deleted => {
+                    tracker.file_added_or_removed(cell_path);
+                    let mut parent = path.parent();
+                    while let Some(dir) = parent {
+                        if !dir_appeared_or_vanished(&self.root, dir, change, &created) {
+                            break;
+                        }
+                        if !dirs.insert(self.cells.get_cell_path(dir)?) {
+                            break;
+                        }
+                        parent = dir.parent();
+                    }
+                }
+            }
+        }
+
+        for dir in dirs {
+            tracker.dir_added_or_removed(dir);
+        }
+
+        stats.add_ignored(ignored);
+        tracker.write_to_dice(&mut dice, &self.cells)?;
+        Ok((stats.finish(), dice))
+    }
+}
+
+/// Whether `dir` was created or deleted along with a file below it. A deleted directory is gone
+/// from disk, and a created directory only contains files that Git reports as new.
+fn dir_appeared_or_vanished(
+    root: &ProjectRoot,
+    dir: &ProjectRelativePath,
+    change: GitChange,
+    created: &HashSet<ProjectRelativePathBuf>,
+) -> bool {
+    match change {
+        GitChange::Deleted => !root.resolve(dir).as_path().exists(),
+        GitChange::Created => only_created_files(root, dir, created),
+        GitChange::Modified => false,
+    }
+}
+
+fn only_created_files(
+    root: &ProjectRoot,
+    dir: &ProjectRelativePath,
+    created: &HashSet<ProjectRelativePathBuf>,
+) -> bool {
+    let Ok(entries) = std::fs::read_dir(root.resolve(dir).as_path()) else {
+        return false;
+    };
+    entries.into_iter().all(|entry| {
+        let Ok(entry) = entry else {
+            return false;
+        };
+        let Some(path) = entry
+            .file_name()
+            .to_str()
+            .and_then(|name| ForwardRelativePath::new(name).ok())
+            .map(|name| dir.join(name))
+        else {
+            return false;
+        };
+        match entry.file_type() {
+            Ok(t) if t.is_dir() => only_created_files(root, &path, created),
+            _ => created.contains(&path),
+        }
+    })
+}
+
+fn fresh_instance_stats(
+    mergebase: Option<String>,
+    new_mergebase: bool,
+) -> buck2_data::FileWatcherStats {
+    buck2_data::FileWatcherStats {
+        fresh_instance: true,
+        branched_from_revision: mergebase,
+        incomplete_events_reason: Some("Fresh instance".to_owned()),
+        fresh_instance_data: Some(buck2_data::FreshInstance {
+            new_mergebase,
+            cleared_dice: true,
+            cleared_dep_files: new_mergebase,
+        }),
+        ..Default::default()
+    }
+}
+
+/// Parses `git status --porcelain=v1 -z --no-renames` output into `(status code, path)` pairs.
+fn parse_porcelain_status(output: &[u8]) -> anyhow::Result<Vec<(String, String)>> {
+    let mut res = Vec::new();
+    for entry in output.split(|b| *b == 0) {
+        if entry.is_empty() {
+            continue;
+        }
+        let entry = std::str::from_utf8(entry).context("`git status` output is not UTF-8")?;
+        match (entry.get(..2), entry.get(3..)) {
+            (Some(code), Some(path)) if !path.is_empty() => {
+                res.push((code.to_owned(), path.to_owned()))
+            }
+            _ => return Err(GitFileWatcherError::MalformedStatus(entry.to_owned()).into()),
+        }
+    }
+    Ok(res)
+}
+
+/// Parses `git diff --name-status -z --no-renames` output into `(status, path)` pairs.
+fn parse_name_status(output: &[u8]) -> anyhow::Result<Vec<(String, String)>> {
+    let mut res = Vec::new();
+    let mut fields = output.split(|b| *b == 0).filter(|f| !f.is_empty());
+    while let Some(status) = fields.next() {
+        let status = std::str::from_utf8(status).context("`git diff` output is not UTF-8")?;
+        let path = fields
+            .next()
+            .with_context(|| GitFileWatcherError::MalformedStatus(status.to_owned()))?;
+        let path = std::str::from_utf8(path).context("`git diff` output is not UTF-8")?;
+        res.push((status.to_owned(), path.to_owned()));
+    }
+    Ok(res)
+}
+
+#[async_trait]
+impl FileWatcher for GitFileWatcher {
+    async fn sync(
+        &self,
+        dice: DiceTransactionUpdater,
+    ) -> anyhow::Result<(DiceTransactionUpdater, Mergebase)> {
+        span_async(
+            buck2_data::FileWatcherStart {
+                provider: buck2_data::FileWatcherProvider::Git as i32,
+            },
+            async {
+                let (stats, res) = match self.update(dice).await {
+                    Ok((stats, dice)) => {
+                        let mergebase = Mergebase(Arc::new(stats.branched_from_revision.clone()));
+                        ((Some(stats)), Ok((dice, mergebase)))
+                    }
+                    Err(e) => (None, Err(e)),
+                };
+                (res, buck2_data::FileWatcherEnd { stats })
+            },
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::collections::HashSet;
+
+    use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
+    use buck2_core::fs::project::ProjectRoot;
+    use buck2_core::fs::project_rel_path::ProjectRelativePathBuf;
+
+    use crate::git::dir_appeared_or_vanished;
+    use crate::git::parse_name_status;
+    use crate::git::parse_porcelain_status;
+    use crate::git::DirtyPath;
+    use crate::git::FileStat;
+    use crate::git::GitChange;
+    use crate::git::GitSnapshot;
+    use crate::git::HeadChange;
+
+    fn path(p: &str) -> ProjectRelativePathBuf {
+        ProjectRelativePathBuf::unchecked_new(p.to_owned())
+    }
+
+    fn stat(len: u64) -> Option<FileStat> {
+        Some(FileStat {
+            is_dir: false,
+            len,
+            modified: None,
+        })
+    }
+
+    fn snapshot(head: &str, dirty: &[(&str, bool, Option<FileStat>)]) -> GitSnapshot {
+        GitSnapshot {
+            head: Some(head.to_owned()),
+            dirty: dirty
+                .iter()
+                .map(|(p, untracked, stat)| {
+                    (
+                        path(p),
+                        DirtyPath {
+                            untracked: *untracked,
+                            stat: stat.clone(),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_porcelain_status() -> anyhow::Result<()> {
+        let out = b" M a/b.txt\0?? new file\0 D gone\0";
+        assert_eq!(
+            parse_porcelain_status(out)?,
+            vec![
+                (" M".to_owned(), "a/b.txt".to_owned()),
+                ("??".to_owned(), "new file".to_owned()),
+                (" D".to_owned(), "gone".to_owned()),
+            ]
+        );
+        assert!(parse_porcelain_status(b"??\0").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_name_status() -> anyhow::Result<()> {
+        let out = b"A\0x\0M\0y/z\0D\0w\0";
+        assert_eq!(
+            parse_name_status(out)?,
+            vec![
+                ("A".to_owned(), "x".to_owned()),
+                ("M".to_owned(), "y/z".to_owned()),
+                ("D".to_owned(), "w".to_owned()),
+            ]
+        );
+        assert!(parse_name_status(b"A\0").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_changes_working_copy() {
+        let old = snapshot(
+            "h",
+            &[
+                ("edited", false, stat(1)),
+                ("unchanged", false, stat(1)),
+                ("reverted", false, stat(1)),
+                ("untracked_removed", true, stat(1)),
+            ],
+        );
+        let new = snapshot(
+            "h",
+            &[
+                ("edited", false, stat(2)),
+                ("unchanged", false, stat(1)),
+                ("deleted", false, None),
+                ("untracked_added", true, stat(1)),
+                ("newly_edited", false, stat(3)),
+            ],
+        );
+
+        let changes = old.changes(&new, &HashMap::new());
+        let expected = [
+            ("deleted", GitChange::Deleted),
+            ("edited", GitChange::Modified),
+            ("newly_edited", GitChange::Modified),
+            ("reverted", GitChange::Modified),
+            ("untracked_added", GitChange::Created),
+            ("untracked_removed", GitChange::Deleted),
+        ]
+        .into_iter()
+        .map(|(p, c)| (path(p), c))
+        .collect();
+        assert_eq!(changes, expected);
+    }
+
+    #[test]
+    fn test_changes_head_moved() {
+        let old = snapshot("h1", &[("dirty_added_upstream", true, stat(1))]);
+        let new = snapshot("h2", &[]);
+
+        let head_changes = [
+            ("added", HeadChange::Added),
+            ("deleted", HeadChange::Deleted),
+            ("modified", HeadChange::Modified),
+            ("dirty_added_upstream", HeadChange::Added),
+        ]
+        .into_iter()
+        .map(|(p, c)| (path(p), c))
+        .collect();
+
+        let changes = old.changes(&new, &head_changes);
+        let expected = [
+            ("added", GitChange::Created),
+            ("deleted", GitChange::Deleted),
+            ("dirty_added_upstream", GitChange::Modified),
+            ("modified", GitChange::Modified),
+        ]
+        .into_iter()
+        .map(|(p, c)| (path(p), c))
+        .collect();
+        assert_eq!(changes, expected);
+    }
+
+    #[test]
+    fn test_dir_appeared_or_vanished() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let root = ProjectRoot::new_unchecked(AbsNormPathBuf::new(tmp.path().to_owned())?);
+        std::fs::create_dir_all(tmp.path().join("old/new/sub"))?;
+        std::fs::write(tmp.path().join("old/existing"), "")?;
+        std::fs::write(tmp.path().join("old/new/a"), "")?;
+        std::fs::write(tmp.path().join("old/new/sub/b"), "")?;
+        let created: HashSet<_> = ["old/new/a", "old/new/sub/b"]
+            .into_iter()
+            .map(path)
+            .collect();
+
+        let appeared =
+            |p: &str| dir_appeared_or_vanished(&root, &path(p), GitChange::Created, &created);
+        assert!(appeared("old/new/sub"));
+        assert!(appeared("old/new"));
+        assert!(!appeared("old"));
+
+        let vanished =
+            |p: &str| dir_appeared_or_vanished(&root, &path(p), GitChange::Deleted, &created);
+        assert!(vanished("gone"));
+        assert!(!vanished("old"));
+        Ok(())
+    }
+}
@@ -13,6 +13,7 @@
 pub mod dep_files;
 pub mod file_watcher;
 mod fs_hash_crawler;
+mod git;
 pub mod mergebase;
 mod notify;
 mod stats;
@@ -181,6 +181,9 @@ pub struct NotifyFileWatcher {
     #[allocative(skip)]
     watcher: RecommendedWatcher,
     data: Arc<Mutex<anyhow::Result<NotifyFileData>>>,
+    cells: CellResolver,
+    #[allocative(skip)]
+    changes: tokio::sync::watch::Receiver<()>,
 }
 
 impl NotifyFileWatcher {
@@ -192,16 +195,32 @@ impl NotifyFileWatcher {
         let data = Arc::new(Mutex::new(Ok(NotifyFileData::new())));
         let data2 = data.dupe();
         let root2 = root.dupe();
+        let cells2 = cells.dupe();
+        let (changes_tx, changes) = tokio::sync::watch::channel(());
         let mut watcher = notify::recommended_watcher(move |event| {
             let mut guard = data2.lock().unwrap();
             if let Ok(state) = &mut *guard {
-                if let Err(e) = state.process(event, &root2, &cells, &ignore_specs) {
-                    *guard = Err(e);
+                let events = state.events.len();
+                match state.process(event, &root2, &cells2, &ignore_specs) {
+                    Ok(()) if state.events.len() == events => {}
+                    Ok(()) => {
+                        changes_tx.send_replace(());
+                    }
+                    Err(e) => {
+                        *guard = Err(e);
+                        // So that the error is reported by the next sync.
+                        changes_tx.send_replace(());
+                    }
                 }
             }
         })?;
         watcher.watch(root.root().as_path(), notify::RecursiveMode::Recursive)?;
-        Ok(Self { watcher, data })
+        Ok(Self {
+            watcher,
+            data,
+            cells,
+            changes,
+        })
     }
 
     fn sync2(
@@ -211,7 +230,7 @@ impl NotifyFileWatcher {
         let mut guard = self.data.lock().unwrap();
         let old = mem::replace(&mut *guard, Ok(NotifyFileData::new()));
         let (stats, changes) = old?.sync();
-        changes.write_to_dice(&mut dice)?;
+        changes.write_to_dice(&mut dice, &self.cells)?;
         Ok((stats, dice))
     }
 }
@@ -239,4 +258,8 @@ impl FileWatcher for NotifyFileWatcher {
         )
         .await
     }
+
+    fn changes(&self) -> Option<tokio::sync::watch::Receiver<()>> {
+        Some(self.changes.clone())
+    }
 }
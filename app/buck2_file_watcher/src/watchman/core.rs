@@ -557,3 +557,34 @@ where
         Ok(Self { control_tx })
     }
 }
+
+/// Marks `changes` changed whenever watchman reports changes to files matching `expr` under
+/// `path`, without syncing anything. Returns when watchman cancels the subscription.
+pub(crate) async fn notify_changes(
+    connector: Connector,
+    path: CanonicalPath,
+    expr: Expr,
+    changes: tokio::sync::watch::Sender<()>,
+) -> anyhow::Result<()> {
+    let client = WatchmanClient::connect(&connector, path).await?;
+    let (mut subscription, _) = with_timeout(client.client().subscribe::<NameOnly>(
+        client.root(),
+        SubscribeRequest {
+            expression: Some(expr),
+            fields: vec!["name"],
+            empty_on_fresh_instance: true,
+            ..SubscribeRequest::default()
+        },
+    ))
+    .await
+    .context("Subscribing to watchman")?;
+    loop {
+        match subscription.next().await? {
+            SubscriptionData::FilesChanged(_) => {
+                changes.send_replace(());
+            }
+            SubscriptionData::Canceled => return Ok(()),
+            _ => {}
+        }
+    }
+}
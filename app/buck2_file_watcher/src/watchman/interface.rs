@@ -10,6 +10,7 @@
 use std::collections::HashMap;
 use std::path::Path;
 use std::sync::Arc;
+use std::sync::Mutex;
 
 use allocative::Allocative;
 use anyhow::Context as _;
@@ -21,6 +22,7 @@ use buck2_common::legacy_configs::key::BuckconfigKeyRef;
 use buck2_core::cells::name::CellName;
 use buck2_core::cells::CellResolver;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
+use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
 use buck2_core::fs::project_rel_path::ProjectRelativePath;
 use buck2_core::rollout_percentage::RolloutPercentage;
 use buck2_events::dispatch::span_async;
@@ -29,12 +31,14 @@ use dice::DiceTransactionUpdater;
 use tracing::info;
 use tracing::warn;
 use watchman_client::expr::Expr;
+use watchman_client::prelude::CanonicalPath;
 use watchman_client::prelude::Connector;
 use watchman_client::prelude::FileType;
 
 use crate::file_watcher::FileWatcher;
 use crate::mergebase::Mergebase;
 use crate::stats::FileWatcherStats;
+use crate::watchman::core::notify_changes;
 use crate::watchman::core::SyncableQuery;
 use crate::watchman::core::SyncableQueryProcessor;
 use crate::watchman::core::WatchmanEvent;
@@ -95,7 +99,7 @@ impl WatchmanQueryProcessor {
         }
 
         let stats = stats.finish();
-        handler.write_to_dice(&mut ctx)?;
+        handler.write_to_dice(&mut ctx, &self.cells)?;
 
         Ok((stats, ctx))
     }
@@ -325,6 +329,19 @@ impl SyncableQueryProcessor for WatchmanQueryProcessor {
 pub(crate) struct WatchmanFileWatcher {
     #[allocative(skip)]
     query: SyncableQuery<buck2_data::FileWatcherStats, DiceTransactionUpdater>,
+    project_root: AbsNormPathBuf,
+    /// Subscription to changes, only set up once a command asks for them, and again if it ended.
+    #[allocative(skip)]
+    changes: Mutex<Option<tokio::sync::watch::Receiver<()>>>,
+}
+
+/// Everything in the project, like the query.
+fn watched_files() -> Expr {
+    Expr::Any(vec![
+        Expr::FileType(FileType::Regular),
+        Expr::FileType(FileType::Directory),
+        Expr::FileType(FileType::Symlink),
+    ])
 }
 
 /// The watchman query is constructed once on daemon startup. It is an unfiltered watchman query
@@ -373,11 +390,7 @@ impl WatchmanFileWatcher {
         let query = SyncableQuery::new(
             Connector::new(),
             project_root,
-            Expr::Any(vec![
-                Expr::FileType(FileType::Regular),
-                Expr::FileType(FileType::Directory),
-                Expr::FileType(FileType::Symlink),
-            ]),
+            watched_files(),
             Box::new(WatchmanQueryProcessor {
                 cells,
                 ignore_specs,
@@ -390,7 +403,11 @@ impl WatchmanFileWatcher {
             empty_on_fresh_instance,
         )?;
 
-        Ok(Self { query })
+        Ok(Self {
+            query,
+            project_root: project_root.to_buf(),
+            changes: Mutex::new(None),
+        })
     }
 }
 
@@ -417,4 +434,28 @@ impl FileWatcher for WatchmanFileWatcher {
         )
         .await
     }
+
+    fn changes(&self) -> Option<tokio::sync::watch::Receiver<()>> {
+        let mut changes = self.changes.lock().unwrap();
+        if let Some(changes) = &*changes {
+            if changes.has_changed().is_ok() {
+                return Some(changes.clone());
+            }
+        }
+        let path = match CanonicalPath::canonicalize(&self.project_root) {
+            Ok(path) => path,
+            Err(e) => {
+                warn!("Not subscribing to watchman: {:#}", e);
+                return None;
+            }
+        };
+        let (tx, rx) = tokio::sync::watch::channel(());
+        tokio::spawn(async move {
+            if let Err(e) = notify_changes(Connector::new(), path, watched_files(), tx).await {
+                warn!("Watchman subscription failed: {:#}", e);
+            }
+        });
+        *changes = Some(rx.clone());
+        Some(rx)
+    }
 }
@@ -0,0 +1,440 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Run each command in its own cgroup v2, to account for the resources it uses and to enforce
+//! the limits it was given.
+//!
+//! This relies on resource control: the daemon then runs in a systemd scope with `Delegate=yes`,
+//! which the forkserver inherits. Processes can only live in leaves of a cgroup that has
+//! controllers enabled, so the processes of the scope (the daemon and the forkserver) move to a
+//! `daemon` child, and commands get a child of `actions`:
+//!
+//! ```text
+//! buck2-daemon-<project>-<isolation>.scope/daemon
+//! buck2-daemon-<project>-<isolation>.scope/actions/<n>
+//! ```
+
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::ExitStatus;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use anyhow::Context as _;
+use async_trait::async_trait;
+use buck2_common::init::ResourceControlConfig;
+use buck2_common::init::ResourceControlStatus;
+use buck2_forkserver_proto::ResourceLimits;
+
+use crate::run::status_decoder::DecodedStatus;
+use crate::run::status_decoder::StatusDecoder;
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+const CONTROLLERS: &[&str] = &["memory", "cpu", "io"];
+
+/// `cpu.max` period, in microseconds.
+const CPU_PERIOD_USEC: u64 = 100_000;
+
+#[derive(Debug, buck2_error::Error)]
+enum CgroupError {
+    #[error("Per-action cgroups are only supported on Linux")]
+    UnsupportedPlatform,
+    #[error("The forkserver is not running in a cgroup v2 hierarchy")]
+    NoCgroupV2,
+    #[error(
+        "The forkserver is in cgroup `{0}`, which is not the scope of the buck2 daemon. Is systemd available?"
+    )]
+    NotInDaemonScope(String),
+}
+
+pub(crate) struct ActionCgroups {
+    actions: PathBuf,
+    next_id: AtomicU64,
+}
+
+impl ActionCgroups {
+    /// Sets up per-action cgroups if resource control is enabled. Like resource control itself,
+    /// failing to do so is only an error if it's required.
+    pub(crate) fn new(config: &ResourceControlConfig) -> anyhow::Result<Option<Self>> {
+        match config.status {
+            ResourceControlStatus::Off => Ok(None),
+            ResourceControlStatus::IfAvailable | ResourceControlStatus::Required => {
+                match Self::setup() {
+                    Ok(cgroups) => Ok(Some(cgroups)),
+                    Err(e) if config.status == ResourceControlStatus::Required => Err(e.context(
+                        "Per-action cgroups are unavailable but resource control is required by buckconfig",
+                    )),
+                    Err(e) => {
+                        tracing::warn!(
+                            "Per-action cgroups are unavailable. Continuing without them: {:#}",
+                            e
+                        );
+                        Ok(None)
+                    }
+                }
+            }
+        }
+    }
+
+    fn setup() -> anyhow::Result<Self> {
+        if !cfg!(target_os = "linux") {
+            return Err(CgroupError::UnsupportedPlatform.into());
+        }
+
+        let own = fs::read_to_string("/proc/self/cgroup").context("Error reading own cgroup")?;
+        let own = own
+            .lines()
+            .find_map(|l| l.strip_prefix("0::"))
+            .ok_or(CgroupError::NoCgroupV2)?;
+        // Don't reorganize a cgroup we don't own, such as the session of the user.
+        let is_daemon_scope = Path::new(own)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .map_or(false, |n| {
+                n.starts_with("buck2-daemon-") && n.ends_with(".scope")
+            });
+        if !is_daemon_scope {
+            return Err(CgroupError::NotInDaemonScope(own.to_owned()).into());
+        }
+        Self::setup_in(&Path::new(CGROUP_ROOT).join(own.trim_start_matches('/')))
+    }
+
+    /// Moves the processes of `scope` to a `daemon` child, and creates the `actions` child the
+    /// cgroups of commands go in.
+    fn setup_in(scope: &Path) -> anyhow::Result<Self> {
+        let daemon = scope.join("daemon");
+        create_dir(&daemon)?;
+        let procs = fs::read_to_string(scope.join("cgroup.procs"))
+            .with_context(|| format!("Error reading processes of `{}`", scope.display()))?;
+        for pid in procs.lines() {
+            write(&daemon.join("cgroup.procs"), pid)?;
+        }
+
+        let available = fs::read_to_string(scope.join("cgroup.controllers"))
+            .with_context(|| format!("Error reading controllers of `{}`", scope.display()))?;
+        let enable = CONTROLLERS
+            .iter()
+            .filter(|c| available.split_whitespace().any(|a| a == **c))
+            .map(|c| format!("+{}", c))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let actions = scope.join("actions");
+        write(&scope.join("cgroup.subtree_control"), &enable)?;
+        create_dir(&actions)?;
+        write(&actions.join("cgroup.subtree_control"), &enable)?;
+
+        Ok(Self {
+            actions,
+            next_id: AtomicU64::new(0),
+        })
+    }
+
+    /// Creates the cgroup for a command, and makes `cmd` move itself there before `exec`.
+    pub(crate) fn create(
+        &self,
+        cmd: &mut Command,
+        limits: Option<&ResourceLimits>,
+    ) -> anyhow::Result<ActionCgroup> {
+        let path = self
+            .actions
+            .join(self.next_id.fetch_add(1, Ordering::Relaxed).to_string());
+        create_dir(&path)?;
+        let cgroup = ActionCgroup { path };
+
+        // Kill the whole command on OOM, not just the process that happened to be picked.
+        write(&cgroup.path.join("memory.oom.group"), "1")?;
+        if let Some(limits) = limits {
+            if let Some(memory_max) = limits.memory_max_bytes {
+                write(&cgroup.path.join("memory.max"), &memory_max.to_string())?;
+                write(&cgroup.path.join("memory.swap.max"), "0")?;
+            }
+            if let Some(millicores) = limits.cpu_max_millicores {
+                let quota = (millicores * CPU_PERIOD_USEC / 1000).max(1000);
+                write(
+                    &cgroup.path.join("cpu.max"),
+                    &format!("{} {}", quota, CPU_PERIOD_USEC),
+                )?;
+            }
+        }
+
+        let procs = fs::OpenOptions::new()
+            .write(true)
+            .open(cgroup.path.join("cgroup.procs"))
+            .with_context(|| format!("Error opening `{}`", cgroup.path.display()))?;
+        // SAFETY: This only makes a syscall. Writing `0` moves the writer.
+        unsafe {
+            cmd.pre_exec(move || enter(&procs));
+        }
+
+        Ok(cgroup)
+    }
+}
+
+fn enter(procs: &File) -> io::Result<()> {
+    let written = unsafe { libc::write(procs.as_raw_fd(), b"0".as_ptr().cast(), 1) };
+    if written < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+fn create_dir(path: &Path) -> anyhow::Result<()> {
+    match fs::create_dir(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("Error creating cgroup `{}`", path.display())),
+    }
+}
+
+fn write(path: &Path, value: &str) -> anyhow::Result<()> {
+    fs::write(path, value)
+        .with_context(|| format!("Error writing `{}` to `{}`", value, path.display()))
+}
+
+pub(crate) struct ActionCgroup {
+    path: PathBuf,
+}
+
+impl ActionCgroup {
+    fn read(&self, file: &str) -> Option<String> {
+        fs::read_to_string(self.path.join(file)).ok()
+    }
+
+    /// Reads what the command used.
+    fn usage(&self) -> CgroupUsage {
+        let cpu_stat = self.read("cpu.stat").unwrap_or_default();
+        let memory_events = self.read("memory.events").unwrap_or_default();
+        let (io_read_bytes, io_write_bytes) = self.read("io.stat").map_or((None, None), |s| {
+            let (r, w) = parse_io_stat(&s);
+            (Some(r), Some(w))
+        });
+        CgroupUsage {
+            // `memory.peak` is only available since Linux 5.19.
+            memory_peak_bytes: self.read("memory.peak").and_then(|s| s.trim().parse().ok()),
+            cpu_user_usec: parse_keyed(&cpu_stat, "user_usec"),
+            cpu_system_usec: parse_keyed(&cpu_stat, "system_usec"),
+            io_read_bytes,
+            io_write_bytes,
+            oom_killed: parse_keyed(&memory_events, "oom_kill").map_or(false, |n| n > 0),
+        }
+    }
+}
+
+impl Drop for ActionCgroup {
+    fn drop(&mut self) {
+        // This fails if the command left processes behind. There isn't much to do about those,
+        // they're not ours to kill.
+        if let Err(e) = fs::remove_dir(&self.path) {
+            tracing::debug!("Error removing cgroup `{}`: {}", self.path.display(), e);
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct CgroupUsage {
+    memory_peak_bytes: Option<u64>,
+    cpu_user_usec: Option<u64>,
+    cpu_system_usec: Option<u64>,
+    io_read_bytes: Option<u64>,
+    io_write_bytes: Option<u64>,
+    oom_killed: bool,
+}
+
+impl CgroupUsage {
+    fn record(self, stats: &mut buck2_data::CommandExecutionStats) {
+        stats.memory_peak_bytes = self.memory_peak_bytes;
+        stats.cpu_user_usec = self.cpu_user_usec;
+        stats.cpu_system_usec = self.cpu_system_usec;
+        stats.io_read_bytes = self.io_read_bytes;
+        stats.io_write_bytes = self.io_write_bytes;
+        stats.memory_limit_exceeded = self.oom_killed;
+    }
+}
+
+/// Parses a value out of a flat keyed file such as `cpu.stat`.
+fn parse_keyed(contents: &str, key: &str) -> Option<u64> {
+    contents.lines().find_map(|line| {
+        let (k, v) = line.split_once(' ')?;
+        if k == key {
+            v.trim().parse().ok()
+        } else {
+            None
+        }
+    })
+}
+
+/// Sums the bytes read and written across devices in `io.stat`.
+fn parse_io_stat(contents: &str) -> (u64, u64) {
+    let mut read = 0;
+    let mut written = 0;
+    for field in contents.split_whitespace() {
+        if let Some(v) = field.strip_prefix("rbytes=") {
+            read += v.parse::<u64>().unwrap_or(0);
+        } else if let Some(v) = field.strip_prefix("wbytes=") {
+            written += v.parse::<u64>().unwrap_or(0);
+        }
+    }
+    (read, written)
+}
+
+/// Adds the resource usage of the command's cgroup to whatever the inner decoder reports.
+pub(crate) struct CgroupStatusDecoder<D> {
+    inner: D,
+    cgroup: Option<ActionCgroup>,
+}
+
+impl<D> CgroupStatusDecoder<D> {
+    pub(crate) fn new(inner: D, cgroup: Option<ActionCgroup>) -> Self {
+        Self { inner, cgroup }
+    }
+}
+
+#[async_trait]
+impl<D: StatusDecoder + Send> StatusDecoder for CgroupStatusDecoder<D> {
+    async fn decode_status(self, status: ExitStatus) -> anyhow::Result<DecodedStatus> {
+        let mut decoded = self.inner.decode_status(status).await?;
+        if let Some(cgroup) = self.cgroup {
+            let usage = cgroup.usage();
+            if let DecodedStatus::Status {
+                execution_stats, ..
+            } = &mut decoded
+            {
+                usage.record(execution_stats.get_or_insert_with(Default::default));
+            }
+        }
+        Ok(decoded)
+    }
+
+    async fn cancel(self) -> anyhow::Result<()> {
+        self.inner.cancel().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_keyed() {
+        let cpu_stat = "usage_usec 1500\nuser_usec 1000\nsystem_usec 500\nnr_periods 0\n";
+        assert_eq!(parse_keyed(cpu_stat, "user_usec"), Some(1000));
+        assert_eq!(parse_keyed(cpu_stat, "system_usec"), Some(500));
+        assert_eq!(parse_keyed(cpu_stat, "usage"), None);
+
+        let memory_events = "low 0\nhigh 0\nmax 12\noom 1\noom_kill 1\noom_group_kill 1\n";
+        assert_eq!(parse_keyed(memory_events, "oom_kill"), Some(1));
+    }
+
+    #[test]
+    fn test_parse_io_stat() {
+        let io_stat = "259:0 rbytes=4096 wbytes=1024 rios=1 wios=1 dbytes=0 dios=0\n\
+            8:0 rbytes=100 wbytes=0 rios=1 wios=0 dbytes=0 dios=0\n";
+        assert_eq!(parse_io_stat(io_stat), (4196, 1024));
+        assert_eq!(parse_io_stat(""), (0, 0));
+    }
+
+    /// The cgroup the test runs in, if we may reorganize it, e.g. under
+    /// `systemd-run --user --scope -p Delegate=yes cargo test`.
+    #[cfg(target_os = "linux")]
+    fn delegated_scope() -> Option<PathBuf> {
+        let own = fs::read_to_string("/proc/self/cgroup").ok()?;
+        let own = own.lines().find_map(|l| l.strip_prefix("0::"))?;
+        let scope = Path::new(CGROUP_ROOT).join(own.trim_start_matches('/'));
+        let controllers = fs::read_to_string(scope.join("cgroup.controllers")).ok()?;
+        let writable = fs::OpenOptions::new()
+            .write(true)
+            .open(scope.join("cgroup.subtree_control"))
+            .is_ok();
+        (scope.extension().is_some_and(|e| e == "scope")
+            && controllers.split_whitespace().any(|c| c == "memory")
+            && writable)
+            .then_some(scope)
+    }
+
+    /// Runs a command that needs more memory than its limit in its own cgroup. This only does
+    /// something in a delegated cgroup v2 scope, see `delegated_scope`.
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_memory_limit() -> anyhow::Result<()> {
+        use buck2_util::process::background_command;
+
+        use crate::run::decode_command_event_stream;
+        use crate::run::process_group::ProcessCommand;
+        use crate::run::status_decoder::DefaultStatusDecoder;
+        use crate::run::stream_command_events;
+        use crate::run::DefaultKillProcess;
+        use crate::run::GatherOutputStatus;
+
+        const MIB: u64 = 1024 * 1024;
+
+        let Some(scope) = delegated_scope() else {
+            eprintln!("Not in a delegated cgroup v2 scope, skipping");
+            return Ok(());
+        };
+        let cgroups = ActionCgroups::setup_in(&scope)?;
+        assert!(scope.join("daemon").is_dir());
+
+        // `tail` keeps the whole line in memory, and there is no newline in 256 MiB of zeros.
+        let mut cmd = background_command("sh");
+        cmd.args(["-c", "head -c 268435456 /dev/zero | tail"]);
+        let cgroup = cgroups.create(
+            &mut cmd,
+            Some(&ResourceLimits {
+                memory_max_bytes: Some(64 * MIB),
+                cpu_max_millicores: None,
+            }),
+        )?;
+        let path = cgroup.path.clone();
+        assert_eq!(
+            fs::read_to_string(path.join("memory.max"))?.trim(),
+            (64 * MIB).to_string()
+        );
+        assert_eq!(
+            fs::read_to_string(path.join("memory.swap.max"))?.trim(),
+            "0"
+        );
+
+        let stream = stream_command_events(
+            ProcessCommand::new(cmd)
+                .spawn()
+                .map_err(anyhow::Error::from),
+            futures::future::pending(),
+            CgroupStatusDecoder::new(DefaultStatusDecoder, Some(cgroup)),
+            DefaultKillProcess::default(),
+            true,
+        )?;
+        let (status, _stdout, _stderr) = decode_command_event_stream(stream).await?;
+
+        let GatherOutputStatus::Finished {
+            exit_code,
+            execution_stats: Some(stats),
+        } = status
+        else {
+            panic!("Unexpected status: {:?}", status);
+        };
+        assert_ne!(exit_code, 0);
+        assert!(stats.memory_limit_exceeded);
+        // `memory.peak` is only available since Linux 5.19.
+        if let Some(peak) = stats.memory_peak_bytes {
+            assert!(peak > 32 * MIB && peak <= 64 * MIB, "peak: {}", peak);
+        }
+        // The cgroup goes away with the command.
+        assert!(!path.exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,477 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Run a command in Linux user and mount namespaces, so that the project root only contains the
+//! declared inputs (read-only) and the output directories (read-write). The rest of the
+//! filesystem is left alone, except for `/tmp` which is private to the command.
+//!
+//! The sandboxed view of the project root is backed by a scaffold directory, which holds empty
+//! mount points for the exposed paths. Anything else the command writes in the project root ends
+//! up in the scaffold, where the caller can find it once the command has exited.
+
+use std::process::Command;
+
+use buck2_core::fs::paths::abs_path::AbsPath;
+use buck2_forkserver_proto::SandboxConfig;
+
+#[cfg(not(target_os = "linux"))]
+#[derive(Debug, buck2_error::Error)]
+#[error("Sandboxed local execution is only supported on Linux")]
+struct SandboxUnsupported;
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn apply(
+    _cmd: &mut Command,
+    _config: SandboxConfig,
+    _cwd: &AbsPath,
+) -> anyhow::Result<()> {
+    Err(SandboxUnsupported.into())
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn apply(cmd: &mut Command, config: SandboxConfig, cwd: &AbsPath) -> anyhow::Result<()> {
+    use std::os::unix::process::CommandExt;
+
+    let sandbox = linux::Sandbox::prepare(config, cwd)?;
+    // SAFETY: `enter` only makes syscalls, everything it needs was allocated by `prepare`.
+    unsafe {
+        cmd.pre_exec(move || sandbox.enter());
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::ffi::CStr;
+    use std::ffi::CString;
+    use std::ffi::OsStr;
+    use std::fs;
+    use std::io;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Component;
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    use anyhow::Context as _;
+    use buck2_core::fs::paths::abs_path::AbsPath;
+    use buck2_forkserver_proto::SandboxConfig;
+
+    #[derive(Debug, buck2_error::Error)]
+    #[error("Invalid sandbox path `{}`: must be relative and normalized", .0)]
+    struct InvalidSandboxPath(String);
+
+    /// Where the real project root stays reachable while the sandbox is being set up. It's
+    /// inside the private `/tmp`, and unmounted before running the command.
+    const STAGING: &CStr = c"/tmp/.buck2-sandbox-root";
+
+    struct Mount {
+        source: CString,
+        target: CString,
+        read_only: bool,
+        /// Flags of the mount `source` lives on. They are locked in a user namespace, so a
+        /// read-only remount has to keep them.
+        locked_flags: libc::c_ulong,
+    }
+
+    /// Everything needed in the child. All the allocation happens in `prepare`, since `enter`
+    /// runs between `fork` and `exec`.
+    pub(super) struct Sandbox {
+        isolate_network: bool,
+        uid_map: Vec<u8>,
+        gid_map: Vec<u8>,
+        project_root: CString,
+        /// The directories to create in the private `/tmp` for the project root to be found
+        /// there, if it is under `/tmp`.
+        tmp_dirs: Vec<CString>,
+        scaffold: CString,
+        mounts: Vec<Mount>,
+        cwd: CString,
+    }
+
+    fn relative_path(bytes: &[u8]) -> anyhow::Result<&Path> {
+        let path = Path::new(OsStr::from_bytes(bytes));
+        if path.as_os_str().is_empty()
+            || !path.components().all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(InvalidSandboxPath(path.display().to_string()).into());
+        }
+        Ok(path)
+    }
+
+    fn cstring(path: &Path) -> anyhow::Result<CString> {
+        CString::new(path.as_os_str().as_bytes())
+            .with_context(|| format!("Path contains a nul byte: `{}`", path.display()))
+    }
+
+    fn locked_flags(path: &Path) -> anyhow::Result<libc::c_ulong> {
+        let c_path = cstring(path)?;
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+            return Err(io::Error::last_os_error())
+                .with_context(|| format!("statvfs `{}`", path.display()));
+        }
+        let mut flags = 0;
+        for (st, ms) in [
+            (libc::ST_NOSUID, libc::MS_NOSUID),
+            (libc::ST_NODEV, libc::MS_NODEV),
+            (libc::ST_NOEXEC, libc::MS_NOEXEC),
+            (libc::ST_NOATIME, libc::MS_NOATIME),
+            (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+            (libc::ST_RELATIME, libc::MS_RELATIME),
+        ] {
+            if stat.f_flag & st != 0 {
+                flags |= ms;
+            }
+        }
+        Ok(flags)
+    }
+
+    impl Sandbox {
+        pub(super) fn prepare(config: SandboxConfig, cwd: &AbsPath) -> anyhow::Result<Self> {
+            let project_root = PathBuf::from(OsStr::from_bytes(&config.project_root));
+            let scaffold_rel = relative_path(&config.scaffold_dir)?;
+            let scaffold = project_root.join(scaffold_rel);
+            let staging = Path::new(STAGING.to_str()?);
+
+            // Leftovers from an earlier command would show up as violations.
+            if scaffold.exists() {
+                fs::remove_dir_all(&scaffold)
+                    .with_context(|| format!("Error removing `{}`", scaffold.display()))?;
+            }
+            fs::create_dir_all(&scaffold)
+                .with_context(|| format!("Error creating `{}`", scaffold.display()))?;
+
+            let mut paths = Vec::new();
+            for path in &config.readable_paths {
+                paths.push((relative_path(path)?, true));
+            }
+            for path in &config.writable_paths {
+                let path = relative_path(path)?;
+                fs::create_dir_all(project_root.join(path))
+                    .with_context(|| format!("Error creating `{}`", path.display()))?;
+                paths.push((path, false));
+            }
+            // Parents first, so that nested paths can be skipped or mounted on top.
+            paths.sort();
+
+            let mut mounted: Vec<(&Path, bool)> = Vec::new();
+            let mut mounts = Vec::new();
+            for (path, read_only) in paths {
+                let source = project_root.join(path);
+                let target = scaffold.join(path);
+
+                let covering = mounted
+                    .iter()
+                    .rev()
+                    .find(|(m, _)| path.starts_with(m))
+                    .map(|(_, ro)| *ro);
+                match covering {
+                    // Already visible with the same or more access.
+                    Some(ro) if !ro || read_only => continue,
+                    // A writable path under a read-only one: the mount point exists already
+                    // since the source is bind mounted.
+                    Some(_) => {}
+                    None => {
+                        let metadata = match fs::symlink_metadata(&source) {
+                            Ok(m) => m,
+                            // Not there (yet), the command will fail to find it like it would
+                            // without the sandbox.
+                            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                            Err(e) => {
+                                return Err(e)
+                                    .with_context(|| format!("stat `{}`", source.display()));
+                            }
+                        };
+                        if let Some(parent) = target.parent() {
+                            fs::create_dir_all(parent)?;
+                        }
+                        if metadata.is_symlink() {
+                            // Bind mounts follow symlinks, so recreate them instead.
+                            std::os::unix::fs::symlink(fs::read_link(&source)?, &target)
+                                .with_context(|| {
+                                    format!("Error creating symlink `{}`", target.display())
+                                })?;
+                            mounted.push((path, read_only));
+                            continue;
+                        } else if metadata.is_dir() {
+                            fs::create_dir_all(&target)?;
+                        } else {
+                            fs::File::create(&target).with_context(|| {
+                                format!("Error creating mount point `{}`", target.display())
+                            })?;
+                        }
+                    }
+                }
+
+                mounts.push(Mount {
+                    source: cstring(&staging.join(path))?,
+                    target: cstring(&project_root.join(path))?,
+                    read_only,
+                    locked_flags: locked_flags(&source)?,
+                });
+                mounted.push((path, read_only));
+            }
+
+            // The working directory has to exist in the sandboxed view.
+            if let Ok(cwd_rel) = cwd.as_path().strip_prefix(&project_root) {
+                fs::create_dir_all(scaffold.join(cwd_rel))?;
+            }
+
+            let mut tmp_dirs = Vec::new();
+            if let Ok(rel) = project_root.strip_prefix("/tmp") {
+                let mut dir = PathBuf::from("/tmp");
+                for component in rel.components() {
+                    dir.push(component);
+                    tmp_dirs.push(cstring(&dir)?);
+                }
+            }
+
+            let uid = unsafe { libc::getuid() };
+            let gid = unsafe { libc::getgid() };
+
+            Ok(Self {
+                isolate_network: config.isolate_network,
+                uid_map: format!("{uid} {uid} 1\n").into_bytes(),
+                gid_map: format!("{gid} {gid} 1\n").into_bytes(),
+                project_root: cstring(&project_root)?,
+                tmp_dirs,
+                scaffold: cstring(&staging.join(scaffold_rel))?,
+                mounts,
+                cwd: cstring(cwd.as_path())?,
+            })
+        }
+
+        /// Runs in the child, after `fork` and before `exec`.
+        pub(super) fn enter(&self) -> io::Result<()> {
+            let mut flags = libc::CLONE_NEWUSER | libc::CLONE_NEWNS;
+            if self.isolate_network {
+                flags |= libc::CLONE_NEWNET;
+            }
+            check(unsafe { libc::unshare(flags) })?;
+
+            write_file(c"/proc/self/setgroups", b"deny")?;
+            write_file(c"/proc/self/uid_map", &self.uid_map)?;
+            write_file(c"/proc/self/gid_map", &self.gid_map)?;
+
+            // Don't propagate anything we do back to the parent namespace.
+            mount(None, c"/", None, libc::MS_REC | libc::MS_PRIVATE)?;
+
+            // The project root may be under `/tmp`, keep it reachable once that is hidden.
+            let root_fd = unsafe {
+                libc::open(
+                    self.project_root.as_ptr(),
+                    libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC,
+                )
+            };
+            check(root_fd)?;
+            mount(Some(c"tmpfs"), c"/tmp", Some(c"tmpfs"), 0)?;
+            check(unsafe { libc::mkdir(STAGING.as_ptr(), 0o700) })?;
+            let mut buf = [0; 32];
+            mount(
+                Some(fd_path(&mut buf, root_fd)?),
+                STAGING,
+                None,
+                libc::MS_BIND | libc::MS_REC,
+            )?;
+            check(unsafe { libc::close(root_fd) })?;
+            for dir in &self.tmp_dirs {
+                check(unsafe { libc::mkdir(dir.as_ptr(), 0o755) })?;
+            }
+
+            mount(
+                Some(&self.scaffold),
+                &self.project_root,
+                None,
+                libc::MS_BIND,
+            )?;
+            for m in &self.mounts {
+                mount(
+                    Some(&m.source),
+                    &m.target,
+                    None,
+                    libc::MS_BIND | libc::MS_REC,
+                )?;
+                if m.read_only {
+                    mount(
+                        None,
+                        &m.target,
+                        None,
+                        libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY | m.locked_flags,
+                    )?;
+                }
+            }
+
+            check(unsafe { libc::umount2(STAGING.as_ptr(), libc::MNT_DETACH) })?;
+            check(unsafe { libc::rmdir(STAGING.as_ptr()) })?;
+
+            // The working directory was set before we got here, and still points at the real
+            // project root.
+            check(unsafe { libc::chdir(self.cwd.as_ptr()) })?;
+
+            Ok(())
+        }
+    }
+
+    fn check(res: libc::c_int) -> io::Result<()> {
+        if res < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn mount(
+        source: Option<&CStr>,
+        target: &CStr,
+        fstype: Option<&CStr>,
+        flags: libc::c_ulong,
+    ) -> io::Result<()> {
+        check(unsafe {
+            libc::mount(
+                source.map_or(std::ptr::null(), |s| s.as_ptr()),
+                target.as_ptr(),
+                fstype.map_or(std::ptr::null(), |s| s.as_ptr()),
+                flags,
+                std::ptr::null(),
+            )
+        })
+    }
+
+    /// `/proc/self/fd/<fd>`, without allocating.
+    fn fd_path(buf: &mut [u8; 32], fd: libc::c_int) -> io::Result<&CStr> {
+        const PREFIX: &[u8] = b"/proc/self/fd/";
+        buf[..PREFIX.len()].copy_from_slice(PREFIX);
+        let mut digits = [0; 10];
+        let mut len = 0;
+        let mut n = fd as u32;
+        loop {
+            digits[len] = b'0' + (n % 10) as u8;
+            len += 1;
+            n /= 10;
+            if n == 0 {
+                break;
+            }
+        }
+        for (i, d) in digits[..len].iter().rev().enumerate() {
+            buf[PREFIX.len() + i] = *d;
+        }
+        buf[PREFIX.len() + len] = 0;
+        CStr::from_bytes_until_nul(&buf[..])
+            .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))
+    }
+
+    fn write_file(path: &CStr, data: &[u8]) -> io::Result<()> {
+        let fd = unsafe { libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC) };
+        check(fd)?;
+        let written = unsafe { libc::write(fd, data.as_ptr().cast(), data.len()) };
+        let res = if written < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        };
+        unsafe { libc::close(fd) };
+        res
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command;
+    use std::process::Output;
+
+    use buck2_core::fs::paths::abs_path::AbsPath;
+    use buck2_forkserver_proto::SandboxConfig;
+
+    use super::apply;
+
+    fn config(root: &Path, readable: &[&str], isolate_network: bool) -> SandboxConfig {
+        SandboxConfig {
+            project_root: root.as_os_str().as_encoded_bytes().to_vec(),
+            readable_paths: readable.iter().map(|p| p.as_bytes().to_vec()).collect(),
+            writable_paths: vec![b"out".to_vec()],
+            scaffold_dir: b"buck-out/sandbox/test".to_vec(),
+            isolate_network,
+        }
+    }
+
+    /// Runs `script` in a sandbox, or returns `None` if this machine does not allow
+    /// unprivileged user namespaces.
+    fn run(root: &Path, config: SandboxConfig, script: &str) -> anyhow::Result<Option<Output>> {
+        let mut cmd = Command::new("/bin/sh");
+        cmd.args(["-c", script]).current_dir(root);
+        apply(&mut cmd, config, AbsPath::new(root)?)?;
+        match cmd.output() {
+            Ok(output) => Ok(Some(output)),
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                eprintln!("Skipping, user namespaces are not available: {}", e);
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn project() -> anyhow::Result<tempfile::TempDir> {
+        let root = tempfile::tempdir()?;
+        fs::write(root.path().join("declared.txt"), "declared")?;
+        fs::write(root.path().join("undeclared.txt"), "undeclared")?;
+        Ok(root)
+    }
+
+    #[test]
+    fn test_declared_read() -> anyhow::Result<()> {
+        let root = project()?;
+        let config = config(root.path(), &["declared.txt"], true);
+        let Some(output) = run(root.path(), config, "cat declared.txt > out/copy")? else {
+            return Ok(());
+        };
+        assert!(output.status.success(), "{:?}", output);
+        assert_eq!(
+            "declared",
+            fs::read_to_string(root.path().join("out/copy"))?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_undeclared_read() -> anyhow::Result<()> {
+        let root = project()?;
+        let config = config(root.path(), &["declared.txt"], true);
+        let Some(output) = run(root.path(), config, "cat undeclared.txt")? else {
+            return Ok(());
+        };
+        assert!(!output.status.success(), "{:?}", output);
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("No such file"),
+            "{:?}",
+            output
+        );
+        // Still there outside of the sandbox.
+        assert!(root.path().join("undeclared.txt").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_network_denied() -> anyhow::Result<()> {
+        let root = project()?;
+        let config = config(root.path(), &[], true);
+        let Some(output) = run(root.path(), config, "cat /proc/self/net/dev")? else {
+            return Ok(());
+        };
+        assert!(output.status.success(), "{:?}", output);
+        // A new network namespace only has a loopback interface, which is down.
+        let interfaces: Vec<String> = String::from_utf8(output.stdout)?
+            .lines()
+            .filter_map(|line| Some(line.split_once(':')?.0.trim().to_owned()))
+            .collect();
+        assert_eq!(vec!["lo".to_owned()], interfaces);
+        Ok(())
+    }
+}
@@ -18,6 +18,7 @@ use std::sync::Arc;
 
 use anyhow::Context as _;
 use buck2_common::convert::ProstDurationExt;
+use buck2_common::init::ResourceControlConfig;
 use buck2_core::fs::fs_util;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPath;
 use buck2_core::fs::paths::abs_norm_path::AbsNormPathBuf;
@@ -51,6 +52,9 @@ use crate::run::stream_command_events;
 use crate::run::timeout_into_cancellation;
 use crate::run::DefaultKillProcess;
 use crate::run::GatherOutputStatus;
+use crate::unix::cgroup::ActionCgroups;
+use crate::unix::cgroup::CgroupStatusDecoder;
+use crate::unix::sandbox;
 
 // Not quite BoxStream: it has to be Sync (...)
 type RunStream =
@@ -61,18 +65,24 @@ pub struct UnixForkserverService {
 
     /// State for Miniperf.
     miniperf: Option<MiniperfContainer>,
+
+    /// Set if commands run in their own cgroup.
+    cgroups: Option<ActionCgroups>,
 }
 
 impl UnixForkserverService {
     pub fn new(
         log_reload_handle: Arc<dyn LogConfigurationReloadHandle>,
         state_dir: &AbsNormPath,
+        resource_control: &ResourceControlConfig,
     ) -> anyhow::Result<Self> {
         let miniperf = MiniperfContainer::new(state_dir)?;
+        let cgroups = ActionCgroups::new(resource_control)?;
 
         Ok(Self {
             log_reload_handle,
             miniperf,
+            cgroups,
         })
     }
 }
@@ -115,6 +125,8 @@ impl Forkserver for UnixForkserverService {
                 enable_miniperf,
                 std_redirects,
                 graceful_shutdown_timeout_s,
+                sandbox,
+                resource_limits,
             } = msg;
 
             let exe = OsStr::from_bytes(&exe);
@@ -142,6 +154,17 @@ impl Forkserver for UnixForkserverService {
             cmd.current_dir(cwd);
             cmd.args(argv);
 
+            // Before the sandbox, which leaves the cgroup namespace alone but changes users.
+            let cgroup = self
+                .cgroups
+                .as_ref()
+                .map(|cgroups| cgroups.create(&mut cmd, resource_limits.as_ref()))
+                .transpose()?;
+
+            if let Some(sandbox) = sandbox {
+                sandbox::apply(&mut cmd, sandbox, cwd)?;
+            }
+
             {
                 use buck2_forkserver_proto::env_directive::Data;
 
@@ -177,7 +200,7 @@ impl Forkserver for UnixForkserverService {
                 Some(out) => stream_command_events(
                     process_group,
                     cancellation,
-                    MiniperfStatusDecoder::new(out),
+                    CgroupStatusDecoder::new(MiniperfStatusDecoder::new(out), cgroup),
                     DefaultKillProcess {
                         graceful_shutdown_timeout_s,
                     },
@@ -187,7 +210,7 @@ impl Forkserver for UnixForkserverService {
                 None => stream_command_events(
                     process_group,
                     cancellation,
-                    DefaultStatusDecoder,
+                    CgroupStatusDecoder::new(DefaultStatusDecoder, cgroup),
                     DefaultKillProcess {
                         graceful_shutdown_timeout_s,
                     },
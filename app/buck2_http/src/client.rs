@@ -7,6 +7,8 @@
  * of this source tree.
  */
 
+use std::borrow::Cow;
+use std::future::Future;
 use std::sync::Arc;
 
 use allocative::Allocative;
@@ -30,6 +32,7 @@ use tokio_util::io::StreamReader;
 
 use crate::redirect::PendingRequest;
 use crate::redirect::RedirectEngine;
+use crate::rewrite::UrlRewrites;
 use crate::stats::CountingStream;
 use crate::stats::HttpNetworkStats;
 use crate::x2p::X2PAgentError;
@@ -48,10 +51,63 @@ pub struct HttpClient {
     max_redirects: Option<usize>,
     supports_vpnless: bool,
     http2: bool,
+    /// Refuse to make any requests.
+    offline: bool,
+    /// Applied to the URLs of `head` and `get` requests.
+    url_rewrites: Option<Arc<UrlRewrites>>,
     stats: HttpNetworkStats,
 }
 
 impl HttpClient {
+    /// A client sharing this one's connections and stats, which fails all requests. Used for
+    /// builds that must not touch the network.
+    pub fn to_offline(&self) -> Self {
+        Self {
+            offline: true,
+            ..self.dupe()
+        }
+    }
+
+    /// A client sharing this one's connections and stats, which requests URLs as they are.
+    pub fn without_url_rewrites(&self) -> Self {
+        Self {
+            url_rewrites: None,
+            ..self.dupe()
+        }
+    }
+
+    /// The URLs that `head` and `get` try, in order, for a request to `uri`.
+    pub fn url_candidates<'a>(&self, uri: &'a str) -> Vec<Cow<'a, str>> {
+        match &self.url_rewrites {
+            Some(url_rewrites) => url_rewrites.candidates(uri),
+            None => vec![Cow::Borrowed(uri)],
+        }
+    }
+
+    /// Make a request to each of the URLs `uri` is rewritten to, until one succeeds. If all of
+    /// them fail, this returns the last error.
+    async fn request_with_url_rewrites<T, F, Fut>(&self, uri: &str, f: F) -> Result<T, HttpError>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<T, HttpError>>,
+    {
+        let mut error = None;
+        for candidate in self.url_candidates(uri) {
+            let candidate = candidate.into_owned();
+            if candidate != uri {
+                tracing::debug!("http: rewrote '{}' to '{}'", uri, candidate);
+            }
+            match f(candidate.clone()).await {
+                Ok(res) => return Ok(res),
+                Err(e) => {
+                    tracing::debug!("http: request to '{}' failed: {}", candidate, e);
+                    error = Some(e);
+                }
+            }
+        }
+        Err(error.expect("There is always at least one candidate URL"))
+    }
+
     fn request_builder(&self, uri: &str) -> Builder {
         Request::builder()
             .uri(uri)
@@ -60,12 +116,15 @@ impl HttpClient {
 
     /// Send a HEAD request. Assumes no body will be returned. If one is returned, it will be ignored.
     pub async fn head(&self, uri: &str) -> Result<Response<()>, HttpError> {
-        let req = self
-            .request_builder(uri)
-            .method(Method::HEAD)
-            .body(Bytes::new())
-            .map_err(HttpError::BuildRequest)?;
-        self.request(req).await.map(|resp| resp.map(|_| ()))
+        self.request_with_url_rewrites(uri, |uri| async move {
+            let req = self
+                .request_builder(&uri)
+                .method(Method::HEAD)
+                .body(Bytes::new())
+                .map_err(HttpError::BuildRequest)?;
+            self.request(req).await.map(|resp| resp.map(|_| ()))
+        })
+        .await
     }
 
     /// Send a GET request.
@@ -73,12 +132,15 @@ impl HttpClient {
         &self,
         uri: &str,
     ) -> Result<Response<BoxStream<hyper::Result<Bytes>>>, HttpError> {
-        let req = self
-            .request_builder(uri)
-            .method(Method::GET)
-            .body(Bytes::new())
-            .map_err(HttpError::BuildRequest)?;
-        self.request(req).await
+        self.request_with_url_rewrites(uri, |uri| async move {
+            let req = self
+                .request_builder(&uri)
+                .method(Method::GET)
+                .body(Bytes::new())
+                .map_err(HttpError::BuildRequest)?;
+            self.request(req).await
+        })
+        .await
     }
 
     pub async fn post(
@@ -114,6 +176,9 @@ impl HttpClient {
         mut request: Request<Bytes>,
     ) -> Result<Response<BoxStream<hyper::Result<Bytes>>>, HttpError> {
         let uri = request.uri().to_string();
+        if self.offline {
+            return Err(HttpError::Offline { uri });
+        }
         let now = tokio::time::Instant::now();
 
         // x2p requires scheme to be http since it handles all TLS.
@@ -407,6 +472,43 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_url_rewrites_fall_back() -> anyhow::Result<()> {
+        let test_server = httptest::Server::run();
+        test_server.expect(
+            Expectation::matching(request::method_path("GET", "/mirror/foo"))
+                .times(1)
+                .respond_with(responders::status_code(404)),
+        );
+        test_server.expect(
+            Expectation::matching(request::method_path("GET", "/foo"))
+                .times(1)
+                .respond_with(responders::status_code(200)),
+        );
+
+        let client = HttpClientBuilder::https_with_system_roots()
+            .await?
+            .with_url_rewrites(UrlRewrites::parse("/foo$=>/mirror/foo", true)?)
+            .build();
+        let url = test_server.url_str("/foo");
+        assert_eq!(
+            vec![test_server.url_str("/mirror/foo"), url.clone()],
+            client.url_candidates(&url)
+        );
+        let resp = client.get(&url).await?;
+        assert_eq!(200, resp.status().as_u16());
+
+        // Requests made without rewrites go straight to the original URL.
+        test_server.expect(
+            Expectation::matching(request::method_path("HEAD", "/foo"))
+                .times(1)
+                .respond_with(responders::status_code(200)),
+        );
+        client.without_url_rewrites().head(&url).await?;
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_count_response_size() -> anyhow::Result<()> {
         let test_server = httptest::Server::run();
@@ -33,6 +33,7 @@ use tokio_rustls::TlsConnector;
 use super::HttpClient;
 use super::RequestClient;
 use crate::proxy;
+use crate::rewrite::UrlRewrites;
 use crate::stats::HttpNetworkStats;
 use crate::x2p;
 
@@ -66,6 +67,7 @@ pub struct HttpClientBuilder {
     supports_vpnless: bool,
     http2: bool,
     timeout_config: Option<TimeoutConfig>,
+    url_rewrites: Option<Arc<UrlRewrites>>,
 }
 
 impl HttpClientBuilder {
@@ -104,6 +106,7 @@ impl HttpClientBuilder {
             supports_vpnless: false,
             http2: true,
             timeout_config: None,
+            url_rewrites: None,
         })
     }
 
@@ -214,6 +217,11 @@ impl HttpClientBuilder {
         self.supports_vpnless
     }
 
+    pub fn with_url_rewrites(&mut self, url_rewrites: UrlRewrites) -> &mut Self {
+        self.url_rewrites = Some(Arc::new(url_rewrites));
+        self
+    }
+
     fn build_inner(&self) -> Arc<dyn RequestClient> {
         match (self.proxies.as_slice(), &self.timeout_config) {
             // Construct x2p unix socket client.
@@ -293,6 +301,8 @@ impl HttpClientBuilder {
             max_redirects: self.max_redirects,
             supports_vpnless: self.supports_vpnless,
             http2: self.http2,
+            offline: false,
+            url_rewrites: self.url_rewrites.clone(),
             stats: HttpNetworkStats::new(),
         }
     }
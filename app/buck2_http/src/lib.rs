@@ -16,12 +16,14 @@ mod client;
 mod proxy;
 mod redirect;
 pub mod retries;
+mod rewrite;
 mod stats;
 mod x2p;
 
 pub use client::to_bytes;
 pub use client::HttpClient;
 pub use client::HttpClientBuilder;
+pub use rewrite::UrlRewrites;
 
 fn http_error_label(status: StatusCode) -> &'static str {
     if status.is_server_error() {
@@ -81,6 +83,9 @@ pub enum HttpError {
     #[error("HTTP: Timed out while making request to URI: {uri} after {duration} seconds.")]
     #[buck2(tier0)]
     Timeout { uri: String, duration: u64 },
+    #[error("HTTP: Refusing to make a request to {uri}: network access is disabled by `--offline`")]
+    #[buck2(input)]
+    Offline { uri: String },
     #[error("While making request to {uri} via x2p")]
     X2P {
         uri: String,
@@ -0,0 +1,151 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! Rewriting of request URLs, typically to send downloads to a mirror instead of the internet.
+
+use std::borrow::Cow;
+
+use allocative::Allocative;
+use regex::Regex;
+
+#[derive(Debug, buck2_error::Error)]
+enum UrlRewriteError {
+    #[error("Invalid URL rewrite rule `{0}`, expected `<regex>=><replacement>`")]
+    #[buck2(input)]
+    InvalidRule(String),
+    #[error("Invalid regex in URL rewrite rule `{rule}`")]
+    #[buck2(input)]
+    InvalidRegex {
+        rule: String,
+        #[source]
+        source: regex::Error,
+    },
+}
+
+#[derive(Debug, Allocative)]
+struct UrlRewriteRule {
+    #[allocative(skip)]
+    regex: Regex,
+    replacement: String,
+}
+
+/// An ordered list of `regex => replacement` rules.
+#[derive(Debug, Allocative)]
+pub struct UrlRewrites {
+    rules: Vec<UrlRewriteRule>,
+    /// Whether to try the original URL once all the rewritten ones failed.
+    fallback_to_original: bool,
+}
+
+impl UrlRewrites {
+    /// Parse whitespace separated `<regex>=><replacement>` rules. Replacements can refer to the
+    /// regex's capture groups as `$1` or `${name}`.
+    pub fn parse(rules: &str, fallback_to_original: bool) -> anyhow::Result<Self> {
+        let rules = rules
+            .split_whitespace()
+            .map(|rule| {
+                let (regex, replacement) = rule
+                    .split_once("=>")
+                    .ok_or_else(|| UrlRewriteError::InvalidRule(rule.to_owned()))?;
+                let regex = Regex::new(regex).map_err(|source| UrlRewriteError::InvalidRegex {
+                    rule: rule.to_owned(),
+                    source,
+                })?;
+                anyhow::Ok(UrlRewriteRule {
+                    regex,
+                    replacement: replacement.to_owned(),
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            rules,
+            fallback_to_original,
+        })
+    }
+
+    /// The URLs to try for a request to `url`, in order: what every matching rule rewrites it to,
+    /// then the URL itself, unless a rule matched and falling back is disabled.
+    pub fn candidates<'a>(&self, url: &'a str) -> Vec<Cow<'a, str>> {
+        let mut candidates: Vec<Cow<'a, str>> = Vec::new();
+        for rule in &self.rules {
+            if rule.regex.is_match(url) {
+                let rewritten = rule.regex.replace(url, rule.replacement.as_str());
+                let rewritten = Cow::Owned(rewritten.into_owned());
+                if !candidates.contains(&rewritten) {
+                    candidates.push(rewritten);
+                }
+            }
+        }
+        if (candidates.is_empty() || self.fallback_to_original)
+            && !candidates.iter().any(|c| c == url)
+        {
+            candidates.push(Cow::Borrowed(url));
+        }
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_candidates() -> anyhow::Result<()> {
+        let rewrites = UrlRewrites::parse(
+            r"
+            ^https://github\.com/(.*)$=>https://mirror.example.com/github/$1
+            ^https://(?P<rest>.*)$=>https://fallback.example.com/${rest}
+            ",
+            true,
+        )?;
+
+        assert_eq!(
+            vec![
+                "https://mirror.example.com/github/foo/bar.tar.gz",
+                "https://fallback.example.com/github.com/foo/bar.tar.gz",
+                "https://github.com/foo/bar.tar.gz",
+            ],
+            rewrites.candidates("https://github.com/foo/bar.tar.gz")
+        );
+        // Rules that don't match don't change anything.
+        assert_eq!(
+            vec!["http://example.com/x"],
+            rewrites.candidates("http://example.com/x")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_candidates_without_fallback() -> anyhow::Result<()> {
+        let rewrites = UrlRewrites::parse(
+            r"^https://github\.com/=>https://mirror.example.com/github/",
+            false,
+        )?;
+
+        assert_eq!(
+            vec!["https://mirror.example.com/github/foo"],
+            rewrites.candidates("https://github.com/foo")
+        );
+        // Without a matching rule, the URL is used as is.
+        assert_eq!(
+            vec!["https://example.com/foo"],
+            rewrites.candidates("https://example.com/foo")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(UrlRewrites::parse("no-arrow", true).is_err());
+        assert!(UrlRewrites::parse("(unclosed=>x", true).is_err());
+        assert!(UrlRewrites::parse("", true).is_ok());
+    }
+}
@@ -0,0 +1,87 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::Context;
+use clap::Parser;
+use tokio::net::TcpListener;
+
+use crate::destination::DirectoryDestination;
+use crate::destination::RsyncDestination;
+use crate::progress::Progress;
+use crate::service::InstallerService;
+
+/// Installs the artifacts of `buck2 install` into a directory, on this machine or on another host.
+///
+/// Arguments after `--` on the `buck2 install` command line are passed to the installer, e.g.
+/// `buck2 install //:app -- --dst /tmp/app`.
+#[derive(Debug, Parser)]
+#[clap(name = "buck2_installer")]
+pub struct InstallerArgs {
+    /// Port to serve the installer protocol on. Passed by buck2.
+    #[clap(long)]
+    tcp_port: u16,
+
+    /// File to append progress reports to. Passed by buck2.
+    #[clap(long)]
+    log_path: Option<PathBuf>,
+
+    /// Directory to install the artifacts into. With `--host`, a directory on that host.
+    #[clap(long)]
+    dst: String,
+
+    /// Install on this host with rsync over ssh, e.g. `user@device`.
+    #[clap(long)]
+    host: Option<String>,
+
+    /// Command used to connect to `--host`, with its arguments separated by spaces.
+    #[clap(long, default_value = "ssh")]
+    ssh: String,
+
+    /// The rsync binary used to copy to `--host`.
+    #[clap(long, default_value = "rsync")]
+    rsync: String,
+
+    /// Seconds to wait before installing each artifact, to simulate a slow device.
+    #[clap(long, default_value = "0")]
+    delay: f64,
+}
+
+impl InstallerArgs {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let progress = Progress::new(self.log_path.as_deref())?;
+        let delay = Duration::try_from_secs_f64(self.delay).context("Invalid `--delay`")?;
+        // buck2 picked a free port and connects to it as soon as we are listening.
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, self.tcp_port))
+            .await
+            .with_context(|| format!("Failed to bind to port {}", self.tcp_port))?;
+
+        match self.host {
+            Some(host) => {
+                let destination = RsyncDestination::new(host, self.dst, self.ssh, self.rsync);
+                InstallerService::new(destination, progress)
+                    .await?
+                    .with_delay(delay)
+                    .serve(listener)
+                    .await
+            }
+            None => {
+                let destination = DirectoryDestination::new(PathBuf::from(self.dst));
+                InstallerService::new(destination, progress)
+                    .await?
+                    .with_delay(delay)
+                    .serve(listener)
+                    .await
+            }
+        }
+    }
+}
@@ -0,0 +1,421 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::process::Output;
+use std::process::Stdio;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::manifest::MANIFEST_NAME;
+
+#[derive(Debug, buck2_error::Error)]
+enum DestinationError {
+    #[error("Invalid artifact name `{0}`, expected a relative path without `..`")]
+    InvalidName(String),
+    #[error("`{command}` failed with {status}:\n{stderr}")]
+    CommandFailed {
+        command: String,
+        status: ExitStatus,
+        stderr: String,
+    },
+}
+
+/// Where an installer puts artifacts. Artifacts are identified by the name they have in the
+/// `install_files` of the installed target.
+#[async_trait]
+pub trait Destination: Send + Sync + 'static {
+    /// Human readable description of the destination, for progress reports and the device
+    /// metadata sent back to buck2.
+    fn describe(&self) -> String;
+
+    /// Returns the manifest written by the last install, if any.
+    async fn read_manifest(&self) -> anyhow::Result<Option<String>>;
+
+    async fn write_manifest(&self, contents: &str) -> anyhow::Result<()>;
+
+    /// Which of the artifacts `names` are still at the destination. The manifest only says what
+    /// was installed, not whether it was deleted since. Asked once for all the artifacts of an
+    /// install, since each question may be a round trip to the device.
+    async fn existing(&self, names: &[String]) -> anyhow::Result<HashSet<String>>;
+
+    /// Copies `source`, a file or a directory, to `name`, replacing what was there. Symlinks are
+    /// followed, so that nothing at the destination points back into `buck-out`.
+    async fn install(&self, source: &Path, name: &str) -> anyhow::Result<()>;
+}
+
+/// Artifact names come from the build, but still shouldn't be able to escape the destination.
+fn check_name(name: &str) -> anyhow::Result<&Path> {
+    let path = Path::new(name);
+    let valid = name != MANIFEST_NAME
+        && path.components().next().is_some()
+        && path.components().all(|c| matches!(c, Component::Normal(_)));
+    if !valid {
+        return Err(DestinationError::InvalidName(name.to_owned()).into());
+    }
+    Ok(path)
+}
+
+/// Installs into a directory on this machine.
+pub struct DirectoryDestination {
+    dir: PathBuf,
+}
+
+impl DirectoryDestination {
+    pub fn new(dir: PathBuf) -> DirectoryDestination {
+        DirectoryDestination { dir }
+    }
+}
+
+#[async_trait]
+impl Destination for DirectoryDestination {
+    fn describe(&self) -> String {
+        self.dir.display().to_string()
+    }
+
+    async fn read_manifest(&self) -> anyhow::Result<Option<String>> {
+        let path = self.dir.join(MANIFEST_NAME);
+        match tokio::fs::read_to_string(&path).await {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Failed to read `{}`", path.display())),
+        }
+    }
+
+    async fn write_manifest(&self, contents: &str) -> anyhow::Result<()> {
+        let path = self.dir.join(MANIFEST_NAME);
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| format!("Failed to create `{}`", self.dir.display()))?;
+        tokio::fs::write(&path, contents)
+            .await
+            .with_context(|| format!("Failed to write `{}`", path.display()))
+    }
+
+    async fn existing(&self, names: &[String]) -> anyhow::Result<HashSet<String>> {
+        let mut existing = HashSet::new();
+        for name in names {
+            let Ok(path) = check_name(name) else {
+                continue;
+            };
+            if tokio::fs::symlink_metadata(self.dir.join(path))
+                .await
+                .is_ok()
+            {
+                existing.insert(name.clone());
+            }
+        }
+        Ok(existing)
+    }
+
+    async fn install(&self, source: &Path, name: &str) -> anyhow::Result<()> {
+        let dest = self.dir.join(check_name(name)?);
+        let source = source.to_owned();
+        tokio::task::spawn_blocking(move || {
+            copy_replacing(&source, &dest).with_context(|| {
+                format!(
+                    "Failed to copy `{}` to `{}`",
+                    source.display(),
+                    dest.display()
+                )
+            })
+        })
+        .await?
+    }
+}
+
+/// Copies next to `dest` first and then renames, so that `dest` is never left half-written.
+fn copy_replacing(source: &Path, dest: &Path) -> io::Result<()> {
+    let mut staging = dest.as_os_str().to_owned();
+    staging.push(".buck2-installing");
+    let staging = PathBuf::from(staging);
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    remove_if_exists(&staging)?;
+    copy_recursive(source, &staging)?;
+    remove_if_exists(dest)?;
+    fs::rename(&staging, dest)
+}
+
+fn copy_recursive(source: &Path, dest: &Path) -> io::Result<()> {
+    if fs::metadata(source)?.is_dir() {
+        fs::create_dir(dest)?;
+        for entry in fs::read_dir(source)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &dest.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(source, dest)?;
+    }
+    Ok(())
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path),
+        Ok(_) => fs::remove_file(path),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Installs into a directory on another host, copying with rsync over ssh.
+pub struct RsyncDestination {
+    /// Anything ssh accepts, e.g. `user@device`.
+    host: String,
+    dir: String,
+    /// The ssh command, with its arguments separated by spaces, as for `rsync --rsh`.
+    ssh: String,
+    rsync: String,
+}
+
+impl RsyncDestination {
+    pub fn new(host: String, dir: String, ssh: String, rsync: String) -> RsyncDestination {
+        RsyncDestination {
+            host,
+            dir,
+            ssh,
+            rsync,
+        }
+    }
+
+    fn remote_path(&self, name: &str) -> String {
+        format!("{}/{}", self.dir.trim_end_matches('/'), name)
+    }
+
+    /// Runs `script` on the host, with the remote shell.
+    fn ssh_command(&self, script: &str) -> Command {
+        let mut words = self.ssh.split_whitespace();
+        let mut command = Command::new(words.next().unwrap_or("ssh"));
+        command.args(words).arg(&self.host).arg(script);
+        command
+    }
+}
+
+#[async_trait]
+impl Destination for RsyncDestination {
+    fn describe(&self) -> String {
+        format!("{}:{}", self.host, self.dir)
+    }
+
+    async fn read_manifest(&self) -> anyhow::Result<Option<String>> {
+        let path = shell_quote(&self.remote_path(MANIFEST_NAME));
+        let script = format!("if [ -e {path} ]; then cat {path}; else printf missing; fi");
+        let output = run(self.ssh_command(&script), None).await?;
+        let contents = String::from_utf8_lossy(&output.stdout).into_owned();
+        Ok(if contents == "missing" {
+            None
+        } else {
+            Some(contents)
+        })
+    }
+
+    async fn write_manifest(&self, contents: &str) -> anyhow::Result<()> {
+        let script = format!(
+            "mkdir -p {} && cat > {}",
+            shell_quote(&self.dir),
+            shell_quote(&self.remote_path(MANIFEST_NAME))
+        );
+        run(self.ssh_command(&script), Some(contents)).await?;
+        Ok(())
+    }
+
+    async fn existing(&self, names: &[String]) -> anyhow::Result<HashSet<String>> {
+        // The names are sent one per line on stdin, so the ones with a newline are never found,
+        // and get installed again.
+        let input: String = names
+            .iter()
+            .filter(|name| check_name(name).is_ok() && !name.contains('\n'))
+            .map(|name| format!("{}\n", name))
+            .collect();
+        if input.is_empty() {
+            return Ok(HashSet::new());
+        }
+        let script = format!(
+            "cd {} 2>/dev/null || {{ cat > /dev/null; exit 0; }}; \
+             while IFS= read -r name; do \
+             if [ -e \"$name\" ]; then printf '%s\\n' \"$name\"; fi; \
+             done",
+            shell_quote(&self.dir)
+        );
+        let output = run(self.ssh_command(&script), Some(&input)).await?;
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_owned)
+            .collect())
+    }
+
+    async fn install(&self, source: &Path, name: &str) -> anyhow::Result<()> {
+        check_name(name)?;
+        let dest = self.remote_path(name);
+        if let Some((parent, _)) = dest.rsplit_once('/') {
+            if !parent.is_empty() {
+                run(
+                    self.ssh_command(&format!("mkdir -p {}", shell_quote(parent))),
+                    None,
+                )
+                .await?;
+            }
+        }
+
+        // With a trailing slash, rsync copies the contents of a directory rather than the
+        // directory itself, so that it ends up at `dest` whatever the name of the source.
+        let is_dir = tokio::fs::metadata(source)
+            .await
+            .with_context(|| format!("Failed to stat `{}`", source.display()))?
+            .is_dir();
+        let mut source = source.as_os_str().to_owned();
+        if is_dir {
+            source.push("/");
+        }
+        let mut command = Command::new(&self.rsync);
+        command
+            .arg("--archive")
+            .arg("--copy-links")
+            .arg("--delete")
+            .arg("--protect-args")
+            .arg(format!("--rsh={}", self.ssh))
+            .arg(source)
+            .arg(format!("{}:{}", self.host, dest));
+        run(command, None).await?;
+        Ok(())
+    }
+}
+
+async fn run(mut command: Command, stdin: Option<&str>) -> anyhow::Result<Output> {
+    let description = format!("{:?}", command.as_std());
+    command
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("Failed to spawn `{}`", description))?;
+    // Written while reading the output, so that neither side blocks on a full pipe.
+    let pipe = child.stdin.take();
+    let write_stdin = async move {
+        if let (Some(contents), Some(mut pipe)) = (stdin, pipe) {
+            pipe.write_all(contents.as_bytes()).await?;
+        }
+        io::Result::Ok(())
+    };
+    let (written, output) = tokio::join!(write_stdin, child.wait_with_output());
+    let output = output?;
+    if !output.status.success() {
+        return Err(DestinationError::CommandFailed {
+            command: description,
+            status: output.status,
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }
+        .into());
+    }
+    written?;
+    Ok(output)
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_name() {
+        assert!(check_name("app.apk").is_ok());
+        assert!(check_name("lib/foo.so").is_ok());
+        assert!(check_name("").is_err());
+        assert!(check_name("/etc/passwd").is_err());
+        assert!(check_name("../app.apk").is_err());
+        assert!(check_name("lib/../../app.apk").is_err());
+        assert!(check_name(MANIFEST_NAME).is_err());
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!("'a b'", shell_quote("a b"));
+        assert_eq!(r"'it'\''s'", shell_quote("it's"));
+    }
+
+    #[tokio::test]
+    async fn test_directory_install_replaces() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let src = tmp.path().join("src");
+        fs::create_dir_all(src.join("dir/sub"))?;
+        fs::write(src.join("dir/sub/a"), "a")?;
+        fs::write(src.join("file"), "file")?;
+
+        let destination = DirectoryDestination::new(tmp.path().join("dst"));
+        destination.install(&src.join("dir"), "out/dir").await?;
+        assert_eq!(
+            "a",
+            fs::read_to_string(tmp.path().join("dst/out/dir/sub/a"))?
+        );
+        assert_eq!(
+            HashSet::from(["out/dir".to_owned()]),
+            destination
+                .existing(&["out/dir".to_owned(), "out/other".to_owned()])
+                .await?
+        );
+
+        // A file replaces the directory, and nothing is left of the previous install.
+        destination.install(&src.join("file"), "out/dir").await?;
+        assert_eq!("file", fs::read_to_string(tmp.path().join("dst/out/dir"))?);
+        assert_eq!(
+            vec!["dir"],
+            fs::read_dir(tmp.path().join("dst/out"))?
+                .map(|e| e.unwrap().file_name().into_string().unwrap())
+                .collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_directory_install_follows_symlinks() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        fs::write(tmp.path().join("target"), "target")?;
+        std::os::unix::fs::symlink(tmp.path().join("target"), tmp.path().join("link"))?;
+
+        let destination = DirectoryDestination::new(tmp.path().join("dst"));
+        destination
+            .install(&tmp.path().join("link"), "link")
+            .await?;
+        let installed = tmp.path().join("dst/link");
+        assert!(!fs::symlink_metadata(&installed)?.is_symlink());
+        assert_eq!("target", fs::read_to_string(&installed)?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_directory_manifest() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let destination = DirectoryDestination::new(tmp.path().join("dst"));
+        assert_eq!(None, destination.read_manifest().await?);
+        destination.write_manifest("{}").await?;
+        assert_eq!(Some("{}".to_owned()), destination.read_manifest().await?);
+        Ok(())
+    }
+}
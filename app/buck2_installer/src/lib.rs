@@ -0,0 +1,37 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+//! A reference implementation of an installer for `buck2 install`.
+//!
+//! buck2 launches the installer with `--tcp-port <port> --log-path <path>` appended to its
+//! `RunInfo`, connects to the `Installer` gRPC service on that port, sends one `Install` request per
+//! installed target, one `FileReady` request per artifact as soon as it is built, and finally
+//! `ShutdownServer`.
+//!
+//! This installer copies every artifact to `<dst>/<name>`, either in a local directory or on a
+//! remote host over rsync and ssh. The digests of what was installed are recorded in a manifest at
+//! the destination, so that artifacts that did not change since the last install are not copied
+//! again.
+//!
+//! Custom installers can reuse the service by implementing [`Destination`].
+
+#![feature(error_generic_member_access)]
+
+mod args;
+mod destination;
+mod manifest;
+mod progress;
+mod service;
+
+pub use crate::args::InstallerArgs;
+pub use crate::destination::Destination;
+pub use crate::destination::DirectoryDestination;
+pub use crate::destination::RsyncDestination;
+pub use crate::progress::Progress;
+pub use crate::service::InstallerService;
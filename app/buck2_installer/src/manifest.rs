@@ -0,0 +1,82 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Name of the manifest, in the root of the destination.
+pub(crate) const MANIFEST_NAME: &str = ".buck2-install-manifest.json";
+
+/// The digests of the artifacts installed in a destination, by name.
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Manifest {
+    files: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    /// A manifest that can't be parsed (e.g. written by another version) is treated as empty:
+    /// the worst that happens is that everything is copied again.
+    pub(crate) fn parse(contents: &str) -> Manifest {
+        serde_json::from_str(contents).unwrap_or_default()
+    }
+
+    pub(crate) fn to_json(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub(crate) fn is_installed(&self, name: &str, digest: &str) -> bool {
+        self.files.get(name).is_some_and(|d| d == digest)
+    }
+
+    pub(crate) fn is_recorded(&self, name: &str) -> bool {
+        self.files.contains_key(name)
+    }
+
+    pub(crate) fn record(&mut self, name: &str, digest: &str) {
+        self.files.insert(name.to_owned(), digest.to_owned());
+    }
+
+    /// Called before overwriting an artifact, since a failed copy can leave it half-written.
+    pub(crate) fn forget(&mut self, name: &str) {
+        self.files.remove(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut manifest = Manifest::default();
+        manifest.record("app.apk", "SHA1:abc");
+        manifest.record("lib/foo.so", "SHA1:def");
+        let parsed = Manifest::parse(&manifest.to_json().unwrap());
+        assert_eq!(manifest, parsed);
+        assert!(parsed.is_installed("app.apk", "SHA1:abc"));
+        assert!(!parsed.is_installed("app.apk", "SHA1:def"));
+        assert!(!parsed.is_installed("other", "SHA1:abc"));
+    }
+
+    #[test]
+    fn test_forget() {
+        let mut manifest = Manifest::default();
+        manifest.record("app.apk", "SHA1:abc");
+        manifest.forget("app.apk");
+        assert!(!manifest.is_installed("app.apk", "SHA1:abc"));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert_eq!(Manifest::default(), Manifest::parse("not json"));
+        assert_eq!(Manifest::default(), Manifest::parse(""));
+    }
+}
@@ -0,0 +1,53 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+use anyhow::Context;
+
+/// Reports what the installer does, one line per event, to stderr and to the log file buck2
+/// passes with `--log-path`. buck2 only shows the installer's stderr with `--installer-debug`, and
+/// points at the log file when an install fails.
+#[derive(Default)]
+pub struct Progress {
+    log: Option<Mutex<File>>,
+}
+
+impl Progress {
+    pub fn new(log_path: Option<&Path>) -> anyhow::Result<Progress> {
+        let log = match log_path {
+            Some(path) => {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)
+                        .with_context(|| format!("Failed to create `{}`", parent.display()))?;
+                }
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .with_context(|| format!("Failed to open `{}`", path.display()))?;
+                Some(Mutex::new(file))
+            }
+            None => None,
+        };
+        Ok(Progress { log })
+    }
+
+    pub fn report(&self, message: &str) {
+        eprintln!("{}", message);
+        if let Some(log) = &self.log {
+            // Failing to log is not a reason to fail the install.
+            let _ignored = writeln!(log.lock().unwrap(), "{}", message);
+        }
+    }
+}
@@ -0,0 +1,431 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use buck2_grpc::to_tonic;
+use buck2_install_proto::device_metadata::Entry;
+use buck2_install_proto::installer_server::Installer;
+use buck2_install_proto::installer_server::InstallerServer;
+use buck2_install_proto::DeviceMetadata;
+use buck2_install_proto::ErrorDetail;
+use buck2_install_proto::FileReadyRequest;
+use buck2_install_proto::FileResponse;
+use buck2_install_proto::InstallInfoRequest;
+use buck2_install_proto::InstallResponse;
+use buck2_install_proto::ShutdownRequest;
+use buck2_install_proto::ShutdownResponse;
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Server;
+use tonic::Request;
+use tonic::Response;
+use tonic::Status;
+
+use crate::destination::Destination;
+use crate::manifest::Manifest;
+use crate::progress::Progress;
+
+/// Implements the `Installer` service on top of a [`Destination`], skipping the artifacts whose
+/// digest did not change since they were last installed there.
+pub struct InstallerService<D> {
+    destination: D,
+    progress: Progress,
+    /// Wait before installing each artifact, to simulate a slow device when used as a test double.
+    delay: Duration,
+    state: Mutex<InstallerState>,
+    /// The `manifest_version` last written to the destination. Held while writing, so that writes
+    /// queued behind another one find it already done.
+    written_manifest_version: tokio::sync::Mutex<u64>,
+    shutdown: Mutex<Option<oneshot::Sender<()>>>,
+}
+
+#[derive(Default)]
+struct InstallerState {
+    manifest: Manifest,
+    /// Incremented on every change to the manifest.
+    manifest_version: u64,
+    /// The artifacts recorded in the manifest that were found at the destination when their
+    /// install was announced.
+    present: HashSet<String>,
+    /// Number of artifacts announced by `Install` requests, for progress reports.
+    expected: usize,
+    /// Number of artifacts handled by `FileReady` requests, successfully or not.
+    handled: usize,
+    /// Device metadata is sent back with the first `FileReady` response only.
+    sent_device_metadata: bool,
+}
+
+#[derive(Debug, PartialEq)]
+enum InstallOutcome {
+    Installed,
+    UpToDate,
+}
+
+/// The digest as buck2 sends it, qualified by its algorithm so that changing the algorithm
+/// reinstalls everything. Symlinks have no algorithm and are sent as `re-symlink:<target>`.
+fn digest_key(request: &FileReadyRequest) -> String {
+    if request.digest_algorithm.is_empty() {
+        request.digest.clone()
+    } else {
+        format!("{}:{}", request.digest_algorithm, request.digest)
+    }
+}
+
+impl<D: Destination> InstallerService<D> {
+    pub async fn new(destination: D, progress: Progress) -> anyhow::Result<Self> {
+        let manifest = destination
+            .read_manifest()
+            .await?
+            .map_or_else(Manifest::default, |contents| Manifest::parse(&contents));
+        Ok(InstallerService {
+            destination,
+            progress,
+            delay: Duration::ZERO,
+            state: Mutex::new(InstallerState {
+                manifest,
+                ..InstallerState::default()
+            }),
+            written_manifest_version: tokio::sync::Mutex::new(0),
+            shutdown: Mutex::new(None),
+        })
+    }
+
+    pub fn with_delay(self, delay: Duration) -> Self {
+        InstallerService { delay, ..self }
+    }
+
+    /// Serves the installer protocol until buck2 sends `ShutdownServer`.
+    pub async fn serve(self, listener: TcpListener) -> anyhow::Result<()> {
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        *self.shutdown.lock().unwrap() = Some(shutdown_tx);
+        Server::builder()
+            .add_service(
+                InstallerServer::new(self)
+                    .max_encoding_message_size(usize::MAX)
+                    .max_decoding_message_size(usize::MAX),
+            )
+            .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async {
+                let _ignored = shutdown_rx.await;
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn install_file(&self, request: &FileReadyRequest) -> anyhow::Result<InstallOutcome> {
+        if !self.delay.is_zero() {
+            tokio::time::sleep(self.delay).await;
+        }
+
+        let digest = digest_key(request);
+        {
+            let mut state = self.state.lock().unwrap();
+            if state.present.contains(&request.name)
+                && state.manifest.is_installed(&request.name, &digest)
+            {
+                return Ok(InstallOutcome::UpToDate);
+            }
+            state.manifest.forget(&request.name);
+            state.manifest_version += 1;
+        }
+
+        self.destination
+            .install(Path::new(&request.path), &request.name)
+            .await?;
+        {
+            let mut state = self.state.lock().unwrap();
+            state.manifest.record(&request.name, &digest);
+            state.manifest_version += 1;
+            state.present.insert(request.name.clone());
+        }
+        // So that an interrupted install doesn't copy everything again next time.
+        self.persist_manifest().await;
+        Ok(InstallOutcome::Installed)
+    }
+
+    /// Looks up which of the recorded artifacts of an install are still at the destination,
+    /// all at once.
+    async fn check_present(&self, names: &[String]) -> anyhow::Result<()> {
+        let recorded: Vec<String> = {
+            let state = self.state.lock().unwrap();
+            names
+                .iter()
+                .filter(|name| state.manifest.is_recorded(name))
+                .cloned()
+                .collect()
+        };
+        if recorded.is_empty() {
+            return Ok(());
+        }
+        let present = self.destination.existing(&recorded).await?;
+        let mut state = self.state.lock().unwrap();
+        for name in recorded {
+            state.present.remove(&name);
+        }
+        state.present.extend(present);
+        Ok(())
+    }
+
+    /// Writes the manifest to the destination, unless a concurrent call already wrote this
+    /// version of it.
+    async fn write_manifest(&self) -> anyhow::Result<()> {
+        let mut written = self.written_manifest_version.lock().await;
+        let (version, manifest) = {
+            let state = self.state.lock().unwrap();
+            (state.manifest_version, state.manifest.to_json()?)
+        };
+        if *written != version {
+            self.destination.write_manifest(&manifest).await?;
+            *written = version;
+        }
+        Ok(())
+    }
+
+    async fn persist_manifest(&self) {
+        // Not being able to record what was installed only makes the next install slower.
+        if let Err(e) = self.write_manifest().await {
+            self.progress
+                .report(&format!("Failed to write the install manifest: {:#}", e));
+        }
+    }
+
+    fn report_file(&self, name: &str, outcome: &str) {
+        let (handled, expected) = {
+            let mut state = self.state.lock().unwrap();
+            state.handled += 1;
+            (state.handled, state.expected.max(state.handled))
+        };
+        self.progress
+            .report(&format!("[{}/{}] {}: {}", handled, expected, name, outcome));
+    }
+
+    fn take_device_metadata(&self) -> Vec<DeviceMetadata> {
+        let mut state = self.state.lock().unwrap();
+        if std::mem::replace(&mut state.sent_device_metadata, true) {
+            return Vec::new();
+        }
+        vec![DeviceMetadata {
+            entry: vec![Entry {
+                key: "destination".to_owned(),
+                value: self.destination.describe(),
+            }],
+        }]
+    }
+}
+
+#[async_trait::async_trait]
+impl<D: Destination> Installer for InstallerService<D> {
+    async fn install(
+        &self,
+        request: Request<InstallInfoRequest>,
+    ) -> Result<Response<InstallResponse>, Status> {
+        to_tonic(async move {
+            let request = request.into_inner();
+            self.state.lock().unwrap().expected += request.files.len();
+            let names: Vec<String> = request.files.keys().cloned().collect();
+            self.check_present(&names).await?;
+            self.progress.report(&format!(
+                "Installing {} artifacts of `{}` to `{}`",
+                request.files.len(),
+                request.install_id,
+                self.destination.describe()
+            ));
+            Ok(InstallResponse {
+                install_id: request.install_id,
+            })
+        })
+        .await
+    }
+
+    async fn file_ready(
+        &self,
+        request: Request<FileReadyRequest>,
+    ) -> Result<Response<FileResponse>, Status> {
+        to_tonic(async move {
+            let request = request.into_inner();
+            let start = Instant::now();
+            // Failures are returned in the response rather than as a gRPC error, so that buck2
+            // reports which artifact failed.
+            let error_detail = match self.install_file(&request).await {
+                Ok(InstallOutcome::Installed) => {
+                    self.report_file(
+                        &request.name,
+                        &format!("installed in {:.2}s", start.elapsed().as_secs_f64()),
+                    );
+                    None
+                }
+                Ok(InstallOutcome::UpToDate) => {
+                    self.report_file(&request.name, "up to date");
+                    None
+                }
+                Err(e) => {
+                    let message = format!("{:#}", e);
+                    self.report_file(&request.name, &format!("failed: {}", message));
+                    Some(ErrorDetail { message })
+                }
+            };
+            Ok(FileResponse {
+                install_id: request.install_id,
+                name: request.name,
+                path: request.path,
+                error_detail,
+                device_metadata: self.take_device_metadata(),
+            })
+        })
+        .await
+    }
+
+    async fn shutdown_server(
+        &self,
+        _request: Request<ShutdownRequest>,
+    ) -> Result<Response<ShutdownResponse>, Status> {
+        to_tonic(async move {
+            self.persist_manifest().await;
+            let (handled, expected) = {
+                let state = self.state.lock().unwrap();
+                (state.handled, state.expected)
+            };
+            self.progress.report(&format!(
+                "Handled {} of {} artifacts, shutting down",
+                handled, expected
+            ));
+            if let Some(shutdown) = self.shutdown.lock().unwrap().take() {
+                let _ignored = shutdown.send(());
+            }
+            Ok(ShutdownResponse {})
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::destination::DirectoryDestination;
+
+    fn file_ready(name: &str, path: &Path, digest: &str) -> FileReadyRequest {
+        FileReadyRequest {
+            install_id: "root//:app".to_owned(),
+            name: name.to_owned(),
+            digest: digest.to_owned(),
+            path: path.display().to_string(),
+            digest_algorithm: "SHA1".to_owned(),
+            size: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_install_is_incremental() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let src = tmp.path().join("src");
+        fs::write(&src, "v1")?;
+        let dst = tmp.path().join("dst");
+        let service =
+            InstallerService::new(DirectoryDestination::new(dst.clone()), Progress::default())
+                .await?;
+
+        let request = file_ready("app", &src, "aaa");
+        assert_eq!(
+            InstallOutcome::Installed,
+            service.install_file(&request).await?
+        );
+        assert_eq!(
+            InstallOutcome::UpToDate,
+            service.install_file(&request).await?
+        );
+
+        fs::write(&src, "v2")?;
+        let request = file_ready("app", &src, "bbb");
+        assert_eq!(
+            InstallOutcome::Installed,
+            service.install_file(&request).await?
+        );
+        assert_eq!("v2", fs::read_to_string(dst.join("app"))?);
+
+        // Deleting the installed artifact is noticed by the next install even though the digest
+        // did not change.
+        fs::remove_file(dst.join("app"))?;
+        service.check_present(&["app".to_owned()]).await?;
+        assert_eq!(
+            InstallOutcome::Installed,
+            service.install_file(&request).await?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_manifest_persists_across_runs() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let src = tmp.path().join("src");
+        fs::write(&src, "v1")?;
+        let dst = tmp.path().join("dst");
+        let request = file_ready("app", &src, "aaa");
+
+        let service =
+            InstallerService::new(DirectoryDestination::new(dst.clone()), Progress::default())
+                .await?;
+        assert_eq!(
+            InstallOutcome::Installed,
+            service.install_file(&request).await?
+        );
+
+        // The manifest is written as soon as the artifact is installed, so it is kept even if
+        // buck2 never sends `ShutdownServer`.
+        let service =
+            InstallerService::new(DirectoryDestination::new(dst.clone()), Progress::default())
+                .await?;
+        service.check_present(&["app".to_owned()]).await?;
+        assert_eq!(
+            InstallOutcome::UpToDate,
+            service.install_file(&request).await?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_failure_is_reported_in_response() -> anyhow::Result<()> {
+        let tmp = tempfile::tempdir()?;
+        let service = InstallerService::new(
+            DirectoryDestination::new(tmp.path().join("dst")),
+            Progress::default(),
+        )
+        .await?;
+
+        let request = file_ready("app", &tmp.path().join("missing"), "aaa");
+        let response = service
+            .file_ready(Request::new(request))
+            .await?
+            .into_inner();
+        assert!(response.error_detail.is_some());
+        // Device metadata is sent once, even if the first file failed.
+        assert_eq!(1, response.device_metadata.len());
+
+        let request = file_ready("../app", &tmp.path().join("missing"), "aaa");
+        let response = service
+            .file_ready(Request::new(request))
+            .await?
+            .into_inner();
+        assert!(
+            response
+                .error_detail
+                .unwrap()
+                .message
+                .contains("Invalid artifact name")
+        );
+        assert!(response.device_metadata.is_empty());
+        Ok(())
+    }
+}
@@ -15,6 +15,7 @@ use async_trait::async_trait;
 use buck2_common::dice::cells::HasCellResolver;
 use buck2_common::dice::cycles::CycleGuard;
 use buck2_common::dice::file_ops::DiceFileComputations;
+use buck2_common::dice::invalidation_tracking::get_invalidation_info;
 use buck2_common::legacy_configs::dice::HasLegacyConfigs;
 use buck2_common::legacy_configs::dice::OpaqueLegacyBuckConfigOnDice;
 use buck2_common::package_boundary::HasPackageBoundaryExceptions;
@@ -225,6 +226,8 @@ impl<'c, 'd: 'c> DiceCalculationDelegate<'c, 'd> {
         let buckconfig = self.get_legacy_buck_config_for_starlark().await?;
         let root_buckconfig = self.ctx.get_legacy_root_config_on_dice().await?;
 
+        let invalidation_info = get_invalidation_info(self.ctx);
+
         let configs = &self.configs;
         let ctx = &mut *self.ctx;
 
@@ -569,6 +572,7 @@ impl<'c, 'd: 'c> DiceCalculationDelegate<'c, 'd> {
                             starlark_peak_allocated_bytes,
                             cpu_instruction_count,
                             error,
+                            invalidation_info,
                         },
                     )
                 })
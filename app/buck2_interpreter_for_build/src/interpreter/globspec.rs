@@ -74,7 +74,7 @@ impl GlobPattern {
 
 #[derive(Derivative)]
 #[derivative(Debug)]
-pub(crate) struct GlobSpec {
+pub struct GlobSpec {
     common_prefix: String,
     exact_matches: HashSet<String>,
     patterns: Vec<GlobPattern>,
@@ -114,7 +114,7 @@ fn longest_common_glob_prefix<P: AsRef<str>>(patterns: &[P]) -> &str {
 
 impl GlobSpec {
     const BINARY_SEARCH_CUTOFF: usize = 100;
-    pub(crate) fn new<P: AsRef<str>, Q: AsRef<str>>(
+    pub fn new<P: AsRef<str>, Q: AsRef<str>>(
         patterns: &[P],
         excludes: &[Q],
     ) -> anyhow::Result<Self> {
@@ -170,7 +170,7 @@ impl GlobSpec {
                 .any(|p| p.0.matches_with(path, options))
     }
 
-    pub(crate) fn resolve_glob<'a>(
+    pub fn resolve_glob<'a>(
         &'a self,
         spec: &'a PackageFileListing,
     ) -> Box<dyn Iterator<Item = &'a PackageRelativePath> + 'a> {
@@ -42,6 +42,16 @@ pub trait CfgConstructorImpl: Send + Sync + Debug + Allocative {
     fn key<'a>(&'a self) -> &'a MetadataKeyRef;
 }
 
+/// Modifiers that apply to a target when it is configured from the command line, by where they
+/// were set.
+#[derive(Debug, Clone, Default)]
+pub struct CfgModifiers {
+    /// Modifiers set in `PACKAGE` files.
+    pub package: Option<MetadataValue>,
+    /// Modifiers set on the target itself.
+    pub target: Option<MetadataValue>,
+}
+
 pub static CFG_CONSTRUCTOR_CALCULATION_IMPL: LateBinding<
     &'static dyn CfgConstructorCalculationImpl,
 > = LateBinding::new("CFG_CONSTRUCTOR_CALCULATION_IMPL");
@@ -59,4 +69,13 @@ pub trait CfgConstructorCalculationImpl: Send + Sync + 'static {
         cli_modifiers: &Arc<Vec<String>>,
         rule_name: &RuleType,
     ) -> anyhow::Result<ConfigurationData>;
+
+    /// Returns the modifiers the cfg constructor would be invoked with for this target,
+    /// or `None` if no cfg constructor is configured.
+    async fn cfg_modifiers(
+        &self,
+        ctx: &mut DiceComputations<'_>,
+        target: TargetNodeRef<'_>,
+        super_package: &SuperPackage,
+    ) -> anyhow::Result<Option<CfgModifiers>>;
 }
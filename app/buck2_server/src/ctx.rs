@@ -11,9 +11,11 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 use std::io::BufWriter;
 use std::marker::PhantomData;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 
 use allocative::Allocative;
+use anyhow::Context as _;
 use async_trait::async_trait;
 use buck2_build_api::actions::execute::dice_data::set_fallback_executor_config;
 use buck2_build_api::actions::execute::dice_data::SetCommandExecutor;
@@ -75,9 +77,11 @@ use buck2_execute::execute::blocking::SetBlockingExecutor;
 use buck2_execute::knobs::ExecutorGlobalKnobs;
 use buck2_execute::materialize::materializer::Materializer;
 use buck2_execute::materialize::materializer::SetMaterializer;
+use buck2_execute::materialize::offline::OfflineMaterializer;
 use buck2_execute::re::client::RemoteExecutionClient;
 use buck2_execute::re::manager::ReConnectionHandle;
 use buck2_execute::re::manager::ReConnectionObserver;
+use buck2_execute_impl::executors::local_input_tracer::paranoid_inputs_strace;
 use buck2_execute_impl::executors::worker::WorkerPool;
 use buck2_execute_impl::low_pass_filter::LowPassFilter;
 use buck2_file_watcher::mergebase::SetMergebase;
@@ -335,25 +339,34 @@ impl<'a> ServerCommandContext<'a> {
         &'s self,
         build_signals: BuildSignalsInstaller,
     ) -> anyhow::Result<DiceCommandUpdater<'s, 'a>> {
+        let offline = self
+            .build_options
+            .as_ref()
+            .map_or(false, |opts| opts.offline);
+
+        // Offline builds can only execute locally, regardless of what else was requested.
         let execution_strategy = self
             .build_options
             .as_ref()
+            .filter(|_| !offline)
             .map(|opts| opts.execution_strategy)
             .map_or(ExecutionStrategy::LocalOnly, |strategy| {
                 ExecutionStrategy::from_i32(strategy).expect("execution strategy should be valid")
             });
 
-        let skip_cache_read = self
-            .build_options
-            .as_ref()
-            .map(|opts| opts.skip_cache_read)
-            .unwrap_or_default();
+        let skip_cache_read = offline
+            || self
+                .build_options
+                .as_ref()
+                .map(|opts| opts.skip_cache_read)
+                .unwrap_or_default();
 
-        let skip_cache_write = self
-            .build_options
-            .as_ref()
-            .map(|opts| opts.skip_cache_write)
-            .unwrap_or_default();
+        let skip_cache_write = offline
+            || self
+                .build_options
+                .as_ref()
+                .map(|opts| opts.skip_cache_write)
+                .unwrap_or_default();
 
         let mut run_action_knobs = RunActionKnobs {
             hash_all_commands: self.base_context.daemon.hash_all_commands,
@@ -367,6 +380,7 @@ impl<'a> ServerCommandContext<'a> {
 
         if let Some(build_options) = self.build_options.as_ref() {
             run_action_knobs.eager_dep_files = build_options.eager_dep_files;
+            run_action_knobs.offline = build_options.offline;
         }
 
         let concurrency = self
@@ -377,12 +391,17 @@ impl<'a> ServerCommandContext<'a> {
             .map(|v| v.map_err(buck2_error::Error::from));
 
         let executor_config = get_default_executor_config(self.host_platform_override);
-        let re_connection = Arc::new(self.get_re_connection());
+        let mut re_connection = self.get_re_connection();
+        if offline {
+            re_connection.set_offline();
+        }
+        let re_connection = Arc::new(re_connection);
 
-        let upload_all_actions = self
-            .build_options
-            .as_ref()
-            .map_or(false, |opts| opts.upload_all_actions);
+        let upload_all_actions = !offline
+            && self
+                .build_options
+                .as_ref()
+                .map_or(false, |opts| opts.upload_all_actions);
 
         let (interpreter_platform, interpreter_architecture, interpreter_xcode_version) =
             host_info::get_host_info(
@@ -413,6 +432,7 @@ impl<'a> ServerCommandContext<'a> {
             interpreter_platform,
             interpreter_architecture,
             interpreter_xcode_version,
+            offline,
         })
     }
 
@@ -514,6 +534,7 @@ struct DiceCommandUpdater<'s, 'a: 's> {
     interpreter_platform: InterpreterHostPlatform,
     interpreter_architecture: InterpreterHostArchitecture,
     interpreter_xcode_version: Option<XcodeVersionInfo>,
+    offline: bool,
 }
 
 fn create_cycle_detector() -> Arc<dyn UserCycleDetector> {
@@ -567,7 +588,7 @@ impl<'s, 'a> DiceUpdater for DiceCommandUpdater<'s, 'a> {
             self.cmd_ctx.unstable_typecheck,
         )?;
 
-        let (ctx, mergebase) = self
+        let (mut ctx, mergebase) = self
             .cmd_ctx
             .base_context
             .daemon
@@ -575,6 +596,16 @@ impl<'s, 'a> DiceUpdater for DiceCommandUpdater<'s, 'a> {
             .sync(ctx)
             .await?;
 
+        if self
+            .cmd_ctx
+            .base_context
+            .daemon
+            .drop_dice_state
+            .swap(false, Ordering::Relaxed)
+        {
+            ctx = ctx.unstable_take();
+        }
+
         let mut user_data = self.make_user_computation_data(&cells_and_configs.root_config)?;
         ConfigDiffTracker::promote_into(
             existing_state,
@@ -629,6 +660,31 @@ impl<'a, 's> DiceCommandUpdater<'a, 's> {
             .unwrap_or_else(RolloutPercentage::always)
             .roll();
 
+        let sandbox_local_actions = root_config
+            .parse::<bool>(BuckconfigKeyRef {
+                section: "build",
+                property: "sandbox_local_actions",
+            })?
+            .unwrap_or(false);
+
+        let sandbox_allow_network = root_config
+            .parse::<bool>(BuckconfigKeyRef {
+                section: "build",
+                property: "sandbox_allow_network",
+            })?
+            .unwrap_or(false);
+
+        let paranoid_inputs = root_config
+            .parse::<bool>(BuckconfigKeyRef {
+                section: "build",
+                property: "paranoid_inputs",
+            })?
+            .unwrap_or(false);
+        if paranoid_inputs && cfg!(target_os = "linux") {
+            // Fail the command once, rather than every local action.
+            paranoid_inputs_strace()?;
+        }
+
         let log_configured_graph_size = root_config
             .parse::<bool>(BuckconfigKeyRef {
                 section: "buck2",
@@ -653,6 +709,9 @@ impl<'a, 's> DiceCommandUpdater<'a, 's> {
         let executor_global_knobs = ExecutorGlobalKnobs {
             enable_miniperf,
             log_action_keys,
+            sandbox_local_actions,
+            sandbox_allow_network,
+            paranoid_inputs,
         };
 
         let host_sharing_broker =
@@ -688,6 +747,32 @@ impl<'a, 's> DiceCommandUpdater<'a, 's> {
                 property: "use_network_action_output_cache",
             })?
             .unwrap_or(false);
+        run_action_knobs.record_input_fingerprints = root_config
+            .parse::<bool>(BuckconfigKeyRef {
+                section: "buck2",
+                property: "record_action_input_fingerprints",
+            })?
+            .unwrap_or(false);
+        // Relative to the project root.
+        run_action_knobs.download_mirror_dir = root_config
+            .get(BuckconfigKeyRef {
+                section: "download",
+                property: "mirror_dir",
+            })
+            .map(|dir| {
+                let dir = self
+                    .cmd_ctx
+                    .base_context
+                    .daemon
+                    .io
+                    .project_root()
+                    .root()
+                    .as_abs_path()
+                    .join(dir);
+                anyhow::Ok(Arc::new(AbsNormPathBuf::new(dir.into_path_buf())?))
+            })
+            .transpose()
+            .context("Invalid `download.mirror_dir`")?;
 
         let mut data = UserComputationData {
             data,
@@ -737,10 +822,22 @@ impl<'a, 's> DiceCommandUpdater<'a, 's> {
             self.cmd_ctx.base_context.daemon.paranoid.dupe(),
             self.materialize_failed_inputs,
             override_use_case,
+            self.cmd_ctx.base_context.daemon.local_resources.dupe(),
+            self.cmd_ctx.base_context.daemon.hybrid_router.dupe(),
         )));
         data.set_blocking_executor(self.cmd_ctx.base_context.daemon.blocking_executor.dupe());
-        data.set_http_client(self.cmd_ctx.base_context.daemon.http_client.dupe());
-        data.set_materializer(self.cmd_ctx.base_context.daemon.materializer.dupe());
+        data.set_http_client(if self.offline {
+            self.cmd_ctx.base_context.daemon.http_client.to_offline()
+        } else {
+            self.cmd_ctx.base_context.daemon.http_client.dupe()
+        });
+        data.set_materializer(if self.offline {
+            Arc::new(OfflineMaterializer::new(
+                self.cmd_ctx.base_context.daemon.materializer.dupe(),
+            ))
+        } else {
+            self.cmd_ctx.base_context.daemon.materializer.dupe()
+        });
         data.init_materialization_queue_tracker();
         data.set_build_signals(self.build_signals.build_signals.dupe());
         data.set_run_action_knobs(run_action_knobs);
@@ -818,6 +915,10 @@ impl<'a> ServerCommandContextTrait for ServerCommandContext<'a> {
         self.base_context.daemon.materializer.dupe()
     }
 
+    fn file_changes(&self) -> Option<tokio::sync::watch::Receiver<()>> {
+        self.base_context.daemon.file_watcher.changes()
+    }
+
     /// Provides a DiceTransaction, initialized on first use and shared after initialization.
     async fn dice_accessor<'s>(
         &'s self,
@@ -49,7 +49,9 @@ use buck2_execute_impl::executors::action_cache_upload_permission_checker::Actio
 use buck2_execute_impl::executors::caching::CacheUploader;
 use buck2_execute_impl::executors::hybrid::FallbackTracker;
 use buck2_execute_impl::executors::hybrid::HybridExecutor;
+use buck2_execute_impl::executors::hybrid_routing::HybridRouter;
 use buck2_execute_impl::executors::local::LocalExecutor;
+use buck2_execute_impl::executors::local_resources::LocalResourceAdmission;
 use buck2_execute_impl::executors::re::ReExecutor;
 use buck2_execute_impl::executors::stacked::StackedExecutor;
 use buck2_execute_impl::executors::to_re_platform::RePlatformFieldsToRePlatform;
@@ -96,6 +98,8 @@ pub struct CommandExecutorFactory {
     cache_upload_permission_checker: Arc<ActionCacheUploadPermissionChecker>,
     fallback_tracker: Arc<FallbackTracker>,
     re_use_case_override: Option<RemoteExecutorUseCase>,
+    local_resources: Arc<LocalResourceAdmission>,
+    hybrid_router: Option<Arc<HybridRouter>>,
 }
 
 impl CommandExecutorFactory {
@@ -116,6 +120,8 @@ impl CommandExecutorFactory {
         paranoid: Option<ParanoidDownloader>,
         materialize_failed_inputs: bool,
         re_use_case_override: Option<RemoteExecutorUseCase>,
+        local_resources: Arc<LocalResourceAdmission>,
+        hybrid_router: Option<Arc<HybridRouter>>,
     ) -> Self {
         let cache_upload_permission_checker = Arc::new(ActionCacheUploadPermissionChecker::new(
             re_connection
@@ -141,6 +147,8 @@ impl CommandExecutorFactory {
             cache_upload_permission_checker,
             fallback_tracker: Arc::new(FallbackTracker::new()),
             re_use_case_override,
+            local_resources,
+            hybrid_router,
         }
     }
 
@@ -182,6 +190,7 @@ impl HasCommandExecutor for CommandExecutorFactory {
                 self.forkserver.dupe(),
                 self.executor_global_knobs.dupe(),
                 worker_pool,
+                Some(self.local_resources.dupe()),
             )
         };
 
@@ -354,6 +363,8 @@ impl HasCommandExecutor for CommandExecutorFactory {
                                     re_max_input_files_bytes,
                                     low_pass_filter,
                                     fallback_tracker,
+                                    // Paranoid mode always races.
+                                    router: None,
                                 }))
                             } else {
                                 Some(Arc::new(HybridExecutor {
@@ -364,6 +375,7 @@ impl HasCommandExecutor for CommandExecutorFactory {
                                     re_max_input_files_bytes,
                                     low_pass_filter,
                                     fallback_tracker,
+                                    router: self.hybrid_router.dupe(),
                                 }))
                             }
                         }
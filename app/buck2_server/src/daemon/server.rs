@@ -396,7 +396,11 @@ impl BuckdServer {
 
         let daemon_state = self.0.daemon_state.dupe();
         let trace_id = client_ctx.trace_id.parse()?;
-        let (events, dispatch) = daemon_state.prepare_events(trace_id).await?;
+        let offline = req
+            .get_ref()
+            .build_options()
+            .map_or(false, |opts| opts.offline);
+        let (events, dispatch) = daemon_state.prepare_events(trace_id, offline).await?;
         let ActiveCommand {
             guard,
             daemon_shutdown_channel,
@@ -1270,7 +1274,8 @@ impl DaemonApi for BuckdServer {
         let res: anyhow::Result<_> = try {
             let client_ctx = req.get_ref().client_context()?;
             let trace_id = client_ctx.trace_id.parse()?;
-            let (event_source, dispatcher) = self.0.daemon_state.prepare_events(trace_id).await?;
+            let (event_source, dispatcher) =
+                self.0.daemon_state.prepare_events(trace_id, false).await?;
             let active_command = ActiveCommand::new(&dispatcher, client_ctx.sanitized_argv.clone());
             (event_source, dispatcher, active_command)
         };
@@ -10,6 +10,7 @@
 use std::collections::HashMap;
 use std::path::Path;
 use std::str::FromStr;
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use std::time::Duration;
 use std::time::Instant;
@@ -27,6 +28,7 @@ use buck2_common::init::Timeout;
 use buck2_common::invocation_paths::InvocationPaths;
 use buck2_common::io::IoProvider;
 use buck2_common::legacy_configs::cells::BuckConfigBasedCells;
+use buck2_common::legacy_configs::configs::LegacyBuckConfig;
 use buck2_common::legacy_configs::key::BuckconfigKeyRef;
 use buck2_core::buck2_env_anyhow;
 use buck2_core::cells::name::CellName;
@@ -39,9 +41,12 @@ use buck2_core::is_open_source;
 use buck2_core::rollout_percentage::RolloutPercentage;
 use buck2_core::tag_result;
 use buck2_events::dispatch::EventDispatcher;
+use buck2_events::sink::otlp::OtlpConfig;
+use buck2_events::sink::otlp::OtlpEventSink;
 use buck2_events::sink::remote;
 use buck2_events::sink::tee::TeeSink;
 use buck2_events::source::ChannelEventSource;
+use buck2_events::EventSink;
 use buck2_events::EventSinkWithStats;
 use buck2_execute::digest_config::DigestConfig;
 use buck2_execute::execute::blocking::BlockingExecutor;
@@ -49,7 +54,10 @@ use buck2_execute::execute::blocking::BuckBlockingExecutor;
 use buck2_execute::materialize::materializer::MaterializationMethod;
 use buck2_execute::materialize::materializer::Materializer;
 use buck2_execute::re::manager::ReConnectionManager;
+use buck2_execute_impl::executors::hybrid_routing::HybridRouter;
+use buck2_execute_impl::executors::local_resources::LocalResourceAdmission;
 use buck2_execute_impl::materializers::deferred::clean_stale::CleanStaleConfig;
+use buck2_execute_impl::materializers::deferred::local_cas::LocalCasConfig;
 use buck2_execute_impl::materializers::deferred::AccessTimesUpdates;
 use buck2_execute_impl::materializers::deferred::DeferredMaterializer;
 use buck2_execute_impl::materializers::deferred::DeferredMaterializerConfigs;
@@ -63,9 +71,11 @@ use buck2_file_watcher::file_watcher::FileWatcher;
 use buck2_forkserver::client::ForkserverClient;
 use buck2_http::HttpClient;
 use buck2_http::HttpClientBuilder;
+use buck2_http::UrlRewrites;
 use buck2_re_configuration::RemoteExecutionStaticMetadata;
 use buck2_re_configuration::RemoteExecutionStaticMetadataImpl;
 use buck2_server_ctx::concurrency::ConcurrencyHandler;
+use buck2_util::system_stats::system_memory_stats;
 use buck2_wrapper_common::invocation_id::TraceId;
 use dupe::Dupe;
 use fbinit::FacebookInit;
@@ -136,9 +146,28 @@ pub struct DaemonStateData {
 
     pub(crate) forkserver: Option<ForkserverClient>,
 
+    /// The machine's memory and CPUs that local commands are admitted against, shared by all
+    /// commands.
+    #[allocative(skip)]
+    pub local_resources: Arc<LocalResourceAdmission>,
+
+    /// What the hybrid executor learned about where actions run faster, if enabled.
+    #[allocative(skip)]
+    pub hybrid_router: Option<Arc<HybridRouter>>,
+
     #[allocative(skip)]
     pub scribe_sink: Option<Arc<dyn EventSinkWithStats>>,
 
+    /// Exports spans and metrics to an OpenTelemetry collector, if `buck2.otlp_endpoint` is set.
+    #[allocative(skip)]
+    pub otlp_sink: Option<Arc<OtlpEventSink>>,
+
+    /// Set when something outside of DICE invalidated results that DICE holds (e.g. `buck2 debug
+    /// materializer-fsck --repair` found corrupted outputs of actions in the graph). The next
+    /// command then starts from an empty graph, as if the daemon had been restarted.
+    #[allocative(skip)]
+    pub(crate) drop_dice_state: AtomicBool,
+
     /// Whether or not to hash all commands
     pub hash_all_commands: bool,
 
@@ -417,6 +446,7 @@ impl DaemonState {
                     .unwrap_or(false);
 
                 let clean_stale_config = CleanStaleConfig::from_buck_config(root_config)?;
+                let local_cas = LocalCasConfig::from_buck_config(root_config)?;
 
                 DeferredMaterializerConfigs {
                     materialize_final_artifacts: matches!(
@@ -432,6 +462,7 @@ impl DaemonState {
                     update_access_times,
                     verbose_materializer_log,
                     clean_stale_config,
+                    local_cas,
                 }
             };
 
@@ -465,6 +496,21 @@ impl DaemonState {
                 .await
                 .context("Error creating HTTP client")?
                 .build();
+            let otlp_sink = Self::init_otlp_sink(root_config, http_client.dupe())?;
+            let local_resources = Arc::new(
+                Self::init_local_resources(
+                    root_config,
+                    &paths,
+                    blocking_executor.dupe() as Arc<dyn BlockingExecutor>,
+                )
+                .await?,
+            );
+            let hybrid_router = Self::init_hybrid_router(
+                root_config,
+                &paths,
+                blocking_executor.dupe() as Arc<dyn BlockingExecutor>,
+            )
+            .await?;
 
             let materializer_state_identity =
                 materializer_db.as_ref().map(|d| d.identity().clone());
@@ -611,7 +657,11 @@ impl DaemonState {
                 blocking_executor,
                 materializer,
                 forkserver,
+                local_resources,
+                hybrid_router,
                 scribe_sink,
+                otlp_sink,
+                drop_dice_state: AtomicBool::new(false),
                 hash_all_commands,
                 new_style_scratch_path,
                 use_network_action_output_cache,
@@ -686,16 +736,123 @@ impl DaemonState {
         .map(|maybe_scribe| maybe_scribe.map(|scribe| Arc::new(scribe) as _))
     }
 
+    async fn init_local_resources(
+        root_config: &LegacyBuckConfig,
+        paths: &InvocationPaths,
+        io_executor: Arc<dyn BlockingExecutor>,
+    ) -> anyhow::Result<LocalResourceAdmission> {
+        let memory_bytes = match root_config.parse::<u64>(BuckconfigKeyRef {
+            section: "build",
+            property: "local_memory_budget_mb",
+        })? {
+            Some(mb) => mb.saturating_mul(1024 * 1024),
+            None => system_memory_stats(),
+        };
+        let cpus = match root_config.parse::<f64>(BuckconfigKeyRef {
+            section: "build",
+            property: "local_cpu_budget",
+        })? {
+            Some(cpus) => cpus,
+            None => std::thread::available_parallelism().map_or(1, |n| n.get()) as f64,
+        };
+        let learn_estimates = root_config
+            .parse::<bool>(BuckconfigKeyRef {
+                section: "build",
+                property: "learn_local_resource_estimates",
+            })?
+            .unwrap_or(false);
+        let cpu_millicores = (cpus * 1000.0) as u64;
+        if !learn_estimates {
+            return Ok(LocalResourceAdmission::new(
+                memory_bytes,
+                cpu_millicores,
+                false,
+            ));
+        }
+        let state_dir = paths.local_resources_state_path();
+        io_executor
+            .execute_io_inline(|| {
+                LocalResourceAdmission::load(memory_bytes, cpu_millicores, &state_dir)
+            })
+            .await
+            .context("Error loading local resource estimates")
+    }
+
+    async fn init_hybrid_router(
+        root_config: &LegacyBuckConfig,
+        paths: &InvocationPaths,
+        io_executor: Arc<dyn BlockingExecutor>,
+    ) -> anyhow::Result<Option<Arc<HybridRouter>>> {
+        let enabled = root_config
+            .parse::<bool>(BuckconfigKeyRef {
+                section: "build",
+                property: "learned_hybrid_routing",
+            })?
+            .unwrap_or(false);
+        if !enabled {
+            return Ok(None);
+        }
+        let state_dir = paths.hybrid_routing_state_path();
+        let router = io_executor
+            .execute_io_inline(|| HybridRouter::load(&state_dir))
+            .await
+            .context("Error loading hybrid routing stats")?;
+        Ok(Some(Arc::new(router)))
+    }
+
+    fn init_otlp_sink(
+        root_config: &LegacyBuckConfig,
+        http_client: HttpClient,
+    ) -> anyhow::Result<Option<Arc<OtlpEventSink>>> {
+        let Some(endpoint) = root_config.get(BuckconfigKeyRef {
+            section: "buck2",
+            property: "otlp_endpoint",
+        }) else {
+            return Ok(None);
+        };
+        let config = OtlpConfig {
+            endpoint: endpoint.to_owned(),
+            batch_size: root_config
+                .parse(BuckconfigKeyRef {
+                    section: "buck2",
+                    property: "otlp_batch_size",
+                })?
+                .unwrap_or(512),
+            queue_size: root_config
+                .parse(BuckconfigKeyRef {
+                    section: "buck2",
+                    property: "otlp_queue_size",
+                })?
+                .unwrap_or(10000),
+            flush_interval: Duration::from_millis(
+                root_config
+                    .parse(BuckconfigKeyRef {
+                        section: "buck2",
+                        property: "otlp_flush_interval_ms",
+                    })?
+                    .unwrap_or(5000),
+            ),
+        };
+        Ok(Some(Arc::new(OtlpEventSink::new(config, http_client))))
+    }
+
     /// Prepares an event stream for a request by bootstrapping an event source and EventDispatcher pair. The given
     /// EventDispatcher will log to the returned EventSource and (optionally) to Scribe if enabled via buckconfig.
+    /// Events of `offline` commands are not exported to the OpenTelemetry collector, since that would
+    /// access the network.
     pub async fn prepare_events(
         &self,
         trace_id: TraceId,
+        offline: bool,
     ) -> buck2_error::Result<(ChannelEventSource, EventDispatcher)> {
         // facebook only: logging events to Scribe.
         facebook_only();
         let (events, sink) = buck2_events::create_source_sink_pair();
         let data = self.data()?;
+        let sink: Arc<dyn EventSink> = match data.otlp_sink.dupe().filter(|_| !offline) {
+            Some(otlp_sink) => Arc::new(TeeSink::new(otlp_sink.to_event_sync(), sink)),
+            None => Arc::new(sink),
+        };
         let dispatcher = if let Some(scribe_sink) = data.scribe_sink.dupe() {
             EventDispatcher::new(trace_id, TeeSink::new(scribe_sink.to_event_sync(), sink))
         } else {
@@ -899,6 +1056,12 @@ async fn http_client_from_startup_config(
         }
         _ => {}
     }
+    if let Some(url_rewrites) = &config.http.url_rewrites {
+        builder.with_url_rewrites(
+            UrlRewrites::parse(url_rewrites, config.http.url_rewrite_fallback())
+                .context("Error parsing `http.url_rewrites`")?,
+        );
+    }
 
     Ok(builder)
 }
@@ -10,8 +10,12 @@
 use anyhow::Context;
 use buck2_cli_proto::new_generic::MaterializeRequest;
 use buck2_cli_proto::new_generic::MaterializeResponse;
+use buck2_cli_proto::new_generic::MaterializerFsckIssue;
+use buck2_cli_proto::new_generic::MaterializerFsckRequest;
+use buck2_cli_proto::new_generic::MaterializerFsckResponse;
 use buck2_core::fs::project_rel_path::ProjectRelativePath;
 use buck2_events::dispatch::span_async;
+use buck2_execute::materialize::materializer::MaterializerVerifyIssueKind;
 use buck2_server_ctx::commands::command_end;
 use buck2_server_ctx::ctx::ServerCommandContextTrait;
 
@@ -52,3 +56,85 @@ async fn materialize(
         .ensure_materialized(project_paths)
         .await
 }
+
+pub(crate) async fn materializer_fsck_command(
+    context: &ServerCommandContext<'_>,
+    req: MaterializerFsckRequest,
+) -> anyhow::Result<MaterializerFsckResponse> {
+    let start_event = buck2_data::CommandStart {
+        metadata: context.request_metadata().await?,
+        data: Some(buck2_data::MaterializerFsckCommandStart { repair: req.repair }.into()),
+    };
+    span_async(start_event, async move {
+        let result = materializer_fsck(&context.base_context, req.repair)
+            .await
+            .context("Failed to verify materializer state")
+            .map_err(Into::into);
+        let end = match &result {
+            Ok(resp) => {
+                let count = |kind: MaterializerVerifyIssueKind| {
+                    let kind = kind.to_string();
+                    resp.issues.iter().filter(|i| i.kind == kind).count() as u64
+                };
+                buck2_data::MaterializerFsckCommandEnd {
+                    checked: resp.checked,
+                    missing: count(MaterializerVerifyIssueKind::Missing),
+                    modified: count(MaterializerVerifyIssueKind::Modified),
+                    untracked: count(MaterializerVerifyIssueKind::Untracked),
+                    repaired: resp.repaired,
+                }
+            }
+            Err(_) => buck2_data::MaterializerFsckCommandEnd::default(),
+        };
+        let end_event = command_end(&result, end);
+        (result.map_err(Into::into), end_event)
+    })
+    .await
+}
+
+async fn materializer_fsck(
+    server_ctx: &BaseServerCommandContext,
+    repair: bool,
+) -> anyhow::Result<MaterializerFsckResponse> {
+    let report = server_ctx
+        .daemon
+        .materializer
+        .as_deferred_materializer_extension()
+        .context("Deferred materializer is not in use")?
+        .verify(repair)
+        .await?;
+
+    let repaired_active = if repair {
+        report
+            .issues
+            .iter()
+            .filter(|i| i.active && i.kind != MaterializerVerifyIssueKind::Untracked)
+            .count() as u64
+    } else {
+        0
+    };
+    if repaired_active > 0 {
+        // DICE still holds the results of the actions that produced those artifacts, and we can't
+        // tell which actions those are from their paths. Drop the whole graph so that the next
+        // command rebuilds them, rather than assuming they are still materialized.
+        server_ctx
+            .daemon
+            .drop_dice_state
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    Ok(MaterializerFsckResponse {
+        checked: report.checked,
+        issues: report
+            .issues
+            .into_iter()
+            .map(|i| MaterializerFsckIssue {
+                path: i.path.to_string(),
+                kind: i.kind.to_string(),
+                detail: i.detail,
+            })
+            .collect(),
+        repaired: report.repaired,
+        repaired_active,
+    })
+}
@@ -17,6 +17,7 @@ use buck2_server_ctx::partial_result_dispatcher::PartialResultDispatcher;
 
 use crate::ctx::ServerCommandContext;
 use crate::materialize::materialize_command;
+use crate::materialize::materializer_fsck_command;
 
 pub(crate) async fn new_generic_command(
     context: &ServerCommandContext<'_>,
@@ -30,6 +31,9 @@ pub(crate) async fn new_generic_command(
         NewGenericRequest::Materialize(m) => {
             NewGenericResponse::Materialize(materialize_command(context, m).await?)
         }
+        NewGenericRequest::MaterializerFsck(m) => {
+            NewGenericResponse::MaterializerFsck(materializer_fsck_command(context, m).await?)
+        }
         NewGenericRequest::Complete(e) => NewGenericResponse::Complete(
             OTHER_SERVER_COMMANDS
                 .get()?
@@ -51,6 +55,12 @@ pub(crate) async fn new_generic_command(
                 .expand_external_cell(context, partial_result_dispatcher, e)
                 .await?,
         ),
+        NewGenericRequest::ExternalCells(e) => NewGenericResponse::ExternalCells(
+            OTHER_SERVER_COMMANDS
+                .get()?
+                .external_cells(context, partial_result_dispatcher, e)
+                .await?,
+        ),
         NewGenericRequest::Docs(d) => NewGenericResponse::Docs(
             DOCS_SERVER_COMMAND
                 .get()?
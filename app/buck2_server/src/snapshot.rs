@@ -61,6 +61,13 @@ impl SnapshotCollector {
     fn add_daemon_metrics(&self, snapshot: &mut buck2_data::Snapshot) {
         snapshot.blocking_executor_io_queue_size =
             self.daemon.blocking_executor.queue_size() as u64;
+
+        let local_resources = self.daemon.local_resources.stats();
+        snapshot.local_memory_budget_bytes = local_resources.memory_total_bytes;
+        snapshot.local_memory_admitted_bytes = local_resources.memory_admitted_bytes;
+        snapshot.local_cpu_budget_millicores = local_resources.cpu_total_millicores;
+        snapshot.local_cpu_admitted_millicores = local_resources.cpu_admitted_millicores;
+        snapshot.local_resource_waiting = local_resources.waiting;
     }
 
     fn add_io_metrics(&self, snapshot: &mut buck2_data::Snapshot) {
@@ -13,6 +13,7 @@ pub mod ctargets;
 pub mod debug_eval;
 pub mod expand_external_cell;
 pub mod explain;
+pub mod external_cells;
 pub(crate) mod init_commands;
 pub mod install;
 pub mod query;
@@ -0,0 +1,70 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use buck2_cli_proto::new_generic::ExternalCellsRequest;
+use buck2_cli_proto::new_generic::ExternalCellsResponse;
+use buck2_common::external_cells::EXTERNAL_CELLS_IMPL;
+use buck2_server_ctx::ctx::ServerCommandContextTrait;
+use buck2_server_ctx::partial_result_dispatcher::NoPartialResult;
+use buck2_server_ctx::partial_result_dispatcher::PartialResultDispatcher;
+use buck2_server_ctx::template::run_server_command;
+use buck2_server_ctx::template::ServerCommandTemplate;
+use dice::DiceTransaction;
+
+pub(crate) async fn external_cells_command(
+    ctx: &dyn ServerCommandContextTrait,
+    partial_result_dispatcher: PartialResultDispatcher<NoPartialResult>,
+    req: ExternalCellsRequest,
+) -> anyhow::Result<ExternalCellsResponse> {
+    run_server_command(
+        ExternalCellsServerCommand { req },
+        ctx,
+        partial_result_dispatcher,
+    )
+    .await
+}
+
+struct ExternalCellsServerCommand {
+    req: ExternalCellsRequest,
+}
+
+#[async_trait::async_trait]
+impl ServerCommandTemplate for ExternalCellsServerCommand {
+    type StartEvent = buck2_data::ExternalCellsCommandStart;
+    type EndEvent = buck2_data::ExternalCellsCommandEnd;
+    type Response = ExternalCellsResponse;
+    type PartialResult = NoPartialResult;
+
+    fn start_event(&self) -> Self::StartEvent {
+        buck2_data::ExternalCellsCommandStart {
+            update: self.req.update,
+        }
+    }
+
+    async fn command(
+        &self,
+        _server_ctx: &dyn ServerCommandContextTrait,
+        _partial_result_dispatcher: PartialResultDispatcher<Self::PartialResult>,
+        mut ctx: DiceTransaction,
+    ) -> anyhow::Result<Self::Response> {
+        let changes = EXTERNAL_CELLS_IMPL
+            .get()?
+            .resolve_lockfile(&mut ctx, self.req.update)
+            .await?;
+        Ok(ExternalCellsResponse { changes })
+    }
+
+    fn is_success(&self, _response: &Self::Response) -> bool {
+        true
+    }
+
+    fn exclusive_command_name(&self) -> Option<String> {
+        Some("external-cells".to_owned())
+    }
+}
@@ -16,6 +16,8 @@ use buck2_cli_proto::new_generic::ExpandExternalCellRequest;
 use buck2_cli_proto::new_generic::ExpandExternalCellResponse;
 use buck2_cli_proto::new_generic::ExplainRequest;
 use buck2_cli_proto::new_generic::ExplainResponse;
+use buck2_cli_proto::new_generic::ExternalCellsRequest;
+use buck2_cli_proto::new_generic::ExternalCellsResponse;
 use buck2_server_ctx::ctx::ServerCommandContextTrait;
 use buck2_server_ctx::late_bindings::OtherServerCommands;
 use buck2_server_ctx::late_bindings::OTHER_SERVER_COMMANDS;
@@ -28,6 +30,7 @@ use crate::commands::ctargets::configured_targets_command;
 use crate::commands::debug_eval::debug_eval_command;
 use crate::commands::expand_external_cell::expand_external_cell_command;
 use crate::commands::explain::explain_command;
+use crate::commands::external_cells::external_cells_command;
 use crate::commands::install::install_command;
 use crate::commands::query::aquery::aquery_command;
 use crate::commands::query::cquery::cquery_command;
@@ -138,6 +141,15 @@ impl OtherServerCommands for OtherServerCommandsInstance {
     ) -> anyhow::Result<ExpandExternalCellResponse> {
         expand_external_cell_command(ctx, partial_result_dispatcher, req).await
     }
+
+    async fn external_cells(
+        &self,
+        ctx: &dyn ServerCommandContextTrait,
+        partial_result_dispatcher: PartialResultDispatcher<NoPartialResult>,
+        req: ExternalCellsRequest,
+    ) -> anyhow::Result<ExternalCellsResponse> {
+        external_cells_command(ctx, partial_result_dispatcher, req).await
+    }
 }
 
 pub(crate) fn init_other_server_commands() {
@@ -54,6 +54,10 @@ pub trait ServerCommandContextTrait: Send + Sync {
 
     fn materializer(&self) -> Arc<dyn Materializer>;
 
+    /// Marked changed whenever the file watcher sees changes to the project, for commands that
+    /// wait for changes. `None` if the file watcher only finds changes when a command starts.
+    fn file_changes(&self) -> Option<tokio::sync::watch::Receiver<()>>;
+
     /// exposes the dice for scoped access, but isn't intended to be callable by anyone
     async fn dice_accessor<'a>(
         &'a self,
@@ -16,6 +16,8 @@ use buck2_cli_proto::new_generic::ExpandExternalCellRequest;
 use buck2_cli_proto::new_generic::ExpandExternalCellResponse;
 use buck2_cli_proto::new_generic::ExplainRequest;
 use buck2_cli_proto::new_generic::ExplainResponse;
+use buck2_cli_proto::new_generic::ExternalCellsRequest;
+use buck2_cli_proto::new_generic::ExternalCellsResponse;
 use buck2_util::late_binding::LateBinding;
 
 use crate::ctx::ServerCommandContextTrait;
@@ -95,6 +97,12 @@ pub trait OtherServerCommands: Send + Sync + 'static {
         partial_result_dispatcher: PartialResultDispatcher<NoPartialResult>,
         req: ExpandExternalCellRequest,
     ) -> anyhow::Result<ExpandExternalCellResponse>;
+    async fn external_cells(
+        &self,
+        ctx: &dyn ServerCommandContextTrait,
+        partial_result_dispatcher: PartialResultDispatcher<NoPartialResult>,
+        req: ExternalCellsRequest,
+    ) -> anyhow::Result<ExternalCellsResponse>;
 }
 
 pub static OTHER_SERVER_COMMANDS: LateBinding<&'static dyn OtherServerCommands> =
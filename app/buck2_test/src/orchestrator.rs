@@ -1698,6 +1698,7 @@ impl<'a> Execute2RequestExpander<'a> {
                     exe: worker_rendered,
                     id: WorkerId(worker.id),
                     concurrency: worker.concurrency(),
+                    protocol: worker.protocol(),
                 })
             }
             _ => None,
@@ -10,7 +10,7 @@
 use std::io;
 
 fn main() -> io::Result<()> {
-    let proto_files = &["worker.proto"];
+    let proto_files = &["worker.proto", "bazel_worker.proto"];
 
     buck2_protoc_dev::configure()
         .setup_protoc()
@@ -8,6 +8,8 @@
  */
 
 use std::fmt;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
 
 use allocative::Allocative;
 use anyhow::Context;
@@ -120,6 +122,24 @@ pub struct HostSharingBroker {
     permits: SharedSemaphore,
     num_machine_permits: usize,
     named_semaphores: NamedSemaphores,
+    /// Requests that are waiting for their permits.
+    waiting: AtomicUsize,
+}
+
+/// Counts a request as waiting until dropped, so cancelled requests are accounted for too.
+struct WaitingGuard<'a>(&'a AtomicUsize);
+
+impl<'a> WaitingGuard<'a> {
+    fn new(waiting: &'a AtomicUsize) -> Self {
+        waiting.fetch_add(1, Ordering::Relaxed);
+        Self(waiting)
+    }
+}
+
+impl Drop for WaitingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
 }
 
 pub struct RequestedPermits {
@@ -167,6 +187,7 @@ impl HostSharingBroker {
             permits,
             num_machine_permits,
             named_semaphores: NamedSemaphores::new(),
+            waiting: AtomicUsize::new(0),
         }
     }
 
@@ -174,10 +195,16 @@ impl HostSharingBroker {
         self.num_machine_permits
     }
 
+    /// How many requests are currently waiting for permits.
+    pub fn queue_depth(&self) -> usize {
+        self.waiting.load(Ordering::Relaxed)
+    }
+
     pub async fn acquire(
         &self,
         host_sharing_requirements: &HostSharingRequirements,
     ) -> HostSharingGuard {
+        let _waiting = WaitingGuard::new(&self.waiting);
         match host_sharing_requirements {
             HostSharingRequirements::Shared(weight_class) => {
                 let permits = self.requested_permits(weight_class).into_count();
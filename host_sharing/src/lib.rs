@@ -13,8 +13,13 @@ mod named_semaphores;
 pub use named_semaphores::NamedSemaphores;
 
 pub mod host_sharing;
+mod resource_budget;
 pub use crate::host_sharing::HostSharingBroker;
 pub use crate::host_sharing::HostSharingRequirements;
 pub use crate::host_sharing::HostSharingStrategy;
 pub use crate::host_sharing::WeightClass;
 pub use crate::host_sharing::WeightPercentage;
+pub use crate::resource_budget::ResourceBudget;
+pub use crate::resource_budget::ResourceBudgetGuard;
+pub use crate::resource_budget::ResourceBudgetStats;
+pub use crate::resource_budget::ResourceEstimate;
@@ -0,0 +1,213 @@
+/*
+ * Copyright (c) Meta Platforms, Inc. and affiliates.
+ *
+ * This source code is licensed under both the MIT license found in the
+ * LICENSE-MIT file in the root directory of this source tree and the Apache
+ * License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+ * of this source tree.
+ */
+
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use allocative::Allocative;
+use futures_intrusive::sync::SharedSemaphore;
+use futures_intrusive::sync::SharedSemaphoreReleaser;
+
+const MIB: u64 = 1024 * 1024;
+
+/// The resources a command is expected to use while it runs. Unlike a `WeightClass`, which is
+/// relative to the number of jobs, these are absolute, so they can be checked against what the
+/// machine actually has.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Allocative, Hash)]
+pub struct ResourceEstimate {
+    pub memory_bytes: Option<u64>,
+    /// In thousandths of a CPU.
+    pub cpu_millicores: Option<u64>,
+}
+
+impl ResourceEstimate {
+    pub fn is_empty(&self) -> bool {
+        self.memory_bytes.is_none() && self.cpu_millicores.is_none()
+    }
+}
+
+#[derive(Default)]
+struct Admitted {
+    memory_mib: AtomicUsize,
+    cpu_millicores: AtomicUsize,
+    waiting: AtomicUsize,
+}
+
+/// How much of a `ResourceBudget` is in use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceBudgetStats {
+    pub memory_total_bytes: u64,
+    pub memory_admitted_bytes: u64,
+    pub cpu_total_millicores: u64,
+    pub cpu_admitted_millicores: u64,
+    /// Commands waiting for resources to free up.
+    pub waiting: u64,
+}
+
+/// Released when dropped.
+pub struct ResourceBudgetGuard {
+    _memory: Option<SharedSemaphoreReleaser>,
+    _cpu: Option<SharedSemaphoreReleaser>,
+    memory_mib: usize,
+    cpu_millicores: usize,
+    admitted: Arc<Admitted>,
+}
+
+impl Drop for ResourceBudgetGuard {
+    fn drop(&mut self) {
+        self.admitted
+            .memory_mib
+            .fetch_sub(self.memory_mib, Ordering::Relaxed);
+        self.admitted
+            .cpu_millicores
+            .fetch_sub(self.cpu_millicores, Ordering::Relaxed);
+    }
+}
+
+/// The memory and CPU of the machine, which commands are admitted against based on their
+/// `ResourceEstimate`. This complements the `HostSharingBroker`: the job count caps how many
+/// commands run, the budget keeps the ones that do run from exhausting the machine.
+pub struct ResourceBudget {
+    memory_mib: SharedSemaphore,
+    total_memory_mib: usize,
+    cpu_millicores: SharedSemaphore,
+    total_cpu_millicores: usize,
+    admitted: Arc<Admitted>,
+}
+
+impl ResourceBudget {
+    pub fn new(memory_bytes: u64, cpu_millicores: u64) -> Self {
+        let total_memory_mib = (memory_bytes / MIB) as usize;
+        let total_cpu_millicores = cpu_millicores as usize;
+        Self {
+            // Unfair, so that small commands aren't held up behind one waiting for half the
+            // machine.
+            memory_mib: SharedSemaphore::new(false, total_memory_mib),
+            total_memory_mib,
+            cpu_millicores: SharedSemaphore::new(false, total_cpu_millicores),
+            total_cpu_millicores,
+            admitted: Arc::new(Admitted::default()),
+        }
+    }
+
+    /// What admitting this estimate takes from the budget. Estimates larger than the whole
+    /// machine are capped to it, otherwise the command would never run.
+    fn requested(&self, estimate: &ResourceEstimate) -> (usize, usize) {
+        let memory_mib = estimate
+            .memory_bytes
+            .map_or(0, |b| b.div_ceil(MIB) as usize)
+            .min(self.total_memory_mib);
+        let cpu_millicores = estimate
+            .cpu_millicores
+            .map_or(0, |c| c as usize)
+            .min(self.total_cpu_millicores);
+        (memory_mib, cpu_millicores)
+    }
+
+    /// Whether admitting this estimate right now would have to wait.
+    pub fn would_wait(&self, estimate: &ResourceEstimate) -> bool {
+        let (memory_mib, cpu_millicores) = self.requested(estimate);
+        self.memory_mib.permits() < memory_mib || self.cpu_millicores.permits() < cpu_millicores
+    }
+
+    pub async fn acquire(&self, estimate: &ResourceEstimate) -> ResourceBudgetGuard {
+        let (memory_mib, cpu_millicores) = self.requested(estimate);
+
+        self.admitted.waiting.fetch_add(1, Ordering::Relaxed);
+        // Always acquired in the same order, so commands can't deadlock each other.
+        let _memory = if memory_mib > 0 {
+            Some(self.memory_mib.acquire(memory_mib).await)
+        } else {
+            None
+        };
+        let _cpu = if cpu_millicores > 0 {
+            Some(self.cpu_millicores.acquire(cpu_millicores).await)
+        } else {
+            None
+        };
+        self.admitted.waiting.fetch_sub(1, Ordering::Relaxed);
+
+        self.admitted
+            .memory_mib
+            .fetch_add(memory_mib, Ordering::Relaxed);
+        self.admitted
+            .cpu_millicores
+            .fetch_add(cpu_millicores, Ordering::Relaxed);
+
+        ResourceBudgetGuard {
+            _memory,
+            _cpu,
+            memory_mib,
+            cpu_millicores,
+            admitted: self.admitted.clone(),
+        }
+    }
+
+    pub fn stats(&self) -> ResourceBudgetStats {
+        ResourceBudgetStats {
+            memory_total_bytes: self.total_memory_mib as u64 * MIB,
+            memory_admitted_bytes: self.admitted.memory_mib.load(Ordering::Relaxed) as u64 * MIB,
+            cpu_total_millicores: self.total_cpu_millicores as u64,
+            cpu_admitted_millicores: self.admitted.cpu_millicores.load(Ordering::Relaxed) as u64,
+            waiting: self.admitted.waiting.load(Ordering::Relaxed) as u64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requested_is_capped_to_budget() {
+        let budget = ResourceBudget::new(4 * 1024 * MIB, 8000);
+
+        assert_eq!(
+            (1024, 2000),
+            budget.requested(&ResourceEstimate {
+                memory_bytes: Some(1024 * MIB),
+                cpu_millicores: Some(2000),
+            })
+        );
+        // Rounds up to whole MiB.
+        assert_eq!(
+            (1, 0),
+            budget.requested(&ResourceEstimate {
+                memory_bytes: Some(1),
+                cpu_millicores: None,
+            })
+        );
+        assert_eq!(
+            (4096, 8000),
+            budget.requested(&ResourceEstimate {
+                memory_bytes: Some(64 * 1024 * MIB),
+                cpu_millicores: Some(64000),
+            })
+        );
+        assert_eq!((0, 0), budget.requested(&ResourceEstimate::default()));
+    }
+
+    #[test]
+    fn test_would_wait() {
+        let budget = ResourceBudget::new(4 * 1024 * MIB, 8000);
+        let heavy = ResourceEstimate {
+            memory_bytes: Some(3 * 1024 * MIB),
+            cpu_millicores: None,
+        };
+
+        assert!(!budget.would_wait(&heavy));
+        let _held = budget.memory_mib.try_acquire(2048).unwrap();
+        assert!(budget.would_wait(&heavy));
+        assert!(!budget.would_wait(&ResourceEstimate {
+            memory_bytes: Some(1024 * MIB),
+            cpu_millicores: Some(8000),
+        }));
+    }
+}
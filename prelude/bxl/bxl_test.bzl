@@ -0,0 +1,85 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This source code is licensed under both the MIT license found in the
+# LICENSE-MIT file in the root directory of this source tree and the Apache
+# License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+# of this source tree.
+
+def _bxl_test_impl(ctx: AnalysisContext) -> list[Provider]:
+    spec = {
+        "buckconfig": ctx.attrs.buckconfig,
+        "bxl_args": ctx.attrs.bxl_args,
+        "bxl_function": ctx.attrs.bxl_function,
+        "cells": ctx.attrs.cells,
+        "expect_failure": ctx.attrs.expect_failure,
+        "expected_artifacts": ctx.attrs.expected_artifacts,
+        "expected_json": ctx.attrs.expected_json,
+        "expected_output": ctx.attrs.expected_output,
+        "files": ctx.attrs.files,
+        "fixture": ctx.attrs.fixture,
+    }
+    spec_file = ctx.actions.write_json("bxl_test.json", spec, with_inputs = True)
+
+    command = cmd_args(
+        ctx.attrs._runner[RunInfo],
+        "--spec",
+        spec_file,
+        ["--buck2", ctx.attrs.buck2] if ctx.attrs.buck2 != None else [],
+    )
+
+    return [
+        DefaultInfo(),
+        RunInfo(args = command),
+        ExternalRunnerTestInfo(
+            type = "custom",
+            command = [command],
+            labels = ctx.attrs.labels,
+            contacts = ctx.attrs.contacts,
+            run_from_project_root = True,
+            use_project_relative_paths = True,
+        ),
+    ]
+
+bxl_test = rule(
+    doc = """
+    Runs a BXL function against a small fixture project, and checks what it printed and built.
+
+    The fixture is a fresh project in a temporary directory, made of the `fixture` directory, the
+    `files` written on top of it and the extra `cells`. Its `.buckconfig` only has what the test
+    declares, and buckconfigs from outside the project (like `~/.buckconfig.d` or
+    `/etc/buckconfig.d`) are not read, so the test does not depend on the configuration of the
+    repository it lives in or of the machine. Values the function computes can be checked by
+    printing them with `ctx.output.print_json` and setting `expected_json`. The test also fails if
+    the function fails, so they can be checked with `fail()` (or `asserts`, if the prelude is one of
+    the `cells`) in the BXL function itself.
+
+    ```
+    bxl_test(
+        name = "owners_test",
+        files = {
+            "BUCK": "...",
+            "test.bxl": "...",
+        },
+        bxl_function = "//test.bxl:main",
+        expected_output = "root//:foo\\n",
+    )
+    ```
+    """,
+    impl = _bxl_test_impl,
+    attrs = {
+        "buck2": attrs.option(attrs.arg(), default = None, doc = "The `buck2` binary to run the function with. Defaults to the one on `PATH`."),
+        "buckconfig": attrs.dict(key = attrs.string(), value = attrs.string(), sorted = False, default = {}, doc = "Buckconfig of the fixture, as `section.key` to value. The `cells` section is filled in from `cells`."),
+        "bxl_args": attrs.list(attrs.string(), default = [], doc = "Arguments passed to the BXL function."),
+        "bxl_function": attrs.string(doc = "The function to run, as `path/to/file.bxl:function` in the fixture."),
+        "cells": attrs.dict(key = attrs.string(), value = attrs.source(allow_directory = True), sorted = False, default = {}, doc = "Extra cells of the fixture, copied to a directory named after the cell."),
+        "contacts": attrs.list(attrs.string(), default = []),
+        "expect_failure": attrs.option(attrs.string(), default = None, doc = "If set, the function is expected to fail with an error containing this string."),
+        "expected_artifacts": attrs.dict(key = attrs.string(), value = attrs.string(), sorted = False, default = {}, doc = "Expected contents of the artifacts the function ensured and printed, keyed by a suffix of their path."),
+        "expected_json": attrs.option(attrs.any(), default = None, doc = "The value the function printed with `ctx.output.print_json`, compared after parsing the output as JSON."),
+        "expected_output": attrs.option(attrs.string(), default = None, doc = "The exact output of the function."),
+        "files": attrs.dict(key = attrs.string(), value = attrs.string(), sorted = False, default = {}, doc = "Files of the root cell of the fixture, as path to contents."),
+        "fixture": attrs.option(attrs.source(allow_directory = True), default = None, doc = "A directory to use as the root cell of the fixture."),
+        "labels": attrs.list(attrs.string(), default = []),
+        "_runner": attrs.default_only(attrs.dep(default = "prelude//bxl/tools:bxl_test_runner", providers = [RunInfo])),
+    },
+)
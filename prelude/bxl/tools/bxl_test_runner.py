@@ -0,0 +1,174 @@
+#!/usr/bin/env python3
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This source code is licensed under both the MIT license found in the
+# LICENSE-MIT file in the root directory of this source tree and the Apache
+# License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+# of this source tree.
+
+# pyre-strict
+
+"""
+Runs the BXL function of a `bxl_test` against its fixture project.
+
+bxl_test_runner.py --spec bxl_test.json [--buck2 path/to/buck2]
+"""
+
+import argparse
+import json
+import os
+import shutil
+import subprocess
+import sys
+import tempfile
+from pathlib import Path
+from typing import Dict, List, Optional
+
+
+def _copy(src: Path, dst: Path) -> None:
+    if src.is_dir():
+        shutil.copytree(src, dst, dirs_exist_ok=True)
+    else:
+        dst.parent.mkdir(parents=True, exist_ok=True)
+        shutil.copyfile(src, dst)
+
+
+def _buckconfig(cells: List[str], buckconfig: Dict[str, str]) -> str:
+    sections: Dict[str, Dict[str, str]] = {"cells": {"root": "."}}
+    for cell in cells:
+        sections["cells"][cell] = cell
+    for key, value in buckconfig.items():
+        section, _, name = key.partition(".")
+        if not name:
+            raise ValueError(
+                f"buckconfig key `{key}` must be of the form `section.key`"
+            )
+        sections.setdefault(section, {})[name] = value
+
+    lines = []
+    for section, values in sections.items():
+        lines.append(f"[{section}]")
+        lines.extend(f"  {name} = {value}" for name, value in values.items())
+        lines.append("")
+    return "\n".join(lines)
+
+
+def _make_fixture(spec: Dict[str, object], root: Path) -> None:
+    fixture: Optional[str] = spec["fixture"]
+    if fixture is not None:
+        _copy(Path(fixture), root)
+    for path, contents in spec["files"].items():
+        dst = root / path
+        dst.parent.mkdir(parents=True, exist_ok=True)
+        dst.write_text(contents)
+    for cell, src in spec["cells"].items():
+        _copy(Path(src), root / cell)
+
+    # Whatever the fixture brings is overwritten, so that the test is hermetic.
+    (root / ".buckconfig").write_text(
+        _buckconfig(list(spec["cells"]), spec["buckconfig"])
+    )
+    (root / ".buckroot").write_text("")
+
+
+def _check(
+    spec: Dict[str, object], root: Path, result: subprocess.CompletedProcess
+) -> List[str]:
+    errors = []
+    expect_failure: Optional[str] = spec["expect_failure"]
+    if expect_failure is not None:
+        if result.returncode == 0:
+            errors.append("Expected the BXL function to fail, but it succeeded")
+        elif expect_failure not in result.stderr:
+            errors.append(
+                f"Expected the BXL function to fail with `{expect_failure}`, "
+                f"got:\n{result.stderr}"
+            )
+        return errors
+
+    if result.returncode != 0:
+        return [f"The BXL function failed:\n{result.stderr}"]
+
+    expected_output: Optional[str] = spec["expected_output"]
+    if expected_output is not None and result.stdout != expected_output:
+        errors.append(
+            f"Unexpected output.\nExpected:\n{expected_output}\nGot:\n{result.stdout}"
+        )
+
+    expected_json: object = spec["expected_json"]
+    if expected_json is not None:
+        try:
+            actual_json = json.loads(result.stdout)
+        except json.JSONDecodeError as e:
+            errors.append(
+                f"Expected the output to be JSON ({e}), got:\n{result.stdout}"
+            )
+        else:
+            if actual_json != expected_json:
+                want = json.dumps(expected_json, indent=2, sort_keys=True)
+                got = json.dumps(actual_json, indent=2, sort_keys=True)
+                errors.append(f"Unexpected JSON value.\nExpected:\n{want}\nGot:\n{got}")
+
+    printed = [line.strip() for line in result.stdout.splitlines() if line.strip()]
+    for suffix, expected in spec["expected_artifacts"].items():
+        paths = [p for p in printed if p.endswith(suffix)]
+        if len(paths) != 1:
+            errors.append(
+                f"Expected exactly one printed path ending with `{suffix}`, "
+                f"got {len(paths)}"
+            )
+            continue
+        # Ensured artifacts are printed relative to the project root, unless they
+        # were asked to be absolute.
+        path = root / paths[0]
+        actual = path.read_text()
+        if actual != expected:
+            errors.append(
+                f"Unexpected contents of `{paths[0]}`.\n"
+                f"Expected:\n{expected}\nGot:\n{actual}"
+            )
+    return errors
+
+
+def main() -> int:
+    parser = argparse.ArgumentParser()
+    parser.add_argument("--spec", type=Path, required=True)
+    parser.add_argument("--buck2", default="buck2")
+    args = parser.parse_args()
+
+    spec = json.loads(args.spec.read_text())
+
+    root = Path(tempfile.mkdtemp(prefix="bxl_test_"))
+    try:
+        _make_fixture(spec, root)
+
+        # Don't let the buck2 running the test leak into the one running the fixture.
+        env = {
+            k: v
+            for k, v in os.environ.items()
+            if not k.startswith("BUCK2_") and not k.startswith("BUCK_")
+        }
+        # Nor the buckconfigs of the machine or the user.
+        env["BUCK2_IGNORE_EXTERNAL_CONFIG"] = "true"
+        # The fixture is run from its own directory, not from the project root.
+        buck2 = [os.path.abspath(args.buck2) if os.sep in args.buck2 else args.buck2]
+        result = subprocess.run(
+            buck2 + ["bxl", spec["bxl_function"], "--"] + spec["bxl_args"],
+            cwd=root,
+            env=env,
+            capture_output=True,
+            text=True,
+        )
+        subprocess.run(buck2 + ["kill"], cwd=root, env=env, capture_output=True)
+
+        errors = _check(spec, root, result)
+    finally:
+        shutil.rmtree(root, ignore_errors=True)
+
+    for error in errors:
+        print(error, file=sys.stderr)
+    return 1 if errors else 0
+
+
+if __name__ == "__main__":
+    sys.exit(main())
@@ -15,7 +15,7 @@ load(
     "resolve_alias",
     "resolve_modifier",
 )
-load(":name.bzl", "cfg_name")
+load(":name.bzl", "cfg_display_name", "cfg_name")
 load(
     ":types.bzl",
     "Modifier",  # @unused
@@ -182,4 +182,5 @@ def cfg_constructor_post_constraint_analysis(
     return PlatformInfo(
         label = name,
         configuration = cfg,
+        display_name = cfg_display_name(cfg, getattr(params.extra_data, "display_names", None)),
     )
@@ -53,3 +53,19 @@ def cfg_name(cfg: ConfigurationInfo) -> str:
     else:
         name = _CFG_PREFIX + "-".join(name_list)
     return name
+
+def cfg_display_name(cfg: ConfigurationInfo, display_names: dict[str, list[str]] | None) -> str | None:
+    """
+    Picks the display name for a ConfigurationInfo, if the user declared one.
+
+    `display_names` maps display names to the fully qualified constraint values a configuration
+    must have to be given that name. The first entry that matches wins, so more specific entries
+    should come first.
+    """
+    if not display_names:
+        return None
+    constraint_values = {str(value.label): True for value in cfg.constraints.values()}
+    for display_name, required in display_names.items():
+        if all([value in constraint_values for value in required]):
+            return display_name
+    return None
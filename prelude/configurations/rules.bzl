@@ -56,6 +56,7 @@ def constraint_value_impl(ctx):
 # Attributes:
 #  constraint_values: list of constraint values that are set for this platform
 #  deps: a list of platform target dependencies, the constraints from these platforms will be part of this platform (unless overridden)
+#  display_name: optional short name to show for the configuration instead of its label and hash
 def platform_impl(ctx):
     subinfos = (
         [dep[PlatformInfo].configuration for dep in ctx.attrs.deps] +
@@ -72,6 +73,7 @@ def platform_impl(ctx):
             #   If this is intentional, state it explicitly.
             #   Otherwise, fix it.
             configuration = util.configuration_info_union(subinfos),
+            display_name = ctx.attrs.display_name,
         ),
     ]
 
@@ -899,6 +899,7 @@ platform = prelude_rule(
         {
             "constraint_values": attrs.list(attrs.configuration_label(), default = []),
             "deps": attrs.list(attrs.configuration_label(), default = []),
+            "display_name": attrs.option(attrs.string(), default = None),
         }
     ),
 )
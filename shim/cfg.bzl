@@ -13,7 +13,7 @@ SHIM_ALIASES = {
     "ubuntu": "shim//os/linux/distro/constraints:ubuntu",
 }
 
-def set_cfg_constructor(aliases = dict()):
+def set_cfg_constructor(aliases = dict(), display_names = dict()):
     project_root_cell = read_root_config("cell_aliases", "root")
     current_root_cell = read_config("cell_aliases", "root")
     if project_root_cell == current_root_cell:
@@ -22,7 +22,7 @@ def set_cfg_constructor(aliases = dict()):
             stage1 = cfg_constructor_post_constraint_analysis,
             key = MODIFIER_METADATA_KEY,
             aliases = struct(**aliases),
-            extra_data = struct(),
+            extra_data = struct(display_names = display_names),
         )
 
 def get_shim_modifiers():
@@ -0,0 +1,105 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This source code is licensed under both the MIT license found in the
+# LICENSE-MIT file in the root directory of this source tree and the Apache
+# License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+# of this source tree.
+
+# pyre-strict
+
+
+import json
+import re
+from typing import Any, Dict, List
+
+from buck2.tests.e2e_util.api.buck import Buck
+from buck2.tests.e2e_util.buck_workspace import buck_test
+
+
+def _replace_hash(s: str) -> str:
+    return re.sub(r"\b[0-9a-f]{16}\b", "<HASH>", s)
+
+
+async def _audit(buck: Buck, *args: str) -> str:
+    result = await buck.audit("configuration-origins", *args)
+    return _replace_hash(result.stdout)
+
+
+async def _bxl(buck: Buck, target: str, *roots: str) -> List[Dict[str, Any]]:
+    result = await buck.bxl(
+        "root//origins.bxl:configuration_origins",
+        "--",
+        "--target",
+        target,
+        "--roots",
+        *roots,
+    )
+    return json.loads(result.stdout)
+
+
+@buck_test()
+async def test_default_origin(buck: Buck) -> None:
+    out = await _audit(buck, "root//:lib", "root//:plain")
+    assert "root//:lib exists in 1 configuration(s)" in out
+    assert "root//:plain (cfg#<HASH>)" in out
+    assert "-> root//:lib (cfg#<HASH>) via dep" in out
+    assert "Difference from" not in out
+
+
+@buck_test()
+async def test_not_reachable(buck: Buck) -> None:
+    out = await _audit(buck, "root//:incoming", "root//:plain")
+    assert "root//:incoming is not reachable from the given roots" in out
+
+
+@buck_test()
+async def test_transition_origin(buck: Buck) -> None:
+    out = await _audit(buck, "root//:lib", "root//:transitioned")
+    assert "root//:lib exists in 2 configuration(s)" in out
+    assert "-> root//:lib (cfg#<HASH>) via dep" in out
+    assert "-> root//:lib (macos#<HASH>) via transition root//defs.bzl#to_macos" in out
+    assert "Difference from cfg#<HASH>:" in out
+    assert "root//:linux" in out
+    assert "root//:macos" in out
+
+
+@buck_test()
+async def test_incoming_transition_origin(buck: Buck) -> None:
+    out = await _audit(buck, "root//:lib", "root//:with_incoming")
+    assert "root//:lib exists in 1 configuration(s)" in out
+    assert "via incoming transition root//defs.bzl#to_macos" in out
+    assert "-> root//:lib (macos#<HASH>) via dep" in out
+
+
+@buck_test()
+async def test_modifier_origin(buck: Buck) -> None:
+    out = await _audit(buck, "root//:lib", "root//:plain", "root//:modified")
+    assert "root//:lib exists in 2 configuration(s)" in out
+    assert "Modifiers of root//:modified:" in out
+    assert re.search(r'target: \{"root//:os":\s*"root//:macos"\}', out), out
+    assert "Difference from cfg#<HASH>:" in out
+
+
+@buck_test()
+async def test_bxl(buck: Buck) -> None:
+    origins = await _bxl(buck, "root//:lib", "root//:transitioned")
+    assert len(origins) == 2
+
+    [default, transitioned] = origins
+    assert default["diff"] is None
+    assert default["edges"] == [None, "dep"]
+    assert default["path"][0].startswith("root//:transitioned (cfg#")
+    assert default["modifiers"] == {"package": None, "target": None}
+
+    assert transitioned["target"].startswith("root//:lib (macos#")
+    assert transitioned["edges"] == [None, "transition root//defs.bzl#to_macos"]
+    assert "root//:macos" in transitioned["diff"]
+
+
+@buck_test()
+async def test_bxl_modifiers(buck: Buck) -> None:
+    origins = await _bxl(buck, "root//:lib", "root//:modified")
+    assert len(origins) == 1
+    assert json.loads(origins[0]["modifiers"]["target"]) == {
+        "root//:os": "root//:macos"
+    }
@@ -0,0 +1,42 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This source code is licensed under both the MIT license found in the
+# LICENSE-MIT file in the root directory of this source tree and the Apache
+# License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+# of this source tree.
+
+# Modifiers map a constraint setting to the constraint value to use for it.
+MODIFIERS_KEY = "test.cfg_modifiers"
+
+def _stage0(
+        legacy_platform,
+        package_modifiers: dict[str, typing.Any] | None,
+        target_modifiers: dict[str, typing.Any] | None,
+        cli_modifiers: list[str],
+        rule_name: str,
+        aliases: struct | None,
+        extra_data: dict[str, typing.Any] | None):
+    _unused = (cli_modifiers, rule_name, aliases, extra_data)  # buildifier: disable=unused-variable
+    modifiers = {}
+    modifiers.update(package_modifiers or {})
+    modifiers.update(target_modifiers or {})
+    return (list(modifiers.values()), struct(legacy_platform = legacy_platform, modifiers = modifiers))
+
+def _stage1(refs: dict[str, ProviderCollection], params):
+    constraints = {}
+    if params.legacy_platform:
+        constraints.update(params.legacy_platform.configuration.constraints)
+    for value in params.modifiers.values():
+        info = refs[value][ConstraintValueInfo]
+        constraints[info.setting.label] = info
+    return PlatformInfo(
+        label = "cfg",
+        configuration = ConfigurationInfo(constraints = constraints, values = {}),
+    )
+
+def init_cfg_constructor():
+    set_cfg_constructor(
+        key = MODIFIERS_KEY,
+        stage0 = _stage0,
+        stage1 = _stage1,
+    )
@@ -0,0 +1,40 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This source code is licensed under both the MIT license found in the
+# LICENSE-MIT file in the root directory of this source tree and the Apache
+# License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+# of this source tree.
+
+def _to_macos_impl(platform, refs):
+    macos = refs.macos[ConstraintValueInfo]
+    constraints = dict(platform.configuration.constraints)
+    constraints[macos.setting.label] = macos
+    return PlatformInfo(
+        label = "macos",
+        configuration = ConfigurationInfo(
+            constraints = constraints,
+            values = platform.configuration.values,
+        ),
+    )
+
+to_macos = transition(impl = _to_macos_impl, refs = {
+    "macos": "root//:macos",
+})
+
+def _impl(_ctx):
+    return [DefaultInfo()]
+
+lib = rule(impl = _impl, attrs = {})
+
+binary = rule(impl = _impl, attrs = {
+    "deps": attrs.list(attrs.dep(), default = []),
+    "macos_deps": attrs.list(attrs.transition_dep(cfg = to_macos), default = []),
+})
+
+macos_lib = rule(
+    impl = _impl,
+    attrs = {
+        "deps": attrs.list(attrs.dep(), default = []),
+    },
+    cfg = to_macos,
+)
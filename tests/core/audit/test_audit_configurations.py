@@ -47,3 +47,54 @@ async def test_audit_configurations_specific(buck: Buck) -> None:
     # Now audit the specific configuration.
     result = await buck.audit("configurations", configuration)
     assert [configuration] == _parse_audit_configurations(result.stdout)
+
+
+async def _configuration_names(buck: Buck) -> List[str]:
+    await buck.cquery("//:genrule", "//:genrule_linux", "//:genrule_named")
+    result = await buck.audit("configurations")
+    return _parse_audit_configurations(result.stdout)
+
+
+def _find(configurations: List[str], prefix: str) -> str:
+    [configuration] = [c for c in configurations if c.startswith(prefix)]
+    # Drop the display name.
+    return configuration.split(" ")[0]
+
+
+@buck_test()
+async def test_audit_configurations_diff(buck: Buck) -> None:
+    configurations = await _configuration_names(buck)
+    p = _find(configurations, "root//:p#")
+    p_linux = _find(configurations, "root//:p_linux#")
+
+    result = await buck.audit("configurations", "--diff", p, p_linux)
+    assert _replace_hash(result.stdout) == (
+        "--- root//:p#<HASH>\n"
+        "+++ root//:p_linux#<HASH>\n"
+        "- label: root//:p\n"
+        "+ label: root//:p_linux\n"
+        "+ constraint: root//:os -> root//:linux\n"
+    )
+
+    result = await buck.audit("configurations", "--diff", p, p)
+    assert _replace_hash(result.stdout) == (
+        "--- root//:p#<HASH>\n"
+        "+++ root//:p#<HASH>\n"
+        "Configurations are identical\n"
+    )
+
+
+@buck_test()
+async def test_audit_configurations_diff_display_name(buck: Buck) -> None:
+    configurations = await _configuration_names(buck)
+    p = _find(configurations, "root//:p#")
+    p_named = _find(configurations, "root//:p_named#")
+
+    result = await buck.audit("configurations", "--diff", p, p_named)
+    assert _replace_hash(result.stdout) == (
+        "--- root//:p#<HASH>\n"
+        "+++ root//:p_named#<HASH> (named)\n"
+        "- label: root//:p\n"
+        "+ label: root//:p_named\n"
+        "+ display name: named\n"
+    )
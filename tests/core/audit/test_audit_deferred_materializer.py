@@ -8,6 +8,7 @@
 # pyre-strict
 
 from buck2.tests.e2e_util.api.buck import Buck
+from buck2.tests.e2e_util.asserts import expect_failure
 from buck2.tests.e2e_util.buck_workspace import buck_test
 
 
@@ -20,3 +21,49 @@ async def test_audit_deferred_materializer_list(buck: Buck) -> None:
 
     res = await buck.audit("deferred-materializer", "list")
     assert "__simple__" in res.stdout.strip()
+
+
+@buck_test()
+async def test_debug_materializer_fsck(buck: Buck) -> None:
+    res = await buck.build("//:simple")
+    out = res.get_build_report().output_for_target("//:simple")
+
+    res = await buck.debug("materializer-fsck")
+    assert res.stdout.strip() == ""
+
+    original = out.read_text()
+    out.write_text("modified")
+
+    await expect_failure(
+        buck.debug("materializer-fsck"),
+        stdout_regex="modified\t.*__simple__",
+    )
+
+    res = await buck.debug("materializer-fsck", "--repair")
+    assert "__simple__" in res.stdout
+    assert not out.exists()
+
+    res = await buck.debug("materializer-fsck")
+    assert res.stdout.strip() == ""
+
+    # The repair dropped the daemon's graph, so the output is rebuilt without a restart.
+    await buck.build("//:simple")
+    assert out.read_text() == original
+
+
+@buck_test()
+async def test_debug_materializer_fsck_repair_ignores_untracked(buck: Buck) -> None:
+    await buck.build("//:simple")
+    untracked = buck.cwd / "buck-out" / "v2" / "gen" / "untracked"
+    untracked.parent.mkdir(parents=True, exist_ok=True)
+    untracked.write_text("untracked")
+
+    await expect_failure(
+        buck.debug("materializer-fsck"),
+        stdout_regex="untracked\t.*untracked",
+    )
+
+    # Untracked paths are left to `buck2 clean --stale`, and are not a failure when repairing.
+    res = await buck.debug("materializer-fsck", "--repair")
+    assert "untracked" in res.stdout
+    assert untracked.exists()
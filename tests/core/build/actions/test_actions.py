@@ -455,4 +455,28 @@ async def test_action_invalidation_tracking(buck: Buck) -> None:
     )
 
     assert invalidation_info
-    assert invalidation_info[0]["changed_file"] == {}
+    path = invalidation_info[0]["changed_file"]["path"]
+    assert path
+    assert "src.txt" in path[0]["key"]
+
+    result = await buck.debug("why-recomputed", "--json")
+    records = [json.loads(line) for line in result.stdout.splitlines()]
+    recomputed = [
+        r for r in records if r["type"] == "recomputed" and r["kind"] == "action"
+    ]
+    assert recomputed
+    assert "src.txt" in recomputed[0]["path"][0]
+    summary = [r for r in records if r["type"] == "summary"]
+    assert summary
+    assert "src.txt" in summary[0]["source"]
+
+    with open(buck.cwd / "run" / "TARGETS", "a") as buildfile:
+        buildfile.write("# changed\n")
+
+    await buck.build("//run:runs_simple_script")
+    result = await buck.debug("why-recomputed", "--json")
+    records = [json.loads(line) for line in result.stdout.splitlines()]
+    loads = [r for r in records if r["type"] == "recomputed" and r["kind"] == "load"]
+    assert loads
+    assert "run" in loads[0]["key"]
+    assert "TARGETS" in loads[0]["path"][0]
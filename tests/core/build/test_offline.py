@@ -0,0 +1,114 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This source code is licensed under both the MIT license found in the
+# LICENSE-MIT file in the root directory of this source tree and the Apache
+# License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+# of this source tree.
+
+# pyre-strict
+
+
+import hashlib
+import threading
+from contextlib import contextmanager
+from http.server import BaseHTTPRequestHandler, ThreadingHTTPServer
+from typing import Iterator, List, Tuple
+
+from buck2.tests.e2e_util.api.buck import Buck
+from buck2.tests.e2e_util.asserts import expect_failure
+from buck2.tests.e2e_util.buck_workspace import buck_test
+
+CONTENT = b"downloaded\n"
+
+
+class _Server:
+    def __init__(self, url: str) -> None:
+        self.url = url
+        # Method and path of every request received.
+        self.requests: List[Tuple[str, str]] = []
+
+    def config(self) -> List[str]:
+        return [
+            "-c",
+            f"test.url={self.url}/file",
+            "-c",
+            f"test.sha1={hashlib.sha1(CONTENT).hexdigest()}",
+        ]
+
+
+@contextmanager
+def _serve() -> Iterator[_Server]:
+    server: _Server
+
+    class Handler(BaseHTTPRequestHandler):
+        def _respond(self, body: bool) -> None:
+            server.requests.append((self.command, self.path))
+            self.send_response(200)
+            self.send_header("Content-Length", str(len(CONTENT)))
+            self.end_headers()
+            if body:
+                self.wfile.write(CONTENT)
+
+        def do_HEAD(self) -> None:
+            self._respond(body=False)
+
+        def do_GET(self) -> None:
+            self._respond(body=True)
+
+    httpd = ThreadingHTTPServer(("127.0.0.1", 0), Handler)
+    server = _Server(f"http://127.0.0.1:{httpd.server_address[1]}")
+    thread = threading.Thread(target=httpd.serve_forever, daemon=True)
+    thread.start()
+    try:
+        yield server
+    finally:
+        httpd.shutdown()
+        httpd.server_close()
+
+
+@buck_test()
+async def test_offline_local_action(buck: Buck) -> None:
+    result = await buck.build("root//:local", "--offline")
+    output = result.get_build_report().output_for_target("root//:local")
+    assert output.read_text() == "local"
+
+
+@buck_test()
+async def test_offline_refuses_download(buck: Buck) -> None:
+    with _serve() as server:
+        await expect_failure(
+            buck.build("root//:download", "--offline", *server.config()),
+            stderr_regex="--offline",
+        )
+        assert server.requests == []
+
+
+@buck_test()
+async def test_offline_refuses_deferred_download(buck: Buck) -> None:
+    with _serve() as server:
+        # The download is deferred to the materializer, which doesn't need it yet.
+        await buck.build(
+            "root//:download", "--materializations=none", *server.config()
+        )
+        assert [method for method, _ in server.requests] == ["HEAD"]
+        server.requests.clear()
+
+        # The action doesn't run again, but materializing its output would download it.
+        await expect_failure(
+            buck.build(
+                "root//:download",
+                "--offline",
+                "--materializations=all",
+                *server.config(),
+            ),
+            stderr_regex="network access is disabled by `--offline`",
+        )
+        assert server.requests == []
+
+        # Refusing isn't remembered by the materializer.
+        result = await buck.build(
+            "root//:download", "--materializations=all", *server.config()
+        )
+        assert [method for method, _ in server.requests] == ["GET"]
+        output = result.get_build_report().output_for_target("root//:download")
+        assert output.read_bytes() == CONTENT
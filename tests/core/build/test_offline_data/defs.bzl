@@ -0,0 +1,40 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This source code is licensed under both the MIT license found in the
+# LICENSE-MIT file in the root directory of this source tree and the Apache
+# License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+# of this source tree.
+
+def _download_impl(ctx):
+    out = ctx.actions.declare_output("out")
+    ctx.actions.download_file(
+        out.as_output(),
+        read_config("test", "url"),
+        sha1 = read_config("test", "sha1"),
+        is_deferrable = True,
+    )
+    return [DefaultInfo(default_output = out)]
+
+download = rule(
+    attrs = {},
+    impl = _download_impl,
+)
+
+def _local_impl(ctx):
+    out = ctx.actions.declare_output("out")
+    ctx.actions.run(
+        [
+            "python3",
+            "-c",
+            "import sys; open(sys.argv[1], 'w').write('local')",
+            out.as_output(),
+        ],
+        local_only = True,
+        category = "write",
+    )
+    return [DefaultInfo(default_output = out)]
+
+local = rule(
+    attrs = {},
+    impl = _local_impl,
+)
@@ -0,0 +1,41 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This source code is licensed under both the MIT license found in the
+# LICENSE-MIT file in the root directory of this source tree and the Apache
+# License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+# of this source tree.
+
+# pyre-strict
+
+
+from buck2.tests.e2e_util.api.buck import Buck
+from buck2.tests.e2e_util.buck_workspace import buck_test
+
+UNDECLARED_WARNING = "read files that are not declared inputs"
+
+
+@buck_test()
+async def test_undeclared_read_is_reported(buck: Buck) -> None:
+    result = await buck.build("//:undeclared", "-c", "build.paranoid_inputs=true")
+    assert UNDECLARED_WARNING in result.stderr
+    assert "include/b.h" in result.stderr
+    assert "include/a.h" not in result.stderr
+
+
+@buck_test()
+async def test_declared_reads_are_not_reported(buck: Buck) -> None:
+    result = await buck.build("//:declared", "-c", "build.paranoid_inputs=true")
+    assert UNDECLARED_WARNING not in result.stderr
+
+
+@buck_test()
+async def test_read_through_declared_symlink_is_not_reported(buck: Buck) -> None:
+    # The header is read at the target of the symlink in the declared `symlinked_dir`.
+    result = await buck.build("//:realpath", "-c", "build.paranoid_inputs=true")
+    assert UNDECLARED_WARNING not in result.stderr
+
+
+@buck_test()
+async def test_not_reported_without_paranoid_inputs(buck: Buck) -> None:
+    result = await buck.build("//:undeclared")
+    assert UNDECLARED_WARNING not in result.stderr
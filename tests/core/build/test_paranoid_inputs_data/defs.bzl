@@ -0,0 +1,46 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This source code is licensed under both the MIT license found in the
+# LICENSE-MIT file in the root directory of this source tree and the Apache
+# License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+# of this source tree.
+
+def _cat_headers_impl(ctx):
+    headers = ctx.actions.symlinked_dir(
+        "headers",
+        {h.basename: h for h in ctx.attrs.headers},
+    )
+    out = ctx.actions.declare_output("out")
+
+    # Like a compiler, reads the headers from an include directory, optionally resolving symlinks
+    # first, and optionally reads a file it was not given.
+    read = 'cat "$(realpath "$dir/$h")"' if ctx.attrs.realpath else 'cat "$dir/$h"'
+    script = (
+        'out="$1"; dir="$2"; shift 2; ' +
+        'for h in "$@"; do {}; done > "$out"; '.format(read) +
+        'if [ -n "$UNDECLARED" ]; then cat "$UNDECLARED" >> "$out"; fi'
+    )
+    ctx.actions.run(
+        cmd_args(
+            "sh",
+            "-c",
+            script,
+            "--",
+            out.as_output(),
+            headers,
+            [h.basename for h in ctx.attrs.headers],
+        ),
+        env = {"UNDECLARED": ctx.attrs.undeclared},
+        category = "cat_headers",
+        local_only = True,
+    )
+    return [DefaultInfo(default_output = out)]
+
+cat_headers = rule(
+    impl = _cat_headers_impl,
+    attrs = {
+        "headers": attrs.list(attrs.source()),
+        "realpath": attrs.bool(default = False),
+        "undeclared": attrs.string(default = ""),
+    },
+)
@@ -0,0 +1,93 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This source code is licensed under both the MIT license found in the
+# LICENSE-MIT file in the root directory of this source tree and the Apache
+# License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+# of this source tree.
+
+# pyre-strict
+
+
+import json
+from typing import List
+
+from buck2.tests.e2e_util.api.buck import Buck
+from buck2.tests.e2e_util.asserts import expect_failure
+from buck2.tests.e2e_util.buck_workspace import buck_test
+
+
+async def _glob(buck: Buck, package: str, *args: str) -> List[str]:
+    result = await buck.bxl(
+        "root//fs.bxl:glob",
+        "--",
+        "--package",
+        package,
+        *args,
+    )
+    return json.loads(result.stdout)
+
+
+@buck_test()
+async def test_read_text(buck: Buck) -> None:
+    result = await buck.bxl(
+        "root//fs.bxl:read_text", "--", "--path", "pkg/greeting.txt"
+    )
+    assert result.stdout == "hello\n\n"
+
+    # The file is read through DICE, so edits are picked up.
+    (buck.cwd / "pkg" / "greeting.txt").write_text("bye\n")
+    result = await buck.bxl(
+        "root//fs.bxl:read_text", "--", "--path", "pkg/greeting.txt"
+    )
+    assert result.stdout == "bye\n\n"
+
+    await expect_failure(
+        buck.bxl("root//fs.bxl:read_text", "--", "--path", "pkg/missing.txt"),
+        stderr_regex="missing.txt",
+    )
+
+
+@buck_test()
+async def test_read_json(buck: Buck) -> None:
+    result = await buck.bxl(
+        "root//fs.bxl:read_json", "--", "--path", "root//pkg/a.json"
+    )
+    assert json.loads(result.stdout) == {"name": "a", "deps": [1, 2]}
+
+    await expect_failure(
+        buck.bxl("root//fs.bxl:read_json", "--", "--path", "broken.json"),
+        stderr_regex="Error parsing `root//broken.json` as JSON",
+    )
+
+
+@buck_test()
+async def test_glob(buck: Buck) -> None:
+    # Paths are relative to the package, and files of subpackages are not matched.
+    assert await _glob(buck, "pkg", "--pattern", "**/*.json") == [
+        "a.json",
+        "sub/c.json",
+    ]
+    assert await _glob(buck, "root//pkg", "--pattern", "*.json", "sub/*") == [
+        "a.json",
+        "sub/c.json",
+    ]
+    assert await _glob(
+        buck, "pkg", "--pattern", "**/*.json", "--exclude", "sub/**"
+    ) == ["a.json"]
+    assert await _glob(buck, "pkg/subpkg", "--pattern", "**/*.json") == ["d.json"]
+
+    # Added files are picked up.
+    (buck.cwd / "pkg" / "sub" / "e.json").write_text("{}\n")
+    assert await _glob(buck, "pkg", "--pattern", "**/*.json") == [
+        "a.json",
+        "sub/c.json",
+        "sub/e.json",
+    ]
+
+
+@buck_test()
+async def test_glob_not_a_package(buck: Buck) -> None:
+    await expect_failure(
+        _glob(buck, "pkg/sub", "--pattern", "*.json"),
+        stderr_regex="pkg/sub",
+    )
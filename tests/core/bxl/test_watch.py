@@ -0,0 +1,88 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This source code is licensed under both the MIT license found in the
+# LICENSE-MIT file in the root directory of this source tree and the Apache
+# License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+# of this source tree.
+
+# pyre-strict
+
+
+import asyncio
+import contextlib
+import json
+from asyncio import subprocess
+from typing import Any, AsyncIterator, Dict
+
+from buck2.tests.e2e_util.api.buck import Buck
+from buck2.tests.e2e_util.buck_workspace import buck_test
+
+
+class Watch:
+    def __init__(self, process: subprocess.Process) -> None:
+        self._process = process
+
+    async def read_line(self) -> str:
+        stdout = self._process.stdout
+        assert stdout is not None
+        line = await asyncio.wait_for(stdout.readline(), timeout=60)
+        assert line, "`buck2 bxl --watch` exited"
+        return line.decode().rstrip("\n")
+
+    async def read_event(self) -> Dict[str, Any]:
+        return json.loads(await self.read_line())
+
+
+@contextlib.asynccontextmanager
+async def _watch(buck: Buck, *args: str) -> AsyncIterator[Watch]:
+    process = await buck.run_buck_command(
+        "bxl", "--console=none", "--watch", *args
+    ).start()
+    try:
+        yield Watch(process)
+    finally:
+        process.terminate()
+        await process.wait()
+
+
+@buck_test()
+async def test_watch_reruns_on_change(buck: Buck) -> None:
+    async with _watch(buck, "root//watch.bxl:print_src") as watch:
+        assert await watch.read_line() == "original"
+
+        (buck.cwd / "src.txt").write_text("changed\n")
+        assert await watch.read_line() == "changed"
+
+
+@buck_test()
+async def test_watch_events(buck: Buck) -> None:
+    async with _watch(buck, "--watch-events", "root//watch.bxl:copy_src") as watch:
+        event = await watch.read_event()
+        assert event["round"] == 1
+        assert event["errors"] == []
+        [out] = event["changed_outputs"]
+        assert out.endswith("/out.txt")
+        assert (buck.cwd / out).read_text() == "original\n"
+
+        # Files the script does not depend on do not start a round, so the next
+        # event is for the edit of `src.txt`.
+        (buck.cwd / "unrelated.txt").write_text("unrelated\n")
+        (buck.cwd / "src.txt").write_text("changed\n")
+        event = await watch.read_event()
+        assert event["round"] == 2
+        assert event["changed_outputs"] == [out]
+        assert event["errors"] == []
+        assert (buck.cwd / out).read_text() == "changed\n"
+
+        (buck.cwd / "src.txt").unlink()
+        event = await watch.read_event()
+        assert event["round"] == 3
+        assert event["changed_outputs"] == []
+        assert len(event["errors"]) == 1
+
+        (buck.cwd / "src.txt").write_text("restored\n")
+        event = await watch.read_event()
+        assert event["round"] == 4
+        assert event["changed_outputs"] == [out]
+        assert event["errors"] == []
+        assert (buck.cwd / out).read_text() == "restored\n"
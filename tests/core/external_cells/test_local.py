@@ -0,0 +1,46 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This source code is licensed under both the MIT license found in the
+# LICENSE-MIT file in the root directory of this source tree and the Apache
+# License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+# of this source tree.
+
+# pyre-strict
+
+from pathlib import Path
+
+from buck2.tests.e2e_util.api.buck import Buck
+from buck2.tests.e2e_util.buck_workspace import buck_test
+
+
+async def _build_output(buck: Buck) -> str:
+    res = await buck.build_without_report("libfoo//:t", "--show-full-simple-output")
+    return Path(res.stdout.strip()).read_text().strip()
+
+
+@buck_test()
+async def test_reads_in_place(buck: Buck) -> None:
+    assert await _build_output(buck) == "original"
+
+    # Nothing is fetched into buck-out.
+    assert not list((buck.cwd / "buck-out").glob("**/external_cells/local"))
+
+
+@buck_test()
+async def test_picks_up_changes(buck: Buck) -> None:
+    assert await _build_output(buck) == "original"
+
+    (buck.cwd / "checkout" / "src.txt").write_text("change")
+    assert await _build_output(buck) == "change"
+
+    with open(buck.cwd / "checkout" / "TARGETS.fixture", "a") as f:
+        f.write('\ncopy_src(name = "new", src = "src.txt")\n')
+    res = await buck.uquery("libfoo//:")
+    assert "libfoo//:new" in res.stdout
+
+
+@buck_test()
+async def test_expand_local(buck: Buck) -> None:
+    await buck.expand_external_cell("libfoo")
+    assert (buck.cwd / "libfoo" / "src.txt").read_text().strip() == "original"
+    assert "buildfile" in (buck.cwd / "libfoo" / ".buckconfig").read_text()
@@ -0,0 +1,61 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This source code is licensed under both the MIT license found in the
+# LICENSE-MIT file in the root directory of this source tree and the Apache
+# License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+# of this source tree.
+
+# pyre-strict
+
+
+import json
+import os
+from pathlib import Path
+
+from buck2.tests.e2e_util.api.buck import Buck
+from buck2.tests.e2e_util.api.buck_result import BuckResult
+from buck2.tests.e2e_util.buck_workspace import buck_test
+
+MANIFEST = ".buck2-install-manifest.json"
+
+
+async def _install(buck: Buck, dst: Path, *args: str) -> BuckResult:
+    return await buck.install(
+        "-c",
+        "test.installer={}".format(os.environ["BUCK2_INSTALLER"]),
+        *args,
+        "//:app",
+        "--",
+        "--dst",
+        str(dst),
+    )
+
+
+@buck_test()
+async def test_install_copies_artifacts(buck: Buck, tmp_path: Path) -> None:
+    dst = tmp_path / "dst"
+    await _install(buck, dst)
+
+    assert (dst / "a.txt").read_text() == "a1"
+    assert (dst / "lib" / "b.txt").read_text() == "b"
+    assert (dst / "assets" / "x.txt").read_text() == "x"
+    assert not (dst / "a.txt").is_symlink()
+    manifest = json.loads((dst / MANIFEST).read_text())
+    assert sorted(manifest["files"]) == ["a.txt", "assets", "lib/b.txt"]
+
+
+@buck_test()
+async def test_install_is_incremental(buck: Buck, tmp_path: Path) -> None:
+    dst = tmp_path / "dst"
+    await _install(buck, dst)
+
+    # Unchanged artifacts are not copied again, unless they were deleted.
+    (dst / "a.txt").write_text("untouched")
+    (dst / "lib" / "b.txt").unlink()
+    await _install(buck, dst)
+    assert (dst / "a.txt").read_text() == "untouched"
+    assert (dst / "lib" / "b.txt").read_text() == "b"
+
+    # Changed artifacts are.
+    await _install(buck, dst, "-c", "test.version=2")
+    assert (dst / "a.txt").read_text() == "a2"
@@ -0,0 +1,36 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This source code is licensed under both the MIT license found in the
+# LICENSE-MIT file in the root directory of this source tree and the Apache
+# License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+# of this source tree.
+
+def _app_impl(ctx) -> list[Provider]:
+    files = {
+        name: ctx.actions.write(name, content)
+        for name, content in ctx.attrs.files.items()
+    }
+    files["assets"] = ctx.actions.copied_dir("assets", {
+        name: ctx.actions.write("assets_srcs/" + name, content)
+        for name, content in ctx.attrs.dir_files.items()
+    })
+    return [
+        DefaultInfo(),
+        InstallInfo(installer = ctx.attrs.installer, files = files),
+    ]
+
+app = rule(impl = _app_impl, attrs = {
+    "dir_files": attrs.dict(attrs.string(), attrs.string()),
+    "files": attrs.dict(attrs.string(), attrs.string()),
+    "installer": attrs.default_only(attrs.label(default = "//:installer")),
+})
+
+def _installer_impl(ctx) -> list[Provider]:
+    return [
+        DefaultInfo(),
+        RunInfo(args = [ctx.attrs.path]),
+    ]
+
+installer = rule(impl = _installer_impl, attrs = {
+    "path": attrs.string(),
+})
@@ -0,0 +1,76 @@
+# Copyright (c) Meta Platforms, Inc. and affiliates.
+#
+# This source code is licensed under both the MIT license found in the
+# LICENSE-MIT file in the root directory of this source tree and the Apache
+# License, Version 2.0 found in the LICENSE-APACHE file in the root directory
+# of this source tree.
+
+# pyre-strict
+
+
+from pathlib import Path
+from typing import Dict
+
+from buck2.tests.e2e_util.api.buck import Buck
+from buck2.tests.e2e_util.api.buck_result import BuckException, TestResult
+from buck2.tests.e2e_util.api.process import Process
+from buck2.tests.e2e_util.asserts import expect_failure
+from buck2.tests.e2e_util.buck_workspace import buck_test, env
+
+# Empty test executor forces internal test executor to be used.
+INTERNAL_TEST_EXECUTOR = ""
+
+
+def _env(buck: Buck) -> Dict[str, str]:
+    # A buckconfig in the home directory, which the fixtures must not read.
+    home = buck.cwd / "home"
+    (home / ".buckconfig.d").mkdir(parents=True, exist_ok=True)
+    (home / ".buckconfig.d" / "leak").write_text("[bxl_test]\n  from_home = leaked\n")
+    return {"HOME": str(home)}
+
+
+def _test(buck: Buck, target: str) -> Process[TestResult, BuckException]:
+    return buck.test(
+        "-c",
+        f"bxl_test.buck2={Path(buck.path_to_executable).resolve()}",
+        target,
+        test_executor=INTERNAL_TEST_EXECUTOR,
+        env=_env(buck),
+    )
+
+
+@buck_test()
+@env("BUCK2_ALLOW_INTERNAL_TEST_RUNNER_DO_NOT_USE", "1")
+async def test_expected_output(buck: Buck) -> None:
+    await _test(buck, ":output")
+    await expect_failure(
+        _test(buck, ":output_mismatch"), stderr_regex="Unexpected output"
+    )
+
+
+@buck_test()
+@env("BUCK2_ALLOW_INTERNAL_TEST_RUNNER_DO_NOT_USE", "1")
+async def test_expected_json(buck: Buck) -> None:
+    await _test(buck, ":json")
+    await expect_failure(
+        _test(buck, ":json_mismatch"), stderr_regex="Unexpected JSON value"
+    )
+
+
+@buck_test()
+@env("BUCK2_ALLOW_INTERNAL_TEST_RUNNER_DO_NOT_USE", "1")
+async def test_failing_function(buck: Buck) -> None:
+    await expect_failure(
+        _test(buck, ":fails"),
+        stderr_regex="The BXL function failed(.|\\n)*the function failed",
+    )
+
+
+@buck_test()
+@env("BUCK2_ALLOW_INTERNAL_TEST_RUNNER_DO_NOT_USE", "1")
+async def test_expect_failure(buck: Buck) -> None:
+    await _test(buck, ":expect_failure")
+    await expect_failure(
+        _test(buck, ":expect_failure_but_passes"),
+        stderr_regex="Expected the BXL function to fail, but it succeeded",
+    )
@@ -71,6 +71,7 @@ def _platform_impl(ctx):
         PlatformInfo(
             label = str(ctx.label.raw_target()),
             configuration = _configuration_info_union(subinfos),
+            display_name = ctx.attrs.display_name,
         ),
     ]
 
@@ -80,6 +81,7 @@ platform = rule(
     attrs = {
         "constraint_values": attrs.list(attrs.dep(providers = [ConfigurationInfo]), default = []),
         "deps": attrs.list(attrs.dep(providers = [PlatformInfo]), default = []),
+        "display_name": attrs.option(attrs.string(), default = None),
     },
 )
 
@@ -148,6 +148,7 @@ export const sidebars: SidebarsConfig = {
         'rule_authors/incremental_actions',
         'rule_authors/alias',
         'rule_authors/local_resources',
+        'rule_authors/action_resource_limits',
         'rule_authors/package_files',
         isInternal() ? 'rule_authors/client_metadata' : null,
         isInternal() ? 'rule_authors/action_error_handler' : null,